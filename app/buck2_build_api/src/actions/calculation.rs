@@ -304,6 +304,12 @@ async fn command_execution_report_to_proto(
             }
             .into()
         }
+        CommandExecutionStatus::SandboxViolation { paths, .. } => {
+            buck2_data::command_execution::SandboxViolation {
+                paths: paths.iter().map(|p| p.to_string()).collect(),
+            }
+            .into()
+        }
         CommandExecutionStatus::Error { stage, error } => buck2_data::command_execution::Error {
             stage: (*stage).to_owned(),
             error: format!("{:#}", error),
@@ -329,6 +335,15 @@ async fn command_details(
 
     let signed_exit_code = command.exit_code;
 
+    let execution_stats =
+        command
+            .timing
+            .execution_stats
+            .map(|stats| buck2_data::CommandExecutionStats {
+                memory_peak: stats.memory_peak,
+                cpu_time: stats.cpu_time.try_into().ok(),
+            });
+
     let stdout;
     let stderr;
 
@@ -387,6 +402,7 @@ async fn command_details(
         stderr,
         command,
         signed_exit_code,
+        execution_stats,
     }
 }
 
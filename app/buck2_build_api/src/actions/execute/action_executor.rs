@@ -54,7 +54,9 @@ use itertools::Itertools;
 
 use crate::actions::artifact::build_artifact::BuildArtifact;
 use crate::actions::execute::error::CommandExecutionErrorMarker;
+use crate::actions::execute::error::CommandTimedOutMarker;
 use crate::actions::execute::error::ExecuteError;
+use crate::actions::execute::error::SandboxViolationMarker;
 use crate::actions::impls::run::knobs::HasRunActionKnobs;
 use crate::actions::impls::run::knobs::RunActionKnobs;
 use crate::actions::ActionExecutable;
@@ -224,6 +226,7 @@ impl HasActionExecutor for DiceComputations {
                 command_executor,
                 artifact_fs,
                 executor_config.path_separator,
+                executor_config.default_action_timeout,
             ),
             blocking_executor,
             materializer,
@@ -356,6 +359,12 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
                 },
             )),
 
+            CommandExecutionStatus::SandboxViolation { .. } => Err(SandboxViolationMarker.into()),
+
+            CommandExecutionStatus::TimedOut { duration, .. } => {
+                Err(CommandTimedOutMarker(*duration).into())
+            }
+
             _ => Err(CommandExecutionErrorMarker.into()),
         };
 
@@ -598,6 +607,7 @@ mod tests {
                 Arc::new(DryRunExecutor::new(tracker, None)),
                 artifact_fs,
                 PathSeparatorKind::Unix,
+                None,
             ),
             Arc::new(DummyBlockingExecutor { fs: project_fs }),
             Arc::new(NoDiskMaterializer),
@@ -9,6 +9,7 @@
 
 use std::fmt::Display;
 use std::fmt::Write;
+use std::time::Duration;
 
 use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_execute::execute::request::OutputType;
@@ -32,6 +33,10 @@ pub enum ExecuteError {
         error: anyhow::Error,
     },
     CommandExecutionError,
+    SandboxViolation,
+    TimedOut {
+        duration: Duration,
+    },
 }
 
 impl ExecuteError {
@@ -57,6 +62,11 @@ impl ExecuteError {
             .into(),
             ExecuteError::Error { error } => format!("{:#}", error).into(),
             ExecuteError::CommandExecutionError => buck2_data::CommandExecutionError {}.into(),
+            ExecuteError::SandboxViolation => buck2_data::CommandSandboxViolation {}.into(),
+            ExecuteError::TimedOut { duration } => buck2_data::CommandTimedOut {
+                message: format!("Command timed out after {:.3}s", duration.as_secs_f64()),
+            }
+            .into(),
         }
     }
 }
@@ -80,6 +90,14 @@ impl From<anyhow::Error> for ExecuteError {
         if error.is::<CommandExecutionErrorMarker>() {
             return Self::CommandExecutionError;
         }
+        if error.is::<SandboxViolationMarker>() {
+            return Self::SandboxViolation;
+        }
+        if let Some(CommandTimedOutMarker(duration)) = error.downcast_ref() {
+            return Self::TimedOut {
+                duration: *duration,
+            };
+        }
         Self::Error { error }
     }
 }
@@ -87,3 +105,11 @@ impl From<anyhow::Error> for ExecuteError {
 #[derive(Error, Debug)]
 #[error("Command execution failed. Details are in the command report.")]
 pub struct CommandExecutionErrorMarker;
+
+#[derive(Error, Debug)]
+#[error("Command accessed paths that the sandbox hid from it. Details are in the command report.")]
+pub struct SandboxViolationMarker;
+
+#[derive(Error, Debug)]
+#[error("Command timed out after {:.3}s. Details are in the command report.", .0.as_secs_f64())]
+pub struct CommandTimedOutMarker(pub Duration);
@@ -0,0 +1,831 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Reading and writing the archives of `extract_archive` and `create_archive` actions.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::find;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_core::fs::paths::RelativePath;
+use buck2_core::fs::paths::RelativePathBuf;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_execute::directory::new_symlink;
+use buck2_execute::directory::ActionDirectoryBuilder;
+use buck2_execute::directory::ActionDirectoryMember;
+use derive_more::Display;
+use dupe::Dupe;
+use globset::GlobBuilder;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+use indexmap::IndexMap;
+use sha1::Digest;
+use sha1::Sha1;
+use thiserror::Error;
+
+/// The file type bits of a unix mode, as stored in zip archives.
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+#[derive(Debug, Error)]
+enum ArchiveError {
+    #[error("Unknown archive format `{0}`, expected one of `tar`, `tar.gz`, `tar.zst` or `zip`")]
+    UnknownFormat(String),
+    #[error("Cannot infer the archive format of `{0}` from its extension, pass `format`")]
+    CannotInferFormat(String),
+    #[error("Invalid glob `{0}`")]
+    InvalidGlob(String),
+    #[error("Archive entry `{0}` is outside of the archive")]
+    EntryOutsideArchive(String),
+    #[error("Archive entry `{0}` conflicts with another entry")]
+    ConflictingEntry(ForwardRelativePathBuf),
+    #[error("Symlink `{0}` points outside of the archive: `{1}`")]
+    SymlinkOutsideArchive(ForwardRelativePathBuf, String),
+    #[error("Archive entry `{0}` has an unsupported type: {1}")]
+    UnsupportedEntry(String, String),
+    #[error("Hard link `{0}` points to `{1}`, which isn't a file that comes before it")]
+    HardLinkTargetNotExtracted(ForwardRelativePathBuf, String),
+    #[error("Symlinks cannot be stored in zip archives, got `{0}`")]
+    SymlinkInZip(ForwardRelativePathBuf),
+}
+
+#[derive(Debug, Display, Clone, Copy, Dupe, PartialEq, Eq, Allocative)]
+pub enum ArchiveFormat {
+    #[display(fmt = "tar")]
+    Tar,
+    #[display(fmt = "tar.gz")]
+    TarGz,
+    #[display(fmt = "tar.zst")]
+    TarZst,
+    #[display(fmt = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn parse(format: &str) -> anyhow::Result<Self> {
+        match format {
+            "tar" => Ok(Self::Tar),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "tar.zst" | "tzst" => Ok(Self::TarZst),
+            "zip" => Ok(Self::Zip),
+            _ => Err(ArchiveError::UnknownFormat(format.to_owned()).into()),
+        }
+    }
+
+    /// Infers the format from the extension of an archive's file name.
+    pub fn from_file_name(file_name: &str) -> anyhow::Result<Self> {
+        let extensions = [
+            (".tar", Self::Tar),
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.zst", Self::TarZst),
+            (".tzst", Self::TarZst),
+            (".zip", Self::Zip),
+        ];
+        extensions
+            .into_iter()
+            .find(|(extension, _)| file_name.ends_with(extension))
+            .map(|(_, format)| format)
+            .ok_or_else(|| ArchiveError::CannotInferFormat(file_name.to_owned()).into())
+    }
+}
+
+/// Selects the entries of an archive, and where they go.
+#[derive(Debug, Allocative)]
+pub struct ArchiveFilter {
+    strip_prefix: Option<ForwardRelativePathBuf>,
+    includes: Vec<String>,
+    excludes: Vec<String>,
+    #[allocative(skip)]
+    include_set: GlobSet,
+    #[allocative(skip)]
+    exclude_set: GlobSet,
+}
+
+impl ArchiveFilter {
+    pub fn new(
+        strip_prefix: Option<&str>,
+        includes: Vec<String>,
+        excludes: Vec<String>,
+    ) -> anyhow::Result<Self> {
+        fn glob_set(globs: &[String]) -> anyhow::Result<GlobSet> {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(
+                    GlobBuilder::new(glob)
+                        .literal_separator(true)
+                        .build()
+                        .with_context(|| ArchiveError::InvalidGlob(glob.clone()))?,
+                );
+            }
+            Ok(builder.build()?)
+        }
+
+        let strip_prefix = strip_prefix
+            .map(entry_path)
+            .transpose()?
+            .filter(|prefix| !prefix.is_empty());
+
+        Ok(Self {
+            strip_prefix,
+            include_set: glob_set(&includes)?,
+            exclude_set: glob_set(&excludes)?,
+            includes,
+            excludes,
+        })
+    }
+
+    /// Returns where the entry at `path` goes, once `strip_prefix` is removed, or `None` if it's
+    /// filtered out. Globs are matched against that stripped path.
+    pub fn apply<'a>(&self, path: &'a ForwardRelativePath) -> Option<&'a ForwardRelativePath> {
+        let path = match &self.strip_prefix {
+            Some(prefix) => path.strip_prefix(prefix).ok()?,
+            None => path,
+        };
+
+        if path.is_empty()
+            || (!self.includes.is_empty() && !self.include_set.is_match(path.as_str()))
+            || self.exclude_set.is_match(path.as_str())
+        {
+            return None;
+        }
+
+        Some(path)
+    }
+
+    pub fn aquery_attributes(&self, attributes: &mut IndexMap<String, String>) {
+        if let Some(prefix) = &self.strip_prefix {
+            attributes.insert("strip_prefix".to_owned(), prefix.to_string());
+        }
+        if !self.includes.is_empty() {
+            attributes.insert("includes".to_owned(), self.includes.join(" "));
+        }
+        if !self.excludes.is_empty() {
+            attributes.insert("excludes".to_owned(), self.excludes.join(" "));
+        }
+    }
+}
+
+/// Normalizes the path of an archive entry. Leading `/` and `.` components are dropped, as tar
+/// does.
+fn entry_path(name: &str) -> anyhow::Result<ForwardRelativePathBuf> {
+    let mut components = Vec::new();
+    for component in name.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return Err(ArchiveError::EntryOutsideArchive(name.to_owned()).into()),
+            component => components.push(component),
+        }
+    }
+    ForwardRelativePathBuf::new(components.join("/"))
+        .with_context(|| format!("Invalid archive entry `{}`", name))
+}
+
+/// Something to put in an archive.
+pub enum ArchiveMember {
+    File {
+        src: ProjectRelativePathBuf,
+        is_executable: bool,
+    },
+    Symlink {
+        target: RelativePathBuf,
+    },
+}
+
+/// Writes an archive of `members` to `output`. Members are written in order of their path, with
+/// no timestamps or owners, so the same members always make the same archive.
+pub fn create_archive(
+    fs: &ProjectRoot,
+    format: ArchiveFormat,
+    members: &BTreeMap<ForwardRelativePathBuf, ArchiveMember>,
+    output: &ProjectRelativePath,
+) -> anyhow::Result<()> {
+    let writer = BufWriter::new(fs.create_file(output, false)?);
+
+    let writer = match format {
+        ArchiveFormat::Tar => write_tar(fs, members, writer)?,
+        ArchiveFormat::TarGz => write_tar(
+            fs,
+            members,
+            flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+        )?
+        .finish()?,
+        ArchiveFormat::TarZst => {
+            write_tar(fs, members, zstd::stream::write::Encoder::new(writer, 0)?)?.finish()?
+        }
+        ArchiveFormat::Zip => write_zip(fs, members, writer)?,
+    };
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .with_context(|| format!("Error writing `{}`", output))?;
+    Ok(())
+}
+
+fn write_tar<W: Write>(
+    fs: &ProjectRoot,
+    members: &BTreeMap<ForwardRelativePathBuf, ArchiveMember>,
+    writer: W,
+) -> anyhow::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+
+    for (path, member) in members {
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+
+        match member {
+            ArchiveMember::File { src, is_executable } => {
+                let file = File::open(fs.resolve(src))
+                    .with_context(|| format!("Error opening `{}`", src))?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(if *is_executable { 0o755 } else { 0o644 });
+                header.set_size(file.metadata()?.len());
+                builder
+                    .append_data(&mut header, path.as_str(), BufReader::new(file))
+                    .with_context(|| format!("Error adding `{}` to the archive", path))?;
+            }
+            ArchiveMember::Symlink { target } => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder
+                    .append_link(&mut header, path.as_str(), target.as_str())
+                    .with_context(|| format!("Error adding `{}` to the archive", path))?;
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+fn write_zip<W: Write + Seek>(
+    fs: &ProjectRoot,
+    members: &BTreeMap<ForwardRelativePathBuf, ArchiveMember>,
+    writer: W,
+) -> anyhow::Result<W> {
+    let mut zip = zip::ZipWriter::new(writer);
+
+    for (path, member) in members {
+        match member {
+            ArchiveMember::File { src, is_executable } => {
+                let options = zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .last_modified_time(zip::DateTime::default())
+                    .unix_permissions(if *is_executable { 0o755 } else { 0o644 });
+                zip.start_file(path.as_str(), options)?;
+                let mut file = File::open(fs.resolve(src))
+                    .with_context(|| format!("Error opening `{}`", src))?;
+                io::copy(&mut file, &mut zip)
+                    .with_context(|| format!("Error adding `{}` to the archive", path))?;
+            }
+            ArchiveMember::Symlink { .. } => {
+                return Err(ArchiveError::SymlinkInZip(path.clone()).into());
+            }
+        }
+    }
+
+    Ok(zip.finish()?)
+}
+
+/// Extracts the entries of `archive` that pass `filter` into the directory `output`, and returns
+/// what was extracted.
+pub fn extract_archive(
+    fs: &ProjectRoot,
+    format: ArchiveFormat,
+    filter: &ArchiveFilter,
+    archive: &ProjectRelativePath,
+    output: &ProjectRelativePath,
+) -> anyhow::Result<ActionDirectoryBuilder> {
+    let file =
+        File::open(fs.resolve(archive)).with_context(|| format!("Error opening `{}`", archive))?;
+    let reader = BufReader::new(file);
+
+    fs_util::create_dir_all(fs.resolve(output))?;
+    let mut extractor = Extractor {
+        fs,
+        filter,
+        output,
+        builder: ActionDirectoryBuilder::empty(),
+        // Only tar archives have hard links.
+        skipped: match format {
+            ArchiveFormat::Zip => None,
+            ArchiveFormat::Tar | ArchiveFormat::TarGz | ArchiveFormat::TarZst => {
+                Some(SkippedFiles::default())
+            }
+        },
+    };
+
+    match format {
+        ArchiveFormat::Tar => extract_tar(&mut extractor, reader),
+        ArchiveFormat::TarGz => extract_tar(&mut extractor, flate2::read::GzDecoder::new(reader)),
+        ArchiveFormat::TarZst => extract_tar(
+            &mut extractor,
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        ),
+        ArchiveFormat::Zip => extract_zip(&mut extractor, reader),
+    }
+    .with_context(|| format!("Error extracting `{}`", archive))?;
+
+    Ok(extractor.builder)
+}
+
+fn extract_tar(extractor: &mut Extractor, reader: impl Read) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?;
+        let name = name
+            .to_str()
+            .with_context(|| format!("Archive entry `{}` is not UTF-8", name.display()))?
+            .to_owned();
+        let entry_type = entry.header().entry_type();
+
+        if entry_type.is_dir() {
+            extractor.dir(&name)?;
+        } else if entry_type.is_file() {
+            let is_executable = entry.header().mode()? & 0o111 != 0;
+            extractor.file(&name, is_executable, &mut entry)?;
+        } else if entry_type.is_symlink() {
+            let target = entry
+                .link_name()?
+                .with_context(|| format!("Symlink `{}` has no target", name))?;
+            let target = target
+                .to_str()
+                .with_context(|| format!("Target of symlink `{}` is not UTF-8", name))?
+                .to_owned();
+            extractor.symlink(&name, &target)?;
+        } else if entry_type.is_hard_link() {
+            let target = entry
+                .link_name()?
+                .with_context(|| format!("Hard link `{}` has no target", name))?;
+            let target = target
+                .to_str()
+                .with_context(|| format!("Target of hard link `{}` is not UTF-8", name))?
+                .to_owned();
+            extractor.hard_link(&name, &target)?;
+        } else if entry_type.is_pax_global_extensions() {
+            // Only carries metadata for the entries that follow.
+        } else {
+            return Err(ArchiveError::UnsupportedEntry(name, format!("{:?}", entry_type)).into());
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_zip(extractor: &mut Extractor, reader: impl Read + Seek) -> anyhow::Result<()> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_owned();
+        let mode = file.unix_mode().unwrap_or(0);
+
+        if file.is_dir() {
+            extractor.dir(&name)?;
+        } else if mode & S_IFMT == S_IFLNK {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .with_context(|| format!("Error reading the target of symlink `{}`", name))?;
+            extractor.symlink(&name, &target)?;
+        } else {
+            extractor.file(&name, mode & 0o111 != 0, &mut file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `contents`, the entry `name` of an archive, to `dest`.
+fn write_file(
+    fs: &ProjectRoot,
+    name: &str,
+    dest: &AbsNormPathBuf,
+    is_executable: bool,
+    contents: &mut dyn Read,
+) -> anyhow::Result<FileMetadata> {
+    let mut file = fs.create_file(dest, is_executable)?;
+
+    // Hash the contents as we write them, rather than reading them back afterwards.
+    let mut hasher = Sha1::new();
+    let mut size = 0;
+    let mut buffer = [0; 16 * 1024];
+    loop {
+        let count = contents
+            .read(&mut buffer)
+            .with_context(|| format!("Error reading `{}` from the archive", name))?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+        file.write_all(&buffer[..count])
+            .with_context(|| format!("Error writing `{}`", dest))?;
+        size += count as u64;
+    }
+
+    Ok(FileMetadata {
+        digest: TrackedFileDigest::new(FileDigest::new_sha1(hasher.finalize().into(), size)),
+        is_executable,
+    })
+}
+
+/// Writes the entries of an archive to disk, and keeps track of what it wrote.
+struct Extractor<'a> {
+    fs: &'a ProjectRoot,
+    filter: &'a ArchiveFilter,
+    output: &'a ProjectRelativePath,
+    builder: ActionDirectoryBuilder,
+    /// Where to keep the files that `filter` skips, if hard links may point to them.
+    skipped: Option<SkippedFiles>,
+}
+
+/// Regular files of an archive that weren't extracted, kept in a temporary directory next to the
+/// output in case a hard link that is extracted points to one of them.
+#[derive(Default)]
+struct SkippedFiles {
+    dir: Option<tempfile::TempDir>,
+    /// By path in the archive.
+    files: HashMap<ForwardRelativePathBuf, (AbsNormPathBuf, FileMetadata)>,
+}
+
+impl<'a> Extractor<'a> {
+    /// Checks that nothing was extracted at `path` yet, nor at a parent of `path` that isn't a
+    /// directory, so that no entry can write through a symlink extracted before it.
+    fn check_vacant(&self, path: &ForwardRelativePath) -> anyhow::Result<()> {
+        match find(&self.builder, path) {
+            Ok(None) => Ok(()),
+            _ => Err(ArchiveError::ConflictingEntry(path.to_buf()).into()),
+        }
+    }
+
+    fn dir(&mut self, name: &str) -> anyhow::Result<()> {
+        let path = entry_path(name)?;
+        let path = match self.filter.apply(&path) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        self.builder
+            .mkdir(path)
+            .map_err(|_| ArchiveError::ConflictingEntry(path.to_buf()))?;
+        fs_util::create_dir_all(self.fs.resolve(&self.output.join(path)))
+    }
+
+    fn file(
+        &mut self,
+        name: &str,
+        is_executable: bool,
+        contents: &mut dyn Read,
+    ) -> anyhow::Result<()> {
+        let entry = entry_path(name)?;
+        let path = match self.filter.apply(&entry) {
+            Some(path) => path,
+            None => return self.skip_file(name, &entry, is_executable, contents),
+        };
+        self.check_vacant(path)?;
+
+        let dest = self.fs.resolve(&self.output.join(path));
+        let metadata = write_file(self.fs, name, &dest, is_executable, contents)?;
+        self.builder.insert(
+            path,
+            DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)),
+        )?;
+        Ok(())
+    }
+
+    /// Keeps a file that isn't extracted aside, so that hard links to it still can be.
+    fn skip_file(
+        &mut self,
+        name: &str,
+        entry: &ForwardRelativePath,
+        is_executable: bool,
+        contents: &mut dyn Read,
+    ) -> anyhow::Result<()> {
+        let skipped = match &mut self.skipped {
+            Some(skipped) => skipped,
+            None => return Ok(()),
+        };
+        let dir = match &mut skipped.dir {
+            Some(dir) => dir,
+            dir => {
+                let output = self.fs.resolve(self.output);
+                let parent = output.parent().unwrap_or(&output);
+                dir.insert(
+                    tempfile::Builder::new()
+                        .prefix(".skipped-")
+                        .tempdir_in(parent)
+                        .with_context(|| format!("Error creating a directory in `{}`", parent))?,
+                )
+            }
+        };
+        let dest = AbsNormPathBuf::try_from(dir.path().join(skipped.files.len().to_string()))?;
+        let metadata = write_file(self.fs, name, &dest, is_executable, contents)?;
+        skipped.files.insert(entry.to_buf(), (dest, metadata));
+        Ok(())
+    }
+
+    fn symlink(&mut self, name: &str, target: &str) -> anyhow::Result<()> {
+        let path = entry_path(name)?;
+        let path = match self.filter.apply(&path) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.check_vacant(path)?;
+
+        // The output has to be usable on its own, so symlinks must stay inside of it.
+        let parent = path.parent().unwrap_or_else(ForwardRelativePath::empty);
+        if target.starts_with('/') || parent.join_normalized(RelativePath::new(target)).is_err() {
+            return Err(
+                ArchiveError::SymlinkOutsideArchive(path.to_buf(), target.to_owned()).into(),
+            );
+        }
+
+        let dest = self.fs.resolve(&self.output.join(path));
+        if let Some(parent) = dest.parent() {
+            fs_util::create_dir_all(parent)?;
+        }
+        fs_util::symlink(target, &dest)?;
+
+        self.builder
+            .insert(path, DirectoryEntry::Leaf(new_symlink(target)?))?;
+        Ok(())
+    }
+
+    /// Extracts a hard link as a copy of its target, which names a regular file of the archive that
+    /// comes before it, whether or not that file was extracted. The output can't hold hard links,
+    /// since its files are materialized and uploaded independently of each other.
+    fn hard_link(&mut self, name: &str, target: &str) -> anyhow::Result<()> {
+        let path = entry_path(name)?;
+        let path = match self.filter.apply(&path) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        self.check_vacant(path)?;
+
+        let target_entry = entry_path(target)?;
+        let extracted = self.filter.apply(&target_entry).and_then(|target_path| {
+            match find(&self.builder, target_path) {
+                Ok(Some(DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)))) => Some((
+                    self.fs.resolve(&self.output.join(target_path)),
+                    metadata.clone(),
+                )),
+                _ => None,
+            }
+        });
+        let skipped = || {
+            self.skipped
+                .as_ref()
+                .and_then(|skipped| skipped.files.get(&target_entry))
+                .cloned()
+        };
+        let (src, metadata) = extracted.or_else(skipped).ok_or_else(|| {
+            ArchiveError::HardLinkTargetNotExtracted(path.to_buf(), target.to_owned())
+        })?;
+
+        self.fs.copy(&src, &self.output.join(path))?;
+
+        self.builder.insert(
+            path,
+            DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::directory::Directory;
+    use buck2_core::directory::DirectoryIterator;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    fn path(s: &str) -> &ForwardRelativePath {
+        ForwardRelativePath::new(s).unwrap()
+    }
+
+    #[test]
+    fn test_format_from_file_name() -> anyhow::Result<()> {
+        assert_eq!(ArchiveFormat::Tar, ArchiveFormat::from_file_name("a.tar")?);
+        assert_eq!(
+            ArchiveFormat::TarGz,
+            ArchiveFormat::from_file_name("a.tar.gz")?
+        );
+        assert_eq!(
+            ArchiveFormat::TarGz,
+            ArchiveFormat::from_file_name("a.tgz")?
+        );
+        assert_eq!(
+            ArchiveFormat::TarZst,
+            ArchiveFormat::from_file_name("a.tar.zst")?
+        );
+        assert_eq!(ArchiveFormat::Zip, ArchiveFormat::from_file_name("a.zip")?);
+        assert!(ArchiveFormat::from_file_name("a.tar.xz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_path() -> anyhow::Result<()> {
+        assert_eq!("a/b", entry_path("./a/b")?.as_str());
+        assert_eq!("a/b", entry_path("/a//b/")?.as_str());
+        assert_eq!("", entry_path("./")?.as_str());
+        assert!(entry_path("a/../../b").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter() -> anyhow::Result<()> {
+        let filter = ArchiveFilter::new(
+            Some("pkg-1.0/"),
+            vec!["include/**".to_owned(), "*.txt".to_owned()],
+            vec!["include/internal/**".to_owned()],
+        )?;
+
+        assert_eq!(
+            Some(path("include/a.h")),
+            filter.apply(path("pkg-1.0/include/a.h"))
+        );
+        assert_eq!(
+            Some(path("README.txt")),
+            filter.apply(path("pkg-1.0/README.txt"))
+        );
+        // `*` doesn't match across directories.
+        assert_eq!(None, filter.apply(path("pkg-1.0/docs/a.txt")));
+        assert_eq!(None, filter.apply(path("pkg-1.0/include/internal/b.h")));
+        assert_eq!(None, filter.apply(path("pkg-1.0")));
+        assert_eq!(None, filter.apply(path("other/include/a.h")));
+        Ok(())
+    }
+
+    fn round_trip(format: ArchiveFormat) -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = temp.path();
+
+        let src = ProjectRelativePath::unchecked_new("src");
+        fs.write_file(&src.join(path("a.txt")), "a", false)?;
+        fs.write_file(&src.join(path("bin/run")), "#!/bin/sh", true)?;
+
+        let mut members = BTreeMap::new();
+        members.insert(
+            ForwardRelativePathBuf::unchecked_new("pkg/bin/run".to_owned()),
+            ArchiveMember::File {
+                src: src.join(path("bin/run")),
+                is_executable: true,
+            },
+        );
+        members.insert(
+            ForwardRelativePathBuf::unchecked_new("pkg/a.txt".to_owned()),
+            ArchiveMember::File {
+                src: src.join(path("a.txt")),
+                is_executable: false,
+            },
+        );
+
+        let archive = ProjectRelativePath::unchecked_new("archive");
+        create_archive(fs, format, &members, archive)?;
+        let first = fs_util::read(fs.resolve(archive))?;
+        create_archive(fs, format, &members, archive)?;
+        assert_eq!(first, fs_util::read(fs.resolve(archive))?);
+
+        let filter = ArchiveFilter::new(Some("pkg"), Vec::new(), vec!["*.txt".to_owned()])?;
+        let out = ProjectRelativePath::unchecked_new("out");
+        let extracted = extract_archive(fs, format, &filter, archive, out)?;
+
+        let mut walk = extracted.ordered_walk();
+        let mut paths = Vec::new();
+        while let Some((path, entry)) = walk.next() {
+            if let DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)) = entry {
+                paths.push((path.get().to_string(), metadata.is_executable));
+            }
+        }
+        assert_eq!(vec![("bin/run".to_owned(), true)], paths);
+        assert_eq!(
+            "#!/bin/sh",
+            fs_util::read_to_string(fs.resolve(&out.join(path("bin/run"))))?
+        );
+        assert!(!fs_util::try_exists(fs.resolve(&out.join(path("a.txt"))))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_tar_gz() -> anyhow::Result<()> {
+        round_trip(ArchiveFormat::TarGz)
+    }
+
+    #[test]
+    fn test_round_trip_tar_zst() -> anyhow::Result<()> {
+        round_trip(ArchiveFormat::TarZst)
+    }
+
+    #[test]
+    fn test_round_trip_zip() -> anyhow::Result<()> {
+        round_trip(ArchiveFormat::Zip)
+    }
+
+    /// Writes a tar archive with the file `pkg/a.txt` and a hard link to `target` at `pkg/b.txt`.
+    fn write_tar_with_hard_link(fs: &ProjectRoot, target: &str) -> anyhow::Result<()> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(1);
+        builder.append_data(&mut header, "pkg/a.txt", "a".as_bytes())?;
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_mode(0o644);
+        header.set_size(0);
+        builder.append_link(&mut header, "pkg/b.txt", target)?;
+
+        fs.write_file(
+            ProjectRelativePath::unchecked_new("archive.tar"),
+            builder.into_inner()?,
+            false,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_hard_link() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = temp.path();
+        let archive = ProjectRelativePath::unchecked_new("archive.tar");
+        let out = ProjectRelativePath::unchecked_new("out");
+        let filter = ArchiveFilter::new(Some("pkg"), Vec::new(), Vec::new())?;
+
+        write_tar_with_hard_link(fs, "pkg/a.txt")?;
+        let extracted = extract_archive(fs, ArchiveFormat::Tar, &filter, archive, out)?;
+
+        let metadata = |path: &str| -> anyhow::Result<FileMetadata> {
+            match find(&extracted, ForwardRelativePath::new(path)?) {
+                Ok(Some(DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)))) => {
+                    Ok(metadata.clone())
+                }
+                _ => Err(anyhow::anyhow!("`{}` wasn't extracted as a file", path)),
+            }
+        };
+        assert_eq!(metadata("a.txt")?, metadata("b.txt")?);
+        assert_eq!(
+            "a",
+            fs_util::read_to_string(fs.resolve(&out.join(path("b.txt"))))?
+        );
+        // A copy, so that writing to one of the files doesn't change the other.
+        fs.write_file(&out.join(path("a.txt")), "changed", false)?;
+        assert_eq!(
+            "a",
+            fs_util::read_to_string(fs.resolve(&out.join(path("b.txt"))))?
+        );
+
+        // The target has to be in the output.
+        fs.remove_path_recursive(out)?;
+        write_tar_with_hard_link(fs, "pkg/missing.txt")?;
+        assert!(extract_archive(fs, ArchiveFormat::Tar, &filter, archive, out).is_err());
+
+        // The target doesn't have to be extracted itself.
+        fs.remove_path_recursive(out)?;
+        let filter = ArchiveFilter::new(Some("pkg"), Vec::new(), vec!["a.txt".to_owned()])?;
+        write_tar_with_hard_link(fs, "pkg/a.txt")?;
+        let extracted = extract_archive(fs, ArchiveFormat::Tar, &filter, archive, out)?;
+        assert!(matches!(
+            find(&extracted, path("b.txt")),
+            Ok(Some(DirectoryEntry::Leaf(ActionDirectoryMember::File(_))))
+        ));
+        assert!(!fs_util::try_exists(fs.resolve(&out.join(path("a.txt"))))?);
+        assert_eq!(
+            "a",
+            fs_util::read_to_string(fs.resolve(&out.join(path("b.txt"))))?
+        );
+
+        Ok(())
+    }
+}
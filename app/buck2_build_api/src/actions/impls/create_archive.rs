@@ -0,0 +1,298 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::category::Category;
+use buck2_core::directory::ordered_entry_walk;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use indexmap::indexmap;
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use once_cell::sync::Lazy;
+use starlark::values::OwnedFrozenValue;
+use thiserror::Error;
+
+use crate::actions::artifact::build_artifact::BuildArtifact;
+use crate::actions::execute::action_executor::ActionExecutionKind;
+use crate::actions::execute::action_executor::ActionExecutionMetadata;
+use crate::actions::execute::action_executor::ActionOutputs;
+use crate::actions::impls::archive::create_archive;
+use crate::actions::impls::archive::ArchiveFilter;
+use crate::actions::impls::archive::ArchiveFormat;
+use crate::actions::impls::archive::ArchiveMember;
+use crate::actions::Action;
+use crate::actions::ActionExecutable;
+use crate::actions::ActionExecutionCtx;
+use crate::actions::IncrementalActionExecutable;
+use crate::actions::UnregisteredAction;
+use crate::artifact_groups::ArtifactGroup;
+
+#[derive(Debug, Error)]
+enum CreateArchiveActionValidationError {
+    #[error("Exactly one output file must be specified for a create_archive action, got {0}")]
+    WrongNumberOfOutputs(usize),
+    #[error("Only artifact inputs are supported in create_archive actions, got {0}")]
+    UnsupportedInput(ArtifactGroup),
+}
+
+#[derive(Debug, Error)]
+enum CreateArchiveError {
+    #[error("More than one input would be stored at `{0}` in the archive")]
+    DuplicatePath(ForwardRelativePathBuf),
+    #[error("Cannot store `{0}` in an archive, it is a symlink to `{1}` outside of the project")]
+    ExternalSymlink(ForwardRelativePathBuf, String),
+}
+
+#[derive(Allocative)]
+pub struct UnregisteredCreateArchiveAction {
+    /// Inferred from the file name of the output if not set.
+    format: Option<ArchiveFormat>,
+    filter: ArchiveFilter,
+    /// The inputs, and where they go in the archive.
+    srcs: Vec<(ArtifactGroup, ForwardRelativePathBuf)>,
+}
+
+impl UnregisteredCreateArchiveAction {
+    pub fn new(
+        format: Option<ArchiveFormat>,
+        filter: ArchiveFilter,
+        srcs: Vec<(ArtifactGroup, String)>,
+    ) -> anyhow::Result<Self> {
+        let srcs = srcs.into_try_map(|(src, path)| -> anyhow::Result<_> {
+            let path = ForwardRelativePathBuf::new(path)?;
+            match src {
+                ArtifactGroup::Artifact(..) => Ok((src, path)),
+                other => Err(CreateArchiveActionValidationError::UnsupportedInput(other).into()),
+            }
+        })?;
+        Ok(Self {
+            format,
+            filter,
+            srcs,
+        })
+    }
+
+    pub fn inputs(&self) -> IndexSet<ArtifactGroup> {
+        self.srcs.iter().map(|(src, _)| src.dupe()).collect()
+    }
+}
+
+impl UnregisteredAction for UnregisteredCreateArchiveAction {
+    fn register(
+        self: Box<Self>,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+        _starlark_data: Option<OwnedFrozenValue>,
+    ) -> anyhow::Result<Box<dyn Action>> {
+        Ok(box CreateArchiveAction::new(*self, inputs, outputs)?)
+    }
+}
+
+#[derive(Debug, Allocative)]
+struct CreateArchiveAction {
+    format: ArchiveFormat,
+    filter: ArchiveFilter,
+    srcs: Vec<(ArtifactGroup, ForwardRelativePathBuf)>,
+    inputs: IndexSet<ArtifactGroup>,
+    outputs: IndexSet<BuildArtifact>,
+}
+
+impl CreateArchiveAction {
+    fn new(
+        unregistered: UnregisteredCreateArchiveAction,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+    ) -> anyhow::Result<Self> {
+        let output = match outputs.iter().into_singleton() {
+            Some(output) => output,
+            None => {
+                return Err(CreateArchiveActionValidationError::WrongNumberOfOutputs(
+                    outputs.len(),
+                )
+                .into());
+            }
+        };
+
+        let format = match unregistered.format {
+            Some(format) => format,
+            None => {
+                let file_name = output
+                    .get_path()
+                    .path()
+                    .file_name()
+                    .context("Output has no file name")?;
+                ArchiveFormat::from_file_name(file_name.as_str())?
+            }
+        };
+
+        Ok(Self {
+            format,
+            filter: unregistered.filter,
+            srcs: unregistered.srcs,
+            inputs,
+            outputs,
+        })
+    }
+
+    fn output(&self) -> &BuildArtifact {
+        self.outputs
+            .iter()
+            .next()
+            .expect("a single artifact by construction")
+    }
+}
+
+#[async_trait]
+impl Action for CreateArchiveAction {
+    fn kind(&self) -> buck2_data::ActionKind {
+        buck2_data::ActionKind::CreateArchive
+    }
+
+    fn inputs(&self) -> anyhow::Result<Cow<'_, IndexSet<ArtifactGroup>>> {
+        Ok(Cow::Borrowed(&self.inputs))
+    }
+
+    fn outputs(&self) -> anyhow::Result<Cow<'_, IndexSet<BuildArtifact>>> {
+        Ok(Cow::Borrowed(&self.outputs))
+    }
+
+    fn as_executable(&self) -> ActionExecutable<'_> {
+        ActionExecutable::Incremental(self)
+    }
+
+    fn category(&self) -> &Category {
+        static CREATE_ARCHIVE_CATEGORY: Lazy<Category> =
+            Lazy::new(|| Category::try_from("create_archive").unwrap());
+
+        &CREATE_ARCHIVE_CATEGORY
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        Some(self.output().get_path().short_path().as_str())
+    }
+
+    fn aquery_attributes(&self, _fs: &ExecutorFs) -> IndexMap<String, String> {
+        let mut attributes = indexmap! {
+            "format".to_owned() => self.format.to_string(),
+        };
+        self.filter.aquery_attributes(&mut attributes);
+        attributes
+    }
+}
+
+#[async_trait]
+impl IncrementalActionExecutable for CreateArchiveAction {
+    async fn execute(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        // Sorting the members by path is what makes the archive deterministic.
+        let mut members = BTreeMap::new();
+        let mut to_materialize = Vec::new();
+
+        for (group, dest) in &self.srcs {
+            let (src_artifact, value) = ctx
+                .artifact_values(group)
+                .iter()
+                .into_singleton()
+                .context("Input did not dereference to exactly one artifact")?;
+
+            let src = ctx.fs().resolve(src_artifact.get_path())?;
+
+            let mut walk = ordered_entry_walk(value.entry().as_ref());
+            while let Some((path, entry)) = walk.next() {
+                let path = path.get();
+                let archive_path = dest.join(&path);
+
+                let member = match entry {
+                    DirectoryEntry::Dir(..) => continue,
+                    DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)) => {
+                        ArchiveMember::File {
+                            src: src.join(&path),
+                            is_executable: metadata.is_executable,
+                        }
+                    }
+                    DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(symlink)) => {
+                        ArchiveMember::Symlink {
+                            target: symlink.target().to_owned(),
+                        }
+                    }
+                    DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(symlink)) => {
+                        return Err(CreateArchiveError::ExternalSymlink(
+                            archive_path,
+                            symlink.to_string(),
+                        )
+                        .into());
+                    }
+                };
+
+                if let Some(archive_path) = self.filter.apply(&archive_path) {
+                    if members.insert(archive_path.to_buf(), member).is_some() {
+                        return Err(CreateArchiveError::DuplicatePath(archive_path.to_buf()).into());
+                    }
+                }
+            }
+
+            if !src_artifact.is_source() {
+                to_materialize.push(src);
+            }
+        }
+
+        ctx.materializer()
+            .ensure_materialized(to_materialize)
+            .await?;
+        ctx.cleanup_outputs().await?;
+
+        let output = ctx.fs().resolve_build(self.output().get_path());
+
+        let execution_start = Instant::now();
+        let fs = ctx.fs().fs();
+        let metadata = ctx
+            .blocking_executor()
+            .execute_io_inline(|| {
+                create_archive(fs, self.format, &members, &output)?;
+                Ok(FileMetadata {
+                    digest: TrackedFileDigest::new(FileDigest::from_file(fs.resolve(&output))?),
+                    is_executable: false,
+                })
+            })
+            .await?;
+
+        let value = ArtifactValue::file(metadata);
+        ctx.materializer()
+            .declare_existing(vec![(output, value.dupe())])
+            .await?;
+
+        Ok((
+            ActionOutputs::from_single(self.output().get_path().dupe(), value),
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData {
+                    wall_time: execution_start.elapsed(),
+                },
+            },
+        ))
+    }
+}
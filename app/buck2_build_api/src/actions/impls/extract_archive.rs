@@ -0,0 +1,234 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_core::category::Category;
+use buck2_core::directory::DirectoryEntry;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::directory::extract_artifact_value;
+use buck2_execute::directory::insert_entry;
+use buck2_execute::directory::ActionDirectoryBuilder;
+use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use indexmap::indexmap;
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use once_cell::sync::Lazy;
+use starlark::values::OwnedFrozenValue;
+use thiserror::Error;
+
+use crate::actions::artifact::build_artifact::BuildArtifact;
+use crate::actions::execute::action_executor::ActionExecutionKind;
+use crate::actions::execute::action_executor::ActionExecutionMetadata;
+use crate::actions::execute::action_executor::ActionOutputs;
+use crate::actions::impls::archive::extract_archive;
+use crate::actions::impls::archive::ArchiveFilter;
+use crate::actions::impls::archive::ArchiveFormat;
+use crate::actions::Action;
+use crate::actions::ActionExecutable;
+use crate::actions::ActionExecutionCtx;
+use crate::actions::IncrementalActionExecutable;
+use crate::actions::UnregisteredAction;
+use crate::artifact_groups::ArtifactGroup;
+
+#[derive(Debug, Error)]
+enum ExtractArchiveActionValidationError {
+    #[error("Exactly one input file must be specified for an extract_archive action, got {0}")]
+    WrongNumberOfInputs(usize),
+    #[error("Exactly one output must be specified for an extract_archive action, got {0}")]
+    WrongNumberOfOutputs(usize),
+    #[error("Only artifact inputs are supported in extract_archive actions, got {0}")]
+    UnsupportedInput(ArtifactGroup),
+}
+
+#[derive(Allocative)]
+pub struct UnregisteredExtractArchiveAction {
+    /// Inferred from the file name of the archive if not set.
+    format: Option<ArchiveFormat>,
+    filter: ArchiveFilter,
+}
+
+impl UnregisteredExtractArchiveAction {
+    pub fn new(format: Option<ArchiveFormat>, filter: ArchiveFilter) -> Self {
+        Self { format, filter }
+    }
+}
+
+impl UnregisteredAction for UnregisteredExtractArchiveAction {
+    fn register(
+        self: Box<Self>,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+        _starlark_data: Option<OwnedFrozenValue>,
+    ) -> anyhow::Result<Box<dyn Action>> {
+        Ok(box ExtractArchiveAction::new(*self, inputs, outputs)?)
+    }
+}
+
+#[derive(Debug, Allocative)]
+struct ExtractArchiveAction {
+    format: ArchiveFormat,
+    filter: ArchiveFilter,
+    inputs: IndexSet<ArtifactGroup>,
+    outputs: IndexSet<BuildArtifact>,
+}
+
+impl ExtractArchiveAction {
+    fn new(
+        unregistered: UnregisteredExtractArchiveAction,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+    ) -> anyhow::Result<Self> {
+        let archive = match inputs.iter().into_singleton() {
+            Some(ArtifactGroup::Artifact(archive)) => archive,
+            Some(other) => {
+                return Err(
+                    ExtractArchiveActionValidationError::UnsupportedInput(other.dupe()).into(),
+                );
+            }
+            None => {
+                return Err(
+                    ExtractArchiveActionValidationError::WrongNumberOfInputs(inputs.len()).into(),
+                );
+            }
+        };
+
+        if outputs.len() != 1 {
+            return Err(
+                ExtractArchiveActionValidationError::WrongNumberOfOutputs(outputs.len()).into(),
+            );
+        }
+
+        let format = match unregistered.format {
+            Some(format) => format,
+            None => archive
+                .get_path()
+                .with_filename(|file_name| ArchiveFormat::from_file_name(file_name?.as_str()))?,
+        };
+
+        Ok(Self {
+            format,
+            filter: unregistered.filter,
+            inputs,
+            outputs,
+        })
+    }
+
+    fn input(&self) -> &ArtifactGroup {
+        self.inputs
+            .iter()
+            .next()
+            .expect("a single input by construction")
+    }
+
+    fn output(&self) -> &BuildArtifact {
+        self.outputs
+            .iter()
+            .next()
+            .expect("a single artifact by construction")
+    }
+}
+
+#[async_trait]
+impl Action for ExtractArchiveAction {
+    fn kind(&self) -> buck2_data::ActionKind {
+        buck2_data::ActionKind::ExtractArchive
+    }
+
+    fn inputs(&self) -> anyhow::Result<Cow<'_, IndexSet<ArtifactGroup>>> {
+        Ok(Cow::Borrowed(&self.inputs))
+    }
+
+    fn outputs(&self) -> anyhow::Result<Cow<'_, IndexSet<BuildArtifact>>> {
+        Ok(Cow::Borrowed(&self.outputs))
+    }
+
+    fn as_executable(&self) -> ActionExecutable<'_> {
+        ActionExecutable::Incremental(self)
+    }
+
+    fn category(&self) -> &Category {
+        static EXTRACT_ARCHIVE_CATEGORY: Lazy<Category> =
+            Lazy::new(|| Category::try_from("extract_archive").unwrap());
+
+        &EXTRACT_ARCHIVE_CATEGORY
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        Some(self.output().get_path().short_path().as_str())
+    }
+
+    fn aquery_attributes(&self, _fs: &ExecutorFs) -> IndexMap<String, String> {
+        let mut attributes = indexmap! {
+            "format".to_owned() => self.format.to_string(),
+        };
+        self.filter.aquery_attributes(&mut attributes);
+        attributes
+    }
+}
+
+#[async_trait]
+impl IncrementalActionExecutable for ExtractArchiveAction {
+    async fn execute(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        let (input, _) = ctx
+            .artifact_values(self.input())
+            .iter()
+            .into_singleton()
+            .context("Input did not dereference to exactly one artifact")?;
+
+        let archive = ctx.fs().resolve(input.get_path())?;
+        let output = ctx.fs().resolve_build(self.output().get_path());
+
+        if !input.is_source() {
+            ctx.materializer()
+                .ensure_materialized(vec![archive.clone()])
+                .await?;
+        }
+        ctx.cleanup_outputs().await?;
+
+        let execution_start = Instant::now();
+        let fs = ctx.fs().fs();
+        let extracted = ctx
+            .blocking_executor()
+            .execute_io_inline(|| extract_archive(fs, self.format, &self.filter, &archive, &output))
+            .await?;
+
+        let mut builder = ActionDirectoryBuilder::empty();
+        insert_entry(
+            &mut builder,
+            output.as_forward_relative_path(),
+            DirectoryEntry::Dir(extracted),
+        )?;
+        let value = extract_artifact_value(&builder, output.as_forward_relative_path())?
+            .context("Extracted directory is missing")?;
+
+        ctx.materializer()
+            .declare_existing(vec![(output, value.dupe())])
+            .await?;
+
+        Ok((
+            ActionOutputs::from_single(self.output().get_path().dupe(), value),
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData {
+                    wall_time: execution_start.elapsed(),
+                },
+            },
+        ))
+    }
+}
@@ -7,9 +7,12 @@
  * of this source tree.
  */
 
+pub mod archive;
 pub mod cas_artifact;
 pub mod copy;
+pub mod create_archive;
 pub mod download_file;
+pub mod extract_archive;
 pub mod run;
 pub mod symlinked_dir;
 pub mod write;
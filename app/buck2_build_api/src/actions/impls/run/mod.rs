@@ -10,6 +10,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 
 use allocative::Allocative;
 use async_trait::async_trait;
@@ -22,6 +23,8 @@ use buck2_execute::execute::request::ActionMetadataBlob;
 use buck2_execute::execute::request::CommandExecutionInput;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::ResourceLimits;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::path::buck_out_path::BuckOutPath;
 use dupe::Dupe;
 use gazebo::prelude::*;
@@ -122,6 +125,11 @@ pub(crate) struct UnregisteredRunAction {
     pub no_outputs_cleanup: bool,
     pub allow_cache_upload: bool,
     pub force_full_hybrid_if_capable: bool,
+    /// Set if the action can run in a persistent worker.
+    pub worker_protocol: Option<WorkerProtocol>,
+    /// Kill the command if it runs for longer than this.
+    pub timeout: Option<Duration>,
+    pub resource_limits: ResourceLimits,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -274,6 +282,15 @@ impl Action for RunAction {
                 Some(x) => x.to_string(),
             },
             "no_outputs_cleanup".to_owned() => self.inner.no_outputs_cleanup.to_string(),
+            "worker_protocol".to_owned() => match &self.inner.worker_protocol {
+                None => "None".to_owned(),
+                Some(x) => x.to_string(),
+            },
+            "timeout".to_owned() => match &self.inner.timeout {
+                None => "None".to_owned(),
+                Some(x) => format!("{}s", x.as_secs()),
+            },
+            "resource_limits".to_owned() => self.inner.resource_limits.to_string(),
         }
     }
 }
@@ -382,7 +399,7 @@ impl IncrementalActionExecutable for RunAction {
         // Run actions are assumed to be shared
         let host_sharing_requirements = HostSharingRequirements::Shared(self.inner.weight);
 
-        let req = CommandExecutionRequest::new(
+        let mut req = CommandExecutionRequest::new(
             cli,
             inputs,
             self.outputs
@@ -397,7 +414,12 @@ impl IncrementalActionExecutable for RunAction {
         .with_outputs_cleanup(!self.inner.no_outputs_cleanup)
         .with_allow_cache_upload(self.inner.allow_cache_upload)
         .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
-        .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable);
+        .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
+        .with_worker_protocol(self.inner.worker_protocol)
+        .with_resource_limits(self.inner.resource_limits);
+        if let Some(timeout) = self.inner.timeout {
+            req = req.with_timeout(timeout);
+        }
 
         let (outputs, meta) = ctx.exec_cmd(&req).await?;
 
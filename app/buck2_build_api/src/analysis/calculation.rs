@@ -431,6 +431,7 @@ mod tests {
     use buck2_common::legacy_configs::LegacyBuckConfigs;
     use buck2_common::package_listing::listing::testing::PackageListingExt;
     use buck2_common::package_listing::listing::PackageListing;
+    use buck2_common::result::ToSharedResultExt;
     use buck2_core::build_file_path::BuildFilePath;
     use buck2_core::bzl::ImportPath;
     use buck2_core::cells::cell_root_path::CellRootPathBuf;
@@ -446,6 +447,9 @@ mod tests {
     use buck2_core::package::PackageLabel;
     use buck2_core::provider::id::testing::ProviderIdExt;
     use buck2_core::provider::id::ProviderId;
+    use buck2_core::provider::label::ConfiguredProvidersLabel;
+    use buck2_core::provider::label::ProviderName;
+    use buck2_core::provider::label::ProvidersName;
     use buck2_core::target::testing::TargetLabelExt;
     use buck2_core::target::TargetLabel;
     use buck2_events::dispatch::EventDispatcher;
@@ -458,6 +462,7 @@ mod tests {
     use buck2_interpreter::file_loader::LoadedModules;
     use buck2_interpreter_for_build::interpreter::calculation::testing::InterpreterResultsKey;
     use buck2_interpreter_for_build::interpreter::configuror::BuildInterpreterConfiguror;
+    use buck2_node::compatibility::MaybeCompatible;
     use dice::testing::DiceBuilder;
     use dice::UserComputationData;
     use dupe::Dupe;
@@ -465,12 +470,16 @@ mod tests {
     use itertools::Itertools;
     use maplit::hashmap;
 
+    use crate::analysis::calculation::testing::AnalysisKey;
     use crate::analysis::calculation::RuleAnalysisCalculation;
+    use crate::analysis::AnalysisResult;
     use crate::configuration::calculation::ExecutionPlatformsKey;
     use crate::deferred::types::testing::DeferredAnalysisResultExt;
+    use crate::deferred::types::DeferredTable;
     use crate::interpreter::context::configure_build_file_globals;
     use crate::interpreter::context::configure_extension_file_globals;
     use crate::interpreter::rule_defs::provider::builtin::default_info::DefaultInfoCallable;
+    use crate::interpreter::rule_defs::provider::testing::FrozenProviderCollectionValueExt;
     use crate::interpreter::testing::Tester;
     use crate::query::analysis::environment::ConfiguredGraphQueryEnvironment;
     use crate::spawner::BuckSpawner;
@@ -632,4 +641,84 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_get_providers() -> anyhow::Result<()> {
+        let target =
+            TargetLabel::testing_parse("cell//pkg:foo").configure(Configuration::testing_new());
+        let provider_collection = FrozenProviderCollectionValueExt::testing_new(indoc!(
+            r#"
+            Foo = provider(fields=["x"])
+            [DefaultInfo(sub_targets={"sub": [Foo(x=2)]}), Foo(x=1)]
+            "#
+        ));
+
+        let fs = ProjectRootTemp::new()?;
+        let dice = DiceBuilder::new()
+            .set_data(|data| data.set_testing_io_provider(&fs))
+            .mock_and_return(
+                AnalysisKey(target.dupe()),
+                anyhow::Ok(MaybeCompatible::Compatible(AnalysisResult::new(
+                    provider_collection,
+                    DeferredTable::new(Vec::new()),
+                    None,
+                )))
+                .shared_error(),
+            )
+            .build(UserComputationData::new())?;
+
+        let providers = dice
+            .get_providers(&ConfiguredProvidersLabel::new(
+                target.dupe(),
+                ProvidersName::Default,
+            ))
+            .await?
+            .require_compatible()?;
+        assert_eq!(
+            providers
+                .provider_collection()
+                .default_info()
+                .sub_targets()
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec!["sub"]
+        );
+
+        let sub_providers = dice
+            .get_providers(&ConfiguredProvidersLabel::new(
+                target.dupe(),
+                ProvidersName::Named(vec![ProviderName::new("sub".to_owned())?]),
+            ))
+            .await?
+            .require_compatible()?;
+        assert_eq!(
+            sub_providers
+                .provider_collection()
+                .provider_names()
+                .iter()
+                .sorted()
+                .eq(vec!["DefaultInfo", "Foo"]),
+            true
+        );
+        assert_eq!(
+            sub_providers
+                .provider_collection()
+                .default_info()
+                .sub_targets()
+                .is_empty(),
+            true
+        );
+
+        assert!(
+            dice.get_providers(&ConfiguredProvidersLabel::new(
+                target,
+                ProvidersName::Named(vec![ProviderName::new("missing".to_owned())?]),
+            ))
+            .await
+            .is_err()
+        );
+
+        Ok(())
+    }
 }
@@ -28,6 +28,7 @@ pub static EXECUTION_PLATFORM: Lazy<ExecutionPlatformResolution> = Lazy::new(||
                 executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
                 path_separator: PathSeparatorKind::system_default(),
                 cache_upload_behavior: CacheUploadBehavior::Disabled,
+                default_action_timeout: None,
             },
             Configuration::unspecified(),
         )),
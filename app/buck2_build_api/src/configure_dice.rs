@@ -11,26 +11,39 @@ use std::sync::Arc;
 
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::data::SetIoProvider;
+use buck2_common::dice::file_ops::PersistFileOps;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use dice::cycles::DetectCycles;
 use dice::Dice;
+use dice::DiceSnapshot;
 
 use crate::bxl::calculation::BxlCalculationDyn;
 
 /// Utility to configure the dice globals.
 /// One place to not forget to initialize something in all places.
+///
+/// The `snapshot` is restored before anything is injected, so the caller must have checked that
+/// the file watcher can invalidate whatever changed since it was taken.
 pub fn configure_dice_for_buck(
     io: Arc<dyn IoProvider>,
     bxl: &'static dyn BxlCalculationDyn,
     root_config: Option<&LegacyBuckConfig>,
     detect_cycles: Option<DetectCycles>,
+    snapshot: Option<&DiceSnapshot>,
 ) -> anyhow::Result<Arc<Dice>> {
     let mut dice = Dice::builder();
     dice.set_io_provider(io);
     dice.set(bxl);
 
+    let persist = root_config
+        .and_then(|c| c.parse::<bool>("buck2", "dice_snapshots").transpose())
+        .unwrap_or(Ok(false))?;
+    if persist {
+        dice.persist_file_ops();
+    }
+
     let detect_cycles = detect_cycles.map_or_else(
         || {
             root_config
@@ -44,6 +57,10 @@ pub fn configure_dice_for_buck(
     )?;
 
     let dice = dice.build(detect_cycles);
+    if let Some(snapshot) = snapshot {
+        let restored = dice.restore_snapshot(snapshot)?;
+        tracing::info!("Restored {} DICE nodes from the previous daemon", restored);
+    }
     let dice_ctx = dice.ctx();
     dice_ctx.set_none_cell_resolver()?;
     dice_ctx.set_none_legacy_configs()?;
@@ -71,7 +71,8 @@ enum JsonError {
     NumberOutOfBounds(String),
 }
 
-fn json_convert<'v>(v: serde_json::Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+/// Converts a parsed JSON value into the equivalent Starlark value.
+pub fn json_convert<'v>(v: serde_json::Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
     match v {
         serde_json::Value::Null => Ok(Value::new_none()),
         serde_json::Value::Bool(x) => Ok(Value::new_bool(x)),
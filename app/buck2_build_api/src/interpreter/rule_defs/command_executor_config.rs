@@ -9,6 +9,7 @@
 
 use std::borrow::Cow;
 use std::fmt;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -40,6 +41,8 @@ use thiserror::Error;
 enum CommandExecutorConfigErrors {
     #[error("expected a dict, got `{0}` (type `{1}`)")]
     RePropertiesNotADict(String, String),
+    #[error("default_action_timeout_seconds must be positive, got `{0}`")]
+    NonPositiveDefaultActionTimeout(i32),
 }
 
 #[derive(Clone, Debug, Trace, ProvidesStaticType, Allocative)]
@@ -75,6 +78,8 @@ struct StarlarkCommandExecutorConfig<'v> {
     pub(super) max_cache_upload_mebibytes: Option<i32>,
     /// Whether to use the experimental low pass filter.
     pub(super) experimental_low_pass_filter: bool,
+    /// Timeout for commands that don't set their own `timeout_seconds`.
+    pub(super) default_action_timeout_seconds: Option<i32>,
 }
 
 impl<'v> fmt::Display for StarlarkCommandExecutorConfig<'v> {
@@ -110,9 +115,14 @@ impl<'v> fmt::Display for StarlarkCommandExecutorConfig<'v> {
         )?;
         write!(
             f,
-            "use_windows_path_separators = {}",
+            "use_windows_path_separators = {}, ",
             self.use_windows_path_separators
         )?;
+        write!(
+            f,
+            "default_action_timeout_seconds = {:?}",
+            self.default_action_timeout_seconds
+        )?;
         write!(f, ")")?;
         Ok(())
     }
@@ -195,6 +205,15 @@ impl<'v> StarlarkCommandExecutorConfig<'v> {
             .context("max_cache_upload_mebibytes is negative")?
             .map(|b| b * 1024 * 1024);
 
+        let default_action_timeout = match self.default_action_timeout_seconds {
+            Some(secs) if secs <= 0 => {
+                return Err(
+                    CommandExecutorConfigErrors::NonPositiveDefaultActionTimeout(secs).into(),
+                );
+            }
+            secs => secs.map(|secs| Duration::from_secs(secs as u64)),
+        };
+
         Ok(CommandExecutorConfig {
             executor_kind: CommandExecutorKind::new(local_options, remote_options, hybrid_level)?,
             path_separator: if self.use_windows_path_separators {
@@ -209,6 +228,7 @@ impl<'v> StarlarkCommandExecutorConfig<'v> {
             } else {
                 CacheUploadBehavior::Disabled
             },
+            default_action_timeout,
         })
     }
 }
@@ -282,6 +302,9 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
             i32,
         >,
         #[starlark(default = false, require = named)] experimental_low_pass_filter: bool,
+        #[starlark(default = NoneOr::None, require = named)] default_action_timeout_seconds: NoneOr<
+            i32,
+        >,
         heap: &'v Heap,
     ) -> anyhow::Result<Value<'v>> {
         let config = StarlarkCommandExecutorConfig {
@@ -299,6 +322,7 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
             allow_cache_uploads,
             max_cache_upload_mebibytes: max_cache_upload_mebibytes.into_option(),
             experimental_low_pass_filter,
+            default_action_timeout_seconds: default_action_timeout_seconds.into_option(),
         };
         // This checks that the values are valid.
         config.to_command_executor_config()?;
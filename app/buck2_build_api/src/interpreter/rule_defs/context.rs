@@ -12,6 +12,7 @@ use std::cell::RefMut;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -22,6 +23,8 @@ use buck2_core::collections::ordered_set::OrderedSet;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::paths::RelativePathBuf;
 use buck2_execute::execute::request::OutputType;
+use buck2_execute::execute::request::ResourceLimits;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::materialize::http::Checksum;
 use buck2_interpreter::starlark_promise::StarlarkPromise;
 use buck2_interpreter::types::label::Label;
@@ -65,12 +68,16 @@ use starlark_map::small_set::SmallSet;
 use thiserror::Error;
 
 use crate::actions::artifact::OutputArtifact;
+use crate::actions::impls::archive::ArchiveFilter;
+use crate::actions::impls::archive::ArchiveFormat;
 use crate::actions::impls::cas_artifact::ArtifactKind;
 use crate::actions::impls::cas_artifact::DirectoryKind;
 use crate::actions::impls::cas_artifact::UnregisteredCasArtifactAction;
 use crate::actions::impls::copy::CopyMode;
 use crate::actions::impls::copy::UnregisteredCopyAction;
+use crate::actions::impls::create_archive::UnregisteredCreateArchiveAction;
 use crate::actions::impls::download_file::UnregisteredDownloadFileAction;
+use crate::actions::impls::extract_archive::UnregisteredExtractArchiveAction;
 use crate::actions::impls::run::dep_files::RunActionDepFiles;
 use crate::actions::impls::run::new_executor_preference;
 use crate::actions::impls::run::MetadataParameter;
@@ -338,6 +345,10 @@ enum RunActionError {
     InvalidWeight(i32),
     #[error("`weight` and `weight_percentage` cannot both be passed")]
     DuplicateWeightsSpecified,
+    #[error("`timeout_seconds` must be a positive integer, got `{0}`")]
+    InvalidTimeout(i32),
+    #[error("`{0}` must be a positive integer, got `{1}`")]
+    InvalidResourceLimit(&'static str, i32),
     #[error("`dep_files` values must be artifact tags, got `{}` for key `{}`", .value, .key)]
     InvalidDepFileTag { key: String, value: String },
     #[error("`dep_files` value with key `{}` has an invalid count of associated outputs. Expected 1, got {}.", .key, .count)]
@@ -710,6 +721,76 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         create_dir_tree(eval, this, output, srcs, true)
     }
 
+    /// Extract a `tar`, `tar.gz`, `tar.zst` or `zip` archive into a directory. Entries outside of
+    /// `strip_prefix` are skipped, and the globs are matched against paths with it removed.
+    fn extract_archive<'v>(
+        this: &AnalysisActions<'v>,
+        #[starlark(require = pos)] output: Value<'v>,
+        #[starlark(require = pos)] src: Value<'v>,
+        #[starlark(require = named, default = NoneOr::None)] format: NoneOr<&str>,
+        #[starlark(require = named, default = NoneOr::None)] strip_prefix: NoneOr<&str>,
+        #[starlark(require = named, default = Vec::new())] includes: Vec<String>,
+        #[starlark(require = named, default = Vec::new())] excludes: Vec<String>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let src = src
+            .as_artifact()
+            .ok_or_else(|| ValueError::IncorrectParameterTypeNamed("src".to_owned()))?;
+        let artifact = src.get_bound_artifact()?;
+        let format = format.into_option().map(ArchiveFormat::parse).transpose()?;
+        let filter = ArchiveFilter::new(strip_prefix.into_option(), includes, excludes)?;
+
+        let mut this = this.state();
+        let (declaration, output_artifact) =
+            this.get_or_declare_output(eval, output, "output", OutputType::Directory)?;
+        this.register_action(
+            indexset![ArtifactGroup::Artifact(artifact)],
+            indexset![output_artifact],
+            UnregisteredExtractArchiveAction::new(format, filter),
+            None,
+        )?;
+
+        Ok(declaration.into_declared_artifact(Default::default()))
+    }
+
+    /// Create a `tar`, `tar.gz`, `tar.zst` or `zip` archive of `srcs`, a dict from paths in the
+    /// archive to artifacts. The archive is the same for the same inputs: entries are sorted, and
+    /// have no timestamps or owners.
+    fn create_archive<'v>(
+        this: &AnalysisActions<'v>,
+        #[starlark(require = pos)] output: Value<'v>,
+        #[starlark(require = pos)] srcs: DictOf<'v, &'v str, Value<'v>>,
+        #[starlark(require = named, default = NoneOr::None)] format: NoneOr<&str>,
+        #[starlark(require = named, default = NoneOr::None)] strip_prefix: NoneOr<&str>,
+        #[starlark(require = named, default = Vec::new())] includes: Vec<String>,
+        #[starlark(require = named, default = Vec::new())] excludes: Vec<String>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let srcs = srcs
+            .collect_entries()
+            .into_iter()
+            .map(|(path, src)| {
+                let src = src
+                    .as_artifact()
+                    .ok_or_else(|| ValueError::IncorrectParameterTypeNamed("srcs".to_owned()))?;
+                anyhow::Ok((
+                    ArtifactGroup::Artifact(src.get_bound_artifact()?),
+                    path.to_owned(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let format = format.into_option().map(ArchiveFormat::parse).transpose()?;
+        let filter = ArchiveFilter::new(strip_prefix.into_option(), includes, excludes)?;
+        let action = UnregisteredCreateArchiveAction::new(format, filter, srcs)?;
+
+        let mut this = this.state();
+        let (declaration, output_artifact) =
+            this.get_or_declare_output(eval, output, "output", OutputType::File)?;
+        this.register_action(action.inputs(), indexset![output_artifact], action, None)?;
+
+        Ok(declaration.into_declared_artifact(Default::default()))
+    }
+
     fn run<'v>(
         this: &AnalysisActions<'v>,
         #[starlark(require = pos)] arguments: Value<'v>,
@@ -728,6 +809,11 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] no_outputs_cleanup: bool,
         #[starlark(require = named, default = false)] allow_cache_upload: bool,
         #[starlark(require = named, default = false)] force_full_hybrid_if_capable: bool,
+        #[starlark(require = named, default = false)] supports_workers: bool,
+        #[starlark(require = named, default = "proto")] worker_protocol: &str,
+        #[starlark(require = named)] timeout_seconds: Option<i32>,
+        #[starlark(require = named)] memory_limit_mebibytes: Option<i32>,
+        #[starlark(require = named)] cpu_limit_millicores: Option<i32>,
         heap: &'v Heap,
     ) -> anyhow::Result<NoneType> {
         struct RunCommandArtifactVisitor {
@@ -765,6 +851,7 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
         }
 
         let executor_preference = new_executor_preference(local_only, prefer_local)?;
+        let worker_protocol = WorkerProtocol::parse(worker_protocol)?;
 
         let mut artifact_visitor = RunCommandArtifactVisitor::new();
 
@@ -788,6 +875,21 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
             }
         };
 
+        let timeout = match timeout_seconds {
+            Some(v) if v < 1 => return Err(RunActionError::InvalidTimeout(v).into()),
+            v => v.map(|v| Duration::from_secs(v as u64)),
+        };
+
+        let resource_limit = |name, limit: Option<i32>| match limit {
+            Some(v) if v < 1 => Err(RunActionError::InvalidResourceLimit(name, v)),
+            v => Ok(v.map(|v| v as u64)),
+        };
+        let resource_limits = ResourceLimits {
+            memory_max_bytes: resource_limit("memory_limit_mebibytes", memory_limit_mebibytes)?
+                .map(|m| m * 1024 * 1024),
+            cpu_max_millicores: resource_limit("cpu_limit_millicores", cpu_limit_millicores)?,
+        };
+
         let starlark_env = match env {
             None => Value::new_none(),
             Some(env) => {
@@ -871,6 +973,9 @@ fn register_context_actions(builder: &mut MethodsBuilder) {
             no_outputs_cleanup,
             allow_cache_upload,
             force_full_hybrid_if_capable,
+            worker_protocol: supports_workers.then_some(worker_protocol),
+            timeout,
+            resource_limits,
         };
         this.state().register_action(
             artifacts.inputs,
@@ -1239,6 +1344,86 @@ mod tests {
         })
     }
 
+    #[test]
+    fn run_invalid_worker_protocol() -> anyhow::Result<()> {
+        let content = indoc!(
+            r#"
+             def test(c):
+                 a = c.actions.declare_output("a")
+                 c.actions.run([a.as_output(), "@args"], category = "test_category", supports_workers = True, worker_protocol = "xml")
+             "#
+        );
+
+        let expect = "Invalid worker protocol `xml`";
+        run_ctx_test(content, |ret| match ret {
+            Err(e) if e.to_string().contains(expect) => Ok(()),
+            _ => panic!(
+                "Expected a specific failure containing `{}`, got {:?}",
+                expect, ret
+            ),
+        })
+    }
+
+    #[test]
+    fn run_invalid_timeout() -> anyhow::Result<()> {
+        let content = indoc!(
+            r#"
+             def test(c):
+                 a = c.actions.declare_output("a")
+                 c.actions.run([a.as_output()], category = "test_category", timeout_seconds = 0)
+             "#
+        );
+
+        let expect = "`timeout_seconds` must be a positive integer, got `0`";
+        run_ctx_test(content, |ret| match ret {
+            Err(e) if e.to_string().contains(expect) => Ok(()),
+            _ => panic!(
+                "Expected a specific failure containing `{}`, got {:?}",
+                expect, ret
+            ),
+        })
+    }
+
+    #[test]
+    fn run_invalid_resource_limit() -> anyhow::Result<()> {
+        let content = indoc!(
+            r#"
+             def test(c):
+                 a = c.actions.declare_output("a")
+                 c.actions.run([a.as_output()], category = "test_category", memory_limit_mebibytes = -1)
+             "#
+        );
+
+        let expect = "`memory_limit_mebibytes` must be a positive integer, got `-1`";
+        run_ctx_test(content, |ret| match ret {
+            Err(e) if e.to_string().contains(expect) => Ok(()),
+            _ => panic!(
+                "Expected a specific failure containing `{}`, got {:?}",
+                expect, ret
+            ),
+        })
+    }
+
+    #[test]
+    fn extract_archive_unknown_format() -> anyhow::Result<()> {
+        let content = indoc!(
+            r#"
+             def test(c):
+                 a = c.actions.write("a.rar", "")
+                 c.actions.extract_archive("out", a, format = "rar")
+             "#
+        );
+
+        let expect = "Unknown archive format `rar`";
+        run_ctx_test(content, |ret| match ret {
+            Err(e) if e.to_string().contains(expect) => Ok(()),
+            _ => panic!(
+                "Expected a specific failure containing `{}`, got {:?}",
+                expect, ret
+            ),
+        })
+    }
+
     #[test]
     fn declare_output_require_bound() -> anyhow::Result<()> {
         let content = indoc!(
@@ -113,6 +113,38 @@ where
     Ok(res)
 }
 
+/// Evaluate a series of futures, returning a series of results.
+/// Unlike try_join_all, whether to continue past the first error is decided by the caller rather
+/// than by KEEP_GOING. If `keep_going` is true, all futures are evaluated and every error is
+/// returned, otherwise this stops at the first error.
+pub async fn try_join_all_or_errors<C, R, E>(
+    keep_going: bool,
+    mut inputs: impl Stream<Item = Result<R, E>> + Unpin,
+) -> Result<C, Vec<E>>
+where
+    C: KeepGoingCollectable<R>,
+{
+    let size = inputs.size_hint().0;
+    let mut res = C::with_capacity(size);
+    let mut errors = Vec::new();
+    while let Some(x) = inputs.next().await {
+        match x {
+            Ok(x) => res.push(x),
+            Err(e) => {
+                errors.push(e);
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(res)
+    } else {
+        Err(errors)
+    }
+}
+
 /// Similar to try_join_all, but this is meant to combine the outcomes of try_join_all, so it
 /// doesn't do any recording on its own.
 pub async fn try_join<A, B, E>(
@@ -180,3 +212,42 @@ impl<I> KeepGoingCollectable<I> for SmallVec<[I; 1]> {
         SmallVec::push(self, item);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use futures::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_try_join_all_or_errors_ok() {
+        let res: Result<Vec<_>, Vec<&str>> =
+            try_join_all_or_errors(false, stream::iter([Ok(1), Ok(2)])).await;
+        assert_eq!(Ok(vec![1, 2]), res);
+    }
+
+    #[tokio::test]
+    async fn test_try_join_all_or_errors_keep_going() {
+        let polled = AtomicUsize::new(0);
+        let inputs = stream::iter([Ok(1), Err("a"), Ok(2), Err("b")]).inspect(|_| {
+            polled.fetch_add(1, Ordering::Relaxed);
+        });
+        let res: Result<Vec<_>, _> = try_join_all_or_errors(true, inputs).await;
+        assert_eq!(Err(vec!["a", "b"]), res);
+        assert_eq!(4, polled.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_try_join_all_or_errors_stops_at_first_error() {
+        let polled = AtomicUsize::new(0);
+        let inputs = stream::iter([Ok(1), Err("a"), Ok(2), Err("b")]).inspect(|_| {
+            polled.fetch_add(1, Ordering::Relaxed);
+        });
+        let res: Result<Vec<_>, _> = try_join_all_or_errors(false, inputs).await;
+        assert_eq!(Err(vec!["a"]), res);
+        assert_eq!(2, polled.load(Ordering::Relaxed));
+    }
+}
@@ -13,9 +13,11 @@ use std::collections::VecDeque;
 use std::hash::Hash;
 use std::sync::Arc;
 
+use allocative::Allocative;
 use async_trait::async_trait;
 use buck2_core::build_file_path::BuildFilePath;
 use buck2_core::cells::cell_path::CellPath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_execute::artifact::fs::ArtifactFs;
 use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_query::query::environment::LabeledNode;
@@ -41,6 +43,7 @@ use serde::Serializer;
 
 use crate::actions::key::ActionKey;
 use crate::actions::RegisteredAction;
+use crate::artifact_groups::ArtifactGroup;
 use crate::artifact_groups::TransitiveSetProjectionKey;
 use crate::query::cquery::environment::CqueryDelegate;
 use crate::query::uquery::environment::QueryLiterals;
@@ -95,12 +98,14 @@ pub enum ActionInput {
     IndirectInputs(SetProjectionInputs),
 }
 
-#[derive(Derivative, Clone, Dupe)]
-#[derivative(Debug)]
+#[derive(Derivative, Clone, Dupe, Allocative)]
+#[derivative(Debug, Eq, Hash, PartialEq)]
 pub struct ActionQueryNode {
     action: Arc<RegisteredAction>,
+    #[derivative(Hash = "ignore", PartialEq = "ignore")]
+    #[allocative(skip)]
     deps: Arc<Vec<ActionInput>>,
-    #[derivative(Debug = "ignore")]
+    #[derivative(Debug = "ignore", Hash = "ignore", PartialEq = "ignore")]
     fs: Arc<ArtifactFs>,
 }
 
@@ -119,6 +124,29 @@ impl ActionQueryNode {
             self.action.execution_config().path_separator,
         ))
     }
+
+    /// The paths of the artifacts this action reads directly. Inputs that come from transitive
+    /// sets are not included, they are reachable through `deps()`.
+    pub fn inputs(&self) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+        self.action
+            .inputs()?
+            .iter()
+            .filter_map(|input| match input {
+                ArtifactGroup::Artifact(artifact) => Some(self.fs.resolve(artifact.get_path())),
+                ArtifactGroup::TransitiveSetProjection(..) => None,
+            })
+            .collect()
+    }
+
+    /// The paths of the artifacts this action produces.
+    pub fn outputs(&self) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+        Ok(self
+            .action
+            .outputs()?
+            .iter()
+            .map(|output| self.fs.resolve_build(output.get_path()))
+            .collect())
+    }
 }
 
 impl LabeledNode for ActionQueryNode {
@@ -357,3 +385,96 @@ impl<'c> QueryEnvironment for AqueryEnvironment<'c> {
         Err(QueryError::NotAvailableInContext("owner").into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::executor_config::CommandExecutorConfig;
+    use buck2_core::buck_path::BuckPath;
+    use buck2_core::category::Category;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::paths::CellRelativePath;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::cells::CellName;
+    use buck2_core::cells::CellResolver;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_core::package::package_relative_path::PackageRelativePathBuf;
+    use buck2_core::package::PackageLabel;
+    use buck2_core::target::testing::ConfiguredTargetLabelExt;
+    use buck2_core::target::ConfiguredTargetLabel;
+    use buck2_core::target::TargetName;
+    use buck2_execute::artifact::source_artifact::SourceArtifact;
+    use buck2_execute::base_deferred_key::BaseDeferredKey;
+    use buck2_execute::path::buck_out_path::BuckOutPathResolver;
+    use buck2_execute::path::buck_out_path::BuckPathResolver;
+    use indexmap::indexset;
+
+    use super::*;
+    use crate::actions::artifact::build_artifact::BuildArtifact;
+    use crate::actions::artifact::testing::BuildArtifactTestingExt;
+    use crate::actions::artifact::Artifact;
+    use crate::actions::testings::SimpleAction;
+    use crate::deferred::types::testing::DeferredDataExt;
+    use crate::deferred::types::testing::DeferredIdExt;
+    use crate::deferred::types::DeferredData;
+    use crate::deferred::types::DeferredId;
+    use crate::deferred::types::DeferredKey;
+
+    #[test]
+    fn test_action_query_node_inputs_and_outputs() -> anyhow::Result<()> {
+        let temp_fs = ProjectRootTemp::new()?;
+        let fs = Arc::new(ArtifactFs::new(
+            BuckPathResolver::new(CellResolver::of_names_and_paths(&[(
+                CellName::unchecked_new("cell".into()),
+                CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell_path".into())),
+            )])),
+            BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new("buck-out/v2".into())),
+            temp_fs.path().dupe(),
+        ));
+
+        let pkg = PackageLabel::new(
+            &CellName::unchecked_new("cell".into()),
+            CellRelativePath::unchecked_new("pkg"),
+        );
+        let label = ConfiguredTargetLabel::testing_new(
+            pkg.dupe(),
+            TargetName::unchecked_new("foo"),
+            Configuration::testing_new(),
+        );
+        let input = ArtifactGroup::Artifact(Artifact::from(SourceArtifact::new(BuckPath::new(
+            pkg,
+            PackageRelativePathBuf::unchecked_new("source".into()),
+        ))));
+        let output = BuildArtifact::testing_new(
+            label.dupe(),
+            ForwardRelativePathBuf::unchecked_new("output".into()),
+            DeferredId::testing_new(0),
+        );
+
+        let action = RegisteredAction::new(
+            ActionKey::new(DeferredData::testing_new(DeferredKey::Base(
+                BaseDeferredKey::TargetLabel(label),
+                DeferredId::testing_new(0),
+            ))),
+            box SimpleAction::new(
+                indexset![input],
+                indexset![output.dupe()],
+                vec!["cmd".to_owned()],
+                Category::try_from("fake_action").unwrap(),
+                None,
+            ),
+            CommandExecutorConfig::testing_local(),
+        );
+        let node = ActionQueryNode::new(Arc::new(action), Vec::new(), fs.dupe());
+
+        assert_eq!(
+            vec![ProjectRelativePathBuf::unchecked_new(
+                "cell_path/pkg/source".to_owned()
+            )],
+            node.inputs()?
+        );
+        assert_eq!(vec![fs.resolve_build(output.get_path())], node.outputs()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use allocative::Allocative;
+use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
+use buck2_build_api::query::aquery::environment::AqueryEnvironment;
+use buck2_build_api::query::aquery::evaluator::get_aquery_evaluator;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_core::target::TargetLabel;
+use buck2_interpreter::types::label::Label;
+use derivative::Derivative;
+use derive_more::Display;
+use dupe::Dupe;
+use gazebo::any::ProvidesStaticType;
+use starlark::environment::Methods;
+use starlark::environment::MethodsBuilder;
+use starlark::environment::MethodsStatic;
+use starlark::eval::Evaluator;
+use starlark::starlark_module;
+use starlark::starlark_type;
+use starlark::values::dict::Dict;
+use starlark::values::type_repr::StarlarkTypeRepr;
+use starlark::values::AllocValue;
+use starlark::values::Heap;
+use starlark::values::NoSerialize;
+use starlark::values::StarlarkValue;
+use starlark::values::Trace;
+use starlark::values::UnpackValue;
+use starlark::values::Value;
+use starlark::values::ValueLike;
+use starlark::StarlarkDocs;
+
+use crate::bxl::starlark_defs::context::BxlContext;
+use crate::bxl::starlark_defs::providers_expr::ProvidersExpr;
+use crate::bxl::starlark_defs::query_util::parse_query_evaluation_result;
+use crate::bxl::value_as_starlark_target_label::ValueAsStarlarkTargetLabel;
+
+#[derive(
+    ProvidesStaticType,
+    Derivative,
+    Display,
+    Trace,
+    NoSerialize,
+    Allocative,
+    StarlarkDocs
+)]
+#[starlark_docs(directory = "bxl")]
+#[derivative(Debug)]
+#[display(fmt = "{:?}", self)]
+#[allocative(skip)]
+pub struct StarlarkAQueryCtx<'v> {
+    #[trace(unsafe_ignore)]
+    #[derivative(Debug = "ignore")]
+    ctx: &'v BxlContext<'v>,
+    #[derivative(Debug = "ignore")]
+    target_platform: Option<TargetLabel>,
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkAQueryCtx<'v> {
+    starlark_type!("aqueryctx");
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(register_aquery)
+    }
+}
+
+impl<'v> AllocValue<'v> for StarlarkAQueryCtx<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex_no_freeze(self)
+    }
+}
+
+impl<'v> StarlarkTypeRepr for &'v StarlarkAQueryCtx<'v> {
+    fn starlark_type_repr() -> String {
+        StarlarkAQueryCtx::get_type_starlark_repr()
+    }
+}
+
+impl<'v> UnpackValue<'v> for &'v StarlarkAQueryCtx<'v> {
+    fn unpack_value(x: Value<'v>) -> Option<&'v StarlarkAQueryCtx<'v>> {
+        x.downcast_ref()
+    }
+}
+
+impl<'v> StarlarkAQueryCtx<'v> {
+    pub fn new(
+        ctx: &'v BxlContext<'v>,
+        global_target_platform: Value<'v>,
+    ) -> anyhow::Result<StarlarkAQueryCtx<'v>> {
+        let target_platform =
+            global_target_platform.parse_target_platforms(&ctx.target_alias_resolver, &ctx.cell)?;
+
+        Ok(Self {
+            ctx,
+            target_platform,
+        })
+    }
+}
+
+/// The context for performing `aquery` operations in bxl. The functions offered on this ctx are
+/// the same behaviour as the query functions available within aquery command.
+///
+/// Query results are `[StarlarkTargetSet]`s of `[StarlarkActionQueryNode]`s. Evaluating them runs
+/// the analysis of the targets involved, but doesn't build anything. The same goes for `providers`.
+#[starlark_module]
+fn register_aquery(builder: &mut MethodsBuilder) {
+    /// Evaluates some general query string. Target literals resolve to the actions that produce
+    /// the default outputs of the targets.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_eval(ctx):
+    ///     result = ctx.aquery().eval("deps(root//bin:the_binary)")
+    ///     ctx.output.print(result)
+    /// ```
+    fn eval<'v>(
+        this: &StarlarkAQueryCtx<'v>,
+        query: &'v str,
+        #[starlark(default = Vec::new())] query_args: Vec<String>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        this.ctx.async_ctx.via_dice(|ctx| async {
+            match get_aquery_evaluator(
+                ctx,
+                ctx.get_cell_resolver()
+                    .await?
+                    .get(this.ctx.current_bxl.label().bxl_path.cell())?
+                    .path(),
+                this.target_platform.dupe(),
+            )
+            .await
+            {
+                Ok(evaluator) => parse_query_evaluation_result::<AqueryEnvironment>(
+                    evaluator.eval_query(query, &query_args).await?,
+                    eval,
+                ),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// Returns the providers of the given `labels`, running analysis but not building anything.
+    /// Unconfigured targets are configured with the `target_platform` of this context.
+    ///
+    /// The given `labels` is a providers expression, which is either:
+    ///     - a single string that is a `target pattern`.
+    ///     - a single target node or label, configured or unconfigured
+    ///     - a single sub target label, configured or unconfigured
+    ///     - a list of the two options above.
+    ///
+    /// This returns either the provider collection if the given `labels` is "singular", or a dict
+    /// keyed by sub target labels of provider collections if the given `labels` is list-like.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_providers(ctx):
+    ///     providers = ctx.aquery().providers("root//bin:the_binary")
+    ///     ctx.output.print(providers[DefaultInfo])
+    /// ```
+    fn providers<'v>(
+        this: &StarlarkAQueryCtx<'v>,
+        labels: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let providers = ProvidersExpr::unpack_with_target_platform(
+            labels,
+            &this.target_platform,
+            this.ctx,
+            eval,
+        )?;
+
+        let collections = this.ctx.async_ctx.via_dice(|ctx| async {
+            futures::future::join_all(providers.labels().map(|label| async move {
+                anyhow::Ok((
+                    label.clone(),
+                    ctx.get_providers(label).await?.require_compatible()?,
+                ))
+            }))
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+
+        Ok(match providers {
+            ProvidersExpr::Literal(_) => match collections.into_iter().next() {
+                Some((_, collection)) => collection.value().owned_value(eval.frozen_heap()),
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Expected exactly 1 provider collection when requesting a single target"
+                    ));
+                }
+            },
+            ProvidersExpr::Iterable(_) => eval.heap().alloc(Dict::new(
+                collections
+                    .into_iter()
+                    .map(|(label, collection)| {
+                        Ok((
+                            eval.heap().alloc(Label::new(label)).get_hashed()?,
+                            collection.value().owned_value(eval.frozen_heap()),
+                        ))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            )),
+        })
+    }
+}
@@ -8,13 +8,17 @@
  */
 
 use allocative::Allocative;
+use anyhow::Context;
+use buck2_build_api::interpreter::rule_defs::artifact::json_convert;
 use buck2_common::dice::file_ops::HasFileOps;
 use buck2_common::file_ops::FileOps;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_execute::artifact::fs::ArtifactFs;
 use derivative::Derivative;
 use derive_more::Display;
+use dice::DiceComputations;
 use gazebo::any::ProvidesStaticType;
 use starlark::environment::Methods;
 use starlark::environment::MethodsBuilder;
@@ -165,6 +169,42 @@ fn fs_operations(builder: &mut MethodsBuilder) {
         }
     }
 
+    /// Returns the contents of the given file as a string. Errors if the path does not exist or
+    /// is not a file. Reads go through Buck's cached filesystem, so the bxl function is rerun
+    /// when the file changes.
+    /// The input is a either a literal, a source artifact (via `[StarlarkArtifact]`), or a `[StarlarkFileNode]`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_read(ctx):
+    ///     ctx.output.print(ctx.fs.read("root//bin/config.txt"))
+    /// ```
+    fn read<'v>(this: &BxlFilesystem<'v>, expr: FileExpr<'v>) -> anyhow::Result<String> {
+        let cell_path = expr.get(this.dice)?;
+        this.dice.via_dice(|ctx| read_file(ctx, &cell_path))
+    }
+
+    /// Returns the contents of the given file parsed as JSON, as Starlark dicts, lists, strings,
+    /// numbers, bools and `None`. Errors if the file can't be read or isn't valid JSON.
+    /// Like `read`, the bxl function is rerun when the file changes.
+    /// The input is a either a literal, a source artifact (via `[StarlarkArtifact]`), or a `[StarlarkFileNode]`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_read_json(ctx):
+    ///     manifest = ctx.fs.read_json("root//bin/manifest.json")
+    ///     ctx.output.print(manifest["version"])
+    /// ```
+    fn read_json<'v>(
+        this: &BxlFilesystem<'v>,
+        expr: FileExpr<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let cell_path = expr.get(this.dice)?;
+        let contents = this.dice.via_dice(|ctx| read_file(ctx, &cell_path))?;
+        parse_json(&cell_path, &contents, heap)
+    }
+
     /// Returns whether the provided path is a dir. Returns false is the dir does not exist.
     /// The input is a either a literal, a source artifact (via `[StarlarkArtifact]`), or a `[StarlarkFileNode]`.
     ///
@@ -190,9 +230,103 @@ fn fs_operations(builder: &mut MethodsBuilder) {
     }
 }
 
+/// Reads a file through DICE, so that changes to it invalidate the bxl function.
+async fn read_file(ctx: &DiceComputations, cell_path: &CellPath) -> anyhow::Result<String> {
+    ctx.file_ops().read_file(cell_path).await
+}
+
+/// Converts the contents of the JSON file at `cell_path` to Starlark values.
+fn parse_json<'v>(
+    cell_path: &CellPath,
+    contents: &str,
+    heap: &'v Heap,
+) -> anyhow::Result<Value<'v>> {
+    let value: serde_json::Value = serde_json::from_str(contents)
+        .with_context(|| format!("When parsing JSON file `{}`", cell_path))?;
+    json_convert(value, heap)
+}
+
 /// Returns the absolute path for a FileExpr.
 fn resolve<'v>(bxl_fs: &BxlFilesystem<'v>, expr: FileExpr<'v>) -> anyhow::Result<AbsNormPathBuf> {
     let cell_path = expr.get(bxl_fs.dice)?;
     let project_rel_path = bxl_fs.artifact_fs.resolve_cell_path(&cell_path)?;
     Ok(bxl_fs.project_fs.resolve(&project_rel_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use buck2_common::dice::cells::HasCellResolver;
+    use buck2_common::dice::data::testing::SetTestingIoProvider;
+    use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+    use buck2_common::legacy_configs::LegacyBuckConfigs;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::cells::CellName;
+    use buck2_core::cells::CellResolver;
+    use buck2_core::fs::project::ProjectRelativePath;
+    use buck2_core::fs::project::ProjectRelativePathBuf;
+    use buck2_core::fs::project::ProjectRootTemp;
+    use dice::testing::DiceBuilder;
+    use dice::DiceTransaction;
+    use dice::UserComputationData;
+
+    use super::*;
+
+    fn dice(fs: &ProjectRootTemp) -> anyhow::Result<DiceTransaction> {
+        let cell = CellName::unchecked_new("root".to_owned());
+        let dice = DiceBuilder::new()
+            .set_data(|d| d.set_testing_io_provider(fs))
+            .build(UserComputationData::new())?;
+        dice.set_cell_resolver(CellResolver::of_names_and_paths(&[(
+            cell.clone(),
+            CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new(String::new())),
+        )]))?;
+        dice.set_legacy_configs(LegacyBuckConfigs::new(HashMap::from_iter([(
+            cell,
+            legacy_buck_config_from_entries([])?,
+        )])))?;
+        Ok(dice.commit())
+    }
+
+    #[tokio::test]
+    async fn test_read_file() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        fs.path().write_file(
+            ProjectRelativePath::unchecked_new("config.txt"),
+            "contents",
+            false,
+        )?;
+        let dice = dice(&fs)?;
+
+        assert_eq!(
+            "contents",
+            read_file(&dice, &CellPath::testing_new("root", "config.txt")).await?
+        );
+        assert!(
+            read_file(&dice, &CellPath::testing_new("root", "missing.txt"))
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json() -> anyhow::Result<()> {
+        let heap = Heap::new();
+        let cell_path = CellPath::testing_new("root", "manifest.json");
+
+        let value = parse_json(&cell_path, r#"{"deps": ["a", 1, 2.5, true, null]}"#, &heap)?;
+        assert_eq!(r#"{"deps": ["a", 1, 2.5, True, None]}"#, value.to_repr());
+
+        let err = parse_json(&cell_path, r#"{"deps": "#, &heap).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("When parsing JSON file `root//manifest.json`"),
+            "{:#}",
+            err
+        );
+        Ok(())
+    }
+}
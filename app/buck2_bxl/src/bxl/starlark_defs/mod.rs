@@ -53,6 +53,7 @@ use crate::bxl::starlark_defs::functions::register_label_function;
 use crate::bxl::starlark_defs::functions::register_target_function;
 pub mod alloc_node;
 pub mod analysis_result;
+pub mod aquery;
 pub mod artifacts;
 pub mod build_result;
 pub mod cli_args;
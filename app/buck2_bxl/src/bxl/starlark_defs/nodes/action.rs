@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use allocative::Allocative;
+use buck2_build_api::query::aquery::environment::ActionQueryNode;
+use buck2_query::query::environment::LabeledNode;
+use buck2_query::query::environment::QueryTarget;
+use derive_more::Display;
+use dupe::Dupe;
+use gazebo::any::ProvidesStaticType;
+use gazebo::prelude::*;
+use starlark::environment::Methods;
+use starlark::environment::MethodsBuilder;
+use starlark::environment::MethodsStatic;
+use starlark::starlark_module;
+use starlark::starlark_simple_value;
+use starlark::starlark_type;
+use starlark::values::structs::AllocStruct;
+use starlark::values::Heap;
+use starlark::values::NoSerialize;
+use starlark::values::StarlarkValue;
+use starlark::values::UnpackValue;
+use starlark::values::Value;
+use starlark::values::ValueLike;
+use starlark::StarlarkDocs;
+
+#[derive(Debug, Display, ProvidesStaticType, StarlarkDocs, Allocative)]
+#[derive(NoSerialize)] // TODO probably should be serializable the same as how queries serialize
+#[display(fmt = "action_query_node(key = {}, ...)", "self.0.node_ref()")]
+#[starlark_docs(directory = "bxl")]
+pub struct StarlarkActionQueryNode(pub ActionQueryNode);
+
+starlark_simple_value!(StarlarkActionQueryNode);
+
+impl<'v> StarlarkValue<'v> for StarlarkActionQueryNode {
+    starlark_type!("action_query_node");
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(action_query_node_value_methods)
+    }
+}
+
+impl<'a> UnpackValue<'a> for StarlarkActionQueryNode {
+    fn expected() -> String {
+        "action query node".to_owned()
+    }
+
+    fn unpack_value(value: starlark::values::Value<'a>) -> Option<Self> {
+        value
+            .downcast_ref::<Self>()
+            .map(|value| Self(value.0.dupe()))
+    }
+}
+
+/// Methods for action query nodes, as returned by `ctx.aquery()`.
+#[starlark_module]
+fn action_query_node_value_methods(builder: &mut MethodsBuilder) {
+    /// Gets the key of the action, which identifies it uniquely within the build.
+    #[starlark(attribute)]
+    fn key(this: &StarlarkActionQueryNode) -> anyhow::Result<String> {
+        Ok(this.0.node_ref().to_string())
+    }
+
+    /// Gets the kind of the action, e.g. `run`, `write` or `copy`.
+    #[starlark(attribute)]
+    fn kind(this: &StarlarkActionQueryNode) -> anyhow::Result<String> {
+        Ok(this.0.rule_type().into_owned())
+    }
+
+    /// Returns a struct of all the attributes of this action, the same ones that `buck2 aquery
+    /// --output-all-attributes` prints. All the values are strings. The command line of a `run`
+    /// action is in `cmd`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_attrs(ctx):
+    ///     for action in ctx.aquery().eval("root//bin:the_binary"):
+    ///         ctx.output.print(action.attrs().cmd)
+    /// ```
+    fn attrs<'v>(this: &StarlarkActionQueryNode, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let mut attrs = Vec::new();
+        this.0.attrs_for_each(|name, attr| {
+            attrs.push((name.to_owned(), attr.to_string()));
+            Ok::<(), anyhow::Error>(())
+        })?;
+        Ok(heap.alloc(AllocStruct(attrs)))
+    }
+
+    /// Returns the project relative paths of the artifacts this action reads directly. Inputs
+    /// from transitive sets aren't included, query the `deps()` of the action to find their
+    /// actions.
+    fn inputs(this: &StarlarkActionQueryNode) -> anyhow::Result<Vec<String>> {
+        Ok(this.0.inputs()?.into_map(|path| path.to_string()))
+    }
+
+    /// Returns the project relative paths of the artifacts this action produces.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_outputs(ctx):
+    ///     for action in ctx.aquery().eval("deps(root//bin:the_binary)"):
+    ///         ctx.output.print(action.outputs())
+    /// ```
+    fn outputs(this: &StarlarkActionQueryNode) -> anyhow::Result<Vec<String>> {
+        Ok(this.0.outputs()?.into_map(|path| path.to_string()))
+    }
+}
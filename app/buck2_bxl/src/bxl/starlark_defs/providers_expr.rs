@@ -62,11 +62,20 @@ impl ProvidersExpr {
         let target_platform =
             target_platform.parse_target_platforms(&ctx.target_alias_resolver, &ctx.cell)?;
 
+        Self::unpack_with_target_platform(value, &target_platform, ctx, eval)
+    }
+
+    /// Like `unpack`, but with a target platform that has already been parsed.
+    pub fn unpack_with_target_platform<'v>(
+        value: Value<'v>,
+        target_platform: &Option<TargetLabel>,
+        ctx: &BxlContext,
+        eval: &Evaluator<'v, '_>,
+    ) -> anyhow::Result<Self> {
         Ok(
-            if let Some(resolved) = Self::unpack_literal(value, &target_platform, ctx)? {
+            if let Some(resolved) = Self::unpack_literal(value, target_platform, ctx)? {
                 resolved
-            } else if let Some(resolved) =
-                Self::unpack_iterable(value, &target_platform, ctx, eval)?
+            } else if let Some(resolved) = Self::unpack_iterable(value, target_platform, ctx, eval)?
             {
                 resolved
             } else {
@@ -23,6 +23,7 @@ use heap_dump::HeapDumpCommand;
 use internal_version::InternalVersionCommand;
 use materialize::MaterializeCommand;
 use replay::ReplayCommand;
+use why_recomputed::WhyRecomputedCommand;
 
 use crate::commands::debug::allocative::AllocativeCommand;
 use crate::commands::debug::daemon_dir::DaemonDirCommand;
@@ -49,6 +50,7 @@ pub mod replay;
 mod segfault;
 mod set_log_filter;
 mod upload_re_logs;
+mod why_recomputed;
 
 #[derive(Debug, clap::Parser)]
 #[clap(about = "Hidden debug commands useful for testing buck2")]
@@ -79,6 +81,8 @@ pub enum DebugCommand {
     UploadReLogs(UploadReLogsCommand),
     /// Validates that Buck2 and disk agree on the state of files.
     FileStatus(FileStatusCommand),
+    /// Explains why a key in the DICE graph was last invalidated, down to the changes that caused it.
+    WhyRecomputed(WhyRecomputedCommand),
 
     // Those 2 log commands kept here for historical compatibility
     /// Shows the commands that buck ran
@@ -125,6 +129,7 @@ impl DebugCommand {
             DebugCommand::Allocative(cmd) => cmd.exec(matches, ctx),
             DebugCommand::SetLogFilter(cmd) => cmd.exec(matches, ctx),
             DebugCommand::FileStatus(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::WhyRecomputed(cmd) => cmd.exec(matches, ctx),
         }
     }
 }
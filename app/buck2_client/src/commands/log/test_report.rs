@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_client_ctx::subscribers::event_log::file_names::retrieve_nth_recent_log;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use buck2_client_ctx::subscribers::test_report::TestReport;
+use tokio::runtime;
+
+/// Write the reports that `buck2 test --xml` and `--json-report` would have written, from the
+/// event log of a test command.
+#[derive(Debug, clap::Parser)]
+#[clap(group = clap::ArgGroup::with_name("event_log"))]
+#[clap(group = clap::ArgGroup::with_name("report").required(true).multiple(true))]
+pub struct TestReportCommand {
+    /// A path to an event-log file to read from. Only works for log files with a single command in them.
+    #[clap(group = "event_log", value_name = "PATH")]
+    path: Option<PathArg>,
+
+    /// Which recent command to read the event log from.
+    #[clap(
+        long,
+        help = "Use the Nth most recent command (`--recent 0` is the most recent).",
+        group = "event_log",
+        value_name = "NUMBER"
+    )]
+    recent: Option<usize>,
+
+    /// Write a JUnit XML report of the test results to this path.
+    #[clap(long, group = "report", value_name = "PATH")]
+    xml: Option<PathArg>,
+
+    /// Write a JSON report of the test results to this path.
+    #[clap(long, group = "report", value_name = "PATH")]
+    json_report: Option<PathArg>,
+}
+
+impl TestReportCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext) -> ExitResult {
+        let Self {
+            path,
+            recent,
+            xml,
+            json_report,
+        } = self;
+
+        let path = match path {
+            Some(path) => path.resolve(&ctx.working_dir),
+            None => retrieve_nth_recent_log(&ctx, recent.unwrap_or(0))?.into_abs_path_buf(),
+        };
+        let log_path = EventLogPathBuf::infer(path)?;
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let report = rt.block_on(TestReport::from_event_log(&log_path))?;
+
+        report.write(
+            xml.map(|p| p.resolve(&ctx.working_dir)).as_ref(),
+            json_report.map(|p| p.resolve(&ctx.working_dir)).as_ref(),
+        )?;
+
+        ExitResult::success()
+    }
+}
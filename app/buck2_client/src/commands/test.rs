@@ -7,11 +7,14 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+
 use anyhow::Context;
 use async_trait::async_trait;
 use buck2_cli_proto::CounterWithExamples;
 use buck2_cli_proto::TestRequest;
 use buck2_cli_proto::TestSessionOptions;
+use buck2_cli_proto::TestShard;
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_client_ctx::common::CommonBuildConfigurationOptions;
 use buck2_client_ctx::common::CommonBuildOptions;
@@ -20,9 +23,14 @@ use buck2_client_ctx::common::CommonDaemonCommandOptions;
 use buck2_client_ctx::daemon::client::BuckdClientConnector;
 use buck2_client_ctx::exit_result::ExitResult;
 use buck2_client_ctx::final_console::FinalConsole;
+use buck2_client_ctx::path_arg::PathArg;
 use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_client_ctx::subscribers::event_log::EventLogPathBuf;
+use buck2_client_ctx::subscribers::subscriber::EventSubscriber;
 use buck2_client_ctx::subscribers::superconsole::test::StylizedCount;
 use buck2_client_ctx::subscribers::superconsole::test::TestHeader;
+use buck2_client_ctx::subscribers::test_report::TestReport;
+use buck2_client_ctx::subscribers::test_report::TestReportWriter;
 use crossterm::style::Color;
 use gazebo::prelude::*;
 
@@ -95,20 +103,51 @@ If include patterns are present, regardless of whether exclude patterns are pres
     )]
     build_filtered_targets: bool, // TODO(bobyf) this flag should always override the buckconfig option when we use it
 
-    /// This option is currently on by default, but will become a proper option in future (T110004971)
+    /// Keep building the other artifacts of a test target when one of them fails to build, and
+    /// report all of their errors. The other test targets are tested either way, unless
+    /// `--fail-fast` is passed.
     #[clap(long = "keep-going")]
-    #[allow(unused)]
     keep_going: bool,
 
+    /// Stop starting new tests as soon as a test target fails to build. Tests that already
+    /// started still run to completion.
+    #[clap(long = "fail-fast")]
+    fail_fast: bool,
+
+    /// Stop starting new tests once this many tests or test targets have failed.
+    #[clap(long = "max-failures", value_name = "N")]
+    max_failures: Option<u64>,
+
+    /// Only run the tests of this shard, between 0 and `--shard-count`.
+    #[clap(long, value_name = "I", requires = "shard-count")]
+    shard_index: Option<u32>,
+
+    /// Split the tests into this many shards, and only run one of them.
+    #[clap(long, value_name = "N", requires = "shard-index")]
+    shard_count: Option<u32>,
+
+    /// Split individual test cases across shards rather than whole test targets. Every shard then
+    /// builds all the test targets. Targets whose test executor doesn't list their test cases,
+    /// like the bundled runner, are still split as a whole, with a warning.
+    #[clap(long, requires = "shard-count")]
+    shard_by_test_case: bool,
+
+    /// Balance the shards using how long tests took in this event log.
+    #[clap(long, value_name = "PATH", requires = "shard-count")]
+    shard_durations_from_log: Option<PathArg>,
+
     /// This option does nothing. It is here to keep compatibility with Buck1 and ci
     #[allow(unused)] // for v1 compat
     #[clap(long = "deep")]
     deep: bool,
 
-    // ignored. only for e2e tests. compatibility with v1.
-    #[clap(long = "xml")]
-    #[allow(unused)] // for v1 compat
-    xml: Option<String>,
+    /// Write a JUnit XML report of the test results to this path.
+    #[clap(long = "xml", value_name = "PATH")]
+    xml: Option<PathArg>,
+
+    /// Write a JSON report of the test results to this path.
+    #[clap(long = "json-report", value_name = "PATH")]
+    json_report: Option<PathArg>,
 
     #[clap(
         name = "TEST_EXECUTOR_ARGS",
@@ -139,6 +178,27 @@ impl StreamingCommand for TestCommand {
         mut ctx: ClientCommandContext,
     ) -> ExitResult {
         let context = ctx.client_context(&self.config_opts, matches, self.sanitized_argv())?;
+
+        let shard = match (self.shard_index, self.shard_count) {
+            (Some(index), Some(count)) => {
+                let weights_ms = match &self.shard_durations_from_log {
+                    Some(path) => {
+                        let log_path = EventLogPathBuf::infer(path.resolve(&ctx.working_dir))?;
+                        TestReport::from_event_log(&log_path)
+                            .await?
+                            .durations_ms(self.shard_by_test_case)
+                    }
+                    None => HashMap::new(),
+                };
+                Some(TestShard {
+                    index,
+                    count,
+                    by_test_case: self.shard_by_test_case,
+                    weights_ms,
+                })
+            }
+            _ => None,
+        };
         let response = buckd
             .with_flushing()
             .test(
@@ -161,6 +221,10 @@ impl StreamingCommand for TestCommand {
                         force_use_project_relative_paths: self.unstable_force_tests_on_re,
                         force_run_from_project_root: self.unstable_force_tests_on_re,
                     }),
+                    keep_going: self.keep_going,
+                    fail_fast: self.fail_fast,
+                    max_failures: self.max_failures,
+                    shard,
                 },
                 ctx.stdin().console_interaction_stream(&self.console_opts),
             )
@@ -177,6 +241,7 @@ impl StreamingCommand for TestCommand {
         let failed = statuses.failed.context("Missing `failed`")?;
         let fatals = statuses.fatals.context("Missing `fatals`")?;
         let skipped = statuses.skipped.context("Missing `skipped`")?;
+        let build_failed = statuses.build_failed.context("Missing `build_failed`")?;
 
         let console = self.console_opts.final_console();
         print_build_result(&console, &response.error_messages)?;
@@ -229,6 +294,7 @@ impl StreamingCommand for TestCommand {
         print_error_counter(&console, &listing_failed, "LISTINGS FAILED", "⚠")?;
         print_error_counter(&console, &failed, "TESTS FAILED", "✗")?;
         print_error_counter(&console, &fatals, "TESTS FATALS", "⚠")?;
+        print_error_counter(&console, &build_failed, "TEST TARGETS FAILED TO BUILD", "✗")?;
         if passed.count + failed.count + fatals.count + skipped.count == 0 {
             console.print_warning("NO TESTS RAN")?;
         } else if !response.error_messages.is_empty() {
@@ -253,4 +319,19 @@ impl StreamingCommand for TestCommand {
     fn extra_superconsole_component(&self) -> Option<Box<dyn superconsole::Component>> {
         Some(box TestHeader::new())
     }
+
+    fn extra_subscribers(
+        &self,
+        ctx: &ClientCommandContext,
+    ) -> anyhow::Result<Vec<Box<dyn EventSubscriber>>> {
+        if self.xml.is_none() && self.json_report.is_none() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![box TestReportWriter::new(
+            self.xml.as_ref().map(|p| p.resolve(&ctx.working_dir)),
+            self.json_report
+                .as_ref()
+                .map(|p| p.resolve(&ctx.working_dir)),
+        )])
+    }
 }
@@ -586,6 +586,7 @@ impl<'a> FlushingBuckdClient<'a> {
     stream_method!(materialize, MaterializeRequest, MaterializeResponse);
     stream_method!(clean_stale, CleanStaleRequest, CleanStaleResponse);
     stream_method!(file_status, FileStatusRequest, GenericResponse);
+    stream_method!(why_recomputed, WhyRecomputedRequest, GenericResponse);
     stream_method!(unstable_docs, UnstableDocsRequest, UnstableDocsResponse);
     stream_method!(profile, profile2, ProfileRequest, ProfileResponse);
     stream_method!(allocative, AllocativeRequest, AllocativeResponse);
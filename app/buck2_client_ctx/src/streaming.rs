@@ -71,6 +71,7 @@ fn default_subscribers<T: StreamingCommand>(
     if let Some(recorder) = try_get_invocation_recorder(ctx, cmd.sanitized_argv())? {
         subscribers.push(recorder);
     }
+    subscribers.extend(cmd.extra_subscribers(ctx)?);
     Ok(subscribers)
 }
 
@@ -109,6 +110,14 @@ pub trait StreamingCommand: Sized + Send + Sync {
         None
     }
 
+    /// Allows a command to add additional subscribers to its events.
+    fn extra_subscribers(
+        &self,
+        _ctx: &ClientCommandContext,
+    ) -> anyhow::Result<Vec<Box<dyn EventSubscriber>>> {
+        Ok(Vec::new())
+    }
+
     fn sanitized_argv(&self) -> Vec<String> {
         std::env::args().collect()
     }
@@ -266,6 +266,7 @@ fn display_file_watcher(provider: i32) -> &'static str {
     match buck2_data::FileWatcherProvider::from_i32(provider) {
         Some(buck2_data::FileWatcherProvider::Watchman) => "Watchman",
         Some(buck2_data::FileWatcherProvider::RustNotify) => "notify",
+        Some(buck2_data::FileWatcherProvider::Poll) => "polling",
         None => "unknown mechanism",
     }
 }
@@ -474,12 +475,12 @@ pub(crate) fn display_action_error<'a>(
         Error::Unknown(error_string) => {
             format!("Internal error: {}", error_string)
         }
-        Error::CommandExecutionError(buck2_data::CommandExecutionError {}) => {
-            match action.commands.last() {
-                Some(c) => failure_reason_for_command_execution(c)?,
-                None => "Unexpected command status".to_owned(),
-            }
-        }
+        Error::CommandExecutionError(buck2_data::CommandExecutionError {})
+        | Error::SandboxViolation(buck2_data::CommandSandboxViolation {})
+        | Error::TimedOut(buck2_data::CommandTimedOut { .. }) => match action.commands.last() {
+            Some(c) => failure_reason_for_command_execution(c)?,
+            None => "Unexpected command status".to_owned(),
+        },
     };
 
     Ok(ActionErrorDisplay {
@@ -495,6 +496,7 @@ fn failure_reason_for_command_execution(
     use buck2_data::command_execution::ClaimCancelled;
     use buck2_data::command_execution::Error;
     use buck2_data::command_execution::Failure;
+    use buck2_data::command_execution::SandboxViolation;
     use buck2_data::command_execution::Status;
     use buck2_data::command_execution::Success;
     use buck2_data::command_execution::Timeout;
@@ -555,6 +557,11 @@ fn failure_reason_for_command_execution(
             format!("Internal error (stage: {}): {}", stage, error)
         }
         Status::ClaimCancelled(ClaimCancelled {}) => "Command was cancelled".to_owned(),
+        Status::SandboxViolation(SandboxViolation { paths }) => format!(
+            "{}command failed after accessing paths that are not declared inputs, so the sandbox hid them: {}",
+            locality,
+            paths.join(", ")
+        ),
     })
 }
 
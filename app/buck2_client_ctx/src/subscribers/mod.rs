@@ -25,6 +25,7 @@ pub(crate) mod stdout_stderr_forwarder;
 pub mod subscriber;
 pub mod subscriber_unpack;
 pub mod superconsole;
+pub mod test_report;
 pub(crate) mod two_snapshots;
 
 pub fn disable_log_upload() -> anyhow::Result<bool> {
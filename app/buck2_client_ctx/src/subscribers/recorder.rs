@@ -371,7 +371,11 @@ mod imp {
             if action.commands.iter().any(|c| {
                 matches!(
                     c.status,
-                    Some(buck2_data::command_execution::Status::Failure(..))
+                    Some(
+                        buck2_data::command_execution::Status::Failure(..)
+                            | buck2_data::command_execution::Status::SandboxViolation(..)
+                            | buck2_data::command_execution::Status::Timeout(..)
+                    )
                 )
             }) {
                 self.run_command_failure_count += 1;
@@ -0,0 +1,416 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Reports of the results of `buck2 test`, as JUnit XML or JSON, built from the `TestResult`
+//! events of a command.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_events::BuckEvent;
+use buck2_test_api::data::test_case_key;
+use buck2_test_api::data::TestStatus;
+use serde::Serialize;
+use tokio_stream::StreamExt;
+
+use crate::stream_value::StreamValue;
+use crate::subscribers::display::display_configured_target_label;
+use crate::subscribers::display::TargetDisplayOptions;
+use crate::subscribers::event_log::EventLogPathBuf;
+use crate::subscribers::subscriber::EventSubscriber;
+
+/// How much of the output of a test is kept in a report. The end is kept, since that's usually
+/// where the errors are.
+const MAX_OUTPUT_EXCERPT_BYTES: usize = 64 * 1024;
+
+#[derive(Serialize)]
+struct TestCase {
+    name: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_secs: Option<f64>,
+    output: String,
+}
+
+/// The test results of a command, grouped in suites by test target.
+#[derive(Default)]
+pub struct TestReport {
+    suites: BTreeMap<String, Vec<(TestStatus, TestCase)>>,
+}
+
+impl TestReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The test results recorded in an event log.
+    pub async fn from_event_log(log_path: &EventLogPathBuf) -> anyhow::Result<Self> {
+        let (_, mut events) = log_path.unpack_stream().await?;
+
+        let mut report = Self::new();
+        while let Some(event) = events.try_next().await? {
+            match event {
+                StreamValue::Event(event) => report.add_event(&BuckEvent::try_from(event)?)?,
+                StreamValue::Result(..) => {}
+            }
+        }
+        Ok(report)
+    }
+
+    /// How long each test target took to run, in milliseconds, or each test case if
+    /// `by_test_case` is set, keyed by `test_case_key`.
+    pub fn durations_ms(&self, by_test_case: bool) -> HashMap<String, u64> {
+        let mut durations = HashMap::new();
+        for (suite, tests) in &self.suites {
+            for (_, test) in tests {
+                let key = if by_test_case {
+                    test_case_key(suite, &test.name)
+                } else {
+                    suite.clone()
+                };
+                let duration = (test.duration_secs.unwrap_or_default() * 1000.0) as u64;
+                *durations.entry(key).or_default() += duration;
+            }
+        }
+        durations
+    }
+
+    /// Adds the result carried by `event`, if it is a `TestResult`.
+    pub fn add_event(&mut self, event: &BuckEvent) -> anyhow::Result<()> {
+        if let buck2_data::buck_event::Data::Instant(instant) = event.data() {
+            if let Some(buck2_data::instant_event::Data::TestResult(result)) = &instant.data {
+                self.add(result)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, result: &buck2_data::TestResult) -> anyhow::Result<()> {
+        let buck2_data::TestResult {
+            name,
+            status,
+            msg,
+            duration,
+            details,
+            target_label,
+        } = result;
+
+        // Results from before test targets were recorded go in a suite of their own.
+        let suite = match target_label {
+            Some(label) => {
+                display_configured_target_label(label, TargetDisplayOptions::for_console())?
+            }
+            None => name.clone(),
+        };
+        let status = TestStatus::try_from(*status)?;
+        let duration = duration
+            .clone()
+            .and_then(|d| Duration::try_from(d).ok())
+            .map(|d| d.as_secs_f64());
+
+        self.suites.entry(suite).or_default().push((
+            status.clone(),
+            TestCase {
+                name: name.clone(),
+                status: format!("{:?}", status),
+                message: msg.as_ref().map(|m| m.msg.clone()),
+                duration_secs: duration,
+                output: excerpt(details),
+            },
+        ));
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        #[derive(Serialize)]
+        struct Suite<'a> {
+            name: &'a str,
+            tests: Vec<&'a TestCase>,
+        }
+
+        let suites: Vec<_> = self
+            .suites
+            .iter()
+            .map(|(name, tests)| Suite {
+                name,
+                tests: tests.iter().map(|(_, t)| t).collect(),
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&serde_json::json!({
+            "suites": suites
+        }))?)
+    }
+
+    /// A JUnit report. Attempts of a test that were rerun are left out, as they are superseded by
+    /// the final attempt.
+    pub fn to_junit_xml(&self) -> String {
+        let mut suites = String::new();
+        let mut total = Counts::default();
+
+        for (suite, tests) in &self.suites {
+            let mut cases = String::new();
+            let mut counts = Counts::default();
+
+            for (status, test) in tests {
+                let outcome = match status {
+                    TestStatus::RERUN => continue,
+                    TestStatus::PASS | TestStatus::LISTING_SUCCESS => None,
+                    TestStatus::FAIL | TestStatus::LISTING_FAILED => {
+                        counts.failures += 1;
+                        Some("failure")
+                    }
+                    TestStatus::FATAL | TestStatus::TIMEOUT | TestStatus::UNKNOWN => {
+                        counts.errors += 1;
+                        Some("error")
+                    }
+                    TestStatus::SKIP | TestStatus::OMITTED => {
+                        counts.skipped += 1;
+                        Some("skipped")
+                    }
+                };
+                counts.tests += 1;
+                counts.time += test.duration_secs.unwrap_or_default();
+
+                write!(
+                    cases,
+                    "    <testcase name=\"{}\" classname=\"{}\"",
+                    escape(&test.name),
+                    escape(suite)
+                )
+                .unwrap();
+                if let Some(duration) = test.duration_secs {
+                    write!(cases, " time=\"{:.3}\"", duration).unwrap();
+                }
+                cases.push_str(">\n");
+                if let Some(outcome) = outcome {
+                    writeln!(
+                        cases,
+                        "      <{} type=\"{}\" message=\"{}\"/>",
+                        outcome,
+                        test.status,
+                        escape(test.message.as_deref().unwrap_or_default())
+                    )
+                    .unwrap();
+                }
+                if !test.output.is_empty() {
+                    writeln!(
+                        cases,
+                        "      <system-out>{}</system-out>",
+                        escape(&test.output)
+                    )
+                    .unwrap();
+                }
+                cases.push_str("    </testcase>\n");
+            }
+
+            writeln!(
+                suites,
+                "  <testsuite name=\"{}\" {}>\n{}  </testsuite>",
+                escape(suite),
+                counts.attributes(),
+                cases
+            )
+            .unwrap();
+            total.add(&counts);
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites {}>\n{}</testsuites>\n",
+            total.attributes(),
+            suites
+        )
+    }
+
+    pub fn write(
+        &self,
+        junit_xml: Option<&AbsPathBuf>,
+        json: Option<&AbsPathBuf>,
+    ) -> anyhow::Result<()> {
+        if let Some(path) = junit_xml {
+            fs_util::write(path, self.to_junit_xml())
+                .with_context(|| format!("Failed to write JUnit report to `{}`", path.display()))?;
+        }
+        if let Some(path) = json {
+            fs_util::write(path, self.to_json()?)
+                .with_context(|| format!("Failed to write JSON report to `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    time: f64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.tests += other.tests;
+        self.failures += other.failures;
+        self.errors += other.errors;
+        self.skipped += other.skipped;
+        self.time += other.time;
+    }
+
+    fn attributes(&self) -> String {
+        format!(
+            "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+            self.tests, self.failures, self.errors, self.skipped, self.time
+        )
+    }
+}
+
+fn excerpt(output: &str) -> String {
+    if output.len() <= MAX_OUTPUT_EXCERPT_BYTES {
+        return output.to_owned();
+    }
+    let mut start = output.len() - MAX_OUTPUT_EXCERPT_BYTES;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[... {} bytes truncated ...]\n{}", start, &output[start..])
+}
+
+/// Escapes text for XML, dropping the control characters XML doesn't allow.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Collects the test results of the command, and writes the reports once it exits.
+pub struct TestReportWriter {
+    report: TestReport,
+    junit_xml: Option<AbsPathBuf>,
+    json: Option<AbsPathBuf>,
+}
+
+impl TestReportWriter {
+    pub fn new(junit_xml: Option<AbsPathBuf>, json: Option<AbsPathBuf>) -> Self {
+        Self {
+            report: TestReport::new(),
+            junit_xml,
+            json,
+        }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for TestReportWriter {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            self.report.add_event(event)?;
+        }
+        Ok(())
+    }
+
+    async fn exit(&mut self) -> anyhow::Result<()> {
+        self.report
+            .write(self.junit_xml.as_ref(), self.json.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(
+        target: &str,
+        name: &str,
+        status: TestStatus,
+        details: &str,
+    ) -> buck2_data::TestResult {
+        let (package, target) = target.split_once(':').unwrap();
+        buck2_data::TestResult {
+            name: name.to_owned(),
+            status: status.try_into().unwrap(),
+            msg: None,
+            duration: Some(Duration::from_millis(1500).try_into().unwrap()),
+            details: details.to_owned(),
+            target_label: Some(buck2_data::ConfiguredTargetLabel {
+                label: Some(buck2_data::TargetLabel {
+                    package: package.to_owned(),
+                    name: target.to_owned(),
+                }),
+                configuration: Some(buck2_data::Configuration {
+                    full_name: "cfg".to_owned(),
+                }),
+                execution_configuration: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_junit_xml() -> anyhow::Result<()> {
+        let mut report = TestReport::new();
+        report.add(&result("root//a:t", "first", TestStatus::PASS, ""))?;
+        report.add(&result("root//a:t", "second", TestStatus::RERUN, "flaky"))?;
+        report.add(&result("root//a:t", "second", TestStatus::FAIL, "a < b"))?;
+        report.add(&result("root//b:t", "third", TestStatus::OMITTED, ""))?;
+
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="3" failures="1" errors="0" skipped="1" time="4.500">
+  <testsuite name="root//a:t" tests="2" failures="1" errors="0" skipped="0" time="3.000">
+    <testcase name="first" classname="root//a:t" time="1.500">
+    </testcase>
+    <testcase name="second" classname="root//a:t" time="1.500">
+      <failure type="FAIL" message=""/>
+      <system-out>a &lt; b</system-out>
+    </testcase>
+  </testsuite>
+  <testsuite name="root//b:t" tests="1" failures="0" errors="0" skipped="1" time="1.500">
+    <testcase name="third" classname="root//b:t" time="1.500">
+      <skipped type="OMITTED" message=""/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#,
+            report.to_junit_xml()
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+        assert_eq!(2, json["suites"].as_array().unwrap().len());
+        assert_eq!("RERUN", json["suites"][0]["tests"][1]["status"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let output = "é".repeat(MAX_OUTPUT_EXCERPT_BYTES);
+        let excerpt = excerpt(&output);
+        assert!(excerpt.starts_with("[... "));
+        assert!(excerpt.ends_with('é'));
+        assert_eq!("short", super::excerpt("short"));
+    }
+}
@@ -37,4 +37,15 @@ impl DaemonDir {
     pub fn buckd_pid(&self) -> AbsNormPathBuf {
         self.path.join(FileName::new("buckd.pid").unwrap())
     }
+
+    /// Path to the DICE snapshot the daemon writes when it shuts down.
+    pub fn dice_snapshot(&self) -> AbsNormPathBuf {
+        self.path.join(FileName::new("dice_snapshot").unwrap())
+    }
+
+    /// Path to the project snapshot of the `poll` file watcher, read by the next daemon.
+    pub fn poll_file_watcher_snapshot(&self) -> AbsNormPathBuf {
+        self.path
+            .join(FileName::new("poll_file_watcher_snapshot").unwrap())
+    }
 }
@@ -22,11 +22,14 @@ use buck2_core::cells::CellResolver;
 use buck2_core::fs::project::ProjectRelativePath;
 use derive_more::Display;
 use dice::DiceComputations;
+use dice::DiceDataBuilder;
 use dice::DiceTransaction;
 use dice::Key;
 use dupe::Dupe;
 use gazebo::cmp::PartialEqAny;
 use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::dice::cells::HasCellResolver;
 use crate::dice::data::HasIoProvider;
@@ -55,13 +58,38 @@ impl<'c> HasFileOps<'c> for DiceComputations {
     }
 }
 
+pub trait PersistFileOps {
+    /// Writes the file keys that don't depend on the `FileOps` to DICE snapshots. The others hold
+    /// the `FileOps` itself, which can't be serialized.
+    fn persist_file_ops(&mut self);
+}
+
+impl PersistFileOps for DiceDataBuilder {
+    fn persist_file_ops(&mut self) {
+        self.persist::<ReadFileKey>();
+    }
+}
+
 // TODO(cjhopman, bobyf): This FileToken can go away once Dice has support for
 // transient values.
 /// This is used as the "result" of a read_file computation so that we don't
 /// need to store the file content's in dice's cache.
-#[derive(Clone, Dupe, Allocative)]
+#[derive(Clone, Dupe, Allocative, Serialize, Deserialize)]
+#[serde(from = "CellPath", into = "CellPath")]
 struct FileToken(Arc<CellPath>);
 
+impl From<CellPath> for FileToken {
+    fn from(path: CellPath) -> Self {
+        Self(Arc::new(path))
+    }
+}
+
+impl From<FileToken> for CellPath {
+    fn from(token: FileToken) -> Self {
+        (*token.0).clone()
+    }
+}
+
 impl FileToken {
     async fn read(&self, fs: &dyn FileOps) -> anyhow::Result<String> {
         fs.read_file(&self.0).await
@@ -262,12 +290,37 @@ impl FileChangeTracker {
     pub fn dir_removed(&mut self, path: CellPath) {
         self.dir_added_or_removed(path)
     }
+
+    /// Marks everything DICE knows about the files of the cell as changed, for when changes to
+    /// the cell were missed and there's no telling which paths they affected.
+    pub fn cell_changed(&mut self, ctx: &DiceTransaction, cell: &CellName) {
+        self.files_to_dirty
+            .extend(ctx.keys::<ReadFileKey>(|k| k.0.cell() == cell));
+        self.dirs_to_dirty
+            .extend(ctx.keys::<ReadDirKey>(|k| k.0.cell() == cell));
+        self.paths_to_dirty
+            .extend(ctx.keys::<PathMetadataKey>(|k| k.0.cell() == cell));
+    }
 }
 
 #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+#[derive(Serialize, Deserialize)]
 #[display(fmt = "{}", _0)]
+#[serde(from = "CellPath", into = "CellPath")]
 struct ReadFileKey(Arc<CellPath>);
 
+impl From<CellPath> for ReadFileKey {
+    fn from(path: CellPath) -> Self {
+        Self(Arc::new(path))
+    }
+}
+
+impl From<ReadFileKey> for CellPath {
+    fn from(key: ReadFileKey) -> Self {
+        (*key.0).clone()
+    }
+}
+
 #[async_trait]
 impl Key for ReadFileKey {
     type Value = FileToken;
@@ -9,6 +9,7 @@
 
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::time::Duration;
 
 use allocative::Allocative;
 use buck2_core::collections::sorted_map::SortedMap;
@@ -120,6 +121,8 @@ pub struct CommandExecutorConfig {
     pub executor_kind: CommandExecutorKind,
     pub path_separator: PathSeparatorKind,
     pub cache_upload_behavior: CacheUploadBehavior,
+    /// Timeout applied to commands that don't request one themselves.
+    pub default_action_timeout: Option<Duration>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy, Dupe, Hash, Allocative)]
@@ -163,6 +166,7 @@ impl CommandExecutorConfig {
             executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
             path_separator: PathSeparatorKind::system_default(),
             cache_upload_behavior: CacheUploadBehavior::Disabled,
+            default_action_timeout: None,
         }
     }
 }
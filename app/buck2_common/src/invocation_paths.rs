@@ -145,6 +145,11 @@ impl InvocationPaths {
             .join(ForwardRelativePath::unchecked_new("re_logs"))
     }
 
+    pub fn worker_logs_dir(&self) -> AbsNormPathBuf {
+        self.buck_out_path()
+            .join(ForwardRelativePath::unchecked_new("worker_logs"))
+    }
+
     pub fn build_count_dir(&self) -> AbsNormPathBuf {
         self.buck_out_path()
             .join(ForwardRelativePath::unchecked_new("build_count"))
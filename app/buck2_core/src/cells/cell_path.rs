@@ -9,6 +9,8 @@
 
 use allocative::Allocative;
 use relative_path::RelativePath;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::cells::paths::CellRelativePath;
 use crate::cells::paths::CellRelativePathBuf;
@@ -30,7 +32,9 @@ struct StripPrefixError(CellName, CellName);
     PartialEq,
     Ord,
     PartialOrd,
-    Allocative
+    Allocative,
+    Serialize,
+    Deserialize
 )]
 #[display(fmt = "{}//{}", cell, path)]
 pub struct CellPath {
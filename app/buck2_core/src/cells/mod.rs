@@ -149,6 +149,8 @@ use dupe::Dupe;
 use gazebo::prelude::*;
 use itertools::Itertools;
 use sequence_trie::SequenceTrie;
+use serde::Deserialize;
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::cells::cell_path::CellPath;
@@ -265,6 +267,7 @@ impl CellAliasResolver {
 #[derive(
     Clone, Debug, Display, Hash, Eq, PartialEq, Ord, PartialOrd, Allocative
 )]
+#[derive(Serialize, Deserialize)]
 pub struct CellName(String);
 
 impl CellName {
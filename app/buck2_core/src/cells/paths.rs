@@ -23,6 +23,7 @@ use derivative::Derivative;
 use derive_more::Display;
 use ref_cast::RefCast;
 use relative_path::RelativePath;
+use serde::Deserialize;
 use serde::Serialize;
 
 use crate::fs::paths::file_name::FileName;
@@ -43,8 +44,10 @@ pub struct CellRelativePath(
 /// The owned version of the 'CellRelativePath'
 #[derive(Clone, Display, Derivative)]
 // split in two lines because formatters disagree
-#[derive(Hash, PartialEq, Eq, Ord, PartialOrd, Serialize, Allocative)]
+#[derive(Hash, PartialEq, Eq, Ord, PartialOrd, Allocative)]
+#[derive(Serialize, Deserialize)]
 #[derivative(Debug)]
+#[serde(try_from = "String")]
 pub struct CellRelativePathBuf(
     #[derivative(Debug(format_with = "quoted_display"))] ForwardRelativePathBuf,
 );
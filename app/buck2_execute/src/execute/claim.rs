@@ -86,6 +86,24 @@ impl Claim for MutexClaim {
     }
 }
 
+/// A ClaimManager for a claim that is already held, which it hands out to whoever claims first.
+pub struct HeldClaimManager {
+    claim: Box<dyn Claim>,
+}
+
+impl HeldClaimManager {
+    pub fn new(claim: Box<dyn Claim>) -> Self {
+        Self { claim }
+    }
+}
+
+#[async_trait]
+impl ClaimManager for HeldClaimManager {
+    async fn claim(self: Box<Self>) -> Box<dyn Claim> {
+        self.claim
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::task::Poll;
@@ -106,4 +124,19 @@ mod tests {
         claim.release().expect("Can release claim");
         assert_matches!(futures::poll!(claim2.as_mut()), Poll::Ready(..));
     }
+
+    #[tokio::test]
+    async fn test_held_claim() {
+        let claim_manager = MutexClaimManager::new();
+        let claim = (box claim_manager.dupe()).claim().await;
+
+        // The held claim is handed out, while others still can't claim.
+        let claim = (box HeldClaimManager::new(claim)).claim().await;
+        let claim2 = (box claim_manager.dupe()).claim();
+        futures::pin_mut!(claim2);
+        assert_matches!(futures::poll!(claim2.as_mut()), Poll::Pending);
+
+        claim.release().expect("Can release claim");
+        assert_matches!(futures::poll!(claim2.as_mut()), Poll::Ready(..));
+    }
 }
@@ -73,6 +73,8 @@ struct CommandExecutorData {
     inner: Arc<dyn PreparedCommandExecutor>,
     artifact_fs: ArtifactFs,
     path_separator: PathSeparatorKind,
+    /// Timeout for requests that don't carry one.
+    default_timeout: Option<Duration>,
 }
 
 impl CommandExecutor {
@@ -80,11 +82,13 @@ impl CommandExecutor {
         inner: Arc<dyn PreparedCommandExecutor>,
         artifact_fs: ArtifactFs,
         path_separator: PathSeparatorKind,
+        default_timeout: Option<Duration>,
     ) -> Self {
         Self(Arc::new(CommandExecutorData {
             inner,
             artifact_fs,
             path_separator,
+            default_timeout,
         }))
     }
 
@@ -107,7 +111,9 @@ impl CommandExecutor {
         request: &CommandExecutionRequest,
         manager: CommandExecutionManager,
     ) -> CommandExecutionResult {
-        let (manager, action_paths, prepared_action) = self.prepare(manager, request).await?;
+        let timeout = request.timeout().or(self.0.default_timeout);
+        let (manager, action_paths, prepared_action) =
+            self.prepare(manager, request, timeout).await?;
         self.0
             .inner
             .exec_cmd(
@@ -116,6 +122,7 @@ impl CommandExecutor {
                     request,
                     action_paths,
                     prepared_action,
+                    timeout,
                 },
                 manager,
             )
@@ -126,6 +133,7 @@ impl CommandExecutor {
         &self,
         mut manager: CommandExecutionManager,
         request: &CommandExecutionRequest,
+        timeout: Option<Duration>,
     ) -> ControlFlow<CommandExecutionResult, (CommandExecutionManager, ActionPaths, PreparedAction)>
     {
         let (action_paths, action) = match manager.stage(buck2_data::PrepareAction {}, || {
@@ -159,7 +167,7 @@ impl CommandExecutor {
                 request.env(),
                 input_digest,
                 action_metadata_blobs,
-                None,
+                timeout.as_ref(),
                 self.0.inner.re_platform().cloned(),
                 false,
             );
@@ -12,6 +12,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use buck2_events::dispatch::span;
 use buck2_events::dispatch::span_async;
 use buck2_events::dispatch::EventDispatcher;
@@ -20,6 +21,7 @@ use indexmap::IndexMap;
 use crate::artifact_value::ArtifactValue;
 use crate::execute::claim::Claim;
 use crate::execute::claim::ClaimManager;
+use crate::execute::claim::HeldClaimManager;
 use crate::execute::kind::CommandExecutionKind;
 use crate::execute::output::CommandStdStreams;
 use crate::execute::request::CommandExecutionOutput;
@@ -150,6 +152,16 @@ impl CommandExecutionManagerWithClaim {
         )
     }
 
+    /// Hand the command over to another executor, which gets this claim when it claims. This is
+    /// for executors that claimed to write outputs but couldn't, and would rather the command ran.
+    pub fn hand_over(self) -> CommandExecutionManager {
+        CommandExecutionManager::new(
+            box HeldClaimManager::new(self.claim),
+            self.events,
+            self.liveliness_observer,
+        )
+    }
+
     pub fn cancel_claim(self) -> CommandExecutionResult {
         self.result(
             CommandExecutionStatus::ClaimCancelled,
@@ -220,6 +232,7 @@ pub trait CommandExecutionManagerExt: Sized {
         outputs: IndexMap<CommandExecutionOutput, ArtifactValue>,
         std_streams: CommandStdStreams,
         exit_code: Option<i32>,
+        timing: CommandExecutionTimingData,
     ) -> CommandExecutionResult;
 
     fn timeout(
@@ -230,6 +243,15 @@ pub trait CommandExecutionManagerExt: Sized {
         timing: CommandExecutionTimingData,
     ) -> CommandExecutionResult;
 
+    fn sandbox_violation(
+        self,
+        execution_kind: CommandExecutionKind,
+        paths: Vec<ProjectRelativePathBuf>,
+        std_streams: CommandStdStreams,
+        exit_code: Option<i32>,
+        timing: CommandExecutionTimingData,
+    ) -> CommandExecutionResult;
+
     fn error(self, stage: &'static str, error: impl Into<anyhow::Error>) -> CommandExecutionResult;
 }
 
@@ -243,13 +265,14 @@ where
         outputs: IndexMap<CommandExecutionOutput, ArtifactValue>,
         std_streams: CommandStdStreams,
         exit_code: Option<i32>,
+        timing: CommandExecutionTimingData,
     ) -> CommandExecutionResult {
         self.result(
             CommandExecutionStatus::Failure { execution_kind },
             outputs,
             std_streams,
             exit_code,
-            CommandExecutionTimingData::default(),
+            timing,
         )
     }
 
@@ -272,6 +295,26 @@ where
         )
     }
 
+    fn sandbox_violation(
+        self,
+        execution_kind: CommandExecutionKind,
+        paths: Vec<ProjectRelativePathBuf>,
+        std_streams: CommandStdStreams,
+        exit_code: Option<i32>,
+        timing: CommandExecutionTimingData,
+    ) -> CommandExecutionResult {
+        self.result(
+            CommandExecutionStatus::SandboxViolation {
+                execution_kind,
+                paths,
+            },
+            IndexMap::new(),
+            std_streams,
+            exit_code,
+            timing,
+        )
+    }
+
     fn error(self, stage: &'static str, error: impl Into<anyhow::Error>) -> CommandExecutionResult {
         self.result(
             CommandExecutionStatus::Error {
@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::time::Duration;
+
 use async_trait::async_trait;
 use buck2_common::executor_config::RemoteExecutorUseCase;
 use buck2_core::fs::project::ProjectRelativePathBuf;
@@ -41,6 +43,8 @@ pub struct PreparedCommand<'a, 'b> {
     pub target: CommandExecutionTarget<'b>,
     pub action_paths: ActionPaths,
     pub prepared_action: PreparedAction,
+    /// The timeout to enforce, which is the request's own or the executor's default.
+    pub timeout: Option<Duration>,
 }
 
 #[async_trait]
@@ -117,6 +117,63 @@ impl ExecutorPreference {
     }
 }
 
+/// How a command talks to a persistent worker, using the same framing as Bazel's workers.
+#[derive(Copy, Clone, Dupe, Display, Debug, PartialEq, Eq, Hash, Allocative)]
+pub enum WorkerProtocol {
+    /// Newline-delimited JSON `WorkRequest`s and `WorkResponse`s.
+    #[display(fmt = "json")]
+    Json,
+    /// Length-delimited protobuf `WorkRequest`s and `WorkResponse`s.
+    #[display(fmt = "proto")]
+    Proto,
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid worker protocol `{0}`, expected `json` or `proto`")]
+struct InvalidWorkerProtocol(String);
+
+impl WorkerProtocol {
+    pub fn parse(protocol: &str) -> anyhow::Result<Self> {
+        match protocol {
+            "json" => Ok(Self::Json),
+            "proto" => Ok(Self::Proto),
+            _ => Err(InvalidWorkerProtocol(protocol.to_owned()).into()),
+        }
+    }
+}
+
+/// Limits on the resources a command may use when it runs locally. They are only enforced for
+/// commands that run through the forkserver, on Linux with cgroup v2.
+#[derive(Copy, Clone, Dupe, Debug, Default, PartialEq, Eq, Hash, Allocative)]
+pub struct ResourceLimits {
+    pub memory_max_bytes: Option<u64>,
+    /// In thousandths of a core.
+    pub cpu_max_millicores: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.memory_max_bytes.is_none() && self.cpu_max_millicores.is_none()
+    }
+}
+
+impl Display for ResourceLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_unlimited() {
+            return write!(f, "None");
+        }
+        let mut sep = "";
+        if let Some(bytes) = self.memory_max_bytes {
+            write!(f, "memory_max_bytes={}", bytes)?;
+            sep = ", ";
+        }
+        if let Some(millicores) = self.cpu_max_millicores {
+            write!(f, "{}cpu_max_millicores={}", sep, millicores)?;
+        }
+        Ok(())
+    }
+}
+
 /// The data contains the information about the command to be executed.
 pub struct CommandExecutionRequest {
     args: Vec<String>,
@@ -144,6 +201,10 @@ pub struct CommandExecutionRequest {
     /// Whether this command should override the fallback-only behavior on an hybrid executor and
     /// thus always run as if the executor was full-hybrid, assuming it is capable.
     force_full_hybrid_if_capable: bool,
+    /// Whether this command may be run by a persistent worker when executed locally, and how to
+    /// talk to it.
+    worker_protocol: Option<WorkerProtocol>,
+    resource_limits: ResourceLimits,
 }
 
 impl CommandExecutionRequest {
@@ -169,6 +230,8 @@ impl CommandExecutionRequest {
             local_environment_inheritance: None,
             allow_cache_upload: false,
             force_full_hybrid_if_capable: false,
+            worker_protocol: None,
+            resource_limits: ResourceLimits::default(),
         }
     }
 
@@ -303,6 +366,24 @@ impl CommandExecutionRequest {
     pub fn force_full_hybrid_if_capable(&self) -> bool {
         self.force_full_hybrid_if_capable
     }
+
+    pub fn with_worker_protocol(mut self, worker_protocol: Option<WorkerProtocol>) -> Self {
+        self.worker_protocol = worker_protocol;
+        self
+    }
+
+    pub fn worker_protocol(&self) -> Option<WorkerProtocol> {
+        self.worker_protocol
+    }
+
+    pub fn with_resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.resource_limits = resource_limits;
+        self
+    }
+
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.resource_limits
+    }
 }
 
 /// Is an output a file or a directory
@@ -14,6 +14,7 @@ use std::ops::FromResidual;
 use std::time::Duration;
 use std::time::SystemTime;
 
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use dupe::Dupe;
 use indexmap::IndexMap;
 
@@ -40,6 +41,12 @@ pub enum CommandExecutionStatus {
         execution_kind: CommandExecutionKind,
         duration: Duration,
     },
+    /// The command failed after trying to access paths in the project that the sandbox hid from
+    /// it, because they weren't declared as inputs.
+    SandboxViolation {
+        execution_kind: CommandExecutionKind,
+        paths: Vec<ProjectRelativePathBuf>,
+    },
     ClaimCancelled,
 }
 
@@ -50,6 +57,7 @@ impl CommandExecutionStatus {
             CommandExecutionStatus::Failure { execution_kind } => Some(execution_kind),
             CommandExecutionStatus::Error { .. } => None,
             CommandExecutionStatus::TimedOut { execution_kind, .. } => Some(execution_kind),
+            CommandExecutionStatus::SandboxViolation { execution_kind, .. } => Some(execution_kind),
             CommandExecutionStatus::ClaimCancelled => None,
         }
     }
@@ -70,6 +78,16 @@ impl Display for CommandExecutionStatus {
             CommandExecutionStatus::TimedOut { duration, .. } => {
                 write!(f, "timed out after {:.3}s", duration.as_secs_f64())
             }
+            CommandExecutionStatus::SandboxViolation { paths, .. } => {
+                write!(f, "sandbox violation accessing ")?;
+                for (i, path) in paths.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "`{}`", path)?;
+                }
+                Ok(())
+            }
             CommandExecutionStatus::ClaimCancelled => write!(f, "ClaimCancelled"),
         }
     }
@@ -93,6 +111,17 @@ pub struct CommandExecutionTimingData {
 
     /// When execution started.
     pub start_time: SystemTime,
+
+    /// What the command used, if that was measured.
+    pub execution_stats: Option<CommandExecutionStats>,
+}
+
+#[derive(Debug, Copy, Clone, Dupe)]
+pub struct CommandExecutionStats {
+    /// Peak memory usage, in bytes, if the kernel tracks it.
+    pub memory_peak: Option<u64>,
+    /// CPU time, in user and system mode.
+    pub cpu_time: Duration,
 }
 
 impl Default for CommandExecutionTimingData {
@@ -102,6 +131,7 @@ impl Default for CommandExecutionTimingData {
             re_queue_time: None,
             execution_time: Duration::default(),
             start_time: SystemTime::now(),
+            execution_stats: None,
         }
     }
 }
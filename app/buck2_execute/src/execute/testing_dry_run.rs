@@ -61,6 +61,7 @@ impl PreparedCommandExecutor for DryRunExecutor {
             target: _target,
             action_paths: _action_paths,
             prepared_action: _prepared_action,
+            timeout: _timeout,
         } = command;
 
         let manager = manager.claim().await;
@@ -98,7 +99,13 @@ impl PreparedCommandExecutor for DryRunExecutor {
                 CommandExecutionTimingData::default(),
             ),
             // NOTE: This should probaby be an error() but who cares.
-            Err(..) => manager.failure(exec_kind, IndexMap::new(), Default::default(), Some(1)),
+            Err(..) => manager.failure(
+                exec_kind,
+                IndexMap::new(),
+                Default::default(),
+                Some(1),
+                Default::default(),
+            ),
         }
     }
 
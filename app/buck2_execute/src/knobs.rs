@@ -7,8 +7,24 @@
  * of this source tree.
  */
 
+use std::sync::Arc;
+
+use buck2_core::fs::project::ProjectRelativePathBuf;
 use dupe::Dupe;
 
 /// Daemon-level config that can tweak how the executors work.
 #[derive(Clone, Dupe, Default)]
-pub struct ExecutorGlobalKnobs {}
+pub struct ExecutorGlobalKnobs {
+    /// If set, local actions run in a sandbox that only exposes their declared inputs and
+    /// outputs from the project. Requires the forkserver.
+    pub local_sandbox: Option<Arc<LocalSandboxOptions>>,
+}
+
+/// How to sandbox local actions.
+#[derive(Debug, Default)]
+pub struct LocalSandboxOptions {
+    /// Paths in the project that every action may read, like toolchains checked into the repo.
+    pub toolchain_paths: Vec<ProjectRelativePathBuf>,
+    /// Whether to cut actions off from the network.
+    pub isolate_network: bool,
+}
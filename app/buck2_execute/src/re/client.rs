@@ -45,7 +45,9 @@ use remote_execution::ExecuteResponse;
 use remote_execution::ExecuteWithProgressResponse;
 use remote_execution::GetDigestsTtlRequest;
 use remote_execution::HostResourceRequirements;
+use remote_execution::HttpHeader;
 use remote_execution::InlinedBlobWithDigest;
+use remote_execution::InlinedDigestWithStatus;
 use remote_execution::NamedDigest;
 use remote_execution::NamedDigestWithPermissions;
 use remote_execution::NetworkStatisticsResponse;
@@ -59,6 +61,7 @@ use remote_execution::TActionResult2;
 use remote_execution::TCode;
 use remote_execution::TDigest;
 use remote_execution::TExecutionPolicy;
+use remote_execution::TLSCfg;
 use remote_execution::TResultsCachePolicy;
 use remote_execution::UploadRequest;
 use remote_execution::WriteActionResultRequest;
@@ -101,6 +104,17 @@ pub struct RemoteExecutionStaticMetadata {
     pub force_enable_deduplicate_find_missing: Option<bool>,
 
     pub features_config_path: Option<String>,
+
+    pub tls_ca_certs: Option<String>,
+    pub tls_client_cert: Option<String>,
+    pub tls_client_key: Option<String>,
+    /// Comma separated `Name: Value` headers sent with every request.
+    pub http_headers: Option<String>,
+    /// A command printing extra headers, one `Name: Value` per line, e.g. a token helper.
+    pub http_headers_command: Option<String>,
+    /// How often to re-run `http_headers_command`, since the tokens it prints expire.
+    pub http_headers_refresh_interval_secs: Option<u64>,
+    pub use_zstd_compression: bool,
 }
 
 impl RemoteExecutionStaticMetadata {
@@ -148,10 +162,48 @@ impl RemoteExecutionStaticMetadata {
             )?,
             features_config_path: legacy_config
                 .parse(BUCK2_RE_CLIENT_CFG_SECTION, "features_config_path")?,
+            tls_ca_certs: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "tls_ca_certs")?,
+            tls_client_cert: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "tls_client_cert")?,
+            tls_client_key: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "tls_client_key")?,
+            http_headers: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "http_headers")?,
+            http_headers_command: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "http_headers_command")?,
+            http_headers_refresh_interval_secs: legacy_config.parse(
+                BUCK2_RE_CLIENT_CFG_SECTION,
+                "http_headers_refresh_interval_secs",
+            )?,
+            use_zstd_compression: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "use_zstd_compression")?
+                .unwrap_or(false),
         })
     }
 }
 
+/// Blobs that could not be downloaded are returned with an error status and no data.
+fn check_blob_status(blob: &InlinedDigestWithStatus) -> anyhow::Result<()> {
+    if blob.status.code == TCode::OK {
+        Ok(())
+    } else {
+        Err(REClientError {
+            code: blob.status.code.clone(),
+            message: format!(
+                "Unable to download blob '{}': {}",
+                blob.digest, blob.status.message
+            ),
+        }
+        .into())
+    }
+}
+
+fn parse_http_headers(headers: &str) -> anyhow::Result<Vec<HttpHeader>> {
+    headers
+        .split(',')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
 pub struct RemoteExecutionClientOpStats {
     pub started: u32,
     pub finished_successfully: u32,
@@ -575,6 +627,25 @@ impl RemoteExecutionClientImpl {
             re_client_config.execution_client_config.address =
                 static_metadata.engine_address.clone();
 
+            re_client_config.tls_config = TLSCfg {
+                ca_certs: static_metadata.tls_ca_certs.clone(),
+                client_cert: static_metadata.tls_client_cert.clone(),
+                client_key: static_metadata.tls_client_key.clone(),
+            };
+            if let Some(http_headers) = &static_metadata.http_headers {
+                re_client_config.http_headers = parse_http_headers(http_headers)?;
+            }
+            re_client_config.use_zstd_compression = static_metadata.use_zstd_compression;
+            if let Some(command) = &static_metadata.http_headers_command {
+                re_client_config.http_headers_command = Some(
+                    shlex::split(command)
+                        .with_context(|| format!("Invalid http_headers_command `{}`", command))?,
+                );
+            }
+            re_client_config.http_headers_refresh_interval = static_metadata
+                .http_headers_refresh_interval_secs
+                .map(Duration::from_secs);
+
             let mut embedded_cas_daemon_config = EmbeddedCASDaemonClientCfg {
                 connection_count: cas_pool_size,
                 address: static_metadata.cas_address.clone(),
@@ -926,6 +997,7 @@ impl RemoteExecutionClientImpl {
         let mut blobs: Vec<T> = Vec::with_capacity(expected_blobs);
         if let Some(ds) = response.inlined_blobs {
             for d in ds {
+                check_blob_status(&d)?;
                 blobs.push(Message::decode(d.blob.as_slice()).with_context(|| {
                     format!("Failed to Protobuf decode tree at `{}`", d.digest)
                 })?);
@@ -960,13 +1032,14 @@ impl RemoteExecutionClientImpl {
             .await
             .with_context(|| format!("Download request failed for digest {}", digest))?;
 
-        response
+        let blob = response
             .inlined_blobs
             .into_iter()
             .flat_map(|blobs| blobs.into_iter())
             .next()
-            .map(|blob| blob.blob)
-            .with_context(|| format!("No digest was returned in request for {}", digest))
+            .with_context(|| format!("No digest was returned in request for {}", digest))?;
+        check_blob_status(&blob)?;
+        Ok(blob.blob)
     }
 
     pub async fn upload_blob(
@@ -1089,6 +1162,25 @@ fn chunks<T>(v: Vec<T>, chunk_size: usize) -> impl Iterator<Item = Vec<T>> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_http_headers() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_http_headers("Authorization: Bearer token, x-tenant:buck2,")?,
+            vec![
+                HttpHeader {
+                    key: "Authorization".to_owned(),
+                    value: "Bearer token".to_owned(),
+                },
+                HttpHeader {
+                    key: "x-tenant".to_owned(),
+                    value: "buck2".to_owned(),
+                },
+            ]
+        );
+        assert!(parse_http_headers("Authorization").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_chunks_skips() {
         assert_eq!(chunks(Vec::<usize>::new(), 1).next(), None);
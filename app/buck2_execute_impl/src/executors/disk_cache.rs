@@ -0,0 +1,799 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! An action cache and CAS on local disk. This lets action results be reused across checkouts
+//! and `buck2 clean` without a remote execution backend, or saves a round trip to the remote
+//! cache when there is one.
+//!
+//! The cache is laid out as follows:
+//!
+//! - `ac/<shard>/<action digest>`: the outputs of an action, as a serialized `ActionCacheEntry`.
+//! - `cas/<shard>/<file digest>`: the contents of a file.
+//! - `tmp/`: files being written, which are renamed into place once complete.
+//!
+//! Once the total size exceeds the configured limit, the least recently used entries are
+//! evicted. Recency is tracked in memory, and initialized from access times when the cache is
+//! opened.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::cas_digest::CasDigest;
+use buck2_common::executor_config::RemoteExecutorUseCase;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::Directory;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::directory::DirectoryIterator;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_execute::artifact::fs::ArtifactFs;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::digest::CasDigestFromReExt;
+use buck2_execute::digest::CasDigestToReExt;
+use buck2_execute::directory::directory_to_re_tree;
+use buck2_execute::directory::extract_artifact_value;
+use buck2_execute::directory::re_tree_to_directory;
+use buck2_execute::directory::ActionDirectory;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::output::CommandStdStreams;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::request::CommandExecutionOutput;
+use buck2_execute::execute::request::CommandExecutionOutputRef;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStatus;
+use buck2_execute::execute::result::CommandExecutionTimingData;
+use buck2_execute::materialize::materializer::Materializer;
+use chrono::Utc;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use remote_execution as RE;
+use thiserror::Error;
+use tracing::info;
+
+use crate::executors::local::create_output_dirs;
+
+/// The outputs of an action, as stored in the action cache.
+#[derive(Clone, PartialEq, prost::Message)]
+struct ActionCacheEntry {
+    /// The output files. Their names are their paths in the project.
+    #[prost(message, repeated, tag = "1")]
+    output_files: Vec<RE::FileNode>,
+    #[prost(message, repeated, tag = "2")]
+    output_directories: Vec<OutputDirectory>,
+    #[prost(bytes = "vec", tag = "3")]
+    stdout: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    stderr: Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    execution_time_us: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct OutputDirectory {
+    /// The path of the directory in the project.
+    #[prost(string, tag = "1")]
+    path: String,
+    #[prost(message, optional, tag = "2")]
+    tree: Option<RE::Tree>,
+}
+
+impl ActionCacheEntry {
+    fn file_digests(&self) -> anyhow::Result<Vec<FileDigest>> {
+        let mut digests = Vec::new();
+        for file in &self.output_files {
+            digests.push(FileDigest::from_grpc(
+                file.digest
+                    .as_ref()
+                    .with_context(|| DiskCacheError::MissingDigest(file.name.clone()))?,
+            ));
+        }
+        for dir in &self.output_directories {
+            let tree = match &dir.tree {
+                Some(tree) => tree,
+                None => continue,
+            };
+            for re_dir in tree.root.iter().chain(tree.children.iter()) {
+                for file in &re_dir.files {
+                    digests.push(FileDigest::from_grpc(
+                        file.digest
+                            .as_ref()
+                            .with_context(|| DiskCacheError::MissingDigest(file.name.clone()))?,
+                    ));
+                }
+            }
+        }
+        Ok(digests)
+    }
+}
+
+#[derive(Debug, Error)]
+enum DiskCacheError {
+    #[error("Cached output `{0}` has no digest")]
+    MissingDigest(String),
+
+    #[error("Cached output path `{0}` is not normalized")]
+    InvalidPath(String),
+
+    #[error("Cached output `{0}` is an absolute symlink")]
+    ExternalSymlink(String),
+}
+
+/// The entries in the cache, by their path relative to its root, in order of last access.
+#[derive(Default)]
+struct DiskCacheIndex {
+    /// The last access and size of each entry.
+    entries: HashMap<String, (u64, u64)>,
+    by_access: BTreeMap<u64, String>,
+    next_access: u64,
+    total_bytes: u64,
+}
+
+impl DiskCacheIndex {
+    /// Add an entry, or mark it as used if it's already there.
+    fn insert(&mut self, key: String, size: u64) {
+        // The entry might have been overwritten with different contents.
+        self.remove(&key);
+        let access = self.next_access;
+        self.next_access += 1;
+        self.entries.insert(key.clone(), (access, size));
+        self.by_access.insert(access, key);
+        self.total_bytes += size;
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((access, size)) = self.entries.remove(key) {
+            self.by_access.remove(&access);
+            self.total_bytes -= size;
+        }
+    }
+
+    /// Drop the least recently used entries until the total size is under `max_bytes`, and return
+    /// their keys.
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            let key = match self.by_access.values().next() {
+                Some(key) => key.clone(),
+                None => break,
+            };
+            self.remove(&key);
+            evicted.push(key);
+        }
+        evicted
+    }
+}
+
+/// How old files in `tmp/` have to be to be considered left behind by a crashed daemon.
+const STALE_TMP_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A content-addressed action cache and CAS stored in a directory on local disk.
+pub struct DiskActionCache {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    index: Mutex<DiskCacheIndex>,
+    next_tmp: AtomicU64,
+}
+
+/// The key of a blob in the cache. The first two characters of the digest are used to shard the
+/// cache, to avoid putting too many files in a single directory.
+fn digest_key<Kind>(kind: &str, digest: &CasDigest<Kind>) -> String {
+    let hash = hex::encode(digest.digest());
+    format!("{}/{}/{}-{}", kind, &hash[..2], hash, digest.size())
+}
+
+impl DiskActionCache {
+    /// Open the cache in `root`, creating it if needed. This scans the cache, so it should not
+    /// be called on the hot path.
+    pub fn open(root: AbsNormPathBuf, max_bytes: u64) -> anyhow::Result<Self> {
+        // Other daemons might be writing to the cache too, so only clean up files that were left
+        // behind a while ago.
+        let tmp = root.join(ForwardRelativePath::unchecked_new("tmp"));
+        fs_util::create_dir_all(&tmp)?;
+        for entry in fs_util::read_dir(&tmp)? {
+            let entry = entry?;
+            let stale = entry
+                .metadata()?
+                .modified()?
+                .elapsed()
+                .map_or(false, |age| age > STALE_TMP_AGE);
+            if stale {
+                fs_util::remove_all(entry.path())?;
+            }
+        }
+
+        let mut found = Vec::new();
+        for kind in ["ac", "cas"] {
+            let dir = root.join(ForwardRelativePath::unchecked_new(kind));
+            fs_util::create_dir_all(&dir)?;
+            for shard in fs_util::read_dir(&dir)? {
+                let shard = shard?;
+                if !shard.file_type()?.is_dir() {
+                    continue;
+                }
+                for entry in fs_util::read_dir(shard.path())? {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    if !metadata.is_file() {
+                        continue;
+                    }
+                    let last_access = metadata
+                        .accessed()
+                        .or_else(|_| metadata.modified())
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    let key = format!(
+                        "{}/{}/{}",
+                        kind,
+                        shard.file_name().to_string_lossy(),
+                        entry.file_name().to_string_lossy()
+                    );
+                    found.push((last_access, key, metadata.len()));
+                }
+            }
+        }
+
+        let mut index = DiskCacheIndex::default();
+        found.sort();
+        for (_, key, size) in found {
+            index.insert(key, size);
+        }
+
+        let cache = Self {
+            root,
+            max_bytes,
+            index: Mutex::new(index),
+            next_tmp: AtomicU64::new(0),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    fn path(&self, key: &str) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new(key))
+    }
+
+    /// Write a file into the cache under `key` atomically, by writing it elsewhere first.
+    fn write(
+        &self,
+        key: String,
+        write: impl FnOnce(&AbsNormPath) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let tmp = self.root.join(ForwardRelativePath::unchecked_new(&format!(
+            "tmp/{}-{}",
+            std::process::id(),
+            self.next_tmp.fetch_add(1, Ordering::Relaxed)
+        )));
+        write(&tmp)?;
+        let size = fs_util::metadata(&tmp)?.len();
+
+        let path = self.path(&key);
+        fs_util::create_dir_all(path.parent().expect("cache paths have a parent"))?;
+        fs_util::rename(&tmp, &path)?;
+
+        self.index.lock().insert(key, size);
+        Ok(())
+    }
+
+    /// Mark an entry as used, and return whether it exists. Other daemons might share the cache, so
+    /// this checks the disk rather than trusting the index.
+    fn find(&self, key: &str) -> anyhow::Result<bool> {
+        match fs_util::symlink_metadata_if_exists(self.path(key))? {
+            Some(metadata) => {
+                self.index.lock().insert(key.to_owned(), metadata.len());
+                Ok(true)
+            }
+            None => {
+                self.index.lock().remove(key);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Remove the least recently used entries until the cache fits in its size limit.
+    fn evict(&self) {
+        let evicted = self.index.lock().evict(self.max_bytes);
+        for key in evicted {
+            if let Err(e) = fs_util::remove_file(self.path(&key)) {
+                tracing::warn!("Error evicting `{}` from the disk cache: {:#}", key, e);
+            }
+        }
+    }
+
+    /// The path to the contents of a file in the cache.
+    fn blob_path(&self, digest: &FileDigest) -> AbsNormPathBuf {
+        self.path(&digest_key("cas", digest))
+    }
+
+    /// Add the contents of the file at `path` to the cache, unless it's already there.
+    fn store_blob(&self, digest: &FileDigest, path: &AbsNormPath) -> anyhow::Result<()> {
+        let key = digest_key("cas", digest);
+        if self.find(&key)? {
+            return Ok(());
+        }
+        self.write(key, |tmp| {
+            fs_util::copy(path, tmp)?;
+            Ok(())
+        })
+    }
+
+    /// Look up an action. This is a hit only if all the files it produced are still in the cache.
+    fn lookup(&self, digest: &ActionDigest) -> anyhow::Result<Option<ActionCacheEntry>> {
+        let key = digest_key("ac", digest);
+        if !self.find(&key)? {
+            return Ok(None);
+        }
+
+        let data = fs_util::read(self.path(&key))?;
+        let entry = <ActionCacheEntry as prost::Message>::decode(data.as_slice())
+            .with_context(|| format!("Error decoding `{}` in the disk cache", key))?;
+
+        for blob in entry.file_digests()? {
+            if !self.find(&digest_key("cas", &blob))? {
+                // Some of the outputs were evicted, so this entry is useless.
+                self.index.lock().remove(&key);
+                fs_util::remove_file(self.path(&key))?;
+                return Ok(None);
+            }
+        }
+        Ok(Some(entry))
+    }
+
+    fn store(&self, digest: &ActionDigest, entry: &ActionCacheEntry) -> anyhow::Result<()> {
+        self.write(digest_key("ac", digest), |tmp| {
+            fs_util::write(tmp, prost::Message::encode_to_vec(entry))
+        })?;
+        self.evict();
+        Ok(())
+    }
+}
+
+/// A PreparedCommandExecutor that will check a cache on local disk before executing any actions
+/// using the underlying executor, and adds the results of actions that ran locally to it.
+pub struct DiskCachingExecutor {
+    pub inner: Arc<dyn PreparedCommandExecutor>,
+    pub cache: Arc<DiskActionCache>,
+    pub artifact_fs: ArtifactFs,
+    pub materializer: Arc<dyn Materializer>,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+}
+
+impl DiskCachingExecutor {
+    async fn try_disk_cache_fetch(
+        &self,
+        mut manager: CommandExecutionManager,
+        command: &PreparedCommand<'_, '_>,
+    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let action_digest = &command.prepared_action.action;
+        let start = Instant::now();
+
+        let lookup = manager
+            .stage_async(
+                buck2_data::CacheQuery {
+                    action_digest: action_digest.to_string(),
+                },
+                self.blocking_executor
+                    .execute_io_inline(|| self.cache.lookup(action_digest)),
+            )
+            .await;
+
+        let entry = match lookup {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return ControlFlow::Continue(manager),
+            Err(e) => {
+                // A broken cache entry shouldn't fail the build, so just run the action.
+                tracing::warn!("Disk cache lookup for `{}` failed: {:#}", action_digest, e);
+                return ControlFlow::Continue(manager);
+            }
+        };
+
+        info!(
+            "Action result is in the disk cache, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
+            command.request.args().join(" "),
+            action_digest,
+        );
+
+        let mut manager = manager.claim().await;
+
+        let outputs = manager
+            .stage_async(
+                buck2_data::CacheHit {
+                    action_digest: action_digest.to_string(),
+                },
+                self.restore_outputs(command, &entry),
+            )
+            .await;
+
+        let outputs = match outputs {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                // Like a failed lookup, this shouldn't fail the build. We hold the claim, so run
+                // the action with it, which also replaces whatever we restored.
+                tracing::warn!(
+                    "Disk cache restore for `{}` failed, running the action: {:#}",
+                    action_digest,
+                    e
+                );
+                return ControlFlow::Continue(manager.hand_over());
+            }
+        };
+
+        ControlFlow::Break(manager.success(
+            CommandExecutionKind::ActionCache {
+                digest: action_digest.dupe(),
+            },
+            outputs,
+            CommandStdStreams::Local {
+                stdout: entry.stdout,
+                stderr: entry.stderr,
+            },
+            CommandExecutionTimingData {
+                wall_time: start.elapsed(),
+                execution_time: Duration::from_micros(entry.execution_time_us),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Copy the outputs of an action from the cache into place, and declare them to the
+    /// materializer.
+    async fn restore_outputs(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        entry: &ActionCacheEntry,
+    ) -> anyhow::Result<IndexMap<CommandExecutionOutput, ArtifactValue>> {
+        create_output_dirs(
+            &self.artifact_fs,
+            command.request,
+            self.materializer.dupe(),
+            self.blocking_executor.dupe(),
+        )
+        .await?;
+
+        // Like when downloading from RE, merge the outputs into the inputs so that we can compute
+        // the values of outputs with symlinks to inputs.
+        let mut builder = command.action_paths.inputs.clone().into_builder();
+        let mut restored = Vec::new();
+
+        for file in &entry.output_files {
+            let digest = file
+                .digest
+                .as_ref()
+                .with_context(|| DiskCacheError::MissingDigest(file.name.clone()))?;
+            let leaf = DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+                digest: TrackedFileDigest::new(FileDigest::from_grpc(digest)),
+                is_executable: file.is_executable,
+            }));
+            let path = cached_path(&file.name)?;
+            builder.insert(path.as_forward_relative_path(), leaf.clone())?;
+            restored.push((path, leaf));
+        }
+
+        let expires = Utc::now();
+        for dir in &entry.output_directories {
+            let tree = RE::Tree::default();
+            let dir_builder = re_tree_to_directory(dir.tree.as_ref().unwrap_or(&tree), &expires)?;
+            let path = cached_path(&dir.path)?;
+            builder.insert(
+                path.as_forward_relative_path(),
+                DirectoryEntry::Dir(dir_builder.clone()),
+            )?;
+            restored.push((path, DirectoryEntry::Dir(dir_builder)));
+        }
+
+        let fs = self.artifact_fs.fs();
+        self.blocking_executor
+            .execute_io_inline(|| {
+                for (path, entry) in &restored {
+                    self.restore_entry(entry.as_ref(), &fs.resolve(path))?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        let mut to_declare = Vec::new();
+        let mut mapped_outputs = IndexMap::new();
+        for (requested, (path, _)) in command
+            .request
+            .outputs()
+            .zip(command.action_paths.outputs.iter())
+        {
+            if let Some(value) = extract_artifact_value(&builder, path.as_ref())? {
+                match requested {
+                    CommandExecutionOutputRef::BuildArtifact { .. } => {
+                        to_declare.push((path.clone(), value.dupe()));
+                    }
+                    CommandExecutionOutputRef::TestPath { .. } => {
+                        // Like the local executor, don't declare those.
+                    }
+                }
+                mapped_outputs.insert(requested.cloned(), value);
+            }
+        }
+
+        self.materializer.declare_existing(to_declare).await?;
+
+        Ok(mapped_outputs)
+    }
+
+    fn restore_entry<D>(
+        &self,
+        entry: DirectoryEntry<&D, &ActionDirectoryMember>,
+        dest: &AbsNormPath,
+    ) -> anyhow::Result<()>
+    where
+        D: ActionDirectory + ?Sized,
+    {
+        match entry {
+            DirectoryEntry::Dir(d) => {
+                fs_util::create_dir_all(dest)?;
+                for (name, entry) in d.entries() {
+                    self.restore_entry(entry, &dest.join(name))?;
+                }
+            }
+            DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                fs_util::create_dir_all(dest.parent().expect("outputs have a parent"))?;
+                fs_util::copy(self.cache.blob_path(f.digest.data()), dest)?;
+                set_executable(dest, f.is_executable)?;
+            }
+            DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => {
+                fs_util::symlink(s.target().as_str(), dest)?;
+            }
+            DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(..)) => {
+                return Err(DiskCacheError::ExternalSymlink(dest.to_string()).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the result of an action to the cache if it ran locally and succeeded (results from RE
+    /// aren't on disk, and are in the remote cache already), under the same conditions as uploads
+    /// to the remote cache. Returns whether it was added.
+    async fn maybe_store(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        result: &CommandExecutionResult,
+    ) -> anyhow::Result<bool> {
+        if !command.request.allow_cache_upload() {
+            return Ok(false);
+        }
+
+        // Without a cleanup, the outputs might depend on what was there before, not just on the
+        // action.
+        if !command.request.outputs_cleanup() {
+            return Ok(false);
+        }
+
+        match &result.report.status {
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Local { .. },
+            } => {}
+            _ => return Ok(false),
+        }
+
+        let (stdout, stderr) = match &result.report.std_streams {
+            CommandStdStreams::Local { stdout, stderr } => (stdout.clone(), stderr.clone()),
+            _ => return Ok(false),
+        };
+
+        let mut entry = ActionCacheEntry {
+            output_files: Vec::new(),
+            output_directories: Vec::new(),
+            stdout,
+            stderr,
+            execution_time_us: result
+                .report
+                .timing
+                .execution_time
+                .as_micros()
+                .try_into()
+                .unwrap_or(u64::MAX),
+        };
+        // The files to add to the CAS, and where they are.
+        let mut blobs = Vec::new();
+
+        let fs = self.artifact_fs.fs();
+        for (output, value) in result.outputs.iter() {
+            let path = output.as_ref().resolve(&self.artifact_fs).into_path();
+            let abs_path = fs.resolve(&path);
+            match value.entry().as_ref() {
+                DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                    entry.output_files.push(RE::FileNode {
+                        name: path.to_string(),
+                        digest: Some(f.digest.to_grpc()),
+                        is_executable: f.is_executable,
+                    });
+                    blobs.push((f.digest.data().dupe(), abs_path));
+                }
+                DirectoryEntry::Dir(d) => {
+                    let mut walk = d.unordered_walk();
+                    while let Some((member_path, member)) = walk.next() {
+                        match member {
+                            DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                                blobs.push((
+                                    f.digest.data().dupe(),
+                                    abs_path.join(member_path.get()),
+                                ));
+                            }
+                            DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(..)) => {
+                                // Those point outside the project, so they might not mean the
+                                // same thing in another checkout.
+                                return Ok(false);
+                            }
+                            DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(..))
+                            | DirectoryEntry::Dir(..) => {}
+                        }
+                    }
+                    entry.output_directories.push(OutputDirectory {
+                        path: path.to_string(),
+                        tree: Some(directory_to_re_tree(d)),
+                    });
+                }
+                DirectoryEntry::Leaf(..) => {
+                    // Like with the remote cache, a symlink can't be the output of an action.
+                    return Ok(false);
+                }
+            }
+        }
+
+        let action_digest = &command.prepared_action.action;
+        self.blocking_executor
+            .execute_io_inline(|| {
+                for (digest, path) in &blobs {
+                    self.cache.store_blob(digest, path)?;
+                }
+                self.cache.store(action_digest, &entry)
+            })
+            .await?;
+
+        Ok(true)
+    }
+}
+
+/// Parse the path of an output stored in the cache.
+fn cached_path(path: &str) -> anyhow::Result<ProjectRelativePathBuf> {
+    Ok(ProjectRelativePath::new(path)
+        .with_context(|| DiskCacheError::InvalidPath(path.to_owned()))?
+        .to_owned())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &AbsNormPath, executable: bool) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = if executable { 0o755 } else { 0o644 };
+    fs_util::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &AbsNormPath, _executable: bool) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[async_trait]
+impl PreparedCommandExecutor for DiskCachingExecutor {
+    async fn exec_cmd(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+    ) -> CommandExecutionResult {
+        let manager = self.try_disk_cache_fetch(manager, command).await?;
+
+        let res = self.inner.exec_cmd(command, manager).await;
+
+        match self.maybe_store(command, &res).await {
+            Ok(true) => tracing::info!(
+                "Disk cache store for `{}` succeeded",
+                command.prepared_action.action
+            ),
+            Ok(false) => tracing::info!(
+                "Disk cache store for `{}` not attempted",
+                command.prepared_action.action
+            ),
+            Err(e) => tracing::warn!(
+                "Disk cache store for `{}` failed: {:#}",
+                command.prepared_action.action,
+                e
+            ),
+        }
+
+        res
+    }
+
+    fn re_platform(&self) -> Option<&RE::Platform> {
+        self.inner.re_platform()
+    }
+
+    fn re_use_case(&self) -> RemoteExecutorUseCase {
+        self.inner.re_use_case()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> String {
+        name.to_owned()
+    }
+
+    #[test]
+    fn test_index_evicts_least_recently_used() {
+        let mut index = DiskCacheIndex::default();
+        index.insert(key("a"), 10);
+        index.insert(key("b"), 10);
+        index.insert(key("c"), 10);
+        index.insert(key("a"), 10);
+
+        assert_eq!(vec![key("b")], index.evict(20));
+        assert_eq!(20, index.total_bytes);
+
+        assert_eq!(vec![key("c"), key("a")], index.evict(0));
+        assert_eq!(0, index.total_bytes);
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn test_disk_cache_round_trip() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsNormPathBuf::try_from(tempdir.path().to_owned())?;
+        let cache_root = root.join(ForwardRelativePath::new("cache")?);
+        let src = root.join(ForwardRelativePath::new("src")?);
+        fs_util::write(&src, "contents")?;
+
+        let blob = FileDigest::from_bytes_sha1(b"contents");
+        let action = ActionDigest::from_bytes_sha1(b"action");
+        let entry = ActionCacheEntry {
+            output_files: vec![RE::FileNode {
+                name: "out".to_owned(),
+                digest: Some(blob.to_grpc()),
+                is_executable: false,
+            }],
+            stdout: b"stdout".to_vec(),
+            ..Default::default()
+        };
+
+        {
+            let cache = DiskActionCache::open(cache_root.clone(), 1024)?;
+            assert_eq!(None, cache.lookup(&action)?);
+            cache.store_blob(&blob, &src)?;
+            cache.store(&action, &entry)?;
+            assert_eq!(Some(&entry), cache.lookup(&action)?.as_ref());
+        }
+
+        // The cache survives being reopened, but not losing the outputs of an action.
+        let cache = DiskActionCache::open(cache_root.clone(), 1024)?;
+        assert_eq!(Some(&entry), cache.lookup(&action)?.as_ref());
+        assert_eq!("contents", fs_util::read_to_string(cache.blob_path(&blob))?);
+        fs_util::remove_file(cache.blob_path(&blob))?;
+        let cache = DiskActionCache::open(cache_root, 1024)?;
+        assert_eq!(None, cache.lookup(&action)?);
+
+        Ok(())
+    }
+}
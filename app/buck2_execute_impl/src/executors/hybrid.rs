@@ -190,7 +190,10 @@ impl PreparedCommandExecutor for HybridExecutor {
                 // If the execution is successful, use the result.
                 CommandExecutionStatus::Success { .. } => false,
                 // Retry commands that failed (i.e. exit 1) only if we're instructed to do so.
-                CommandExecutionStatus::Failure { .. } => fallback_on_failure,
+                // That includes commands that failed because the sandbox hid their undeclared
+                // inputs.
+                CommandExecutionStatus::Failure { .. }
+                | CommandExecutionStatus::SandboxViolation { .. } => fallback_on_failure,
                 // Errors are infra errors and are always retried because that is the point of
                 // falling back.
                 CommandExecutionStatus::Error { .. } | CommandExecutionStatus::TimedOut { .. } => {
@@ -51,7 +51,9 @@ use buck2_execute::execute::request::CommandExecutionInput;
 use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionOutputRef;
 use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::execute::request::ResourceLimits;
 use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::execute::result::CommandExecutionStats;
 use buck2_execute::execute::result::CommandExecutionTimingData;
 use buck2_execute::execute::target::CommandExecutionTarget;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
@@ -75,6 +77,10 @@ use remote_execution as RE;
 use thiserror::Error;
 use tracing::info;
 
+use crate::executors::sandbox::SandboxPaths;
+use crate::executors::worker::WorkerInput;
+use crate::executors::worker::WorkerPool;
+
 #[derive(Debug, Error)]
 enum LocalExecutionError {
     #[error("Args list was empty")]
@@ -82,6 +88,9 @@ enum LocalExecutionError {
 
     #[error("Trying to execute a remote-only action on a local executor")]
     RemoteOnlyAction,
+
+    #[error("Sandboxing local actions requires the forkserver (`buck2.forkserver`)")]
+    SandboxWithoutForkserver,
 }
 
 #[derive(Clone)]
@@ -93,6 +102,7 @@ pub struct LocalExecutor {
     root: AbsNormPathBuf,
     #[cfg_attr(not(unix), allow(unused))]
     forkserver: Option<ForkserverClient>,
+    worker_pool: Option<Arc<WorkerPool>>,
     #[allow(unused)]
     knobs: ExecutorGlobalKnobs,
 }
@@ -105,6 +115,7 @@ impl LocalExecutor {
         host_sharing_broker: Arc<HostSharingBroker>,
         root: AbsNormPathBuf,
         forkserver: Option<ForkserverClient>,
+        worker_pool: Option<Arc<WorkerPool>>,
         knobs: ExecutorGlobalKnobs,
     ) -> Self {
         Self {
@@ -114,6 +125,7 @@ impl LocalExecutor {
             host_sharing_broker,
             root,
             forkserver,
+            worker_pool,
             knobs,
         }
     }
@@ -129,6 +141,8 @@ impl LocalExecutor {
         timeout: Option<Duration>,
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: Arc<dyn LivelinessObserver>,
+        sandbox: Option<buck2_forkserver_proto::Sandbox>,
+        resource_limits: ResourceLimits,
     ) -> impl futures::future::Future<Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>> + 'a
     {
         async move {
@@ -152,17 +166,24 @@ impl LocalExecutor {
                             timeout,
                             env_inheritance,
                             liveliness_observer,
+                            sandbox,
+                            resource_limits,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = forkserver;
+                        let _unused = (forkserver, sandbox, resource_limits);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
 
+                None if sandbox.is_some() => {
+                    Err(LocalExecutionError::SandboxWithoutForkserver.into())
+                }
+
+                // Without the forkserver, resource limits are not enforced.
                 None => {
                     let mut cmd = background_command(exe);
                     cmd.current_dir(working_directory);
@@ -191,6 +212,7 @@ impl LocalExecutor {
         action_digest: &ActionDigest,
         action: CommandExecutionTarget<'_>,
         request: &CommandExecutionRequest,
+        timeout: Option<Duration>,
         mut manager: CommandExecutionManager,
     ) -> CommandExecutionResult {
         let args = request.args();
@@ -241,6 +263,19 @@ impl LocalExecutor {
         // For the $TMPDIR - important it is absolute
         let scratch_dir_abs = self.artifact_fs.fs().resolve(&scratch_dir);
 
+        let sandbox = match &self.knobs.local_sandbox {
+            Some(options) => match SandboxPaths::new(
+                &self.artifact_fs,
+                request,
+                options,
+                request.custom_tmpdir.then_some(&*scratch_dir),
+            ) {
+                Ok(paths) => Some((paths, options.isolate_network)),
+                Err(e) => return manager.error("sandbox_paths", e),
+            },
+            None => None,
+        };
+
         if let Err(e) = manager
             .stage_async(
                 buck2_data::LocalStage {
@@ -301,6 +336,9 @@ impl LocalExecutor {
         };
 
         let liveliness_observer = manager.liveliness_observer.dupe();
+        let sandbox_proto = sandbox
+            .as_ref()
+            .map(|(paths, isolate_network)| paths.to_proto(&self.root, *isolate_network));
 
         let (timing, res) = manager
             .stage_async(
@@ -326,26 +364,56 @@ impl LocalExecutor {
                     let execution_start = Instant::now();
                     let start_time = SystemTime::now();
 
-                    let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
-                    let r = self
-                        .exec(
-                            &args[0],
-                            &args[1..],
-                            env,
-                            request.working_directory(),
-                            request.timeout(),
-                            request.local_environment_inheritance(),
-                            liveliness_observer,
+                    let r = match self
+                        .exec_in_worker(
+                            request,
+                            timeout,
+                            sandbox_proto.is_some(),
+                            liveliness_observer.dupe(),
                         )
-                        .await;
+                        .await
+                    {
+                        Some(r) => r,
+                        None => {
+                            let env = iter_env().map(|(k, v)| (k, v.into_os_str()));
+                            self.exec(
+                                &args[0],
+                                &args[1..],
+                                env,
+                                request.working_directory(),
+                                timeout,
+                                request.local_environment_inheritance(),
+                                liveliness_observer,
+                                sandbox_proto,
+                                request.resource_limits(),
+                            )
+                            .await
+                        }
+                    };
 
                     let execution_time = execution_start.elapsed();
 
+                    let execution_stats = match &r {
+                        Ok((
+                            GatherOutputStatus::Finished {
+                                execution_stats: Some(stats),
+                                ..
+                            },
+                            _,
+                            _,
+                        )) => Some(CommandExecutionStats {
+                            memory_peak: stats.memory_peak,
+                            cpu_time: stats.cpu_time,
+                        }),
+                        _ => None,
+                    };
+
                     let timing = CommandExecutionTimingData {
                         wall_time: execution_time,
                         re_queue_time: None,
                         execution_time,
                         start_time,
+                        execution_stats,
                     };
 
                     (timing, r)
@@ -364,10 +432,36 @@ impl LocalExecutor {
             Err(e) => return manager.error("exec_failed", e), // TODO (torozco): Can this take CommandExecutionKind? Should this be a failure?
         };
 
+        // Commands that succeeded despite the sandbox were only probing for those paths.
+        let violations = match (&status, &sandbox) {
+            (
+                GatherOutputStatus::Finished {
+                    exit_status: status,
+                    sandbox_violations,
+                    ..
+                },
+                Some((paths, _)),
+            ) if !status.success() => paths.find_violations(&self.root, sandbox_violations),
+            _ => Vec::new(),
+        };
+
         let std_streams = CommandStdStreams::Local { stdout, stderr };
 
         match status {
-            GatherOutputStatus::Finished(status) => {
+            GatherOutputStatus::Finished {
+                exit_status: status,
+                ..
+            } if !violations.is_empty() => manager.sandbox_violation(
+                execution_kind,
+                violations,
+                std_streams,
+                status.code(),
+                timing,
+            ),
+            GatherOutputStatus::Finished {
+                exit_status: status,
+                ..
+            } => {
                 let outputs = match self.calculate_and_declare_output_values(request).await {
                     Ok(output_values) => output_values,
                     Err(e) => return manager.error("calculate_output_values_failed", e),
@@ -375,7 +469,7 @@ impl LocalExecutor {
 
                 match status.code() {
                     Some(0) => manager.success(execution_kind, outputs, std_streams, timing),
-                    v => manager.failure(execution_kind, outputs, std_streams, v),
+                    v => manager.failure(execution_kind, outputs, std_streams, v, timing),
                 }
             }
             GatherOutputStatus::SpawnFailed(reason) => {
@@ -391,6 +485,7 @@ impl LocalExecutor {
                             .into_bytes(),
                     },
                     None,
+                    timing,
                 )
             }
             GatherOutputStatus::TimedOut(duration) => {
@@ -400,6 +495,62 @@ impl LocalExecutor {
         }
     }
 
+    /// Runs the command in a persistent worker, if it supports them and we can. Returns `None` if
+    /// the command should run as a one-shot process instead.
+    async fn exec_in_worker(
+        &self,
+        request: &CommandExecutionRequest,
+        timeout: Option<Duration>,
+        sandboxed: bool,
+        liveliness_observer: Arc<dyn LivelinessObserver>,
+    ) -> Option<anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>> {
+        let protocol = request.worker_protocol()?;
+        let worker_pool = self.worker_pool.as_ref()?;
+        // Workers outlive the actions they run, so they can't be sandboxed to the inputs of
+        // any one of them.
+        if sandboxed {
+            return None;
+        }
+        // Nor can they be held to the resource limits of any one of them.
+        if !request.resource_limits().is_unlimited() {
+            return None;
+        }
+
+        let mut inputs = Vec::new();
+        for input in request.inputs() {
+            if let CommandExecutionInput::Artifact(group) = input {
+                for (artifact, value) in group.iter() {
+                    let path = match self.artifact_fs.resolve(artifact.get_path()) {
+                        Ok(path) => path,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    inputs.push(WorkerInput {
+                        path: path.to_string(),
+                        digest: value.digest().cloned(),
+                    });
+                }
+            }
+        }
+
+        let working_directory = match request.working_directory() {
+            Some(d) => self.root.join(d),
+            None => self.root.clone(),
+        };
+
+        worker_pool
+            .exec(
+                protocol,
+                request.args(),
+                request.env(),
+                &working_directory,
+                request.local_environment_inheritance(),
+                inputs,
+                timeout,
+                liveliness_observer,
+            )
+            .await
+    }
+
     async fn calculate_and_declare_output_values(
         &self,
         request: &CommandExecutionRequest,
@@ -532,6 +683,7 @@ impl PreparedCommandExecutor for LocalExecutor {
             target,
             action_paths: _action_paths,
             prepared_action,
+            timeout,
         } = command;
 
         let _permit = manager
@@ -551,6 +703,7 @@ impl PreparedCommandExecutor for LocalExecutor {
             &prepared_action.action,
             *target,
             request,
+            *timeout,
             manager,
         ))
         .await
@@ -771,6 +924,8 @@ mod unix {
         comand_timeout: Option<Duration>,
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: Arc<dyn LivelinessObserver>,
+        sandbox: Option<buck2_forkserver_proto::Sandbox>,
+        resource_limits: ResourceLimits,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
 
@@ -785,6 +940,15 @@ mod unix {
             }),
             env: vec![],
             timeout: comand_timeout.into_try_map(|d| d.try_into())?,
+            sandbox,
+            resource_limits: if resource_limits.is_unlimited() {
+                None
+            } else {
+                Some(buck2_forkserver_proto::ResourceLimits {
+                    memory_max_bytes: resource_limits.memory_max_bytes,
+                    cpu_max_millicores: resource_limits.cpu_max_millicores,
+                })
+            },
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -843,15 +1007,28 @@ mod tests {
     use std::sync::Arc;
     use std::time::Instant;
 
+    use buck2_common::executor_config::PathSeparatorKind;
     use buck2_common::liveliness_observer::NoopLivelinessObserver;
+    use buck2_core::category::Category;
     use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::paths::CellRelativePath;
     use buck2_core::cells::testing::CellResolverExt;
     use buck2_core::cells::CellName;
     use buck2_core::cells::CellResolver;
+    use buck2_core::configuration::Configuration;
     use buck2_core::fs::project::ProjectRelativePathBuf;
     use buck2_core::fs::project::ProjectRoot;
+    use buck2_core::package::PackageLabel;
+    use buck2_core::target::TargetLabel;
+    use buck2_core::target::TargetName;
+    use buck2_data::ToProtoMessage;
+    use buck2_events::dispatch::EventDispatcher;
     use buck2_execute::artifact::fs::ArtifactFs;
+    use buck2_execute::base_deferred_key::BaseDeferredKey;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
+    use buck2_execute::execute::claim::MutexClaimManager;
+    use buck2_execute::execute::command_executor::CommandExecutor;
+    use buck2_execute::execute::result::CommandExecutionStatus;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
     use buck2_execute::path::buck_out_path::BuckOutPathResolver;
     use buck2_execute::path::buck_out_path::BuckPathResolver;
@@ -869,7 +1046,9 @@ mod tests {
         cmd.args(["-c", "echo hello"]);
 
         let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
@@ -900,7 +1079,7 @@ mod tests {
         )
         .await?;
         assert!(
-            matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)),
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0)),
             "status: {:?}",
             status
         );
@@ -967,6 +1146,7 @@ mod tests {
             )),
             root.clone(),
             None,
+            None,
             ExecutorGlobalKnobs::default(),
         );
 
@@ -987,9 +1167,13 @@ mod tests {
                 None,
                 None,
                 NoopLivelinessObserver::create(),
+                None,
+                ResourceLimits::default(),
             )
             .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0))
+        );
 
         let stdout = std::str::from_utf8(&stdout).context("Invalid stdout")?;
 
@@ -1022,11 +1206,85 @@ mod tests {
                 None,
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
+                None,
+                ResourceLimits::default(),
             )
             .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0))
+        );
         assert_eq!(stdout, b"\n");
 
         Ok(())
     }
+
+    struct TestingActionKey;
+
+    impl ToProtoMessage for TestingActionKey {
+        type Message = buck2_data::ActionKey;
+
+        fn as_proto(&self) -> Self::Message {
+            buck2_data::ActionKey::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exec_cmd_timeout() -> anyhow::Result<()> {
+        let (executor, _root, _tmpdir) = test_executor()?;
+        let artifact_fs = executor.artifact_fs.clone();
+        let executor = CommandExecutor::new(
+            Arc::new(executor),
+            artifact_fs,
+            PathSeparatorKind::Unix,
+            None,
+        );
+
+        let pkg = PackageLabel::new(
+            &CellName::unchecked_new("cell".into()),
+            CellRelativePath::unchecked_new("pkg"),
+        );
+        let owner = BaseDeferredKey::TargetLabel(
+            TargetLabel::new(pkg, TargetName::unchecked_new("foo"))
+                .configure(Configuration::testing_new()),
+        );
+        let category = Category::try_from("testing")?;
+        let target = CommandExecutionTarget {
+            owner: &owner,
+            category: &category,
+            identifier: None,
+            action_key: &TestingActionKey,
+        };
+
+        // What a `run` action with `timeout_seconds = 1` requests.
+        let request = CommandExecutionRequest::new(
+            vec!["sh".to_owned(), "-c".to_owned(), "sleep 10".to_owned()],
+            Vec::new(),
+            IndexMap::new(),
+            HashMap::new(),
+        )
+        .with_timeout(Duration::from_secs(1));
+        let manager = CommandExecutionManager::new(
+            box MutexClaimManager::new(),
+            EventDispatcher::null(),
+            NoopLivelinessObserver::create(),
+        );
+
+        let now = Instant::now();
+        let res = executor.exec_cmd(target, &request, manager).await;
+        assert!(
+            matches!(
+                res.report.status,
+                CommandExecutionStatus::TimedOut {
+                    execution_kind: CommandExecutionKind::Local { .. },
+                    duration,
+                } if duration == Duration::from_secs(1)
+            ),
+            "status: {:?}",
+            res.report.status
+        );
+        assert!(now.elapsed() < Duration::from_secs(9));
+
+        Ok(())
+    }
 }
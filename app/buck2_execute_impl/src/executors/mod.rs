@@ -8,6 +8,9 @@
  */
 
 pub mod caching;
+pub mod disk_cache;
 pub mod hybrid;
 pub mod local;
 pub mod re;
+mod sandbox;
+pub mod worker;
@@ -9,6 +9,7 @@
 
 use std::ops::ControlFlow;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use buck2_common::executor_config::RemoteExecutorUseCase;
@@ -160,6 +161,7 @@ impl ReExecutor {
         request: &CommandExecutionRequest,
         action_digest: &ActionDigest,
         action_paths: &ActionPaths,
+        timeout: Option<Duration>,
     ) -> ControlFlow<CommandExecutionResult, (CommandExecutionManager, ExecuteResponse)> {
         info!(
             "RE command line:\n```\n$ {}\n```\n for action `{}`",
@@ -188,6 +190,21 @@ impl ReExecutor {
 
         let action_result = &response.action_result;
 
+        if let Some(timeout) = timeout {
+            if response.error.code == TCode::DEADLINE_EXCEEDED {
+                return ControlFlow::Break(manager.timeout(
+                    CommandExecutionKind::Remote {
+                        digest: action_digest.dupe(),
+                    },
+                    timeout,
+                    CommandStdStreams::Remote(
+                        response.std_streams(&self.re_client, self.re_use_case),
+                    ),
+                    response.timing(),
+                ));
+            }
+        }
+
         if response.error.code != TCode::OK {
             return ControlFlow::Break(manager.error(
                 "remote_exec_error",
@@ -208,6 +225,7 @@ impl ReExecutor {
                 IndexMap::new(),
                 CommandStdStreams::Remote(response.std_streams(&self.re_client, self.re_use_case)),
                 Some(action_result.exit_code),
+                response.timing(),
             ));
         }
 
@@ -231,6 +249,7 @@ impl PreparedCommandExecutor for ReExecutor {
                     action: action_digest,
                     blobs,
                 },
+            timeout,
         } = command;
 
         if command.request.executor_preference().requires_local() {
@@ -241,7 +260,14 @@ impl PreparedCommandExecutor for ReExecutor {
         let manager = self.upload(manager, blobs, action_paths).await?;
 
         let (manager, response) = self
-            .re_execute(manager, target, request, action_digest, action_paths)
+            .re_execute(
+                manager,
+                target,
+                request,
+                action_digest,
+                action_paths,
+                *timeout,
+            )
             .await?;
 
         download_action_results(
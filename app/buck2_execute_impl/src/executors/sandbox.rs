@@ -0,0 +1,130 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! What the sandbox of a local command exposes of the project, and which of the paths that the
+//! forkserver saw it hide from a command are in the project.
+
+use std::path::PathBuf;
+
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_execute::artifact::fs::ArtifactFs;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::knobs::LocalSandboxOptions;
+
+pub(crate) struct SandboxPaths {
+    readable: Vec<ProjectRelativePathBuf>,
+    writable: Vec<ProjectRelativePathBuf>,
+}
+
+impl SandboxPaths {
+    /// A command can read its inputs and the toolchains, and can write its outputs and its scratch
+    /// directory.
+    pub(crate) fn new(
+        artifact_fs: &ArtifactFs,
+        request: &CommandExecutionRequest,
+        options: &LocalSandboxOptions,
+        scratch_dir: Option<&ProjectRelativePath>,
+    ) -> anyhow::Result<Self> {
+        let mut readable = options.toolchain_paths.clone();
+        for input in request.inputs() {
+            match input {
+                CommandExecutionInput::Artifact(group) => {
+                    for (artifact, _) in group.iter() {
+                        readable.push(artifact_fs.resolve(artifact.get_path())?);
+                    }
+                }
+                CommandExecutionInput::ActionMetadata(metadata) => {
+                    readable.push(
+                        artifact_fs
+                            .buck_out_path_resolver()
+                            .resolve_gen(&metadata.path),
+                    );
+                }
+            }
+        }
+
+        let writable = request
+            .outputs()
+            .map(|output| output.resolve(artifact_fs).path)
+            .chain(scratch_dir.map(|d| d.to_owned()))
+            .collect();
+
+        Ok(Self { readable, writable })
+    }
+
+    pub(crate) fn to_proto(
+        &self,
+        root: &AbsNormPath,
+        isolate_network: bool,
+    ) -> buck2_forkserver_proto::Sandbox {
+        let bytes = |path: &ProjectRelativePath| {
+            root.join(path)
+                .as_os_str()
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+        };
+        buck2_forkserver_proto::Sandbox {
+            hidden_root: root.as_os_str().to_string_lossy().into_owned().into_bytes(),
+            readable_paths: self.readable.iter().map(|p| bytes(p)).collect(),
+            writable_paths: self.writable.iter().map(|p| bytes(p)).collect(),
+            isolate_network,
+        }
+    }
+
+    /// The paths in the project that a command tried to access but that its sandbox hid from it,
+    /// as the forkserver recorded them.
+    pub(crate) fn find_violations(
+        &self,
+        root: &AbsNormPath,
+        recorded: &[PathBuf],
+    ) -> Vec<ProjectRelativePathBuf> {
+        recorded
+            .iter()
+            .filter_map(|path| {
+                let path = AbsNormPath::new(path).ok()?;
+                let rel = path.strip_prefix(root).ok()?;
+                Some(ProjectRelativePathBuf::from(rel.into_owned()))
+            })
+            .filter(|path| !path.as_str().is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_violations() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsNormPath::new(tempdir.path())?;
+        let sandbox = SandboxPaths {
+            readable: Vec::new(),
+            writable: Vec::new(),
+        };
+
+        assert_eq!(
+            vec![ProjectRelativePath::new("src/undeclared")?.to_owned()],
+            sandbox.find_violations(
+                root,
+                &[
+                    root.as_path().join("src/undeclared"),
+                    root.as_path().to_owned(),
+                    PathBuf::from("/elsewhere/file"),
+                ]
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,749 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Persistent workers for local actions.
+//!
+//! Actions that opt in are run by long-lived worker processes instead of a fresh process each
+//! time, using the same protocol as Bazel's workers. The action's command line has to end with
+//! one or more flagfiles (`@file`, `--flagfile=file` or `-flagfile=file`): the rest of the
+//! command line starts the worker (with `--persistent_worker` appended), and the contents of the
+//! flagfiles are sent to it as the arguments of a `WorkRequest`.
+//!
+//! Workers are keyed by everything that affects how they start, and each key gets a bounded
+//! number of instances. A worker that crashes or breaks the protocol is killed and replaced, and
+//! if that keeps happening, or the action can't use a worker at all, the action runs as a normal
+//! one-shot process instead.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+use buck2_execute::execute::request::WorkerProtocol;
+use buck2_forkserver::run::timeout_into_cancellation;
+use buck2_forkserver::run::GatherOutputStatus;
+use buck2_util::process::background_command;
+use dupe::Dupe;
+use futures::future::select;
+use futures::future::Either;
+use futures::future::FutureExt;
+use parking_lot::Mutex;
+use prost::Message;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::process::Child;
+use tokio::process::ChildStdin;
+use tokio::process::ChildStdout;
+use tokio::sync::Semaphore;
+use tracing::info;
+use tracing::warn;
+
+use crate::executors::local::apply_local_execution_environment;
+
+/// How many times we start a fresh worker for an action before giving up and running it as a
+/// one-shot process.
+const MAX_WORKER_ATTEMPTS: usize = 2;
+
+#[derive(Debug, Error)]
+enum WorkerError {
+    #[error("Worker exited")]
+    Exited,
+
+    #[error("Worker sent an invalid message length")]
+    InvalidLength,
+
+    #[error("Worker responded to request {actual}, expected {expected}")]
+    WrongRequestId { expected: i32, actual: i32 },
+}
+
+/// An input of a `WorkRequest`.
+#[derive(Clone, PartialEq, prost::Message)]
+struct WorkInput {
+    /// The path of the input, relative to the project root.
+    #[prost(string, tag = "1")]
+    path: String,
+    #[prost(bytes = "vec", tag = "2")]
+    digest: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct WorkRequest {
+    #[prost(string, repeated, tag = "1")]
+    arguments: Vec<String>,
+    #[prost(message, repeated, tag = "2")]
+    inputs: Vec<WorkInput>,
+    #[prost(int32, tag = "3")]
+    request_id: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct WorkResponse {
+    #[prost(int32, tag = "1")]
+    exit_code: i32,
+    #[prost(string, tag = "2")]
+    output: String,
+    #[prost(int32, tag = "3")]
+    request_id: i32,
+}
+
+/// An input of an action, as reported to workers.
+pub(crate) struct WorkerInput {
+    /// The path of the input in the project.
+    pub path: String,
+    /// The digest of the input, if it's a file or a directory.
+    pub digest: Option<FileDigest>,
+}
+
+/// Everything that affects how a worker starts. Actions with the same key can share workers.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct WorkerKey {
+    protocol: WorkerProtocol,
+    startup_args: Vec<String>,
+    env: Vec<(String, String)>,
+    working_directory: AbsNormPathBuf,
+    /// The digests of the inputs that appear on the worker's command line, so that we start new
+    /// workers when the tools they run change. This is approximate: matching an input by
+    /// substring might include inputs that aren't tools, which only costs an extra worker.
+    tools: Vec<(String, Option<FileDigest>)>,
+}
+
+/// The workers that share a key.
+struct WorkerSet {
+    /// Bounds how many workers of this key run at once.
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<Worker>>,
+}
+
+/// The persistent workers of the daemon. They live for as long as the daemon does, and are killed
+/// when it exits.
+pub struct WorkerPool {
+    logs_dir: AbsNormPathBuf,
+    max_instances_per_key: usize,
+    next_worker_id: AtomicU64,
+    sets: Mutex<HashMap<WorkerKey, Arc<WorkerSet>>>,
+}
+
+impl WorkerPool {
+    /// Creates a pool that runs up to `max_instances_per_key` workers for each distinct worker
+    /// command, and writes their stderr to files in `logs_dir`.
+    pub fn new(logs_dir: AbsNormPathBuf, max_instances_per_key: usize) -> Self {
+        Self {
+            logs_dir,
+            max_instances_per_key: max_instances_per_key.max(1),
+            next_worker_id: AtomicU64::new(0),
+            sets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs a command in a persistent worker. Returns `None` if the command can't be run by a
+    /// worker, in which case it should be run as a one-shot process.
+    pub(crate) async fn exec(
+        &self,
+        protocol: WorkerProtocol,
+        args: &[String],
+        env: &HashMap<String, String>,
+        working_directory: &AbsNormPathBuf,
+        env_inheritance: Option<&EnvironmentInheritance>,
+        inputs: Vec<WorkerInput>,
+        timeout: Option<Duration>,
+        liveliness_observer: Arc<dyn LivelinessObserver>,
+    ) -> Option<anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>> {
+        let (startup_args, flagfiles) = match split_args(args) {
+            Some(split) => split,
+            None => {
+                info!(
+                    "Not running `{}` in a persistent worker: it must end with flagfiles, and \
+                    no other argument may be one",
+                    args.join(" ")
+                );
+                return None;
+            }
+        };
+
+        let arguments = match expand_flagfiles(&flagfiles, working_directory.as_ref()) {
+            Ok(arguments) => arguments,
+            Err(e) => {
+                warn!("Not running in a persistent worker: {:#}", e);
+                return None;
+            }
+        };
+
+        let mut env: Vec<_> = env.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        env.sort();
+
+        let tools = inputs
+            .iter()
+            .filter(|input| startup_args.iter().any(|arg| arg.contains(&input.path)))
+            .map(|input| (input.path.clone(), input.digest.clone()))
+            .collect();
+
+        let key = WorkerKey {
+            protocol,
+            startup_args: startup_args.into_iter().map(|arg| arg.to_owned()).collect(),
+            env,
+            working_directory: working_directory.clone(),
+            tools,
+        };
+
+        let request = WorkRequest {
+            arguments,
+            inputs: inputs
+                .into_iter()
+                .map(|input| WorkInput {
+                    path: input.path,
+                    digest: input
+                        .digest
+                        .map_or_else(Vec::new, |digest| digest.digest().to_vec()),
+                })
+                .collect(),
+            request_id: 0,
+        };
+
+        let set = self.set(&key);
+
+        let work = async {
+            for attempt in 1..=MAX_WORKER_ATTEMPTS {
+                // The semaphore is never closed.
+                let permit = set.permits.dupe().acquire_owned().await.ok()?;
+
+                let mut worker = match set.take_idle() {
+                    Some(worker) => worker,
+                    None => match self.spawn(&key, env_inheritance) {
+                        Ok(worker) => worker,
+                        Err(e) => {
+                            warn!("Failed to start persistent worker: {:#}", e);
+                            return None;
+                        }
+                    },
+                };
+
+                match worker.request(&request).await {
+                    Ok(response) => {
+                        set.idle.lock().push(worker);
+                        drop(permit);
+                        return Some(Ok((
+                            GatherOutputStatus::Finished {
+                                exit_status: exit_status_from_code(response.exit_code),
+                                execution_stats: None,
+                                sandbox_violations: Vec::new(),
+                            },
+                            Vec::new(),
+                            response.output.into_bytes(),
+                        )));
+                    }
+                    Err(e) => {
+                        // Dropping the worker kills it, and the next attempt starts a new one.
+                        warn!(
+                            "Persistent worker `{}` failed (attempt {} of {}): {:#}",
+                            key.startup_args.join(" "),
+                            attempt,
+                            MAX_WORKER_ATTEMPTS,
+                            e
+                        );
+                    }
+                }
+            }
+
+            None
+        };
+
+        let timeout = timeout_into_cancellation(timeout);
+        let alive = liveliness_observer
+            .while_alive()
+            .map(|()| anyhow::Ok(GatherOutputStatus::Cancelled));
+        let cancellation = select(timeout.boxed(), alive.boxed()).map(|r| r.factor_first().0);
+
+        // If we stop waiting on a worker in the middle of a request, the worker is dropped with
+        // the future, which kills it: we can't know what state it's in anymore.
+        match select(work.boxed(), cancellation.boxed()).await {
+            Either::Left((res, _)) => res,
+            Either::Right((status, _)) => Some(status.map(|s| (s, Vec::new(), Vec::new()))),
+        }
+    }
+
+    fn set(&self, key: &WorkerKey) -> Arc<WorkerSet> {
+        self.sets
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(WorkerSet {
+                    permits: Arc::new(Semaphore::new(self.max_instances_per_key)),
+                    idle: Mutex::new(Vec::new()),
+                })
+            })
+            .dupe()
+    }
+
+    fn spawn(
+        &self,
+        key: &WorkerKey,
+        env_inheritance: Option<&EnvironmentInheritance>,
+    ) -> anyhow::Result<Worker> {
+        let id = self.next_worker_id.fetch_add(1, Ordering::Relaxed);
+
+        fs_util::create_dir_all(&self.logs_dir)?;
+        let log_path = self
+            .logs_dir
+            .join(ForwardRelativePath::new(&format!("worker-{}.log", id))?);
+        let log = std::fs::File::create(&log_path)
+            .with_context(|| format!("Error creating worker log `{}`", log_path))?;
+
+        let mut cmd = background_command(&key.startup_args[0]);
+        cmd.current_dir(&key.working_directory);
+        cmd.args(&key.startup_args[1..]);
+        cmd.arg("--persistent_worker");
+        apply_local_execution_environment(
+            &mut cmd,
+            key.working_directory.as_ref(),
+            key.env.iter().map(|(k, v)| (k, v)),
+            env_inheritance,
+        );
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::from(log));
+
+        let mut cmd = tokio::process::Command::from(cmd);
+        cmd.kill_on_drop(true);
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Error spawning `{}`", key.startup_args[0]))?;
+
+        info!(
+            "Started persistent worker {} (`{}`), logging to `{}`",
+            id,
+            key.startup_args.join(" "),
+            log_path
+        );
+
+        Ok(Worker {
+            stdin: child.stdin.take().context("Worker has no stdin")?,
+            stdout: BufReader::new(child.stdout.take().context("Worker has no stdout")?),
+            child,
+            protocol: key.protocol,
+        })
+    }
+}
+
+impl WorkerSet {
+    /// Returns an idle worker that is still running, if any.
+    fn take_idle(&self) -> Option<Worker> {
+        let mut idle = self.idle.lock();
+        while let Some(mut worker) = idle.pop() {
+            if matches!(worker.child.try_wait(), Ok(None)) {
+                return Some(worker);
+            }
+        }
+        None
+    }
+}
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    protocol: WorkerProtocol,
+}
+
+impl Worker {
+    async fn request(&mut self, request: &WorkRequest) -> anyhow::Result<WorkResponse> {
+        write_request(self.protocol, &mut self.stdin, request).await?;
+        let response = read_response(self.protocol, &mut self.stdout).await?;
+        if response.request_id != request.request_id {
+            return Err(WorkerError::WrongRequestId {
+                expected: request.request_id,
+                actual: response.request_id,
+            }
+            .into());
+        }
+        Ok(response)
+    }
+}
+
+async fn write_request(
+    protocol: WorkerProtocol,
+    writer: &mut (impl AsyncWrite + Unpin),
+    request: &WorkRequest,
+) -> anyhow::Result<()> {
+    let data = match protocol {
+        WorkerProtocol::Json => {
+            let inputs: Vec<_> = request
+                .inputs
+                .iter()
+                .map(|input| {
+                    serde_json::json!({
+                        "path": input.path,
+                        "digest": base64::encode(&input.digest),
+                    })
+                })
+                .collect();
+            let mut data = serde_json::to_vec(&serde_json::json!({
+                "arguments": request.arguments,
+                "inputs": inputs,
+                "requestId": request.request_id,
+            }))?;
+            data.push(b'\n');
+            data
+        }
+        WorkerProtocol::Proto => request.encode_length_delimited_to_vec(),
+    };
+
+    writer
+        .write_all(&data)
+        .await
+        .context("Error writing to worker")?;
+    writer.flush().await.context("Error writing to worker")?;
+    Ok(())
+}
+
+async fn read_response(
+    protocol: WorkerProtocol,
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> anyhow::Result<WorkResponse> {
+    match protocol {
+        WorkerProtocol::Json => {
+            let mut line = String::new();
+            if reader
+                .read_line(&mut line)
+                .await
+                .context("Error reading from worker")?
+                == 0
+            {
+                return Err(WorkerError::Exited.into());
+            }
+            serde_json::from_str(&line)
+                .with_context(|| format!("Invalid response from worker: `{}`", line.trim_end()))
+        }
+        WorkerProtocol::Proto => {
+            let len = read_varint(reader).await?;
+            let mut data = vec![0; len];
+            reader
+                .read_exact(&mut data)
+                .await
+                .context("Error reading from worker")?;
+            WorkResponse::decode(data.as_slice()).context("Invalid response from worker")
+        }
+    }
+}
+
+async fn read_varint(reader: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<usize> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = match reader.read_u8().await {
+            Ok(byte) => byte,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && shift == 0 => {
+                return Err(WorkerError::Exited.into());
+            }
+            Err(e) => return Err(anyhow::Error::new(e).context("Error reading from worker")),
+        };
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return value
+                .try_into()
+                .map_err(|_| WorkerError::InvalidLength.into());
+        }
+    }
+    Err(WorkerError::InvalidLength.into())
+}
+
+/// Splits a command line into the arguments that start the worker and the flagfiles that hold
+/// the arguments of each request. Like in Bazel, those flagfiles are the trailing arguments.
+/// Returns `None` for command lines that have no such flagfiles, or that have flagfiles among
+/// their startup arguments, since those can't be told apart from the arguments of a request.
+fn split_args(args: &[String]) -> Option<(Vec<&str>, Vec<&str>)> {
+    let startup_len = args
+        .iter()
+        .rposition(|arg| flagfile(arg).is_none())
+        .map_or(0, |i| i + 1);
+    let (startup_args, flagfiles) = args.split_at(startup_len);
+    if startup_args.is_empty()
+        || flagfiles.is_empty()
+        || startup_args.iter().any(|arg| flagfile(arg).is_some())
+    {
+        return None;
+    }
+    Some((
+        startup_args.iter().map(|arg| arg.as_str()).collect(),
+        flagfiles.iter().filter_map(|arg| flagfile(arg)).collect(),
+    ))
+}
+
+fn flagfile(arg: &str) -> Option<&str> {
+    if let Some(path) = arg.strip_prefix('@') {
+        // `@@` escapes a literal `@`.
+        if !path.starts_with('@') {
+            return Some(path);
+        }
+    }
+    arg.strip_prefix("--flagfile=")
+        .or_else(|| arg.strip_prefix("-flagfile="))
+}
+
+/// Reads the flagfiles, one argument per line. Flagfiles may refer to other flagfiles.
+fn expand_flagfiles(flagfiles: &[&str], working_directory: &Path) -> anyhow::Result<Vec<String>> {
+    fn expand(path: &str, working_directory: &Path, args: &mut Vec<String>) -> anyhow::Result<()> {
+        let contents = fs_util::read_to_string(working_directory.join(path))
+            .with_context(|| format!("Error reading flagfile `{}`", path))?;
+        for line in contents.lines() {
+            match flagfile(line) {
+                Some(nested) => expand(nested, working_directory, args)?,
+                None => args.push(line.to_owned()),
+            }
+        }
+        Ok(())
+    }
+
+    let mut args = Vec::new();
+    for path in flagfiles {
+        expand(path, working_directory, &mut args)?;
+    }
+    Ok(args)
+}
+
+fn exit_status_from_code(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        // This is a wait status, in which the exit code is in the second byte.
+        ExitStatus::from_raw((code & 0xff) << 8)
+    }
+
+    #[cfg(not(unix))]
+    {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::liveliness_observer::NoopLivelinessObserver;
+
+    use super::*;
+
+    fn request() -> WorkRequest {
+        WorkRequest {
+            arguments: vec!["--out".to_owned(), "foo.o".to_owned()],
+            inputs: vec![WorkInput {
+                path: "foo.c".to_owned(),
+                digest: vec![1, 2, 3],
+            }],
+            request_id: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_protocol() -> anyhow::Result<()> {
+        let mut written = Vec::new();
+        write_request(WorkerProtocol::Json, &mut written, &request()).await?;
+        assert_eq!(written.last(), Some(&b'\n'));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&written)?,
+            serde_json::json!({
+                "arguments": ["--out", "foo.o"],
+                "inputs": [{"path": "foo.c", "digest": "AQID"}],
+                "requestId": 0,
+            })
+        );
+
+        let mut response: &[u8] = b"{\"exitCode\": 1, \"output\": \"oops\"}\n";
+        assert_eq!(
+            read_response(WorkerProtocol::Json, &mut response).await?,
+            WorkResponse {
+                exit_code: 1,
+                output: "oops".to_owned(),
+                request_id: 0,
+            }
+        );
+
+        let mut response: &[u8] = b"";
+        assert!(
+            read_response(WorkerProtocol::Json, &mut response)
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proto_protocol() -> anyhow::Result<()> {
+        let mut written = Vec::new();
+        write_request(WorkerProtocol::Proto, &mut written, &request()).await?;
+        assert_eq!(
+            WorkRequest::decode_length_delimited(&written[..])?,
+            request()
+        );
+
+        let expected = WorkResponse {
+            exit_code: 0,
+            output: "x".repeat(200),
+            request_id: 0,
+        };
+        let data = expected.encode_length_delimited_to_vec();
+        assert_eq!(
+            read_response(WorkerProtocol::Proto, &mut data.as_slice()).await?,
+            expected
+        );
+
+        let mut truncated = &data[..10];
+        assert!(
+            read_response(WorkerProtocol::Proto, &mut truncated)
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_args() {
+        let args = [
+            "javac",
+            "-J-Xmx1g",
+            "@@literal",
+            "@args",
+            "--flagfile=more",
+            "-flagfile=last",
+        ]
+        .map(|arg| arg.to_owned());
+        assert_eq!(
+            split_args(&args),
+            Some((
+                vec!["javac", "-J-Xmx1g", "@@literal"],
+                vec!["args", "more", "last"]
+            ))
+        );
+
+        let args = ["javac", "@args", "-d", "out"].map(|arg| arg.to_owned());
+        assert_eq!(split_args(&args), None);
+        let args = ["javac", "@startup", "-d", "@args"].map(|arg| arg.to_owned());
+        assert_eq!(split_args(&args), None);
+        let args = ["javac", "-d", "out"].map(|arg| arg.to_owned());
+        assert_eq!(split_args(&args), None);
+        let args = ["@args"].map(|arg| arg.to_owned());
+        assert_eq!(split_args(&args), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_workers_are_reused() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = AbsNormPathBuf::try_from(dir.path().canonicalize()?)?;
+        fs_util::write(root.join(ForwardRelativePath::new("args")?), "a\nb\n")?;
+
+        let pool = WorkerPool::new(root.join(ForwardRelativePath::new("logs")?), 1);
+        let args = [
+            "sh",
+            "-c",
+            r#"while read line; do echo "{\"output\": \"$$\"}"; done"#,
+            "@args",
+        ]
+        .map(|arg| arg.to_owned());
+
+        let mut pids = Vec::new();
+        for _ in 0..2 {
+            let (status, _, stderr) = pool
+                .exec(
+                    WorkerProtocol::Json,
+                    &args,
+                    &HashMap::new(),
+                    &root,
+                    None,
+                    Vec::new(),
+                    None,
+                    NoopLivelinessObserver::create(),
+                )
+                .await
+                .context("Worker wasn't used")??;
+            assert!(
+                matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.success())
+            );
+            pids.push(String::from_utf8(stderr)?);
+        }
+        assert_eq!(pids[0], pids[1]);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_crashing_workers_fall_back() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = AbsNormPathBuf::try_from(dir.path().canonicalize()?)?;
+        fs_util::write(root.join(ForwardRelativePath::new("args")?), "a\n")?;
+
+        let pool = WorkerPool::new(root.join(ForwardRelativePath::new("logs")?), 1);
+        let args = ["sh", "-c", "exit 1", "@args"].map(|arg| arg.to_owned());
+
+        let res = pool
+            .exec(
+                WorkerProtocol::Proto,
+                &args,
+                &HashMap::new(),
+                &root,
+                None,
+                Vec::new(),
+                None,
+                NoopLivelinessObserver::create(),
+            )
+            .await;
+        assert!(res.is_none());
+
+        // Commands without a flagfile can't use workers.
+        let res = pool
+            .exec(
+                WorkerProtocol::Proto,
+                &["true".to_owned()],
+                &HashMap::new(),
+                &root,
+                None,
+                Vec::new(),
+                None,
+                NoopLivelinessObserver::create(),
+            )
+            .await;
+        assert!(res.is_none());
+
+        // Neither can commands whose flagfiles aren't their last arguments.
+        let res = pool
+            .exec(
+                WorkerProtocol::Proto,
+                &["cat", "@args", "-"].map(|arg| arg.to_owned()),
+                &HashMap::new(),
+                &root,
+                None,
+                Vec::new(),
+                None,
+                NoopLivelinessObserver::create(),
+            )
+            .await;
+        assert!(res.is_none());
+
+        Ok(())
+    }
+}
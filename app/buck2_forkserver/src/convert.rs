@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::path::PathBuf;
 use std::process::ExitStatus;
 
 use anyhow::Context as _;
@@ -15,6 +16,7 @@ use futures::stream::Stream;
 use futures::stream::StreamExt;
 
 use crate::run::CommandEvent;
+use crate::run::ExecutionStats;
 use crate::run::GatherOutputStatus;
 
 pub fn encode_event_stream<S>(
@@ -33,7 +35,11 @@ where
             CommandEvent::Stderr(bytes) => Data::Stderr(buck2_forkserver_proto::StreamEvent {
                 data: bytes.to_vec(),
             }),
-            CommandEvent::Exit(GatherOutputStatus::Finished(status)) => {
+            CommandEvent::Exit(GatherOutputStatus::Finished {
+                exit_status: status,
+                execution_stats,
+                sandbox_violations,
+            }) => {
                 let exit_code;
 
                 #[cfg(unix)]
@@ -48,7 +54,16 @@ where
                     exit_code = status.code().unwrap_or(1);
                 }
 
-                Data::Exit(buck2_forkserver_proto::ExitEvent { exit_code })
+                Data::Exit(buck2_forkserver_proto::ExitEvent {
+                    exit_code,
+                    execution_stats: execution_stats.map(|stats| {
+                        buck2_forkserver_proto::ExecutionStats {
+                            memory_peak: stats.memory_peak,
+                            cpu_time: stats.cpu_time.try_into().ok(),
+                        }
+                    }),
+                    sandbox_violations: sandbox_violations.into_iter().map(path_to_bytes).collect(),
+                })
             }
             CommandEvent::Exit(GatherOutputStatus::TimedOut(duration)) => {
                 Data::Timeout(buck2_forkserver_proto::TimeoutEvent {
@@ -87,7 +102,11 @@ where
             Data::Stderr(buck2_forkserver_proto::StreamEvent { data }) => {
                 CommandEvent::Stderr(data.into())
             }
-            Data::Exit(buck2_forkserver_proto::ExitEvent { exit_code }) => {
+            Data::Exit(buck2_forkserver_proto::ExitEvent {
+                exit_code,
+                execution_stats,
+                sandbox_violations,
+            }) => {
                 let exit_status;
 
                 #[cfg(unix)]
@@ -102,7 +121,25 @@ where
                     exit_status = ExitStatus::from_raw(exit_code as _)
                 }
 
-                CommandEvent::Exit(GatherOutputStatus::Finished(exit_status))
+                let execution_stats = execution_stats
+                    .map(|stats| {
+                        anyhow::Ok(ExecutionStats {
+                            memory_peak: stats.memory_peak,
+                            cpu_time: stats
+                                .cpu_time
+                                .map(|t| t.try_into_duration())
+                                .transpose()
+                                .context("Invalid `cpu_time`")?
+                                .unwrap_or_default(),
+                        })
+                    })
+                    .transpose()?;
+
+                CommandEvent::Exit(GatherOutputStatus::Finished {
+                    exit_status,
+                    execution_stats,
+                    sandbox_violations: sandbox_violations.into_iter().map(bytes_to_path).collect(),
+                })
             }
             Data::Timeout(buck2_forkserver_proto::TimeoutEvent { duration }) => {
                 CommandEvent::Exit(GatherOutputStatus::TimedOut(
@@ -129,3 +166,25 @@ where
 
     s.map(|r| r.map_err(convert_err).and_then(convert_event))
 }
+
+#[cfg(unix)]
+fn path_to_bytes(path: PathBuf) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    path.into_os_string().into_vec()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: PathBuf) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    std::ffi::OsString::from_vec(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&bytes).into_owned().into()
+}
@@ -10,6 +10,7 @@
 mod interruptible_async_read;
 
 use std::io;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::process::Command;
 use std::process::ExitStatus;
@@ -35,12 +36,26 @@ use self::interruptible_async_read::InterruptibleAsyncRead;
 
 #[derive(Debug)]
 pub enum GatherOutputStatus {
-    Finished(ExitStatus),
+    Finished {
+        exit_status: ExitStatus,
+        /// Set if the command ran in a cgroup of its own.
+        execution_stats: Option<ExecutionStats>,
+        /// Paths that the command tried to access but that its sandbox hid from it.
+        sandbox_violations: Vec<PathBuf>,
+    },
     TimedOut(Duration),
     Cancelled,
     SpawnFailed(String),
 }
 
+/// What a command used, as measured by the cgroup it ran in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutionStats {
+    /// Only tracked by recent kernels.
+    pub memory_peak: Option<u64>,
+    pub cpu_time: Duration,
+}
+
 #[derive(Debug)]
 pub enum CommandEvent {
     Stdout(Bytes),
@@ -177,7 +192,11 @@ where
     let status = async move {
         let (result, cancelled) = {
             let wait = async {
-                let status = GatherOutputStatus::Finished(child.wait().await?);
+                let status = GatherOutputStatus::Finished {
+                    exit_status: child.wait().await?,
+                    execution_stats: None,
+                    sandbox_violations: Vec::new(),
+                };
                 anyhow::Ok((status, false))
             };
 
@@ -369,7 +388,9 @@ mod tests {
         cmd.args(["-c", "echo hello"]);
 
         let (status, stdout, stderr) = gather_output(cmd, futures::future::pending()).await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
@@ -399,7 +420,9 @@ mod tests {
             timeout_into_cancellation(Some(Duration::from_secs(timeout))),
         )
         .await?;
-        assert!(matches!(status, GatherOutputStatus::Finished(s) if s.code() == Some(0)));
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.code() == Some(0))
+        );
         assert_eq!(str::from_utf8(&stdout)?.trim(), "hello");
         assert_eq!(stderr, b"");
 
@@ -0,0 +1,404 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Runs commands in cgroups (v2) of their own, measures what they used, and enforces their memory
+//! and CPU limits if they have any.
+//!
+//! The command cgroups are created in a cgroup that buck2 was given to manage, which is either:
+//!
+//! * The one configured with `buck2.action_cgroup`, which must have no processes of its own.
+//! * The forkserver's own cgroup, if systemd delegated it to us, e.g. because buck2 was started
+//!   under `systemd-run --user --scope -p Delegate=yes`. A cgroup can only hand controllers down to
+//!   its children if it has no processes of its own, so the processes in it (the forkserver, and
+//!   usually the daemon that spawned it) move into a `buck2` leaf next to the command cgroups.
+//!
+//! We never touch any other cgroup: without one of these, commands run where the forkserver is,
+//! and those that have limits fail.
+//!
+//! This only works on Linux.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use buck2_forkserver_proto::ResourceLimits;
+
+use crate::run::ExecutionStats;
+
+/// The period CPU limits are enforced over, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Where the cgroup v2 hierarchy is mounted.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+pub(crate) struct ActionCgroups {
+    parent: PathBuf,
+    next_id: AtomicU64,
+}
+
+impl ActionCgroups {
+    /// Prepares the `configured` cgroup (relative to the cgroup root, or absolute), or else the
+    /// forkserver's cgroup if it was delegated to us, to host command cgroups.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn init(configured: Option<&Path>) -> anyhow::Result<Self> {
+        let parent = match configured {
+            Some(configured) => {
+                let parent = Path::new(CGROUP_ROOT).join(configured);
+                let procs = parent.join("cgroup.procs");
+                let pids = fs::read_to_string(&procs)
+                    .with_context(|| format!("Error reading `{}`", procs.display()))?;
+                if !pids.trim().is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "The configured `buck2.action_cgroup` `{}` has processes of its own, so \
+                        it can't hand controllers down to command cgroups",
+                        parent.display()
+                    ));
+                }
+                parent
+            }
+            None => {
+                let proc_cgroup = fs::read_to_string("/proc/self/cgroup")
+                    .context("Error reading `/proc/self/cgroup`")?;
+                let parent = Path::new(CGROUP_ROOT).join(own_cgroup(&proc_cgroup)?);
+                if !is_delegated(&parent) {
+                    return Err(anyhow::anyhow!(
+                        "The forkserver's cgroup `{}` was not delegated to buck2. Start buck2 in \
+                        a scope with `Delegate=yes`, or set `buck2.action_cgroup` to a cgroup it \
+                        may manage",
+                        parent.display()
+                    ));
+                }
+                move_processes_to_leaf(&parent)?;
+                parent
+            }
+        };
+
+        let subtree_control = parent.join("cgroup.subtree_control");
+        fs::write(&subtree_control, "+memory +cpu").with_context(|| {
+            format!(
+                "Error enabling the memory and cpu controllers in `{}`",
+                subtree_control.display()
+            )
+        })?;
+
+        Ok(Self {
+            parent,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn init(_configured: Option<&Path>) -> anyhow::Result<Self> {
+        Err(anyhow::anyhow!(
+            "Running commands in cgroups is only supported on Linux"
+        ))
+    }
+
+    /// Creates a cgroup for one command, with its limits if it has any.
+    pub(crate) fn create(&self, limits: Option<&ResourceLimits>) -> anyhow::Result<ActionCgroup> {
+        let path = self.parent.join(format!(
+            "action-{}-{}",
+            std::process::id(),
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path).with_context(|| format!("Error creating `{}`", path.display()))?;
+
+        let procs = match fs::OpenOptions::new()
+            .write(true)
+            .open(path.join("cgroup.procs"))
+        {
+            Ok(procs) => procs,
+            Err(e) => {
+                let _ignored = fs::remove_dir(&path);
+                return Err(e)
+                    .with_context(|| format!("Error opening `{}/cgroup.procs`", path.display()));
+            }
+        };
+        // From here on, dropping the cgroup removes it.
+        let cgroup = ActionCgroup {
+            path,
+            procs,
+            removed: false,
+        };
+
+        let limits = match limits {
+            Some(limits) => limits,
+            None => return Ok(cgroup),
+        };
+        if let Some(bytes) = limits.memory_max_bytes {
+            cgroup.write("memory.max", &bytes.to_string())?;
+            // Don't let the command get around the limit by swapping, if swap is accounted for.
+            if cgroup.path.join("memory.swap.max").exists() {
+                cgroup.write("memory.swap.max", "0")?;
+            }
+        }
+        if let Some(millicores) = limits.cpu_max_millicores {
+            cgroup.write("cpu.max", &cpu_max(millicores))?;
+        }
+
+        Ok(cgroup)
+    }
+}
+
+/// Moves the processes of the delegated cgroup at `parent` into a `buck2` leaf of it.
+#[cfg(target_os = "linux")]
+fn move_processes_to_leaf(parent: &Path) -> anyhow::Result<()> {
+    let leaf = parent.join("buck2");
+    match fs::create_dir(&leaf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Error creating `{}`", leaf.display()));
+        }
+    }
+
+    // Processes can fork while we move them, so keep going until none are left.
+    let procs = parent.join("cgroup.procs");
+    for _ in 0..10 {
+        let pids = fs::read_to_string(&procs)
+            .with_context(|| format!("Error reading `{}`", procs.display()))?;
+        if pids.trim().is_empty() {
+            break;
+        }
+        for pid in pids.lines() {
+            match fs::write(leaf.join("cgroup.procs"), pid) {
+                Ok(()) => {}
+                // The process exited in the meantime.
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Error moving process {} to `{}`", pid, leaf.display())
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether systemd delegated the cgroup at `path`, which it marks with an extended attribute:
+/// `trusted.delegate` for the system manager, and `user.delegate` for user managers.
+#[cfg(target_os = "linux")]
+fn is_delegated(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    ["trusted.delegate", "user.delegate"].iter().any(|name| {
+        let name = CString::new(*name).unwrap();
+        let mut value = [0u8; 1];
+        // SAFETY: The buffer is as long as we say it is.
+        let len = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        len == 1 && value[0] == b'1'
+    })
+}
+
+/// The cgroup of one command. Anything left running in it is killed when it's removed, which
+/// happens in the background if it's dropped without being removed.
+pub(crate) struct ActionCgroup {
+    path: PathBuf,
+    /// Opened before forking, so that the child can join the cgroup without allocating.
+    procs: File,
+    removed: bool,
+}
+
+impl ActionCgroup {
+    fn write(&self, file: &str, value: &str) -> anyhow::Result<()> {
+        let path = self.path.join(file);
+        fs::write(&path, value)
+            .with_context(|| format!("Error writing `{}` to `{}`", value, path.display()))
+    }
+
+    /// Makes the command join this cgroup between fork and exec.
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        let fd = self.procs.as_raw_fd();
+        // SAFETY: This only makes a syscall, and doesn't allocate.
+        unsafe {
+            cmd.pre_exec(move || {
+                // Writing 0 moves the process that writes it.
+                if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Reads what the processes in this cgroup used so far.
+    pub(crate) fn stats(&self) -> anyhow::Result<ExecutionStats> {
+        let cpu_stat = fs::read_to_string(self.path.join("cpu.stat"))
+            .with_context(|| format!("Error reading `{}/cpu.stat`", self.path.display()))?;
+        let cpu_time = parse_cpu_usage(&cpu_stat)?;
+
+        // `memory.peak` only exists since Linux 5.19.
+        let memory_peak = match fs::read_to_string(self.path.join("memory.peak")) {
+            Ok(peak) => Some(
+                peak.trim()
+                    .parse()
+                    .with_context(|| format!("Invalid `memory.peak`: `{}`", peak.trim()))?,
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Error reading `{}/memory.peak`", self.path.display())
+                });
+            }
+        };
+
+        Ok(ExecutionStats {
+            memory_peak,
+            cpu_time,
+        })
+    }
+
+    /// Kills anything the command left running, and removes this cgroup.
+    pub(crate) async fn remove(mut self) {
+        self.removed = true;
+        remove_cgroup(std::mem::take(&mut self.path)).await;
+    }
+}
+
+impl Drop for ActionCgroup {
+    fn drop(&mut self) {
+        if self.removed {
+            return;
+        }
+        // Removing may have to wait for killed processes, which must not block this thread.
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(remove_cgroup(std::mem::take(&mut self.path)));
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "Not removing `{}`: dropped outside of a runtime",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
+
+/// Kills anything left running in the cgroup at `path`, and removes it.
+async fn remove_cgroup(path: PathBuf) {
+    // `cgroup.kill` only exists since Linux 5.14. Without it, anything the command left running
+    // keeps the cgroup from being removed.
+    let _ignored = fs::write(path.join("cgroup.kill"), "1");
+
+    // Processes that were just killed take a moment to leave the cgroup.
+    for _ in 0..10 {
+        match fs::remove_dir(&path) {
+            Ok(()) => return,
+            Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Err(e) => {
+                tracing::warn!("Error removing `{}`: {}", path.display(), e);
+                return;
+            }
+        }
+    }
+
+    tracing::warn!(
+        "Error removing `{}`: processes are still running in it",
+        path.display()
+    );
+}
+
+/// The path of the cgroup v2 of a process, relative to the cgroup root, from its
+/// `/proc/<pid>/cgroup`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn own_cgroup(proc_cgroup: &str) -> anyhow::Result<&str> {
+    proc_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| path.trim_start_matches('/'))
+        .context("The forkserver is not in a cgroup v2 hierarchy")
+}
+
+/// The `cpu.max` that allows using `millicores` thousandths of a core.
+fn cpu_max(millicores: u64) -> String {
+    // The kernel refuses quotas under 1ms.
+    let quota = (millicores.saturating_mul(CPU_PERIOD_US) / 1000).max(1000);
+    format!("{} {}", quota, CPU_PERIOD_US)
+}
+
+fn parse_cpu_usage(cpu_stat: &str) -> anyhow::Result<Duration> {
+    let usage = cpu_stat
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .context("`cpu.stat` has no `usage_usec`")?;
+    let usage = usage
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid `usage_usec`: `{}`", usage))?;
+    Ok(Duration::from_micros(usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_own_cgroup() -> anyhow::Result<()> {
+        assert_eq!(
+            "user.slice/user-1000.slice/session-2.scope",
+            own_cgroup("0::/user.slice/user-1000.slice/session-2.scope\n")?
+        );
+        assert_eq!("", own_cgroup("0::/\n")?);
+        assert!(own_cgroup("4:memory:/user.slice\n").is_err());
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_delegated() -> anyhow::Result<()> {
+        // Only systemd marks cgroups as delegated, so nothing else is.
+        let tempdir = tempfile::tempdir()?;
+        assert!(!is_delegated(tempdir.path()));
+        assert!(!is_delegated(&tempdir.path().join("missing")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cpu_max() {
+        assert_eq!("100000 100000", cpu_max(1000));
+        assert_eq!("250000 100000", cpu_max(2500));
+        assert_eq!("1000 100000", cpu_max(1));
+    }
+
+    #[test]
+    fn test_parse_cpu_usage() -> anyhow::Result<()> {
+        assert_eq!(
+            Duration::from_micros(1234567),
+            parse_cpu_usage("usage_usec 1234567\nuser_usec 1000000\nsystem_usec 234567\n")?
+        );
+        assert!(parse_cpu_usage("user_usec 1000000\n").is_err());
+        Ok(())
+    }
+}
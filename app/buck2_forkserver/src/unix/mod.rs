@@ -7,8 +7,10 @@
  * of this source tree.
  */
 
+mod cgroup;
 mod command;
 mod launch;
+mod sandbox;
 mod service;
 
 pub use command::run_forkserver;
@@ -0,0 +1,769 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Runs commands with a directory (typically the project root) hidden from them, except for the
+//! paths under it that they were given.
+//!
+//! Between fork and exec, the child enters new user and mount namespaces (and, optionally, a new
+//! network namespace, which has nothing but a loopback interface that is down), mounts an empty
+//! tmpfs over the hidden directory, and bind mounts the paths it was given back on top of it:
+//! read-only unless they are writable. The original directory is reached through a file
+//! descriptor opened before the tmpfs covers it.
+//!
+//! Outputs that don't exist yet can't be bind mounted, and their parent directories usually hold
+//! the outputs of other actions. So the command writes them to an empty directory that stands in
+//! for their parent, and they are moved into place once it exits.
+//!
+//! The paths that the command tries to access but that the sandbox hides are recorded (see
+//! `trace`), so that violations can be reported.
+//!
+//! This only works on Linux, with unprivileged user namespaces enabled.
+
+#[cfg(target_os = "linux")]
+mod trace;
+
+use std::path::Path;
+use std::process::Command;
+
+use buck2_forkserver_proto::Sandbox;
+#[cfg(target_os = "linux")]
+pub(crate) use linux::Sandboxed;
+
+#[cfg(target_os = "linux")]
+pub(crate) fn apply_sandbox(
+    cmd: &mut Command,
+    sandbox: Sandbox,
+    cwd: Option<&Path>,
+) -> anyhow::Result<Sandboxed> {
+    use std::os::unix::process::CommandExt;
+
+    let (setup, sandboxed) = linux::SandboxSetup::new(sandbox, cwd)?;
+    // SAFETY: `SandboxSetup::enter` only makes syscalls, and doesn't allocate.
+    unsafe {
+        cmd.pre_exec(move || setup.enter());
+    }
+    Ok(sandboxed)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) enum Sandboxed {}
+
+#[cfg(not(target_os = "linux"))]
+impl Sandboxed {
+    pub(crate) fn finish(self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        match self {}
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply_sandbox(
+    _cmd: &mut Command,
+    _sandbox: Sandbox,
+    _cwd: Option<&Path>,
+) -> anyhow::Result<Sandboxed> {
+    Err(anyhow::anyhow!(
+        "Sandboxing commands is only supported on Linux"
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+    use std::ffi::CStr;
+    use std::ffi::CString;
+    use std::ffi::OsStr;
+    use std::ffi::OsString;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+
+    use anyhow::Context as _;
+    use buck2_forkserver_proto::Sandbox;
+    use nix::mount::mount;
+    use nix::mount::MsFlags;
+    use nix::sched::unshare;
+    use nix::sched::CloneFlags;
+    use nix::sys::statvfs::statvfs;
+    use nix::sys::statvfs::FsFlags;
+    use nix::unistd::chdir;
+    use nix::unistd::mkdir;
+    use nix::unistd::Gid;
+    use nix::unistd::Uid;
+
+    use super::trace::start_trace;
+    use super::trace::TraceSetup;
+    use super::trace::Violations;
+    use super::trace::Visibility;
+
+    /// Distinguishes the staging directories of the commands this forkserver runs.
+    static NEXT_STAGING: AtomicU64 = AtomicU64::new(0);
+
+    /// A bind mount of a path under the hidden directory back on top of the tmpfs.
+    struct BindMount {
+        source: CString,
+        target: CString,
+        /// The flags to remount it read-only with, if it is not writable. Those include the flags
+        /// of the mount the source is on, which we're not allowed to drop.
+        read_only: Option<MsFlags>,
+    }
+
+    /// Everything the child needs to enter the sandbox, prepared before forking so that the child
+    /// doesn't allocate.
+    pub(super) struct SandboxSetup {
+        namespaces: CloneFlags,
+        uid_map: CString,
+        gid_map: CString,
+        root: CString,
+        /// Reserves the file descriptor that keeps the hidden directory reachable (as
+        /// `/proc/self/fd/N`) after the tmpfs covers it.
+        root_fd: File,
+        /// Mountpoints to create in the tmpfs, parents first.
+        dirs: Vec<CString>,
+        files: Vec<CString>,
+        mounts: Vec<BindMount>,
+        cwd: CString,
+        /// Set if the system supports recording what the command accesses.
+        trace: Option<TraceSetup>,
+    }
+
+    /// Outputs that didn't exist when the command started, and the directory it writes them to
+    /// instead of their parent.
+    struct Staging {
+        parent: PathBuf,
+        dir: PathBuf,
+        outputs: Vec<OsString>,
+    }
+
+    /// What is left to do once a sandboxed command exited. If it's dropped instead, the outputs
+    /// are discarded.
+    pub(crate) struct Sandboxed {
+        staging: Vec<Staging>,
+        violations: Option<Violations>,
+        finished: bool,
+    }
+
+    impl Sandboxed {
+        /// Moves the outputs the command wrote into place, and returns the paths it tried to
+        /// access but that the sandbox hid from it.
+        pub(crate) fn finish(mut self) -> anyhow::Result<Vec<PathBuf>> {
+            self.finished = true;
+            let res = self.move_outputs();
+            remove_staging(std::mem::take(&mut self.staging));
+            res?;
+
+            Ok(match &self.violations {
+                Some(violations) => violations
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .iter()
+                    .cloned()
+                    .collect(),
+                None => Vec::new(),
+            })
+        }
+
+        fn move_outputs(&self) -> anyhow::Result<()> {
+            for staging in &self.staging {
+                for name in &staging.outputs {
+                    let from = staging.dir.join(name);
+                    if from.symlink_metadata().is_err() {
+                        // The command didn't produce this output.
+                        continue;
+                    }
+                    let to = staging.parent.join(name);
+                    std::fs::rename(&from, &to).with_context(|| {
+                        format!("Error moving `{}` to `{}`", from.display(), to.display())
+                    })?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Sandboxed {
+        fn drop(&mut self) {
+            if self.finished {
+                return;
+            }
+            let staging = std::mem::take(&mut self.staging);
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    handle.spawn_blocking(move || remove_staging(staging));
+                }
+                Err(_) => remove_staging(staging),
+            }
+        }
+    }
+
+    fn remove_staging(staging: Vec<Staging>) {
+        for staging in staging {
+            if let Err(e) = std::fs::remove_dir_all(&staging.dir) {
+                tracing::warn!("Error removing `{}`: {}", staging.dir.display(), e);
+            }
+        }
+    }
+
+    fn cstring(path: impl AsRef<OsStr>) -> anyhow::Result<CString> {
+        let path = path.as_ref();
+        CString::new(path.as_bytes())
+            .with_context(|| format!("Path contains a nul byte: `{}`", path.to_string_lossy()))
+    }
+
+    fn path(bytes: &[u8]) -> &Path {
+        Path::new(OsStr::from_bytes(bytes))
+    }
+
+    /// The flags of the mount `path` is on that a bind mount of it has to keep.
+    fn locked_flags(path: &Path) -> anyhow::Result<MsFlags> {
+        let stat = statvfs(path).with_context(|| format!("statvfs `{}`", path.display()))?;
+        let flags = stat.flags();
+
+        let mut res = MsFlags::empty();
+        for (fs_flag, ms_flag) in [
+            (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+            (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+            (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+            (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+            (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+            (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+        ] {
+            if flags.contains(fs_flag) {
+                res |= ms_flag;
+            }
+        }
+        Ok(res)
+    }
+
+    impl SandboxSetup {
+        pub(super) fn new(
+            sandbox: Sandbox,
+            cwd: Option<&Path>,
+        ) -> anyhow::Result<(Self, Sandboxed)> {
+            let Sandbox {
+                hidden_root,
+                readable_paths,
+                writable_paths,
+                isolate_network,
+            } = sandbox;
+
+            let hidden_root = path(&hidden_root);
+            if !hidden_root.is_absolute() {
+                return Err(anyhow::anyhow!(
+                    "Sandbox root is not absolute: `{}`",
+                    hidden_root.display()
+                ));
+            }
+
+            let root_fd = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+                .open(hidden_root)
+                .with_context(|| format!("Error opening `{}`", hidden_root.display()))?;
+
+            // Paths relative to the root that exist, and whether they are writable. Paths outside
+            // of the root aren't hidden in the first place.
+            let mut visible = BTreeMap::<PathBuf, bool>::new();
+            // Outputs that don't exist yet, by the directory they'll be created in.
+            let mut outputs = BTreeMap::<PathBuf, Vec<OsString>>::new();
+            for (paths, writable) in [(readable_paths, false), (writable_paths, true)] {
+                for p in &paths {
+                    let rel = match path(p).strip_prefix(hidden_root) {
+                        Ok(rel) => rel,
+                        Err(_) => continue,
+                    };
+                    if hidden_root.join(rel).exists() {
+                        *visible.entry(rel.to_owned()).or_default() |= writable;
+                        continue;
+                    }
+                    if !writable {
+                        continue;
+                    }
+                    match (rel.parent(), rel.file_name()) {
+                        // Never give access to the whole root.
+                        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+                            outputs
+                                .entry(parent.to_owned())
+                                .or_default()
+                                .push(name.to_owned());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            let mut sandboxed = Sandboxed {
+                staging: Vec::new(),
+                violations: None,
+                finished: false,
+            };
+            // The directories that stand in for the parents of outputs, by that parent.
+            let mut staged = BTreeMap::<PathBuf, PathBuf>::new();
+            for (parent, names) in outputs {
+                // Outputs can be created in place if their parent is writable already.
+                let writable = parent
+                    .ancestors()
+                    .any(|a| visible.get(a).copied().unwrap_or(false));
+                if writable {
+                    continue;
+                }
+
+                let real_parent = hidden_root.join(&parent);
+                std::fs::create_dir_all(&real_parent)
+                    .with_context(|| format!("Error creating `{}`", real_parent.display()))?;
+                let name = format!(
+                    ".buck2-sandbox-{}-{}",
+                    std::process::id(),
+                    NEXT_STAGING.fetch_add(1, Ordering::Relaxed)
+                );
+                let dir = real_parent.join(&name);
+                std::fs::create_dir(&dir)
+                    .with_context(|| format!("Error creating `{}`", dir.display()))?;
+                sandboxed.staging.push(Staging {
+                    parent: real_parent,
+                    dir,
+                    outputs: names,
+                });
+                staged.insert(parent, Path::new(&name).to_owned());
+            }
+
+            let mut dirs = BTreeSet::new();
+            let mut files = Vec::new();
+            let mut mounts = Vec::new();
+
+            // Parents sort before their children, so they are mounted first.
+            let mut targets = visible
+                .iter()
+                .map(|(rel, writable)| (rel, *writable))
+                .chain(staged.keys().map(|rel| (rel, true)))
+                .collect::<Vec<_>>();
+            targets.sort();
+            for (rel, writable) in targets {
+                // Skip paths that are already visible, the same way, through their parent.
+                let covered = rel
+                    .ancestors()
+                    .skip(1)
+                    .find_map(|a| visible.get(a))
+                    .map_or(false, |w| *w == writable);
+                let staged_ancestor = rel
+                    .ancestors()
+                    .skip(1)
+                    .find_map(|a| staged.get(a).map(|dir| (a, dir)));
+                if covered && staged_ancestor.is_none() {
+                    continue;
+                }
+
+                let target = hidden_root.join(rel);
+                let is_dir = staged.contains_key(rel) || target.is_dir();
+                match staged_ancestor {
+                    // The mountpoint has to be in the directory that stands in for the ancestor,
+                    // which is mounted first.
+                    Some((ancestor, dir)) => {
+                        let mountpoint = hidden_root
+                            .join(ancestor)
+                            .join(dir)
+                            .join(rel.strip_prefix(ancestor)?);
+                        let created = if is_dir {
+                            std::fs::create_dir_all(&mountpoint)
+                        } else {
+                            std::fs::create_dir_all(mountpoint.parent().expect("has a parent"))
+                                .and_then(|()| File::create(&mountpoint).map(|_| ()))
+                        };
+                        created.with_context(|| {
+                            format!("Error creating `{}`", mountpoint.display())
+                        })?;
+                    }
+                    None => {
+                        for dir in rel.ancestors().skip(1) {
+                            if dir.as_os_str().is_empty() {
+                                break;
+                            }
+                            dirs.insert(hidden_root.join(dir));
+                        }
+                        if is_dir {
+                            dirs.insert(target.clone());
+                        } else {
+                            files.push(cstring(&target)?);
+                        }
+                    }
+                }
+
+                let source_rel = match staged.get(rel) {
+                    Some(dir) => rel.join(dir),
+                    None => rel.to_owned(),
+                };
+                let source = Path::new("/proc/self/fd")
+                    .join(root_fd.as_raw_fd().to_string())
+                    .join(source_rel);
+                let read_only = if writable {
+                    None
+                } else {
+                    Some(locked_flags(&target)?)
+                };
+                mounts.push(BindMount {
+                    source: cstring(source)?,
+                    target: cstring(target)?,
+                    read_only,
+                });
+            }
+
+            let cwd = match cwd {
+                Some(cwd) => cwd.to_owned(),
+                None => std::env::current_dir().context("Error getting current directory")?,
+            };
+            let mut cwd_dirs = BTreeSet::new();
+            if let Ok(rel) = cwd.strip_prefix(hidden_root) {
+                for dir in rel.ancestors() {
+                    if dir.as_os_str().is_empty() {
+                        break;
+                    }
+                    dirs.insert(hidden_root.join(dir));
+                    cwd_dirs.insert(dir.to_owned());
+                }
+            }
+
+            let visibility = Visibility {
+                root: hidden_root.to_owned(),
+                visible: visible
+                    .keys()
+                    .cloned()
+                    .chain(sandboxed.staging.iter().flat_map(|staging| {
+                        staging.outputs.iter().map(|name| {
+                            staging
+                                .parent
+                                .strip_prefix(hidden_root)
+                                .expect("staged under the root")
+                                .join(name)
+                        })
+                    }))
+                    .collect(),
+                dirs: cwd_dirs,
+            };
+            let trace = match start_trace(visibility)? {
+                Some((trace, violations)) => {
+                    sandboxed.violations = Some(violations);
+                    Some(trace)
+                }
+                None => None,
+            };
+
+            let mut namespaces = CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS;
+            if isolate_network {
+                namespaces |= CloneFlags::CLONE_NEWNET;
+            }
+
+            // Map our own user and group to themselves, so that file ownership is unchanged.
+            let uid = Uid::current();
+            let gid = Gid::current();
+
+            let setup = Self {
+                namespaces,
+                uid_map: CString::new(format!("{} {} 1\n", uid, uid))?,
+                gid_map: CString::new(format!("{} {} 1\n", gid, gid))?,
+                root: cstring(hidden_root)?,
+                root_fd,
+                dirs: dirs.iter().map(cstring).collect::<anyhow::Result<_>>()?,
+                files,
+                mounts,
+                cwd: cstring(cwd)?,
+                trace,
+            };
+            Ok((setup, sandboxed))
+        }
+
+        /// Runs in the child, between fork and exec.
+        pub(super) fn enter(&self) -> io::Result<()> {
+            unshare(self.namespaces)?;
+
+            write_file(
+                CStr::from_bytes_with_nul(b"/proc/self/setgroups\0").unwrap(),
+                b"deny",
+            )?;
+            write_file(
+                CStr::from_bytes_with_nul(b"/proc/self/uid_map\0").unwrap(),
+                self.uid_map.as_bytes(),
+            )?;
+            write_file(
+                CStr::from_bytes_with_nul(b"/proc/self/gid_map\0").unwrap(),
+                self.gid_map.as_bytes(),
+            )?;
+
+            // Don't propagate any of this back to the parent namespace.
+            mount::<CStr, CStr, CStr, CStr>(
+                None,
+                CStr::from_bytes_with_nul(b"/\0").unwrap(),
+                None,
+                MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+                None,
+            )?;
+
+            // We can only bind mount from the copies of the mounts in our new namespace, so point
+            // the reserved file descriptor at the hidden directory in it.
+            // SAFETY: `self.root` is a valid C string.
+            let fd = unsafe {
+                libc::open(
+                    self.root.as_ptr(),
+                    libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: Both are file descriptors we own.
+            let res = unsafe { libc::dup3(fd, self.root_fd.as_raw_fd(), libc::O_CLOEXEC) };
+            // SAFETY: We just opened `fd`.
+            unsafe { libc::close(fd) };
+            if res < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            mount::<CStr, CStr, CStr, CStr>(
+                Some(CStr::from_bytes_with_nul(b"tmpfs\0").unwrap()),
+                &self.root,
+                Some(CStr::from_bytes_with_nul(b"tmpfs\0").unwrap()),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                Some(CStr::from_bytes_with_nul(b"mode=0755\0").unwrap()),
+            )?;
+
+            for dir in &self.dirs {
+                match mkdir(
+                    dir.as_c_str(),
+                    nix::sys::stat::Mode::from_bits_truncate(0o755),
+                ) {
+                    Ok(()) | Err(nix::errno::Errno::EEXIST) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            for file in &self.files {
+                // SAFETY: `file` is a valid C string.
+                let fd = unsafe {
+                    libc::open(
+                        file.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    )
+                };
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // SAFETY: We just opened `fd`.
+                unsafe { libc::close(fd) };
+            }
+
+            for m in &self.mounts {
+                mount::<CStr, CStr, CStr, CStr>(
+                    Some(&m.source),
+                    &m.target,
+                    None,
+                    MsFlags::MS_BIND | MsFlags::MS_REC,
+                    None,
+                )?;
+                if let Some(flags) = m.read_only {
+                    mount::<CStr, CStr, CStr, CStr>(
+                        None,
+                        &m.target,
+                        None,
+                        MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | flags,
+                        None,
+                    )?;
+                }
+            }
+
+            // Nothing may be created in the root other than through the writable paths.
+            mount::<CStr, CStr, CStr, CStr>(
+                None,
+                &self.root,
+                None,
+                MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                None,
+            )?;
+
+            // The working directory was entered before the tmpfs covered it, so enter it again.
+            chdir(self.cwd.as_c_str())?;
+
+            // Last, so that setting up the sandbox isn't traced.
+            if let Some(trace) = &self.trace {
+                trace.enter()?;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+        // SAFETY: `path` is a valid C string.
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `data` is valid for `data.len()` bytes.
+        let written = unsafe { libc::write(fd, data.as_ptr() as *const libc::c_void, data.len()) };
+        let res = if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        // SAFETY: We just opened `fd`.
+        unsafe { libc::close(fd) };
+        res
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use buck2_util::process::background_command;
+
+    use super::*;
+    use crate::run::gather_output;
+    use crate::run::timeout_into_cancellation;
+    use crate::run::GatherOutputStatus;
+
+    fn bytes(path: &Path) -> Vec<u8> {
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    async fn run_sandboxed(
+        root: &Path,
+        readable: &[&Path],
+        writable: &[&Path],
+        script: &str,
+    ) -> anyhow::Result<(GatherOutputStatus, String, Vec<PathBuf>)> {
+        let mut cmd = background_command("sh");
+        cmd.current_dir(root).arg("-c").arg(script);
+        let sandboxed = apply_sandbox(
+            &mut cmd,
+            Sandbox {
+                hidden_root: bytes(root),
+                readable_paths: readable.iter().map(|p| bytes(p)).collect(),
+                writable_paths: writable.iter().map(|p| bytes(p)).collect(),
+                isolate_network: true,
+            },
+            Some(root),
+        )?;
+        let (status, stdout, _stderr) = gather_output(
+            cmd,
+            timeout_into_cancellation(Some(Duration::from_secs(10))),
+        )
+        .await?;
+        let violations = sandboxed.finish()?;
+        Ok((status, String::from_utf8(stdout)?, violations))
+    }
+
+    /// The sandbox needs unprivileged user namespaces, which not every host allows.
+    fn sandbox_unavailable(status: &GatherOutputStatus) -> bool {
+        match status {
+            GatherOutputStatus::SpawnFailed(..) => true,
+            GatherOutputStatus::Finished { .. } => false,
+            status => panic!("Unexpected status: {:?}", status),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_hides_undeclared_paths() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::create_dir_all(root.join("out"))?;
+        std::fs::write(root.join("src/declared"), "declared")?;
+        std::fs::write(root.join("src/undeclared"), "undeclared")?;
+        std::fs::write(root.join("out/sibling"), "sibling")?;
+        std::fs::write(root.join("secret"), "secret")?;
+
+        let (status, stdout, violations) = run_sandboxed(
+            root,
+            &[&root.join("src/declared")],
+            &[&root.join("out/result")],
+            "cat src/declared; \
+             test -e src/undeclared || echo no-undeclared; \
+             test -e out/sibling || echo no-sibling; \
+             test -e secret || echo no-secret; \
+             echo x > src/declared 2>/dev/null || echo read-only; \
+             cat src/declared > out/tmp && mv out/tmp out/result",
+        )
+        .await?;
+        if sandbox_unavailable(&status) {
+            return Ok(());
+        }
+
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if exit_status.success())
+        );
+        assert_eq!(
+            "declaredno-undeclared\nno-sibling\nno-secret\nread-only\n",
+            stdout
+        );
+        assert_eq!(
+            "declared",
+            std::fs::read_to_string(root.join("out/result"))?
+        );
+        assert_eq!(
+            "declared",
+            std::fs::read_to_string(root.join("src/declared"))?
+        );
+        // Only the declared output is moved into place, and nothing else is left behind.
+        let mut out = std::fs::read_dir(root.join("out"))?
+            .map(|e| Ok(e?.file_name()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        out.sort();
+        assert_eq!(vec!["result", "sibling"], out);
+
+        if trace::is_supported() {
+            assert_eq!(
+                vec![
+                    root.join("out/sibling"),
+                    root.join("secret"),
+                    root.join("src/undeclared"),
+                ],
+                violations
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_records_violations_of_failed_commands() -> anyhow::Result<()> {
+        if !trace::is_supported() {
+            return Ok(());
+        }
+
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::write(root.join("src/undeclared"), "undeclared")?;
+
+        let (status, _stdout, violations) = run_sandboxed(
+            root,
+            &[],
+            &[&root.join("out/result")],
+            "cat src/undeclared > out/result",
+        )
+        .await?;
+        if sandbox_unavailable(&status) {
+            return Ok(());
+        }
+
+        assert!(
+            matches!(status, GatherOutputStatus::Finished { exit_status, .. } if !exit_status.success())
+        );
+        assert_eq!(vec![root.join("src/undeclared")], violations);
+
+        Ok(())
+    }
+}
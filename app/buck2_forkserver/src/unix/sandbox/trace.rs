@@ -0,0 +1,513 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Records the paths that a sandboxed command tried to access but that its sandbox hides, so that
+//! a command that failed because of the sandbox can be told apart from one that is just broken.
+//!
+//! Between fork and exec, once in the sandbox, the child installs a seccomp filter that turns the
+//! system calls that look up a path into notifications, and sends the file descriptor to receive
+//! them on to the forkserver. A thread in the forkserver reads each path out of the command's
+//! memory, records it if the sandbox hides it, and lets the system call continue unchanged. That
+//! costs a round trip to the forkserver for each such system call.
+//!
+//! This needs Linux 5.5, and is only implemented on x86_64 and aarch64.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use nix::sys::socket::recvmsg;
+use nix::sys::socket::ControlMessageOwned;
+use nix::sys::socket::MsgFlags;
+use nix::sys::uio::IoVec;
+
+const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: libc::c_ulong = 1 << 3;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+
+/// Where the system call number and architecture are in `struct seccomp_data`.
+const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+/// Where a traced system call takes the path it looks up.
+#[derive(Clone, Copy)]
+enum PathArg {
+    /// The first argument, relative to the working directory.
+    First,
+    /// The second argument, relative to the directory the first one refers to.
+    Second,
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+#[cfg(target_arch = "x86_64")]
+const TRACED: &[(libc::c_long, PathArg)] = &[
+    (libc::SYS_open, PathArg::First),
+    (libc::SYS_stat, PathArg::First),
+    (libc::SYS_lstat, PathArg::First),
+    (libc::SYS_access, PathArg::First),
+    (libc::SYS_readlink, PathArg::First),
+    (libc::SYS_execve, PathArg::First),
+    (libc::SYS_openat, PathArg::Second),
+    (libc::SYS_openat2, PathArg::Second),
+    (libc::SYS_newfstatat, PathArg::Second),
+    (libc::SYS_statx, PathArg::Second),
+    (libc::SYS_faccessat, PathArg::Second),
+    (libc::SYS_faccessat2, PathArg::Second),
+    (libc::SYS_readlinkat, PathArg::Second),
+    (libc::SYS_execveat, PathArg::Second),
+];
+#[cfg(target_arch = "aarch64")]
+const TRACED: &[(libc::c_long, PathArg)] = &[
+    (libc::SYS_execve, PathArg::First),
+    (libc::SYS_openat, PathArg::Second),
+    (libc::SYS_openat2, PathArg::Second),
+    (libc::SYS_newfstatat, PathArg::Second),
+    (libc::SYS_statx, PathArg::Second),
+    (libc::SYS_faccessat, PathArg::Second),
+    (libc::SYS_faccessat2, PathArg::Second),
+    (libc::SYS_readlinkat, PathArg::Second),
+    (libc::SYS_execveat, PathArg::Second),
+];
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const TRACED: &[(libc::c_long, PathArg)] = &[];
+
+// Filled in by the kernel, not all of it is used.
+#[allow(dead_code)]
+#[repr(C)]
+struct SeccompData {
+    nr: libc::c_int,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+#[repr(C)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
+
+/// The paths a traced command accessed that its sandbox hides. More can be added while anything
+/// the command started is still running.
+pub(crate) type Violations = Arc<Mutex<BTreeSet<PathBuf>>>;
+
+/// What the sandbox of a command shows of its hidden root, as paths relative to it.
+pub(super) struct Visibility {
+    pub(super) root: PathBuf,
+    /// Paths that are visible along with everything under them, and the directories leading to
+    /// them, but not what else is in those.
+    pub(super) visible: Vec<PathBuf>,
+    /// Other directories that are visible, but not what's in them.
+    pub(super) dirs: BTreeSet<PathBuf>,
+}
+
+impl Visibility {
+    /// Whether `path`, which is absolute and normalized, exists but is hidden by the sandbox.
+    fn hides(&self, path: &Path) -> bool {
+        let rel = match path.strip_prefix(&self.root) {
+            Ok(rel) => rel,
+            Err(_) => return false,
+        };
+        if rel.as_os_str().is_empty()
+            || self.dirs.contains(rel)
+            || self
+                .visible
+                .iter()
+                .any(|v| rel.starts_with(v) || v.starts_with(rel))
+        {
+            return false;
+        }
+        // Paths that don't exist outside of the sandbox either are just missing.
+        path.symlink_metadata().is_ok()
+    }
+}
+
+/// What the child needs to have its accesses traced, prepared before forking so that the child
+/// doesn't allocate.
+pub(super) struct TraceSetup {
+    filter: Vec<libc::sock_filter>,
+    /// The child's end of the socket it sends the listener on.
+    socket: UnixStream,
+}
+
+/// Starts the thread that records what the command that enters `TraceSetup` accesses, if this
+/// system supports it.
+pub(super) fn start_trace(
+    visibility: Visibility,
+) -> anyhow::Result<Option<(TraceSetup, Violations)>> {
+    if !is_supported() {
+        return Ok(None);
+    }
+
+    let (ours, theirs) = UnixStream::pair().context("Error creating the trace socket")?;
+    let violations = Violations::default();
+    let recorded = violations.clone();
+    std::thread::Builder::new()
+        .name("sandbox-trace".to_owned())
+        .spawn(move || {
+            if let Err(e) = serve(&ours, &visibility, &recorded) {
+                tracing::warn!("Error tracing a sandboxed command: {:#}", e);
+            }
+        })
+        .context("Error starting the trace thread")?;
+
+    Ok(Some((
+        TraceSetup {
+            filter: filter(),
+            socket: theirs,
+        },
+        violations,
+    )))
+}
+
+/// Notifications that continue the system call need Linux 5.5.
+pub(super) fn is_supported() -> bool {
+    if AUDIT_ARCH.is_none() {
+        return false;
+    }
+    let uname = nix::sys::utsname::uname();
+    let mut version = uname
+        .release()
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    let major = version.next().unwrap_or(0);
+    let minor = version.next().unwrap_or(0);
+    (major, minor) >= (5, 5)
+}
+
+fn bpf(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Notifies about the traced system calls, and allows everything else.
+fn filter() -> Vec<libc::sock_filter> {
+    let n = TRACED.len() as u8;
+    let arch = AUDIT_ARCH.expect("only used where supported");
+
+    let mut filter = vec![
+        bpf(BPF_LD_W_ABS, SECCOMP_DATA_ARCH_OFFSET, 0, 0),
+        // Jump offsets count from the next instruction, and the `ALLOW` is after the checks.
+        bpf(BPF_JMP_JEQ_K, arch, 0, n + 1),
+        bpf(BPF_LD_W_ABS, SECCOMP_DATA_NR_OFFSET, 0, 0),
+    ];
+    for (i, (nr, _)) in TRACED.iter().enumerate() {
+        filter.push(bpf(BPF_JMP_JEQ_K, *nr as u32, n - i as u8, 0));
+    }
+    filter.push(bpf(BPF_RET_K, SECCOMP_RET_ALLOW, 0, 0));
+    filter.push(bpf(BPF_RET_K, SECCOMP_RET_USER_NOTIF, 0, 0));
+    filter
+}
+
+impl TraceSetup {
+    /// Runs in the child, between fork and exec, once it's in the sandbox.
+    pub(super) fn enter(&self) -> io::Result<()> {
+        let prog = libc::sock_fprog {
+            len: self.filter.len() as u16,
+            filter: self.filter.as_ptr() as *mut libc::sock_filter,
+        };
+        // SAFETY: `prog` points to a valid filter, which the kernel copies.
+        let listener = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_NEW_LISTENER,
+                &prog as *const libc::sock_fprog,
+            )
+        };
+        if listener < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let listener = listener as RawFd;
+        let res = send_fd(self.socket.as_raw_fd(), listener);
+        // SAFETY: We own `listener`, and the forkserver has its own copy of it now.
+        unsafe { libc::close(listener) };
+        res
+    }
+}
+
+/// Sends `fd` over `socket`, without allocating.
+fn send_fd(socket: RawFd, fd: RawFd) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: byte.as_mut_ptr().cast(),
+        iov_len: byte.len(),
+    };
+    // Room for one file descriptor, aligned like a `cmsghdr`.
+    let mut control = [0u64; 4];
+
+    // SAFETY: All pointers are to buffers that outlive the call, and `control` is large enough
+    // for a header and one file descriptor.
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), fd);
+
+        if libc::sendmsg(socket, &msg, 0) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Receives the listener from the child, or `None` if it never got to send it.
+fn receive_fd(socket: &UnixStream) -> anyhow::Result<Option<File>> {
+    let mut byte = [0u8; 1];
+    let iov = [IoVec::from_mut_slice(&mut byte)];
+    let mut control = nix::cmsg_space!([RawFd; 1]);
+    let msg = recvmsg(
+        socket.as_raw_fd(),
+        &iov,
+        Some(&mut control),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )
+    .context("Error receiving the seccomp listener")?;
+    for cmsg in msg.cmsgs() {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            if let Some(fd) = fds.first() {
+                // SAFETY: We just received this file descriptor, so we own it.
+                return Ok(Some(unsafe { File::from_raw_fd(*fd) }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Answers the notifications for a command until it and everything it started exited.
+fn serve(
+    socket: &UnixStream,
+    visibility: &Visibility,
+    violations: &Mutex<BTreeSet<PathBuf>>,
+) -> anyhow::Result<()> {
+    let listener = match receive_fd(socket)? {
+        Some(listener) => listener,
+        None => return Ok(()),
+    };
+    let fd = listener.as_raw_fd();
+
+    loop {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is valid for the duration of the call.
+        if unsafe { libc::poll(&mut pollfd, 1, -1) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e).context("Error polling the seccomp listener");
+        }
+        if pollfd.revents & libc::POLLIN == 0 {
+            // Everything that used the filter exited.
+            return Ok(());
+        }
+
+        // SAFETY: All zeroes is a valid `SeccompNotif`, which the kernel requires.
+        let mut notif: SeccompNotif = unsafe { mem::zeroed() };
+        // SAFETY: `notif` is the struct this request fills in.
+        if unsafe { libc::ioctl(fd, SECCOMP_IOCTL_NOTIF_RECV as _, &mut notif) } < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                // The process that made the system call was killed in the meantime.
+                Some(libc::ENOENT) | Some(libc::EINTR) => continue,
+                _ => return Err(e).context("Error receiving a seccomp notification"),
+            }
+        }
+
+        if let Some(path) = accessed_path(&notif) {
+            if visibility.hides(&path) {
+                violations
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(path);
+            }
+        }
+
+        let resp = SeccompNotifResp {
+            id: notif.id,
+            val: 0,
+            error: 0,
+            flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+        };
+        // SAFETY: `resp` is the struct this request reads.
+        if unsafe { libc::ioctl(fd, SECCOMP_IOCTL_NOTIF_SEND as _, &resp) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::ENOENT) {
+                return Err(e).context("Error answering a seccomp notification");
+            }
+        }
+    }
+}
+
+/// The absolute path that the system call in `notif` looks up, if we can tell.
+fn accessed_path(notif: &SeccompNotif) -> Option<PathBuf> {
+    let (_, arg) = TRACED
+        .iter()
+        .find(|(nr, _)| *nr == notif.data.nr as libc::c_long)?;
+    let (dirfd, addr) = match arg {
+        PathArg::First => (libc::AT_FDCWD, notif.data.args[0]),
+        PathArg::Second => (notif.data.args[0] as libc::c_int, notif.data.args[1]),
+    };
+
+    let path = read_path(notif.pid, addr)?;
+    if path.is_empty() {
+        // With `AT_EMPTY_PATH`, this is about the directory itself.
+        return None;
+    }
+    let path = Path::new(OsStr::from_bytes(&path));
+    let path = if path.is_absolute() {
+        path.to_owned()
+    } else if dirfd == libc::AT_FDCWD {
+        std::fs::read_link(format!("/proc/{}/cwd", notif.pid))
+            .ok()?
+            .join(path)
+    } else {
+        std::fs::read_link(format!("/proc/{}/fd/{}", notif.pid, dirfd))
+            .ok()?
+            .join(path)
+    };
+    Some(normalize(&path))
+}
+
+/// Reads the nul-terminated string at `addr` in the memory of `pid`.
+fn read_path(pid: u32, mut addr: u64) -> Option<Vec<u8>> {
+    const CHUNK: u64 = 4096;
+
+    let mut path = Vec::new();
+    while path.len() < libc::PATH_MAX as usize {
+        // Don't read across pages, the next one might not be mapped.
+        let mut buf = [0u8; CHUNK as usize];
+        let len = (CHUNK - addr % CHUNK) as usize;
+        let local = libc::iovec {
+            iov_base: buf.as_mut_ptr().cast(),
+            iov_len: len,
+        };
+        let remote = libc::iovec {
+            iov_base: addr as *mut libc::c_void,
+            iov_len: len,
+        };
+        // SAFETY: `local` points to a buffer of `len` bytes.
+        let read = unsafe { libc::process_vm_readv(pid as libc::pid_t, &local, 1, &remote, 1, 0) };
+        if read <= 0 {
+            return None;
+        }
+        let read = &buf[..read as usize];
+        match read.iter().position(|b| *b == 0) {
+            Some(end) => {
+                path.extend_from_slice(&read[..end]);
+                return Some(path);
+            }
+            None => path.extend_from_slice(read),
+        }
+        addr += read.len() as u64;
+    }
+    None
+}
+
+/// Resolves `.` and `..` in an absolute path, without looking at the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                res.pop();
+            }
+            Component::CurDir => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            Path::new("/root/src/file"),
+            normalize(Path::new("/root/out/../src/./file"))
+        );
+        assert_eq!(Path::new("/"), normalize(Path::new("/../..")));
+    }
+
+    #[test]
+    fn test_hides() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path();
+        for file in ["src/declared", "src/undeclared", "out/sibling", "cwd/file"] {
+            std::fs::create_dir_all(root.join(file).parent().unwrap())?;
+            std::fs::write(root.join(file), "")?;
+        }
+
+        let visibility = Visibility {
+            root: root.to_owned(),
+            visible: vec![PathBuf::from("src/declared"), PathBuf::from("out/result")],
+            dirs: [PathBuf::from("cwd")].into_iter().collect(),
+        };
+        assert!(visibility.hides(&root.join("src/undeclared")));
+        assert!(visibility.hides(&root.join("out/sibling")));
+        assert!(!visibility.hides(&root.join("src/declared")));
+        assert!(!visibility.hides(&root.join("src")));
+        assert!(!visibility.hides(&root.join("out/result")));
+        assert!(!visibility.hides(&root.join("cwd")));
+        assert!(visibility.hides(&root.join("cwd/file")));
+        assert!(!visibility.hides(&root.join("src/missing")));
+        assert!(!visibility.hides(Path::new("/etc/hosts")));
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@
 
 use std::ffi::OsStr;
 use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::pin::Pin;
 
 use anyhow::Context as _;
@@ -17,6 +18,7 @@ use buck2_core::logging::LogConfigurationReloadHandle;
 use buck2_forkserver_proto::forkserver_server::Forkserver;
 use buck2_forkserver_proto::CommandRequest;
 use buck2_forkserver_proto::RequestEvent;
+use buck2_forkserver_proto::ResourceLimits;
 use buck2_forkserver_proto::SetLogFilterRequest;
 use buck2_forkserver_proto::SetLogFilterResponse;
 use buck2_grpc::to_tonic;
@@ -24,6 +26,7 @@ use buck2_util::process::background_command;
 use futures::future::select;
 use futures::future::FutureExt;
 use futures::stream::Stream;
+use futures::stream::StreamExt;
 use tonic::Request;
 use tonic::Response;
 use tonic::Status;
@@ -33,14 +36,86 @@ use crate::convert::encode_event_stream;
 use crate::run::prepare_command;
 use crate::run::stream_command_events;
 use crate::run::timeout_into_cancellation;
+use crate::run::CommandEvent;
 use crate::run::GatherOutputStatus;
+use crate::unix::cgroup::ActionCgroup;
+use crate::unix::cgroup::ActionCgroups;
+use crate::unix::sandbox::apply_sandbox;
+use crate::unix::sandbox::Sandboxed;
 
 // Not quite BoxStream: it has to be Sync (...)
 type RunStream =
     Pin<Box<dyn Stream<Item = Result<buck2_forkserver_proto::CommandEvent, Status>> + Send>>;
 
 pub struct UnixForkserverService {
-    pub(super) log_reload_handle: Box<dyn LogConfigurationReloadHandle>,
+    log_reload_handle: Box<dyn LogConfigurationReloadHandle>,
+    /// Where commands get cgroups of their own, or why they can't.
+    action_cgroups: anyhow::Result<ActionCgroups>,
+}
+
+impl UnixForkserverService {
+    pub(super) fn new(
+        log_reload_handle: Box<dyn LogConfigurationReloadHandle>,
+        action_cgroup: Option<&Path>,
+    ) -> Self {
+        let action_cgroups = ActionCgroups::init(action_cgroup);
+        if let Err(e) = &action_cgroups {
+            tracing::info!("Commands will not run in cgroups: {:#}", e);
+        }
+        Self {
+            log_reload_handle,
+            action_cgroups,
+        }
+    }
+
+    /// A cgroup for a command, to measure what it uses and enforce its `limits`. Commands without
+    /// limits run without one if cgroups aren't available.
+    fn action_cgroup(
+        &self,
+        limits: Option<&ResourceLimits>,
+    ) -> anyhow::Result<Option<ActionCgroup>> {
+        match (&self.action_cgroups, limits) {
+            (Ok(action_cgroups), limits) => Ok(Some(action_cgroups.create(limits)?)),
+            (Err(_), None) => Ok(None),
+            (Err(e), Some(_)) => Err(anyhow::anyhow!(
+                "The command has resource limits, but they can't be enforced: {:#}",
+                e
+            )),
+        }
+    }
+}
+
+/// Adds what the command used and what its sandbox hid from it to its exit event, removes its
+/// cgroup, and moves its outputs out of its sandbox.
+async fn finish_command(
+    mut event: anyhow::Result<CommandEvent>,
+    cgroup: Option<ActionCgroup>,
+    sandboxed: Option<Sandboxed>,
+) -> anyhow::Result<CommandEvent> {
+    if let Some(cgroup) = cgroup {
+        if let Ok(CommandEvent::Exit(GatherOutputStatus::Finished {
+            execution_stats, ..
+        })) = &mut event
+        {
+            *execution_stats = cgroup.stats().map_err(|e| tracing::warn!("{:#}", e)).ok();
+        }
+        // This kills anything the command left running, which could still write outputs.
+        cgroup.remove().await;
+    }
+
+    if let Some(sandboxed) = sandboxed {
+        let violations = tokio::task::spawn_blocking(move || sandboxed.finish())
+            .await
+            .context("Sandbox task panicked")??;
+        if let Ok(CommandEvent::Exit(GatherOutputStatus::Finished {
+            sandbox_violations, ..
+        })) = &mut event
+        {
+            *sandbox_violations = violations;
+        }
+    }
+
+    event
 }
 
 #[async_trait::async_trait]
@@ -78,6 +153,8 @@ impl Forkserver for UnixForkserverService {
                 env,
                 cwd,
                 timeout,
+                sandbox,
+                resource_limits,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -112,6 +189,17 @@ impl Forkserver for UnixForkserverService {
                 }
             }
 
+            let cgroup = self.action_cgroup(resource_limits.as_ref())?;
+
+            // Join the cgroup before the sandbox puts the command in a user namespace of its own.
+            if let Some(cgroup) = &cgroup {
+                cgroup.apply(&mut cmd);
+            }
+
+            let sandboxed = sandbox
+                .map(|sandbox| apply_sandbox(&mut cmd, sandbox, cwd.map(Path::new)))
+                .transpose()?;
+
             let mut cmd = prepare_command(cmd);
 
             let child = cmd.spawn();
@@ -121,6 +209,16 @@ impl Forkserver for UnixForkserverService {
             let cancellation = select(timeout.boxed(), cancel.boxed()).map(|r| r.factor_first().0);
 
             let stream = stream_command_events(child, cancellation)?;
+            // The cgroup and sandbox are cleaned up once the command exits. If the stream is
+            // dropped before that, they're cleaned up in the background.
+            let mut cleanup = Some((cgroup, sandboxed));
+            let stream = stream.then(move |event| {
+                let (cgroup, sandboxed) = match &event {
+                    Ok(CommandEvent::Exit(..)) => cleanup.take().unwrap_or_default(),
+                    _ => (None, None),
+                };
+                finish_command(event, cgroup, sandboxed)
+            });
             let stream = encode_event_stream(stream);
             Ok(Box::pin(stream) as _)
         })
@@ -91,7 +91,8 @@ async fn main() -> anyhow::Result<()> {
             let (status, out, err) = forkserver
                 .execute(req.clone(), futures::future::pending())
                 .await?;
-            if !matches!(status, GatherOutputStatus::Finished(s) if s.success()) {
+            if !matches!(status, GatherOutputStatus::Finished { exit_status: s, .. } if s.success())
+            {
                 failures.fetch_add(1, Ordering::Relaxed);
             }
             if !no_stdout {
@@ -77,6 +77,7 @@ impl<'a> StarlarkPath<'a> {
             enable_tabs: true,
             enable_load_reexport: false,
             enable_top_level_stmt: false,
+            enable_set: false,
         };
         let bzl_dialect: Dialect = Dialect {
             enable_def: true,
@@ -92,6 +93,7 @@ impl<'a> StarlarkPath<'a> {
             enable_tabs: true,
             enable_load_reexport: false,
             enable_top_level_stmt: true,
+            enable_set: true,
         };
         let bxl_dialect: Dialect = Dialect {
             enable_def: true,
@@ -106,6 +108,7 @@ impl<'a> StarlarkPath<'a> {
             enable_tabs: false,
             enable_load_reexport: false,
             enable_top_level_stmt: true,
+            enable_set: true,
         };
 
         match self {
@@ -226,6 +229,7 @@ pub fn configure_base_globals(
         LibraryExtension::Print,
         LibraryExtension::RecordType,
         LibraryExtension::ExperimentalRegex,
+        LibraryExtension::SetType,
         LibraryExtension::StructType,
     ];
     let mut global_env = GlobalsBuilder::extended_by(&starlark_extensions)
@@ -64,12 +64,15 @@ use buck2_execute::execute::dice_data::set_fallback_executor_config;
 use buck2_execute::execute::dice_data::SetCommandExecutor;
 use buck2_execute::execute::dice_data::SetReClient;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
+use buck2_execute::knobs::LocalSandboxOptions;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::SetMaterializer;
 use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionManager;
 use buck2_execute::re::manager::ReConnectionObserver;
+use buck2_execute_impl::executors::disk_cache::DiskActionCache;
+use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use buck2_interpreter::dice::interpreter_setup::setup_interpreter;
@@ -136,6 +139,10 @@ pub struct BaseServerCommandContext {
     pub materializer: Arc<dyn Materializer>,
     /// Forkserver connection, if any was started
     pub forkserver: Option<ForkserverClient>,
+    /// The action cache on local disk, if enabled.
+    pub disk_action_cache: Option<Arc<DiskActionCache>>,
+    /// The persistent workers for local actions, if enabled.
+    pub worker_pool: Option<Arc<WorkerPool>>,
     /// The event dispatcher for this command context.
     pub events: EventDispatcher,
     /// Removes this command from the set of active commands when dropped.
@@ -331,6 +338,8 @@ impl ServerCommandContext {
         let build_signals = self.build_signals.dupe();
 
         let forkserver = self.base_context.forkserver.dupe();
+        let disk_action_cache = self.base_context.disk_action_cache.dupe();
+        let worker_pool = self.base_context.worker_pool.dupe();
 
         let upload_all_actions = self
             .build_options
@@ -351,6 +360,8 @@ impl ServerCommandContext {
             re_connection,
             build_signals,
             forkserver,
+            disk_action_cache,
+            worker_pool,
             upload_all_actions,
             no_remote_cache,
             create_unhashed_symlink_lock,
@@ -438,6 +449,8 @@ struct DiceCommandDataProvider {
     re_connection: ReConnectionHandle,
     build_signals: BuildSignalSender,
     forkserver: Option<ForkserverClient>,
+    disk_action_cache: Option<Arc<DiskActionCache>>,
+    worker_pool: Option<Arc<WorkerPool>>,
     upload_all_actions: bool,
     run_action_knobs: RunActionKnobs,
     no_remote_cache: bool,
@@ -466,7 +479,30 @@ impl DiceDataProvider for DiceCommandDataProvider {
             .concurrency
             .unwrap_or_else(|| parse_concurrency(config_threads))?;
 
-        let executor_global_knobs = ExecutorGlobalKnobs {};
+        let local_sandbox = if root_config
+            .parse("buck2", "sandbox_local_actions")?
+            .unwrap_or(false)
+        {
+            let toolchain_paths = root_config
+                .get("buck2", "sandbox_toolchain_paths")
+                .unwrap_or("")
+                .split(',')
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .map(|p| anyhow::Ok(ProjectRelativePath::new(p)?.to_owned()))
+                .collect::<anyhow::Result<_>>()
+                .context("Invalid `buck2.sandbox_toolchain_paths`")?;
+            Some(Arc::new(LocalSandboxOptions {
+                toolchain_paths,
+                isolate_network: root_config
+                    .parse("buck2", "sandbox_isolate_network")?
+                    .unwrap_or(false),
+            }))
+        } else {
+            None
+        };
+
+        let executor_global_knobs = ExecutorGlobalKnobs { local_sandbox };
 
         let host_sharing_broker =
             HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, concurrency);
@@ -488,6 +524,7 @@ impl DiceDataProvider for DiceCommandDataProvider {
                 executor_kind: CommandExecutorKind::Local(LocalExecutorOptions {}),
                 path_separator: PathSeparatorKind::system_default(),
                 cache_upload_behavior: CacheUploadBehavior::Disabled,
+                default_action_timeout: None,
             },
         );
 
@@ -509,6 +546,8 @@ impl DiceDataProvider for DiceCommandDataProvider {
             executor_global_knobs,
             self.upload_all_actions,
             self.forkserver,
+            self.disk_action_cache,
+            self.worker_pool,
             self.no_remote_cache,
             ctx.global_data()
                 .get_io_provider()
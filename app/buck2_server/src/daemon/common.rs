@@ -30,10 +30,13 @@ use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute_impl::executors::caching::CachingExecutor;
+use buck2_execute_impl::executors::disk_cache::DiskActionCache;
+use buck2_execute_impl::executors::disk_cache::DiskCachingExecutor;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
 use buck2_execute_impl::executors::re::ReExecutionPlatform;
 use buck2_execute_impl::executors::re::ReExecutor;
+use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use dupe::Dupe;
@@ -66,6 +69,8 @@ pub struct CommandExecutorFactory {
     pub executor_global_knobs: ExecutorGlobalKnobs,
     pub upload_all_actions: bool,
     pub forkserver: Option<ForkserverClient>,
+    pub disk_action_cache: Option<Arc<DiskActionCache>>,
+    pub worker_pool: Option<Arc<WorkerPool>>,
     pub no_remote_cache: bool,
     project_root: ProjectRoot,
 }
@@ -81,6 +86,8 @@ impl CommandExecutorFactory {
         executor_global_knobs: ExecutorGlobalKnobs,
         upload_all_actions: bool,
         forkserver: Option<ForkserverClient>,
+        disk_action_cache: Option<Arc<DiskActionCache>>,
+        worker_pool: Option<Arc<WorkerPool>>,
         no_remote_cache: bool,
         project_root: ProjectRoot,
     ) -> Self {
@@ -94,6 +101,8 @@ impl CommandExecutorFactory {
             executor_global_knobs,
             upload_all_actions,
             forkserver,
+            disk_action_cache,
+            worker_pool,
             no_remote_cache,
             project_root,
         }
@@ -114,10 +123,27 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 self.host_sharing_broker.dupe(),
                 self.project_root.root().to_owned(),
                 self.forkserver.dupe(),
+                self.worker_pool.dupe(),
                 self.executor_global_knobs.dupe(),
             )
         };
 
+        // The disk cache goes in front of everything else, including the remote cache, since it's
+        // the cheapest to query.
+        let with_disk_cache =
+            |inner: Arc<dyn PreparedCommandExecutor>| -> Arc<dyn PreparedCommandExecutor> {
+                match &self.disk_action_cache {
+                    Some(cache) => Arc::new(DiskCachingExecutor {
+                        inner,
+                        cache: cache.dupe(),
+                        artifact_fs: artifact_fs.clone(),
+                        materializer: self.materializer.dupe(),
+                        blocking_executor: self.blocking_executor.dupe(),
+                    }),
+                    None => inner,
+                }
+            };
+
         if !cfg!(fbcode_build) {
             static WARN: OnceCell<()> = OnceCell::new();
             WARN.get_or_init(|| {
@@ -131,7 +157,9 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 ));
             }
 
-            return Ok(Arc::new(local_executor_new(&LocalExecutorOptions {})));
+            return Ok(with_disk_cache(Arc::new(local_executor_new(
+                &LocalExecutorOptions {},
+            ))));
         }
 
         let remote_executor_new = |options: &RemoteExecutorOptions| {
@@ -197,10 +225,10 @@ impl HasCommandExecutor for CommandExecutorFactory {
             .get_copied()?
             .unwrap_or(self.no_remote_cache)
         {
-            return Ok(inner_executor);
+            return Ok(with_disk_cache(inner_executor));
         }
 
-        Ok(Arc::new(CachingExecutor::new(
+        Ok(with_disk_cache(Arc::new(CachingExecutor::new(
             inner_executor,
             artifact_fs.clone(),
             self.materializer.dupe(),
@@ -208,7 +236,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
             self.upload_all_actions,
             self.executor_global_knobs.dupe(),
             executor_config.cache_upload_behavior,
-        )))
+        ))))
     }
 }
 
@@ -292,6 +320,7 @@ pub fn get_executor_config_for_strategy(
         executor_kind,
         path_separator: PathSeparatorKind::system_default(),
         cache_upload_behavior: CacheUploadBehavior::Disabled,
+        default_action_timeout: None,
     }
 }
 
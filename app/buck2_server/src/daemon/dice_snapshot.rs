@@ -0,0 +1,296 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! DICE snapshots across daemons, enabled with `buck2.dice_snapshots`. The daemon writes one after
+//! every command, and the next daemon restores it if its file watcher can tell what changed since,
+//! which only the `poll` watcher can. The first sync of the watcher then invalidates those changes
+//! like any others.
+//!
+//! Only the keys that track file contents are persisted. Parsing and analysis results hold
+//! Starlark heaps, which can't be serialized, so a restored daemon still redoes those.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+
+use anyhow::Context;
+use buck2_common::daemon_dir::DaemonDir;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_server_ctx::concurrency::ConcurrencyHandler;
+use dice::DiceSnapshot;
+use tracing::info;
+use tracing::warn;
+
+use crate::file_watcher::FileWatcher;
+
+pub(crate) struct DiceSnapshots {
+    path: AbsNormPathBuf,
+    /// The version of the binary, since values are only read back by the build that wrote them.
+    version: String,
+    /// Held while writing, since all writes go through the same temporary file.
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl DiceSnapshots {
+    pub(crate) fn new(daemon_dir: &DaemonDir, version: String) -> Self {
+        Self {
+            path: daemon_dir.dice_snapshot(),
+            version,
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn token(&self, file_watcher_token: &str) -> String {
+        format!("{} {}", self.version, file_watcher_token)
+    }
+
+    /// The snapshot of the previous daemon, if it was taken at the state of the files that the
+    /// first sync of `file_watcher` reports changes since.
+    pub(crate) async fn load(&self, file_watcher: &dyn FileWatcher) -> Option<DiceSnapshot> {
+        let token = match file_watcher.snapshot_token().await {
+            Some(token) => self.token(&token),
+            None => {
+                info!("Not restoring a DICE snapshot: the file watcher can't tell what changed");
+                return None;
+            }
+        };
+        let path = self.path.clone();
+        let snapshot = tokio::task::spawn_blocking(move || read(&path))
+            .await
+            .context("Loading task panicked")
+            .and_then(|r| r);
+        match snapshot {
+            Ok(Some(snapshot)) if snapshot.token() == token => Some(snapshot),
+            Ok(Some(_)) => {
+                info!("Not restoring the DICE snapshot: it's from another build or file state");
+                None
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Ignoring the previous DICE snapshot: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Writes a snapshot of the DICE of `dice_manager` for the next daemon, unless a command is
+    /// running. Commands sync `file_watcher` and commit its changes while holding `dice_manager`,
+    /// so the snapshot always goes with the token of the last sync.
+    pub(crate) async fn write(
+        &self,
+        dice_manager: &ConcurrencyHandler,
+        file_watcher: &dyn FileWatcher,
+    ) -> anyhow::Result<()> {
+        let _writing = self.writing.lock().await;
+        let snapshot = dice_manager
+            .when_idle(async {
+                // Without a token, the next daemon wouldn't know what to invalidate.
+                let token = file_watcher.snapshot_token().await?;
+                Some(dice_manager.unsafe_dice().snapshot(self.token(&token)))
+            })
+            .await
+            .flatten();
+        let snapshot = match snapshot {
+            Some(snapshot) => snapshot?,
+            None => return Ok(()),
+        };
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || write(&snapshot, &path))
+            .await
+            .context("Snapshotting task panicked")?
+    }
+}
+
+fn read(path: &AbsNormPath) -> anyhow::Result<Option<DiceSnapshot>> {
+    if !fs_util::try_exists(path)? {
+        return Ok(None);
+    }
+    let file = File::open(path).with_context(|| format!("Failed to open `{}`", path))?;
+    let snapshot = DiceSnapshot::read(BufReader::new(file))
+        .with_context(|| format!("Failed to read the DICE snapshot at `{}`", path))?;
+    Ok(Some(snapshot))
+}
+
+/// Writes to a temporary file that replaces the snapshot at the end, so that a daemon killed
+/// halfway doesn't leave a truncated snapshot behind.
+fn write(snapshot: &DiceSnapshot, path: &AbsNormPath) -> anyhow::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    let file = File::create(&tmp).with_context(|| format!("Failed to create `{}`", tmp))?;
+    let mut out = BufWriter::new(file);
+    snapshot.write(&mut out)?;
+    out.flush()?;
+    drop(out);
+    fs_util::rename(&tmp, path)?;
+    info!("Wrote {} DICE nodes to `{}`", snapshot.len(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use buck2_common::dice::cells::HasCellResolver;
+    use buck2_common::dice::data::SetIoProvider;
+    use buck2_common::dice::file_ops::HasFileOps;
+    use buck2_common::dice::file_ops::PersistFileOps;
+    use buck2_common::file_ops::FileOps;
+    use buck2_common::file_ops::IgnoreSet;
+    use buck2_common::io::fs::FsIoProvider;
+    use buck2_common::legacy_configs::dice::HasLegacyConfigs;
+    use buck2_common::legacy_configs::testing::legacy_buck_config_from_entries;
+    use buck2_common::legacy_configs::LegacyBuckConfigs;
+    use buck2_core::cells::cell_path::CellPath;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::cells::CellName;
+    use buck2_core::cells::CellResolver;
+    use buck2_core::fs::project::ProjectRelativePathBuf;
+    use buck2_core::fs::project::ProjectRoot;
+    use buck2_server_ctx::concurrency::NestedInvocation;
+    use buck2_server_ctx::concurrency::ParallelInvocation;
+    use dice::cycles::DetectCycles;
+    use dice::Dice;
+    use dupe::Dupe;
+
+    use super::*;
+
+    struct Daemon {
+        dice_manager: ConcurrencyHandler,
+        file_watcher: Arc<dyn FileWatcher>,
+        snapshots: DiceSnapshots,
+        restored: usize,
+    }
+
+    impl Daemon {
+        /// Starts like `DaemonState::init_data` does, with the `poll` file watcher.
+        async fn start(
+            project: &ProjectRoot,
+            daemon_dir: &DaemonDir,
+            version: &str,
+        ) -> anyhow::Result<Self> {
+            let cell = CellName::unchecked_new("root".to_owned());
+            let cells = CellResolver::of_names_and_paths(&[(
+                cell.clone(),
+                CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new(String::new())),
+            )]);
+            let config = legacy_buck_config_from_entries([
+                ("buck2", "file_watcher", "poll"),
+                ("buck2", "dice_snapshots", "true"),
+            ])?;
+            let file_watcher = <dyn FileWatcher>::new(
+                project,
+                daemon_dir,
+                &config,
+                cells.dupe(),
+                HashMap::from_iter([(cell.clone(), IgnoreSet::from_ignore_spec("")?)]),
+            )?;
+            let snapshots = DiceSnapshots::new(daemon_dir, version.to_owned());
+            let snapshot = snapshots.load(&*file_watcher).await;
+
+            let mut builder = Dice::builder();
+            builder.set_io_provider(Arc::new(FsIoProvider::new(project.dupe())));
+            builder.persist_file_ops();
+            let dice = builder.build(DetectCycles::Enabled);
+            let restored = match &snapshot {
+                Some(snapshot) => dice.restore_snapshot(snapshot)?,
+                None => 0,
+            };
+            let ctx = dice.ctx();
+            ctx.set_cell_resolver(cells)?;
+            ctx.set_legacy_configs(LegacyBuckConfigs::new(HashMap::from_iter([(cell, config)])))?;
+            ctx.commit();
+
+            Ok(Self {
+                dice_manager: ConcurrencyHandler::new(
+                    dice,
+                    NestedInvocation::Run,
+                    ParallelInvocation::Run,
+                ),
+                file_watcher,
+                snapshots,
+                restored,
+            })
+        }
+
+        /// Reads a file like a command would, after syncing the file watcher, and writes the
+        /// snapshot like the daemon does once the command is done.
+        async fn read(&self, path: &str) -> anyhow::Result<String> {
+            let ctx = self
+                .file_watcher
+                .sync(self.dice_manager.unsafe_dice().ctx())
+                .await?
+                .commit();
+            let res = ctx
+                .file_ops()
+                .read_file(&CellPath::testing_new("root", path))
+                .await?;
+            self.snapshots
+                .write(&self.dice_manager, &*self.file_watcher)
+                .await?;
+            Ok(res)
+        }
+
+        fn invalidated(&self, path: &str) -> bool {
+            let why = self
+                .dice_manager
+                .unsafe_dice()
+                .why_recomputed(|k| k == path);
+            assert_eq!(1, why.len(), "{:?}", why);
+            why[0].invalidated_at.is_some()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_and_invalidate() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let project_path = tempdir.path().join("project");
+        fs_util::create_dir_all(&project_path)?;
+        let project = ProjectRoot::new(AbsNormPathBuf::try_from(project_path.clone())?);
+        let daemon_dir = DaemonDir {
+            path: AbsNormPathBuf::try_from(tempdir.path().join("daemon"))?,
+        };
+        fs_util::create_dir_all(&daemon_dir.path)?;
+        fs_util::write(project_path.join("a.txt"), "a")?;
+        fs_util::write(project_path.join("b.txt"), "b")?;
+
+        // Daemons are killed rather than shut down: the snapshot was written after each command.
+        let daemon = Daemon::start(&project, &daemon_dir, "v1").await?;
+        assert_eq!(0, daemon.restored);
+        assert_eq!("a", daemon.read("a.txt").await?);
+        assert_eq!("b", daemon.read("b.txt").await?);
+        drop(daemon);
+
+        // Changed while no daemon was running.
+        fs_util::write(project_path.join("a.txt"), "aa")?;
+
+        let daemon = Daemon::start(&project, &daemon_dir, "v1").await?;
+        assert_eq!(2, daemon.restored);
+        assert_eq!("aa", daemon.read("a.txt").await?);
+        assert_eq!("b", daemon.read("b.txt").await?);
+        assert!(daemon.invalidated("root//a.txt"));
+        assert!(!daemon.invalidated("root//b.txt"));
+        drop(daemon);
+
+        // Another binary can't read the snapshot back.
+        let daemon = Daemon::start(&project, &daemon_dir, "v2").await?;
+        assert_eq!(0, daemon.restored);
+        assert_eq!("b", daemon.read("b.txt").await?);
+        drop(daemon);
+
+        // Neither can a daemon that doesn't know what changed since.
+        fs_util::remove_file(daemon_dir.poll_file_watcher_snapshot())?;
+        let daemon = Daemon::start(&project, &daemon_dir, "v2").await?;
+        assert_eq!(0, daemon.restored);
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@
  */
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
 use allocative::Allocative;
@@ -16,11 +17,14 @@ use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::RelativePath;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_execute::execute::blocking::BlockingExecutor;
 use buck2_execute::materialize::materializer::MaterializationMethod;
+use buck2_execute_impl::executors::disk_cache::DiskActionCache;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
 use buck2_execute_impl::materializers::sqlite::MaterializerState;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateSqliteDb;
@@ -114,6 +118,40 @@ pub(crate) async fn maybe_initialize_materializer_sqlite_db(
     Ok((Some(db), materializer_state))
 }
 
+/// 10GiB, which is plenty for most projects without filling up most disks.
+const DEFAULT_DISK_ACTION_CACHE_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Opens the disk action cache, if `buck2.disk_action_cache_dir` is set. Unlike the rest of the
+/// disk state, this lives outside of buck-out, so that it survives `buck2 clean` and can be shared
+/// by several checkouts.
+pub(crate) async fn maybe_open_disk_action_cache(
+    root_config: &LegacyBuckConfig,
+    io_executor: Arc<dyn BlockingExecutor>,
+    fs: &ProjectRoot,
+) -> anyhow::Result<Option<Arc<DiskActionCache>>> {
+    let dir = match root_config.get("buck2", "disk_action_cache_dir") {
+        Some(dir) => Path::new(dir),
+        None => return Ok(None),
+    };
+    // Relative paths are relative to the project root.
+    let dir = if dir.is_absolute() {
+        AbsNormPathBuf::try_from(dir.to_owned())?
+    } else {
+        fs.root().join_normalized(RelativePath::from_path(dir)?)?
+    };
+    let max_bytes = root_config
+        .parse("buck2", "disk_action_cache_max_bytes")?
+        .unwrap_or(DEFAULT_DISK_ACTION_CACHE_MAX_BYTES);
+
+    let cache = io_executor
+        .execute_io_inline(|| {
+            DiskActionCache::open(dir.clone(), max_bytes)
+                .with_context(|| format!("opening the disk action cache in `{}`", dir))
+        })
+        .await?;
+    Ok(Some(Arc::new(cache)))
+}
+
 // Once we start storing disk state in the cache directory, we need to make sure
 // buck2 always deletes the cache directory if the cache is disabled.
 // Otherwise, buck-out state can diverge from the state of on-disk cache when
@@ -25,8 +25,14 @@ pub async fn maybe_launch_forkserver(
         return Ok(None);
     }
 
+    let mut args = vec!["forkserver".to_owned()];
+    if let Some(action_cgroup) = root_config.get("buck2", "action_cgroup") {
+        args.push("--action-cgroup".to_owned());
+        args.push(action_cgroup.to_owned());
+    }
+
     let exe = std::env::current_exe().context("Cannot access current_exe")?;
-    Some(buck2_forkserver::unix::launch_forkserver(exe, &["forkserver"]).await).transpose()
+    Some(buck2_forkserver::unix::launch_forkserver(exe, &args).await).transpose()
 }
 
 #[cfg(not(unix))]
@@ -11,6 +11,7 @@ pub mod check_working_dir;
 pub mod common;
 pub mod daemon_tcp;
 pub mod dice_dump;
+pub(crate) mod dice_snapshot;
 pub mod disk_state;
 pub mod forkserver;
 pub mod panic;
@@ -49,6 +49,7 @@ use buck2_profile::starlark_profiler_configuration_from_request;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use dice::cycles::DetectCycles;
 use dice::Dice;
+use dice::DiceSnapshot;
 use dupe::Dupe;
 use futures::channel::mpsc;
 use futures::channel::mpsc::UnboundedReceiver;
@@ -84,6 +85,7 @@ use crate::lsp::run_lsp_server_command;
 use crate::materialize::materialize_command;
 use crate::snapshot;
 use crate::streaming_request_handler::StreamingRequestHandler;
+use crate::why_recomputed::why_recomputed_command;
 
 // TODO(cjhopman): Figure out a reasonable value for this.
 static DEFAULT_KILL_TIMEOUT: Duration = Duration::from_millis(500);
@@ -136,12 +138,14 @@ impl DaemonStateDiceConstructor for DaemonStateDiceConstructorImpl {
         &self,
         io: Arc<dyn IoProvider>,
         root_config: &LegacyBuckConfig,
+        snapshot: Option<&DiceSnapshot>,
     ) -> anyhow::Result<Arc<Dice>> {
         configure_dice_for_buck(
             io,
             self.bxl_calculations,
             Some(root_config),
             self.detect_cycles,
+            snapshot,
         )
     }
 }
@@ -280,6 +284,7 @@ impl BuckdServer {
         let (command_channel, command_receiver): (UnboundedSender<()>, _) = mpsc::unbounded();
 
         let auth_token = process_info.auth_token.clone();
+        let version = process_info.version.clone();
         let api_server = BuckdServer(Arc::new(BuckdServerData {
             stop_accepting_requests: AtomicBool::new(false),
             process_info,
@@ -296,6 +301,7 @@ impl BuckdServer {
                 DaemonState::new(
                     fb,
                     paths,
+                    &version,
                     box DaemonStateDiceConstructorImpl {
                         detect_cycles,
                         bxl_calculations: callbacks.bxl_calculation(),
@@ -423,6 +429,14 @@ impl BuckdServer {
 
                 let result: CommandResult = result_to_command_result(result);
                 dispatch.control_event(ControlEvent::CommandResult(result));
+
+                // Written after every command rather than at shutdown, which a killed daemon
+                // never gets to. This doesn't hold up the client.
+                tokio::spawn(async move {
+                    if let Err(e) = data.write_dice_snapshot().await {
+                        tracing::warn!("Failed to write the DICE snapshot: {:#}", e);
+                    }
+                });
             },
         )
         .await;
@@ -804,6 +818,17 @@ impl DaemonApi for BuckdServer {
         .await
     }
 
+    type WhyRecomputedStream = ResponseStream;
+    async fn why_recomputed(
+        &self,
+        req: Request<WhyRecomputedRequest>,
+    ) -> Result<Response<ResponseStream>, Status> {
+        self.run_streaming(req, DefaultCommandOptions, |context, req| {
+            why_recomputed_command(context, req)
+        })
+        .await
+    }
+
     type BuildStream = ResponseStream;
     async fn build(&self, req: Request<BuildRequest>) -> Result<Response<ResponseStream>, Status> {
         let callbacks = self.0.callbacks;
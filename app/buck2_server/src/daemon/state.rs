@@ -40,6 +40,8 @@ use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::client::RemoteExecutionStaticMetadata;
 use buck2_execute::re::manager::ReConnectionManager;
+use buck2_execute_impl::executors::disk_cache::DiskActionCache;
+use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
 use buck2_execute_impl::materializers::deferred::TtlRefreshConfiguration;
@@ -51,6 +53,7 @@ use buck2_server_ctx::concurrency::ConcurrencyHandler;
 use buck2_server_ctx::concurrency::NestedInvocation;
 use buck2_server_ctx::concurrency::ParallelInvocation;
 use dice::Dice;
+use dice::DiceSnapshot;
 use dupe::Dupe;
 use fbinit::FacebookInit;
 use gazebo::variants::VariantName;
@@ -59,8 +62,10 @@ use tokio::sync::Mutex;
 use crate::active_commands::ActiveCommandDropGuard;
 use crate::ctx::BaseServerCommandContext;
 use crate::daemon::check_working_dir;
+use crate::daemon::dice_snapshot::DiceSnapshots;
 use crate::daemon::disk_state::delete_unknown_disk_state;
 use crate::daemon::disk_state::maybe_initialize_materializer_sqlite_db;
+use crate::daemon::disk_state::maybe_open_disk_action_cache;
 use crate::daemon::disk_state::DiskStateOptions;
 use crate::daemon::forkserver::maybe_launch_forkserver;
 use crate::daemon::panic::DaemonStatePanicDiceDump;
@@ -93,6 +98,10 @@ pub struct DaemonStateData {
     /// Synced every time we run a command.
     file_watcher: Arc<dyn FileWatcher>,
 
+    /// Where the DICE graph is written for the next daemon, if `buck2.dice_snapshots` is set.
+    #[allocative(skip)]
+    dice_snapshots: Option<DiceSnapshots>,
+
     /// Settled every time we run a command.
     io: Arc<dyn IoProvider>,
 
@@ -113,6 +122,16 @@ pub struct DaemonStateData {
 
     pub(crate) forkserver: Option<ForkserverClient>,
 
+    /// The action cache on local disk, if enabled. It is shared by all commands so that it can
+    /// keep track of what was used most recently.
+    #[allocative(skip)]
+    pub(crate) disk_action_cache: Option<Arc<DiskActionCache>>,
+
+    /// The persistent workers for local actions, if enabled. They are shared by all commands so
+    /// that they stay warm between builds.
+    #[allocative(skip)]
+    pub(crate) worker_pool: Option<Arc<WorkerPool>>,
+
     #[allocative(skip)]
     pub scribe_sink: Option<Arc<dyn EventSink>>,
 
@@ -137,6 +156,18 @@ impl DaemonStateData {
         crate::daemon::dice_dump::dice_dump_spawn(self.dice_manager.unsafe_dice(), path, format)
             .await
     }
+
+    /// Writes the DICE graph for the next daemon, if enabled and no command is running.
+    pub async fn write_dice_snapshot(&self) -> anyhow::Result<()> {
+        match &self.dice_snapshots {
+            Some(dice_snapshots) => {
+                dice_snapshots
+                    .write(&self.dice_manager, &*self.file_watcher)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
 }
 
 impl DaemonStatePanicDiceDump for DaemonStateData {
@@ -150,16 +181,19 @@ pub trait DaemonStateDiceConstructor: Allocative + Send + Sync + 'static {
         &self,
         io: Arc<dyn IoProvider>,
         root_config: &LegacyBuckConfig,
+        snapshot: Option<&DiceSnapshot>,
     ) -> anyhow::Result<Arc<Dice>>;
 }
 
 impl DaemonState {
+    /// The `version` identifies the binary, see `DaemonProcessInfo`.
     pub async fn new(
         fb: fbinit::FacebookInit,
         paths: InvocationPaths,
+        version: &str,
         dice_constructor: Box<dyn DaemonStateDiceConstructor>,
     ) -> Self {
-        let data = Self::init_data(fb, &paths, &*dice_constructor)
+        let data = Self::init_data(fb, &paths, version, &*dice_constructor)
             .await
             .context("Error initializing DaemonStateData");
         if let Ok(data) = &data {
@@ -181,6 +215,7 @@ impl DaemonState {
     async fn init_data(
         fb: fbinit::FacebookInit,
         paths: &InvocationPaths,
+        version: &str,
         dice_constructor: &dyn DaemonStateDiceConstructor,
     ) -> anyhow::Result<Arc<DaemonStateData>> {
         let fs = paths.project_root().clone();
@@ -295,7 +330,26 @@ impl DaemonState {
             materializer_state,
         )?;
 
-        let dice = dice_constructor.construct_dice(io.dupe(), root_config)?;
+        let disk_action_cache = maybe_open_disk_action_cache(
+            root_config,
+            blocking_executor.dupe() as Arc<dyn BlockingExecutor>,
+            io.project_root(),
+        )
+        .await?;
+
+        let worker_pool = if root_config
+            .parse("buck2", "persistent_workers")?
+            .unwrap_or(true)
+        {
+            Some(Arc::new(WorkerPool::new(
+                paths.worker_logs_dir(),
+                root_config
+                    .parse("buck2", "max_workers_per_key")?
+                    .unwrap_or(4),
+            )))
+        } else {
+            None
+        };
 
         // TODO(cjhopman): We want to use Expr::True here, but we need to workaround
         // https://github.com/facebook/watchman/issues/911. Adding other filetypes to
@@ -303,12 +357,35 @@ impl DaemonState {
 
         let file_watcher = <dyn FileWatcher>::new(
             paths.project_root(),
+            &paths.daemon_dir()?,
             root_config,
             cells.dupe(),
             ignore_specs,
         )
         .context("Error creating a FileWatcher")?;
 
+        // The file watcher is created first, since it decides whether the snapshot is valid. Only
+        // the `poll` one can tell what changed while no daemon was running.
+        let dice_snapshots = if !root_config
+            .parse("buck2", "dice_snapshots")?
+            .unwrap_or(false)
+        {
+            None
+        } else if root_config.get("buck2", "file_watcher") != Some("poll") {
+            tracing::warn!(
+                "Ignoring `buck2.dice_snapshots`, which requires `buck2.file_watcher = poll`"
+            );
+            None
+        } else {
+            Some(DiceSnapshots::new(&paths.daemon_dir()?, version.to_owned()))
+        };
+        let dice_snapshot = match &dice_snapshots {
+            Some(dice_snapshots) => dice_snapshots.load(&*file_watcher).await,
+            None => None,
+        };
+        let dice =
+            dice_constructor.construct_dice(io.dupe(), root_config, dice_snapshot.as_ref())?;
+
         let hash_all_commands = root_config
             .parse::<RolloutPercentage>("buck2", "hash_all_commands")?
             .unwrap_or_else(RolloutPercentage::never)
@@ -350,11 +427,14 @@ impl DaemonState {
                 parallel_invocation_config,
             ),
             file_watcher,
+            dice_snapshots,
             io,
             re_client_manager,
             blocking_executor,
             materializer,
             forkserver,
+            disk_action_cache,
+            worker_pool,
             scribe_sink,
             hash_all_commands,
             disk_state_options,
@@ -506,6 +586,8 @@ impl DaemonState {
             file_watcher: data.file_watcher.dupe(),
             events: dispatcher,
             forkserver: data.forkserver.dupe(),
+            disk_action_cache: data.disk_action_cache.dupe(),
+            worker_pool: data.worker_pool.dupe(),
             hash_all_commands: data.hash_all_commands,
             _drop_guard: drop_guard,
             daemon_start_time: data.start_time,
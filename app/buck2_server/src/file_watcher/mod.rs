@@ -12,6 +12,7 @@ use std::sync::Arc;
 
 use allocative::Allocative;
 use async_trait::async_trait;
+use buck2_common::daemon_dir::DaemonDir;
 use buck2_common::file_ops::IgnoreSet;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_core::cells::CellName;
@@ -21,15 +22,25 @@ use buck2_core::is_open_source;
 use dice::DiceTransaction;
 
 use crate::file_watcher::notify::NotifyFileWatcher;
+use crate::file_watcher::poll::PollFileWatcher;
 use crate::file_watcher::watchman::interface::WatchmanFileWatcher;
 
 mod notify;
+mod poll;
 mod stats;
 mod watchman;
 
 #[async_trait]
 pub trait FileWatcher: Allocative + Send + Sync + 'static {
     async fn sync(&self, dice: DiceTransaction) -> anyhow::Result<DiceTransaction>;
+
+    /// Identifies the state of the files that the next sync reports changes since, if a watcher
+    /// in a later daemon could pick up from there. At startup, that's the state the previous
+    /// daemon left. DICE snapshots are only written and restored under such a token, which only
+    /// the `poll` watcher has: `notify` and `watchman` can't tell what changed between daemons.
+    async fn snapshot_token(&self) -> Option<String> {
+        None
+    }
 }
 
 impl dyn FileWatcher {
@@ -37,6 +48,7 @@ impl dyn FileWatcher {
     /// startup and shouldn't be doing any work that could warrant suspending.
     pub fn new(
         project_root: &ProjectRoot,
+        daemon_dir: &DaemonDir,
         root_config: &LegacyBuckConfig,
         cells: CellResolver,
         ignore_specs: HashMap<CellName, IgnoreSet>,
@@ -59,6 +71,12 @@ impl dyn FileWatcher {
                 cells,
                 ignore_specs,
             )?)),
+            "poll" => Ok(Arc::new(PollFileWatcher::new(
+                project_root,
+                daemon_dir,
+                cells,
+                ignore_specs,
+            )?)),
             other => Err(anyhow::anyhow!("Invalid buck2.file_watcher: {}", other)),
         }
     }
@@ -9,10 +9,13 @@
 
 use std::collections::HashMap;
 use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use allocative::Allocative;
+use anyhow::Context;
 use async_trait::async_trait;
 use buck2_common::dice::file_ops::FileChangeTracker;
 use buck2_common::file_ops::IgnoreSet;
@@ -26,6 +29,7 @@ use buck2_core::fs::project::ProjectRoot;
 use buck2_events::dispatch::span_async;
 use dice::DiceTransaction;
 use dupe::Dupe;
+use itertools::Itertools;
 use notify::event::CreateKind;
 use notify::event::MetadataKind;
 use notify::event::ModifyKind;
@@ -34,6 +38,7 @@ use notify::EventKind;
 use notify::RecommendedWatcher;
 use notify::Watcher;
 use tracing::info;
+use tracing::warn;
 
 use crate::file_watcher::stats::FileWatcherStats;
 use crate::file_watcher::FileWatcher;
@@ -82,6 +87,10 @@ impl ChangeType {
 struct NotifyFileData {
     ignored: u64,
     events: OrderedSet<(CellPath, ChangeType)>,
+    /// Cells that had changes we lost track of, e.g. because the inotify queue overflowed.
+    lost_cells: OrderedSet<CellName>,
+    /// Why we lost track of changes.
+    lost_reasons: OrderedSet<String>,
 }
 
 impl NotifyFileData {
@@ -89,6 +98,8 @@ impl NotifyFileData {
         Self {
             ignored: 0,
             events: OrderedSet::new(),
+            lost_cells: OrderedSet::new(),
+            lost_reasons: OrderedSet::new(),
         }
     }
 
@@ -98,45 +109,106 @@ impl NotifyFileData {
         root: &ProjectRoot,
         cells: &CellResolver,
         ignore_specs: &HashMap<CellName, IgnoreSet>,
-    ) -> anyhow::Result<()> {
-        let event = event?;
+    ) {
+        let event = match event {
+            Ok(event) if event.need_rescan() => {
+                self.lost(
+                    "Watcher queue overflowed".to_owned(),
+                    &event.paths,
+                    root,
+                    cells,
+                );
+                return;
+            }
+            Ok(event) => event,
+            Err(e) => {
+                self.lost(e.to_string(), &e.paths, root, cells);
+                return;
+            }
+        };
+
         let change_type = ChangeType::new(event.kind);
-        for path in event.paths {
-            // Testing shows that we get absolute paths back from the `notify` library.
-            // It's not documented though.
-            let path = root.relativize(AbsNormPath::new(&path)?)?;
-
-            // We ignore the buck-out prefix, as those are uninteresting events caused by us.
-            // We also ignore other buck-out directories, as if you have two isolation dirs running at once, they are not interesting.
-            // We do this in the notify-watcher, rather than a generic layer, as watchman users should configure
-            // to ignore buck-out, to reduce the number of events, rather than hiding them later.
-            if path.starts_with(InvocationPaths::buck_out_dir_prefix()) {
-                // We don't want to event add them as ignored events, since they are super common
-                // and very boring
-                continue;
+        for path in &event.paths {
+            if let Err(e) = self.process_path(path, change_type, root, cells, ignore_specs) {
+                self.lost(format!("{:#}", e), std::slice::from_ref(path), root, cells);
             }
+        }
+    }
 
-            let cell_path = cells.get_cell_path(&path)?;
-            let ignore = ignore_specs
-                .get(cell_path.cell())
-                .expect("unexpected cell name mismatch")
-                .is_match(cell_path.path());
+    fn process_path(
+        &mut self,
+        path: &Path,
+        change_type: ChangeType,
+        root: &ProjectRoot,
+        cells: &CellResolver,
+        ignore_specs: &HashMap<CellName, IgnoreSet>,
+    ) -> anyhow::Result<()> {
+        // Testing shows that we get absolute paths back from the `notify` library.
+        // It's not documented though.
+        let path = root.relativize(AbsNormPath::new(path)?)?;
 
-            info!(
-                "FileWatcher: {:?} {:?} (ignore = {})",
-                path, change_type, ignore
-            );
+        // We ignore the buck-out prefix, as those are uninteresting events caused by us.
+        // We also ignore other buck-out directories, as if you have two isolation dirs running at once, they are not interesting.
+        // We do this in the notify-watcher, rather than a generic layer, as watchman users should configure
+        // to ignore buck-out, to reduce the number of events, rather than hiding them later.
+        if path.starts_with(InvocationPaths::buck_out_dir_prefix()) {
+            // We don't want to event add them as ignored events, since they are super common
+            // and very boring
+            return Ok(());
+        }
 
-            if ignore || change_type == ChangeType::None {
-                self.ignored += 1;
-            } else {
-                self.events.insert((cell_path, change_type));
-            }
+        let cell_path = cells.get_cell_path(&path)?;
+        let ignore = ignore_specs
+            .get(cell_path.cell())
+            .expect("unexpected cell name mismatch")
+            .is_match(cell_path.path());
+
+        info!(
+            "FileWatcher: {:?} {:?} (ignore = {})",
+            path, change_type, ignore
+        );
+
+        if ignore || change_type == ChangeType::None {
+            self.ignored += 1;
+        } else {
+            self.events.insert((cell_path, change_type));
         }
         Ok(())
     }
 
-    fn sync(self) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
+    /// Records that changes under `paths` were missed. If we can't tell which cells those are in,
+    /// then all of them are affected.
+    fn lost(
+        &mut self,
+        reason: String,
+        paths: &[PathBuf],
+        root: &ProjectRoot,
+        cells: &CellResolver,
+    ) {
+        warn!("FileWatcher: lost events: {}", reason);
+        self.lost_reasons.insert(reason);
+
+        let lost_cells = paths
+            .iter()
+            .map(|path| {
+                let path = root.relativize(AbsNormPath::new(path)?)?;
+                Ok(cells.get_cell_path(&path)?.cell().clone())
+            })
+            .collect::<anyhow::Result<Vec<_>>>();
+        let lost_cells = match lost_cells {
+            Ok(lost_cells) if !lost_cells.is_empty() => lost_cells,
+            _ => cells.cells().map(|(name, _)| name.clone()).collect(),
+        };
+        for cell in lost_cells {
+            self.lost_cells.insert(cell);
+        }
+    }
+
+    fn has_lost_events(&self) -> bool {
+        !self.lost_cells.is_empty()
+    }
+
+    fn sync(self, dice: &DiceTransaction) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
         // The changes that go into the DICE transaction
         let mut changed = FileChangeTracker::new();
         // The files that were changed for accumulating the stats
@@ -171,6 +243,21 @@ impl NotifyFileData {
             );
         }
 
+        if self.has_lost_events() {
+            // We don't know which files changed, so everything we've read from these cells has to
+            // be read again.
+            for cell in &self.lost_cells {
+                changed.cell_changed(dice, cell);
+            }
+            let cells: Vec<String> = self.lost_cells.iter().map(|c| c.to_string()).collect();
+            let reason = format!(
+                "Lost file changes ({}), invalidated cells: {}",
+                self.lost_reasons.iter().join(", "),
+                cells.join(", ")
+            );
+            stats.add_rescanned_cells(cells, reason);
+        }
+
         (stats.finish(), changed)
     }
 }
@@ -178,8 +265,9 @@ impl NotifyFileData {
 #[derive(Allocative)]
 pub struct NotifyFileWatcher {
     #[allocative(skip)]
-    watcher: RecommendedWatcher,
-    data: Arc<Mutex<anyhow::Result<NotifyFileData>>>,
+    watcher: Mutex<RecommendedWatcher>,
+    root: ProjectRoot,
+    data: Arc<Mutex<NotifyFileData>>,
 }
 
 impl NotifyFileWatcher {
@@ -188,28 +276,47 @@ impl NotifyFileWatcher {
         cells: CellResolver,
         ignore_specs: HashMap<CellName, IgnoreSet>,
     ) -> anyhow::Result<Self> {
-        let data = Arc::new(Mutex::new(Ok(NotifyFileData::new())));
+        let data = Arc::new(Mutex::new(NotifyFileData::new()));
         let data2 = data.dupe();
         let root2 = root.dupe();
         let mut watcher = notify::recommended_watcher(move |event| {
-            let mut guard = data2.lock().unwrap();
-            if let Ok(state) = &mut *guard {
-                if let Err(e) = state.process(event, &root2, &cells, &ignore_specs) {
-                    *guard = Err(e);
-                }
-            }
+            data2
+                .lock()
+                .unwrap()
+                .process(event, &root2, &cells, &ignore_specs);
         })?;
         watcher.watch(root.root().as_path(), notify::RecursiveMode::Recursive)?;
-        Ok(Self { watcher, data })
+        Ok(Self {
+            watcher: Mutex::new(watcher),
+            root: root.dupe(),
+            data,
+        })
+    }
+
+    /// Watches the project again from scratch. Directories created while events were being lost
+    /// may not have been watched, so we can't rely on the existing watches any more.
+    fn rewatch(&self) -> anyhow::Result<()> {
+        let mut watcher = self.watcher.lock().unwrap();
+        let root = self.root.root().as_path();
+        // This fails if the root itself stopped being watched, which is fine.
+        let _ignored = watcher.unwatch(root);
+        watcher
+            .watch(root, notify::RecursiveMode::Recursive)
+            .context("Failed to watch the project again after losing file changes")?;
+        Ok(())
     }
 
     fn sync2(
         &self,
         dice: DiceTransaction,
     ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransaction)> {
-        let mut guard = self.data.lock().unwrap();
-        let old = mem::replace(&mut *guard, Ok(NotifyFileData::new()));
-        let (stats, changes) = old?.sync();
+        let old = mem::replace(&mut *self.data.lock().unwrap(), NotifyFileData::new());
+        if old.has_lost_events() {
+            // Changes between taking the data and watching again are covered by invalidating
+            // the cells below.
+            self.rewatch()?;
+        }
+        let (stats, changes) = old.sync(&dice);
         changes.write_to_dice(&dice)?;
         Ok((stats, dice))
     }
@@ -233,3 +340,72 @@ impl FileWatcher for NotifyFileWatcher {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::project::ProjectRelativePathBuf;
+    use notify::event::Flag;
+
+    use super::*;
+
+    fn cells() -> (ProjectRoot, CellResolver, HashMap<CellName, IgnoreSet>) {
+        let root = if cfg!(windows) { "C:\\repo" } else { "/repo" };
+        let root = ProjectRoot::new(AbsNormPathBuf::try_from(root.to_owned()).unwrap());
+        let names = [("root", ""), ("other", "other")];
+        let cells = CellResolver::of_names_and_paths(&names.map(|(name, path)| {
+            (
+                CellName::unchecked_new(name.to_owned()),
+                CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new(path.to_owned())),
+            )
+        }));
+        let ignores = names
+            .iter()
+            .map(|(name, _)| {
+                (
+                    CellName::unchecked_new((*name).to_owned()),
+                    IgnoreSet::from_ignore_spec("").unwrap(),
+                )
+            })
+            .collect();
+        (root, cells, ignores)
+    }
+
+    fn lost_cells(data: &NotifyFileData) -> Vec<String> {
+        let mut cells: Vec<_> = data.lost_cells.iter().map(|c| c.to_string()).collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn test_overflow_loses_all_cells() {
+        let (root, cells, ignores) = cells();
+        let mut data = NotifyFileData::new();
+        data.process(
+            Ok(notify::Event::new(EventKind::Other).set_flag(Flag::Rescan)),
+            &root,
+            &cells,
+            &ignores,
+        );
+        assert!(data.has_lost_events());
+        assert_eq!(vec!["other", "root"], lost_cells(&data));
+        assert!(data.events.is_empty());
+    }
+
+    #[test]
+    fn test_error_loses_cell_of_path() {
+        let (root, cells, ignores) = cells();
+        let mut data = NotifyFileData::new();
+        let path = root.root().as_path().join("other").join("file.txt");
+        data.process(
+            Err(notify::Error::generic("boom").add_path(path)),
+            &root,
+            &cells,
+            &ignores,
+        );
+        assert_eq!(vec!["other"], lost_cells(&data));
+        assert_eq!(1, data.lost_reasons.len());
+    }
+}
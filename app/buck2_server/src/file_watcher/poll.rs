@@ -0,0 +1,667 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A file watcher that doesn't watch: on every command it scans the project and compares the
+//! metadata of every path with what it was on the previous command. That's slower than the other
+//! watchers, but it works wherever the files can be read, e.g. on network filesystems, or in
+//! containers where inotify is unreliable and watchman isn't available.
+//!
+//! The metadata is also written to the daemon directory after every scan that found changes, so a
+//! restarted daemon compares its first scan with the last one of the previous daemon. That makes
+//! this the one watcher under which a DICE snapshot can be restored.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use allocative::Allocative;
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_common::daemon_dir::DaemonDir;
+use buck2_common::dice::file_ops::FileChangeTracker;
+use buck2_common::file_ops::IgnoreSet;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::CellName;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::project::ProjectRelativePath;
+use buck2_core::fs::project::ProjectRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_events::dispatch::span_async;
+use dice::DiceTransaction;
+use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+use crate::file_watcher::stats::FileWatcherStats;
+use crate::file_watcher::FileWatcher;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Dupe,
+    PartialEq,
+    Eq,
+    Allocative,
+    Serialize,
+    Deserialize
+)]
+enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+/// The metadata of a path that we compare between scans. If any of it differs, the path changed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Dupe,
+    PartialEq,
+    Eq,
+    Allocative,
+    Serialize,
+    Deserialize
+)]
+struct EntryState {
+    kind: EntryKind,
+    mtime_nanos: u128,
+    size: u64,
+    inode: u64,
+}
+
+impl EntryState {
+    fn new(meta: &std::fs::Metadata) -> Self {
+        let file_type = meta.file_type();
+        let kind = if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        };
+
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(meta);
+        #[cfg(not(unix))]
+        let inode = 0;
+
+        Self {
+            kind,
+            mtime_nanos: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos()),
+            size: meta.len(),
+            inode,
+        }
+    }
+}
+
+/// The state of every path of the project that isn't ignored.
+type Snapshot = HashMap<CellPath, EntryState>;
+
+/// A `Snapshot` as written to disk. Paths are relative to the project rather than to cells, so
+/// the file still makes sense if the cells are reconfigured between daemons.
+#[derive(Serialize, Deserialize)]
+struct PersistedSnapshot {
+    /// Unique to every write, so that the DICE snapshot can tell which of them it goes with.
+    id: String,
+    entries: Vec<(String, EntryState)>,
+}
+
+struct Scanner {
+    root: ProjectRoot,
+    cells: CellResolver,
+    ignore_specs: HashMap<CellName, IgnoreSet>,
+}
+
+impl Scanner {
+    /// The cell path of a path in the project, or `None` if it's ignored.
+    fn cell_path(&self, path: &Path) -> anyhow::Result<Option<CellPath>> {
+        let path = self.root.relativize(AbsNormPath::new(path)?)?;
+        self.cell_path_of_relative(&path)
+    }
+
+    fn cell_path_of_relative(
+        &self,
+        path: &ProjectRelativePath,
+    ) -> anyhow::Result<Option<CellPath>> {
+        // Like the notify watcher, we never look at buck-out, since we are the ones changing it.
+        if path.starts_with(InvocationPaths::buck_out_dir_prefix()) {
+            return Ok(None);
+        }
+        let cell_path = self.cells.get_cell_path(path)?;
+        let ignored = self
+            .ignore_specs
+            .get(cell_path.cell())
+            .expect("unexpected cell name mismatch")
+            .is_match(cell_path.path());
+        Ok(if ignored { None } else { Some(cell_path) })
+    }
+
+    /// Scans the whole project, with a thread for each top-level directory.
+    async fn scan(self: &Arc<Self>) -> anyhow::Result<Snapshot> {
+        let mut snapshot = Snapshot::new();
+        let mut subtrees = Vec::new();
+
+        for entry in fs_util::read_dir(self.root.root())? {
+            let entry = entry?;
+            let path = entry.path().into_path_buf();
+            let cell_path = match self.cell_path(&path)? {
+                Some(cell_path) => cell_path,
+                None => continue,
+            };
+            let meta = match fs_util::symlink_metadata_if_exists(&path)? {
+                Some(meta) => meta,
+                // Deleted while we were scanning. The next scan will see it's gone.
+                None => continue,
+            };
+            let state = EntryState::new(&meta);
+            snapshot.insert(cell_path, state);
+            if state.kind == EntryKind::Dir {
+                let scanner = self.dupe();
+                subtrees.push(tokio::task::spawn_blocking(move || {
+                    scanner.scan_subtree(&path)
+                }));
+            }
+        }
+
+        for subtree in futures::future::join_all(subtrees).await {
+            snapshot.extend(subtree.context("Scanning task panicked")??);
+        }
+        Ok(snapshot)
+    }
+
+    /// The states of the paths under `dir`, not including `dir` itself.
+    fn scan_subtree(&self, dir: &Path) -> anyhow::Result<Vec<(CellPath, EntryState)>> {
+        let mut res = Vec::new();
+        let mut walk = WalkDir::new(dir)
+            .min_depth(1)
+            .follow_links(false)
+            .into_iter();
+        while let Some(entry) = walk.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => {
+                    return Err(anyhow::Error::from(e))
+                        .with_context(|| format!("Failed to scan `{}`", dir.display()));
+                }
+            };
+            let cell_path = match self.cell_path(entry.path())? {
+                Some(cell_path) => cell_path,
+                None => {
+                    if entry.file_type().is_dir() {
+                        walk.skip_current_dir();
+                    }
+                    continue;
+                }
+            };
+            let meta = match entry.metadata() {
+                Ok(meta) => meta,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => {
+                    return Err(anyhow::Error::from(e)).with_context(|| {
+                        format!("Failed to read metadata of `{}`", entry.path().display())
+                    });
+                }
+            };
+            res.push((cell_path, EntryState::new(&meta)));
+        }
+        Ok(res)
+    }
+
+    /// Reads a snapshot written by `persist` and its id, or `None` if there's none. Paths that are
+    /// ignored now are left out, and paths that aren't anymore will show up as created.
+    fn load(&self, path: &AbsNormPath) -> anyhow::Result<Option<(String, Snapshot)>> {
+        let data = match fs_util::read(path) {
+            Ok(data) => data,
+            Err(_) if !fs_util::try_exists(path)? => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let persisted: PersistedSnapshot = bincode::deserialize(&data)
+            .with_context(|| format!("Failed to deserialize `{}`", path))?;
+        let mut snapshot = Snapshot::with_capacity(persisted.entries.len());
+        for (path, state) in persisted.entries {
+            let path = ProjectRelativePathBuf::try_from(path)?;
+            if let Some(cell_path) = self.cell_path_of_relative(&path)? {
+                snapshot.insert(cell_path, state);
+            }
+        }
+        Ok(Some((persisted.id, snapshot)))
+    }
+
+    /// Writes the snapshot to `path`, replacing it atomically, so that a daemon killed halfway
+    /// leaves the previous snapshot behind rather than a truncated one.
+    fn persist(&self, path: &AbsNormPath, id: String, snapshot: &Snapshot) -> anyhow::Result<()> {
+        let persisted = PersistedSnapshot {
+            id,
+            entries: snapshot
+                .iter()
+                .map(|(cell_path, state)| {
+                    Ok((self.cells.resolve_path(cell_path)?.to_string(), *state))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+        let data = bincode::serialize(&persisted).context("Failed to serialize the snapshot")?;
+        let tmp = format!("{}.tmp", path);
+        fs_util::write(&tmp, data)?;
+        fs_util::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn is_not_found(e: &walkdir::Error) -> bool {
+    e.io_error()
+        .map_or(false, |e| e.kind() == io::ErrorKind::NotFound)
+}
+
+fn watcher_kind(kind: EntryKind) -> buck2_data::FileWatcherKind {
+    match kind {
+        EntryKind::File => buck2_data::FileWatcherKind::File,
+        EntryKind::Dir => buck2_data::FileWatcherKind::Directory,
+        EntryKind::Symlink => buck2_data::FileWatcherKind::Symlink,
+    }
+}
+
+type Event<'a> = (&'a CellPath, buck2_data::FileWatcherEventType, EntryKind);
+
+/// The events of the paths of `chunk`, which are from the `new` snapshot.
+fn diff_new<'a>(old: &Snapshot, chunk: &[(&'a CellPath, &EntryState)]) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    for (path, state) in chunk.iter().copied() {
+        match old.get(path) {
+            None => events.push((path, buck2_data::FileWatcherEventType::Create, state.kind)),
+            Some(old_state) if old_state.kind != state.kind => {
+                events.push((
+                    path,
+                    buck2_data::FileWatcherEventType::Delete,
+                    old_state.kind,
+                ));
+                events.push((path, buck2_data::FileWatcherEventType::Create, state.kind));
+            }
+            // A directory's metadata changes when its entries do, and we see those separately.
+            Some(_) if state.kind == EntryKind::Dir => {}
+            Some(old_state) if old_state != state => {
+                events.push((path, buck2_data::FileWatcherEventType::Modify, state.kind))
+            }
+            Some(_) => {}
+        }
+    }
+    events
+}
+
+/// The deletions of the paths of `chunk`, which are from the `old` snapshot.
+fn diff_old<'a>(new: &Snapshot, chunk: &[(&'a CellPath, &EntryState)]) -> Vec<Event<'a>> {
+    chunk
+        .iter()
+        .filter(|(path, _)| !new.contains_key(*path))
+        .map(|(path, state)| (*path, buck2_data::FileWatcherEventType::Delete, state.kind))
+        .collect()
+}
+
+/// The changes between two scans, to go into DICE. Both snapshots are split in chunks that are
+/// compared on a thread each, since a project can have millions of paths.
+fn diff(old: &Snapshot, new: &Snapshot) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
+    let threads = num_cpus::get();
+    let chunk_size = |len: usize| std::cmp::max(1, (len + threads - 1) / threads);
+    let new_entries: Vec<_> = new.iter().collect();
+    let old_entries: Vec<_> = old.iter().collect();
+
+    let mut events: Vec<Event> = std::thread::scope(|s| {
+        let new_chunks = new_entries
+            .chunks(chunk_size(new_entries.len()))
+            .map(|chunk| s.spawn(move || diff_new(old, chunk)));
+        let old_chunks = old_entries
+            .chunks(chunk_size(old_entries.len()))
+            .map(|chunk| s.spawn(move || diff_old(new, chunk)));
+        // Collect the handles first, so the threads all run before we join any.
+        let handles: Vec<_> = new_chunks.chain(old_chunks).collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("diff thread panicked"))
+            .collect()
+    });
+    // Sorting is stable, so a path changing kind is still deleted before it's created.
+    events.sort_by(|(x, ..), (y, ..)| x.cmp(y));
+
+    let mut changed = FileChangeTracker::new();
+    let mut stats = FileWatcherStats::new(events.len(), None);
+    for (path, event, kind) in events {
+        match (event, kind) {
+            (buck2_data::FileWatcherEventType::Modify, _) => changed.file_changed(path.clone()),
+            (_, EntryKind::Dir) => changed.dir_added_or_removed(path.clone()),
+            (_, EntryKind::File | EntryKind::Symlink) => {
+                changed.file_added_or_removed(path.clone())
+            }
+        }
+        stats.add(path.to_string(), event, watcher_kind(kind));
+    }
+
+    (stats.finish(), changed)
+}
+
+/// What the next sync compares its scan with.
+#[derive(Default)]
+struct Baseline {
+    /// Whether we looked for the snapshot of the previous daemon yet.
+    loaded: bool,
+    /// The state of the project at the last sync, or the one the previous daemon left before
+    /// that. It's kept for the lifetime of the daemon, like the DICE state it describes.
+    snapshot: Option<Arc<Snapshot>>,
+    /// The id `snapshot` is persisted under, if it is.
+    persisted_id: Option<String>,
+}
+
+#[derive(Allocative)]
+pub(crate) struct PollFileWatcher {
+    #[allocative(skip)]
+    scanner: Arc<Scanner>,
+    /// Where the snapshot is persisted for the next daemon.
+    #[allocative(skip)]
+    snapshot_path: AbsNormPathBuf,
+    #[allocative(skip)]
+    baseline: tokio::sync::Mutex<Baseline>,
+}
+
+impl PollFileWatcher {
+    pub(crate) fn new(
+        root: &ProjectRoot,
+        daemon_dir: &DaemonDir,
+        cells: CellResolver,
+        ignore_specs: HashMap<CellName, IgnoreSet>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            scanner: Arc::new(Scanner {
+                root: root.dupe(),
+                cells,
+                ignore_specs,
+            }),
+            snapshot_path: daemon_dir.poll_file_watcher_snapshot(),
+            baseline: tokio::sync::Mutex::new(Baseline::default()),
+        })
+    }
+
+    /// The snapshot of the previous daemon and its id, if it left one we can read.
+    async fn load_snapshot(&self) -> Option<(String, Snapshot)> {
+        let scanner = self.scanner.dupe();
+        let path = self.snapshot_path.clone();
+        let loaded = tokio::task::spawn_blocking(move || scanner.load(&path))
+            .await
+            .context("Loading task panicked")
+            .and_then(|r| r);
+        match loaded {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                // We can always start over from a fresh scan.
+                warn!("Ignoring the previous poll file watcher snapshot: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Persists the snapshot under a new id, returning it, or `None` if that failed. That only
+    /// costs the next daemon a baseline scan, so it's not an error.
+    async fn persist_snapshot(&self, snapshot: Arc<Snapshot>) -> Option<String> {
+        let scanner = self.scanner.dupe();
+        let path = self.snapshot_path.clone();
+        let id = Uuid::new_v4().to_string();
+        let persisted = {
+            let id = id.clone();
+            tokio::task::spawn_blocking(move || scanner.persist(&path, id, &snapshot))
+        };
+        match persisted
+            .await
+            .context("Persisting task panicked")
+            .and_then(|r| r)
+        {
+            Ok(()) => Some(id),
+            Err(e) => {
+                warn!("Failed to persist the poll file watcher snapshot: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Locks the baseline, looking for the snapshot of the previous daemon the first time.
+    async fn lock_baseline(&self) -> tokio::sync::MutexGuard<'_, Baseline> {
+        let mut baseline = self.baseline.lock().await;
+        if !baseline.loaded {
+            baseline.loaded = true;
+            if let Some((id, snapshot)) = self.load_snapshot().await {
+                baseline.snapshot = Some(Arc::new(snapshot));
+                baseline.persisted_id = Some(id);
+            }
+        }
+        baseline
+    }
+
+    async fn sync2(
+        &self,
+        dice: DiceTransaction,
+    ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransaction)> {
+        // Held for the whole sync, so that concurrent commands each diff against the scan the
+        // other one made.
+        let mut baseline = self.lock_baseline().await;
+        let new = Arc::new(self.scanner.scan().await?);
+        let (stats, changes) = match baseline.snapshot.dupe() {
+            Some(old) => {
+                let new = new.dupe();
+                tokio::task::spawn_blocking(move || diff(&old, &new))
+                    .await
+                    .context("Diffing task panicked")?
+            }
+            // Nothing has been computed from the files yet, so this scan is just the baseline.
+            None => (
+                FileWatcherStats::new(0, None).finish(),
+                FileChangeTracker::new(),
+            ),
+        };
+        changes.write_to_dice(&dice)?;
+
+        if baseline.persisted_id.is_none() || stats.events_total != 0 {
+            baseline.persisted_id = self.persist_snapshot(new.dupe()).await;
+        }
+        baseline.snapshot = Some(new);
+        Ok((stats, dice))
+    }
+}
+
+#[async_trait]
+impl FileWatcher for PollFileWatcher {
+    async fn sync(&self, dice: DiceTransaction) -> anyhow::Result<DiceTransaction> {
+        span_async(
+            buck2_data::FileWatcherStart {
+                provider: buck2_data::FileWatcherProvider::Poll as i32,
+            },
+            async {
+                let (stats, res) = match self.sync2(dice).await {
+                    Ok((stats, dice)) => ((Some(stats)), Ok(dice)),
+                    Err(e) => (None, Err(e)),
+                };
+                (res, buck2_data::FileWatcherEnd { stats })
+            },
+        )
+        .await
+    }
+
+    async fn snapshot_token(&self) -> Option<String> {
+        self.lock_baseline().await.persisted_id.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::project::ProjectRelativePathBuf;
+
+    use super::*;
+
+    fn file(mtime_nanos: u128, size: u64) -> EntryState {
+        EntryState {
+            kind: EntryKind::File,
+            mtime_nanos,
+            size,
+            inode: 1,
+        }
+    }
+
+    fn dir(mtime_nanos: u128) -> EntryState {
+        EntryState {
+            kind: EntryKind::Dir,
+            mtime_nanos,
+            size: 0,
+            inode: 2,
+        }
+    }
+
+    fn snapshot(entries: &[(&str, EntryState)]) -> Snapshot {
+        entries
+            .iter()
+            .map(|(path, state)| (CellPath::testing_new("root", path), *state))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(&[
+            ("src", dir(1)),
+            ("src/a.txt", file(1, 10)),
+            ("src/b.txt", file(1, 10)),
+            ("src/c.txt", file(1, 10)),
+            ("src/d", file(1, 10)),
+        ]);
+        let new = snapshot(&[
+            ("src", dir(2)),
+            ("src/a.txt", file(1, 10)),
+            ("src/b.txt", file(2, 10)),
+            ("src/d", dir(2)),
+            ("src/e.txt", file(2, 10)),
+        ]);
+
+        let (stats, _) = diff(&old, &new);
+        let events: Vec<_> = stats
+            .events
+            .iter()
+            .map(|e| {
+                (
+                    e.path.as_str(),
+                    buck2_data::FileWatcherEventType::from_i32(e.event).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("root//src/b.txt", buck2_data::FileWatcherEventType::Modify),
+                ("root//src/c.txt", buck2_data::FileWatcherEventType::Delete),
+                ("root//src/d", buck2_data::FileWatcherEventType::Delete),
+                ("root//src/d", buck2_data::FileWatcherEventType::Create),
+                ("root//src/e.txt", buck2_data::FileWatcherEventType::Create),
+            ],
+            events
+        );
+    }
+
+    /// A scanner of a single `root` cell at `dir`, ignoring `ignored`.
+    fn scanner(dir: &Path) -> anyhow::Result<Arc<Scanner>> {
+        let root = ProjectRoot::new(AbsNormPathBuf::try_from(dir.to_owned())?);
+        let cell = CellName::unchecked_new("root".to_owned());
+        Ok(Arc::new(Scanner {
+            root,
+            cells: CellResolver::of_names_and_paths(&[(
+                cell.clone(),
+                CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new(String::new())),
+            )]),
+            ignore_specs: HashMap::from_iter([(cell, IgnoreSet::from_ignore_spec("ignored")?)]),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_scan() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let scanner = scanner(tempdir.path())?;
+
+        fs_util::create_dir_all(tempdir.path().join("src/nested"))?;
+        fs_util::create_dir_all(tempdir.path().join("ignored"))?;
+        fs_util::create_dir_all(tempdir.path().join("buck-out/v2"))?;
+        fs_util::write(tempdir.path().join("src/nested/a.txt"), "a")?;
+        fs_util::write(tempdir.path().join("ignored/b.txt"), "b")?;
+        fs_util::write(tempdir.path().join("buck-out/v2/c.txt"), "c")?;
+
+        let mut paths: Vec<_> = scanner
+            .scan()
+            .await?
+            .into_keys()
+            .map(|p| p.to_string())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            vec!["root//src", "root//src/nested", "root//src/nested/a.txt"],
+            paths
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_and_load() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let scanner = scanner(tempdir.path())?;
+        let path = AbsNormPathBuf::try_from(tempdir.path().join("snapshot"))?;
+        assert_eq!(None, scanner.load(&path)?);
+
+        let old = snapshot(&[
+            ("src", dir(1)),
+            ("src/a.txt", file(1, 10)),
+            ("ignored/b.txt", file(1, 10)),
+        ]);
+        scanner.persist(&path, "id".to_owned(), &old)?;
+        // Paths that are ignored now are dropped.
+        assert_eq!(
+            Some((
+                "id".to_owned(),
+                snapshot(&[("src", dir(1)), ("src/a.txt", file(1, 10))])
+            )),
+            scanner.load(&path)?
+        );
+
+        // A corrupt snapshot is an error, which the watcher ignores.
+        fs_util::write(&path, "garbage")?;
+        assert!(scanner.load(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_many_chunks() {
+        let old = snapshot(&[]);
+        let paths: Vec<_> = (0..1000).map(|i| format!("src/{:04}.txt", i)).collect();
+        let entries: Vec<_> = paths.iter().map(|p| (p.as_str(), file(1, 10))).collect();
+        let new = snapshot(&entries);
+
+        let (stats, _) = diff(&old, &new);
+        assert_eq!(1000, stats.events_total);
+        let (stats, _) = diff(&new, &old);
+        assert_eq!(1000, stats.events_total);
+        let (stats, _) = diff(&new, &new);
+        assert_eq!(0, stats.events_total);
+    }
+}
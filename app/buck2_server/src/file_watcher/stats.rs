@@ -23,6 +23,8 @@ pub(crate) struct FileWatcherStats {
     changes: Vec<buck2_data::FileWatcherEvent>,
     // Did we not insert things into changes
     changes_missed: bool,
+    // Why changes were lost, if they were
+    lost_events_reason: Option<String>,
 }
 
 impl FileWatcherStats {
@@ -38,6 +40,7 @@ impl FileWatcherStats {
             stats,
             changes,
             changes_missed: false,
+            lost_events_reason: None,
         }
     }
 
@@ -67,15 +70,24 @@ impl FileWatcherStats {
         }
     }
 
+    /// I lost track of changes, so I invalidated everything in these cells
+    pub(crate) fn add_rescanned_cells(&mut self, cells: Vec<String>, reason: String) {
+        self.stats.rescanned_cells = cells;
+        self.lost_events_reason = Some(reason);
+    }
+
     pub(crate) fn finish(self) -> buck2_data::FileWatcherStats {
         let Self {
             mut stats,
             changes,
             changes_missed,
+            lost_events_reason,
         } = self;
 
         stats.events = changes;
-        if changes_missed {
+        if let Some(reason) = lost_events_reason {
+            stats.incomplete_events_reason = Some(reason);
+        } else if changes_missed {
             let reason = format!(
                 "Too many files changed ({}, max {})",
                 stats.events_processed, MAX_FILE_CHANGE_RECORDS
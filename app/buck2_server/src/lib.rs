@@ -28,3 +28,4 @@ mod materialize;
 pub mod profile;
 mod snapshot;
 mod streaming_request_handler;
+mod why_recomputed;
@@ -13,6 +13,7 @@ use std::io;
 use std::io::ErrorKind;
 use std::path::Path;
 
+use anyhow::Context as _;
 use buck2_cli_proto::*;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::file_ops::HasFileOps;
@@ -69,6 +70,7 @@ use tokio::runtime::Handle;
 use tokio::sync::Mutex;
 use tokio::sync::MutexGuard;
 use tonic::Status;
+use walkdir::WalkDir;
 
 use crate::builtin_docs::docs::get_builtin_docs;
 use crate::builtin_docs::docs::get_prelude_docs;
@@ -151,6 +153,8 @@ struct DocsCache {
     global_urls: HashMap<String, LspUrl>,
     /// Mapping of starlark: urls to a synthesized starlark representation.
     native_starlark_files: HashMap<LspUrl, String>,
+    /// The documentation for all of the global symbols.
+    global_docs: Vec<Doc>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -252,6 +256,7 @@ impl DocsCache {
         Ok(Self {
             global_urls,
             native_starlark_files,
+            global_docs: builtin_symbols.to_vec(),
         })
     }
 
@@ -264,6 +269,10 @@ impl DocsCache {
     fn url_for_symbol(&self, symbol: &str) -> Option<&LspUrl> {
         self.global_urls.get(symbol)
     }
+
+    fn global_docs(&self) -> &[Doc] {
+        &self.global_docs
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -546,12 +555,96 @@ impl BuckLspContext {
         }
     }
 
+    /// Find the starlark files under `roots`: the build files, and `.bzl` and `.bxl` files.
+    /// Only the parts of `roots` inside the project are searched, skipping `buck-out`, hidden
+    /// directories and the directories ignored by `project.ignore`. The search happens on a
+    /// blocking thread, since it walks the file system.
+    async fn starlark_files(
+        &self,
+        roots: &[LspUrl],
+        dice_ctx: &DiceTransaction,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        let cell_resolver = dice_ctx.get_cell_resolver().await?;
+        let fs = self.fs.clone();
+        let roots = roots.to_vec();
+        let dice_ctx = dice_ctx.dupe();
+        let runtime = Handle::current();
+        tokio::task::spawn_blocking(move || {
+            walk_starlark_files(&fs, &roots, &cell_resolver, &dice_ctx, &runtime)
+        })
+        .await
+        .context("Listing the starlark files panicked")?
+    }
+
     fn find_target(ast: &AstModule, target: TargetName) -> Option<Range> {
         ast.find_function_call_with_name(target.value())
             .map(Range::from)
     }
 }
 
+/// The walk behind `BuckLspContext::starlark_files`, which checks ignored directories with
+/// `runtime`.
+fn walk_starlark_files(
+    fs: &ProjectRoot,
+    roots: &[LspUrl],
+    cell_resolver: &CellResolver,
+    dice_ctx: &DiceTransaction,
+    runtime: &Handle,
+) -> anyhow::Result<Vec<LspUrl>> {
+    let file_ops = dice_ctx.file_ops();
+    let project_root = fs.root();
+
+    let mut files = Vec::new();
+    for root in roots {
+        let root = match root {
+            LspUrl::File(root) => match AbsNormPath::new(root) {
+                Ok(root) => root,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        let root = if root.starts_with(project_root) {
+            root
+        } else if project_root.starts_with(root) {
+            project_root
+        } else {
+            continue;
+        };
+
+        let mut entries = WalkDir::new(root.as_path()).into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let cell_path =
+                cell_resolver.get_cell_path(&fs.relativize(AbsNormPath::new(entry.path())?)?)?;
+            let name = entry.file_name().to_string_lossy();
+            if entry.file_type().is_dir() {
+                if entry.depth() > 0
+                    && (name.starts_with('.')
+                        || name == "buck-out"
+                        || runtime.block_on(file_ops.is_ignored(&cell_path))?)
+                {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+            let is_starlark = name.ends_with(".bzl")
+                || name.ends_with(".bxl")
+                || cell_resolver
+                    .get(cell_path.cell())?
+                    .buildfiles()
+                    .iter()
+                    .any(|buildfile| buildfile.as_str() == name);
+            if entry.file_type().is_file() && is_starlark {
+                files.push(Url::from_file_path(entry.path()).unwrap().try_into()?);
+            }
+        }
+    }
+    Ok(files)
+}
+
 impl LspContext for BuckLspContext {
     fn parse_file_with_contents(&self, uri: &LspUrl, content: String) -> LspEvalResult {
         let dispatcher = self.server_ctx.events().dupe();
@@ -708,6 +801,30 @@ impl LspContext for BuckLspContext {
                 Ok(docs_cache.url_for_symbol(symbol).cloned())
             }))
     }
+
+    fn get_global_symbols(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<Doc>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                let docs_cache = self
+                    .with_dice_ctx(|dice_ctx| async {
+                        self.docs_cache_manager.get_cache(dice_ctx).await
+                    })
+                    .await?;
+                Ok(docs_cache.global_docs().to_vec())
+            }))
+    }
+
+    fn get_workspace_files(&self, workspace_roots: &[LspUrl]) -> anyhow::Result<Vec<LspUrl>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                self.with_dice_ctx(|dice_ctx| async move {
+                    self.starlark_files(workspace_roots, &dice_ctx).await
+                })
+                .await
+            }))
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
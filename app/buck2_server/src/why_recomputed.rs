@@ -0,0 +1,84 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::template::run_server_command;
+use buck2_server_ctx::template::ServerCommandTemplate;
+use dice::DiceTransaction;
+
+use crate::ctx::ServerCommandContext;
+
+pub(crate) async fn why_recomputed_command(
+    ctx: ServerCommandContext,
+    req: buck2_cli_proto::WhyRecomputedRequest,
+) -> anyhow::Result<buck2_cli_proto::GenericResponse> {
+    run_server_command(WhyRecomputedServerCommand { req }, box ctx).await
+}
+
+struct WhyRecomputedServerCommand {
+    req: buck2_cli_proto::WhyRecomputedRequest,
+}
+
+/// Whether a key, as displayed, is the requested one. Keys of configured targets display as the
+/// label followed by the configuration, so a target label matches them in every configuration.
+fn matches_key(requested: &str, key: &str) -> bool {
+    match key.strip_prefix(requested) {
+        Some(rest) => rest.is_empty() || rest.starts_with(" ("),
+        None => false,
+    }
+}
+
+#[async_trait]
+impl ServerCommandTemplate for WhyRecomputedServerCommand {
+    type StartEvent = buck2_data::WhyRecomputedCommandStart;
+    type EndEvent = buck2_data::WhyRecomputedCommandEnd;
+    type Response = buck2_cli_proto::GenericResponse;
+
+    async fn command<'v>(
+        &self,
+        server_ctx: &'v dyn ServerCommandContextTrait,
+        ctx: DiceTransaction,
+    ) -> anyhow::Result<Self::Response> {
+        let requested = self.req.key.trim();
+        let explanations = ctx.why_recomputed(|key| matches_key(requested, key));
+        if explanations.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No key matching `{}` is in the DICE graph",
+                requested
+            ));
+        }
+
+        let mut stdout = server_ctx.stdout()?;
+        for explanation in explanations {
+            write!(stdout, "{}", explanation)?;
+        }
+        Ok(buck2_cli_proto::GenericResponse {})
+    }
+
+    fn is_success(&self, _response: &Self::Response) -> bool {
+        // No response if we failed.
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_key() {
+        assert!(matches_key("root//foo:bar", "root//foo:bar"));
+        assert!(matches_key("root//foo:bar", "root//foo:bar (cfg//:linux)"));
+        assert!(!matches_key("root//foo:bar", "root//foo:bar_baz"));
+        assert!(!matches_key("root//foo:bar", "root//foo"));
+    }
+}
@@ -321,6 +321,20 @@ impl ConcurrencyHandler {
         &self.dice
     }
 
+    /// Runs `f` if no command is running, and keeps any from starting until it's done. Commands
+    /// update and commit dice while holding the same lock, so `f` sees the state the last one left.
+    #[allow(clippy::await_holding_lock)]
+    // see `wait_for_others`.
+    pub async fn when_idle<Fut: Future>(&self, f: Fut) -> Option<Fut::Output> {
+        let data = self.data.lock();
+        if !data.active_traces.is_empty() {
+            return None;
+        }
+        let res = f.await;
+        drop(data);
+        Some(res)
+    }
+
     fn determine_bypass_semaphore(
         &self,
         is_same_state: bool,
@@ -878,4 +892,30 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn when_idle_only_runs_without_commands() -> anyhow::Result<()> {
+        let dice = Dice::builder().build(DetectCycles::Enabled);
+
+        let concurrency =
+            ConcurrencyHandler::new(dice, NestedInvocation::Run, ParallelInvocation::Run);
+
+        assert_eq!(Some(1), concurrency.when_idle(async { 1 }).await);
+
+        let during = concurrency
+            .enter(
+                EventDispatcher::null_sink_with_trace(TraceId::new()),
+                box TestDiceDataProvider,
+                &NoChanges,
+                |_| concurrency.when_idle(async { 2 }),
+                false,
+                Vec::new(),
+            )
+            .await?;
+        assert_eq!(None, during);
+
+        assert_eq!(Some(3), concurrency.when_idle(async { 3 }).await);
+
+        Ok(())
+    }
 }
@@ -18,6 +18,7 @@ use buck2_build_api::calculation::Calculation;
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollection;
 use buck2_build_api::interpreter::rule_defs::provider::test_provider::TestProvider;
+use buck2_build_api::keep_going;
 use buck2_cli_proto::TestRequest;
 use buck2_cli_proto::TestResponse;
 use buck2_common::dice::cells::HasCellResolver;
@@ -57,6 +58,7 @@ use futures::channel::mpsc;
 use futures::future;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
+use futures::future::TryFutureExt;
 use futures::stream::FuturesUnordered;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
@@ -75,8 +77,12 @@ use crate::orchestrator::BuckTestOrchestrator;
 use crate::orchestrator::TestResultOrExitCode;
 use crate::session::TestSession;
 use crate::session::TestSessionOptions;
+use crate::shard::TestShard;
 use crate::translations::build_configured_target_handle;
 
+/// The name of the test executor binary built from `buck2_test_runner`.
+const DEFAULT_TEST_EXECUTOR: &str = "buck2_test_runner";
+
 #[derive(Debug, Serialize)]
 pub(crate) struct TestReport {
     project_root: AbsNormPathBuf,
@@ -159,6 +165,7 @@ struct TestStatuses {
     fatals: CounterWithExamples,
     listing_success: CounterWithExamples,
     listing_failed: CounterWithExamples,
+    build_failed: CounterWithExamples,
 }
 impl TestStatuses {
     fn ingest(&mut self, result: &TestResult) {
@@ -213,6 +220,25 @@ impl ServerCommandTemplate for TestServerCommand {
     }
 }
 
+/// The test executor that ships next to the Buck binary, used when `test.v2_test_executor` isn't
+/// set.
+fn default_test_executor() -> anyhow::Result<String> {
+    let exe = std::env::current_exe().context("Failed to find the Buck binary")?;
+    let runner = exe.with_file_name(format!(
+        "{}{}",
+        DEFAULT_TEST_EXECUTOR,
+        std::env::consts::EXE_SUFFIX
+    ));
+    if !runner.exists() {
+        return Err(anyhow::anyhow!(
+            "test.v2_test_executor is not set in configuration, and the bundled `{}` was not found next to `{}`",
+            DEFAULT_TEST_EXECUTOR,
+            exe.display()
+        ));
+    }
+    Ok(runner.to_string_lossy().into_owned())
+}
+
 async fn test(
     server_ctx: &dyn ServerCommandContextTrait,
     ctx: DiceTransaction,
@@ -226,12 +252,13 @@ async fn test(
 
     // Get the test runner from the config. Note that we use a different key from v1 since the API
     // is completely different, so there is not expectation that the same binary works for both.
-    let test_executor = ctx
+    let test_executor = match ctx
         .get_legacy_config_property(cell_resolver.root_cell(), "test", "v2_test_executor")
         .await?
-        .context("test.v2_test_executor must be set in configuration")?
-        .as_ref()
-        .to_owned();
+    {
+        Some(test_executor) => test_executor.as_ref().to_owned(),
+        None => default_test_executor()?,
+    };
 
     let parsed_patterns = parse_patterns_from_cli_args(
         &request.target_patterns,
@@ -262,7 +289,14 @@ async fn test(
         allow_re: options.allow_re,
         force_use_project_relative_paths: options.force_use_project_relative_paths,
         force_run_from_project_root: options.force_run_from_project_root,
+        keep_going: request.keep_going,
+        fail_fast: request.fail_fast,
+        max_failures: request.max_failures,
     });
+    let session = match &request.shard {
+        Some(shard) => session.with_shard(TestShard::from_proto(shard)?),
+        None => session,
+    };
 
     let test_outcome = test_targets(
         &ctx,
@@ -327,6 +361,13 @@ async fn test(
                 .listing_failed
                 .to_cli_proto_counter(),
         ),
+        build_failed: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .build_failed
+                .to_cli_proto_counter(),
+        ),
     };
 
     Ok(TestResponse {
@@ -422,7 +463,7 @@ async fn test_targets(
 
                 // Wait for the tests to finish running.
 
-                let test_statuses = test_status_receiver
+                let mut test_statuses = test_status_receiver
                     .try_fold(ExecutorReport::default(), |mut acc, result| {
                         acc.ingest(&result);
                         future::ready(Ok(acc))
@@ -440,6 +481,13 @@ async fn test_targets(
 
                 // And finally return our results;
 
+                for label in &driver.build_failed {
+                    test_statuses.statuses.build_failed.add(label);
+                }
+                for label in &driver.skipped {
+                    test_statuses.statuses.skipped.add(label);
+                }
+
                 anyhow::Ok((driver.build_errors, test_statuses))
             })
         }
@@ -485,6 +533,14 @@ enum TestDriverTask {
     TestTargets {
         labels: Vec<ConfiguredProvidersLabel>,
     },
+    BuildFailed {
+        label: ConfiguredProvidersLabel,
+        error: anyhow::Error,
+    },
+    /// The target wasn't tested because `--max-failures` was reached.
+    Skipped {
+        label: ConfiguredProvidersLabel,
+    },
     Done,
 }
 
@@ -504,6 +560,10 @@ struct TestDriver<'a, 'e> {
     work: FuturesUnordered<BoxFuture<'a, anyhow::Result<TestDriverTask>>>,
     labels_seen: HashSet<ConfiguredProvidersLabel>,
     build_errors: Vec<String>,
+    /// The test targets that failed to build.
+    build_failed: Vec<String>,
+    /// The test targets that weren't tested because too many tests failed.
+    skipped: Vec<String>,
 }
 
 impl<'a, 'e> TestDriver<'a, 'e> {
@@ -513,6 +573,8 @@ impl<'a, 'e> TestDriver<'a, 'e> {
             work: FuturesUnordered::new(),
             labels_seen: HashSet::new(),
             build_errors: Vec::new(),
+            build_failed: Vec::new(),
+            skipped: Vec::new(),
         }
     }
 
@@ -542,17 +604,35 @@ impl<'a, 'e> TestDriver<'a, 'e> {
                 Ok(TestDriverTask::TestTargets { labels }) => {
                     self.test_targets(labels);
                 }
+                Ok(TestDriverTask::BuildFailed { label, error }) => {
+                    self.state.session.record_failure();
+                    self.build_failed.push(label.to_string());
+                    self.build_error(error);
+                }
+                Ok(TestDriverTask::Skipped { label }) => {
+                    self.skipped.push(label.to_string());
+                }
                 Ok(TestDriverTask::Done) => {
                     // Nothing to do here
                 }
                 Err(e) => {
-                    // TODO(brasselsprouts): filter out duplicate errors.
-                    self.build_errors.push(format!("{:#}", e));
+                    self.build_error(e);
                 }
             };
         }
     }
 
+    fn build_error(&mut self, error: anyhow::Error) {
+        // TODO(brasselsprouts): filter out duplicate errors.
+        self.build_errors.push(format!("{:#}", error));
+
+        if self.state.session.options().fail_fast {
+            // Stop here, without starting any of the remaining work. Tests already handed to the
+            // executor still run to completion.
+            self.work.clear();
+        }
+    }
+
     fn interpret_targets(&mut self, package: PackageLabel, spec: PackageSpec<ProvidersPattern>) {
         let state = self.state;
 
@@ -611,25 +691,28 @@ impl<'a, 'e> TestDriver<'a, 'e> {
 
     fn test_targets(&mut self, labels: Vec<ConfiguredProvidersLabel>) {
         self.work.extend(labels.into_iter().filter_map(|label| {
+            if !self.state.session.runs_target(&label) {
+                return None;
+            }
+
             if !self.labels_seen.insert(label.clone()) {
                 return None;
             }
 
-            let state = self.state;
+            if self.state.session.reached_max_failures() {
+                return Some(future::ready(anyhow::Ok(TestDriverTask::Skipped { label })).boxed());
+            }
 
-            let fut = async move {
-                test_target(
-                    state.ctx,
-                    label,
-                    state.test_executor.dupe(),
-                    state.session,
-                    state.label_filtering.dupe(),
-                    state.cell_resolver,
-                )
-                .await?;
+            let state = self.state;
 
-                anyhow::Ok(TestDriverTask::Done)
-            }
+            let fut = test_target(
+                state.ctx,
+                label,
+                state.test_executor.dupe(),
+                state.session,
+                state.label_filtering.dupe(),
+                state.cell_resolver,
+            )
             .boxed();
 
             Some(fut)
@@ -683,26 +766,44 @@ async fn test_target(
     session: &TestSession,
     label_filtering: Arc<TestLabelFiltering>,
     cell_resolver: &CellResolver,
-) -> anyhow::Result<Option<ConfiguredProvidersLabel>> {
+) -> anyhow::Result<TestDriverTask> {
     // NOTE: We fail if we hit an incompatible target here. This can happen if we reach an
     // incompatible target via `tests = [...]`. This should perhaps change, but that's how it works
     // in v1: https://fb.workplace.com/groups/buckeng/posts/8520953297953210
     let frozen_providers = ctx.get_providers(&target).await?.require_compatible()?;
     let providers = frozen_providers.provider_collection();
-    build_artifacts(ctx, providers, &label_filtering).await?;
+    // Only failures to build the test count as build failures, not failures to analyze it or to
+    // hand it to the executor.
+    if let Err(error) = build_artifacts(
+        ctx,
+        providers,
+        &label_filtering,
+        session.options().keep_going,
+    )
+    .await
+    {
+        return Ok(TestDriverTask::BuildFailed {
+            label: target,
+            error,
+        });
+    }
 
     let fut = match <dyn TestProvider>::from_collection(providers) {
         Some(test_info) => {
             if skip_run_based_on_labels(test_info, &label_filtering) {
-                return Ok(None);
+                return Ok(TestDriverTask::Done);
+            }
+            // Other tests failed while this one was building.
+            if session.reached_max_failures() {
+                return Ok(TestDriverTask::Skipped { label: target });
             }
             run_tests(test_executor, target, test_info, session, cell_resolver)
-                .map(|l| Some(l).transpose())
+                .map_ok(|_| TestDriverTask::Done)
                 .left_future()
         }
         None => {
             // not a test
-            future::ready(Ok(None)).right_future()
+            future::ready(Ok(TestDriverTask::Done)).right_future()
         }
     };
 
@@ -731,6 +832,7 @@ async fn build_artifacts(
     ctx: &DiceComputations,
     providers: &FrozenProviderCollection,
     label_filtering: &TestLabelFiltering,
+    keep_going: bool,
 ) -> anyhow::Result<()> {
     fn get_artifacts_to_build(
         label_filtering: &TestLabelFiltering,
@@ -753,15 +855,27 @@ async fn build_artifacts(
     }
     let artifacts_to_build = get_artifacts_to_build(label_filtering, providers)?;
     // build the test target first
-    let _ignored = future::join_all(
+    let res: Result<IndexMap<_, _>, Vec<anyhow::Error>> = keep_going::try_join_all_or_errors(
+        keep_going,
         artifacts_to_build
             .into_iter()
-            .map(|input| async { ctx.ensure_artifact_group(&input).await.map(|v| (input, v)) }),
+            .map(|input| async { ctx.ensure_artifact_group(&input).await.map(|v| (input, v)) })
+            .collect::<FuturesUnordered<_>>(),
     )
-    .await
-    .into_iter()
-    .collect::<Result<IndexMap<_, _>, _>>()?;
-    Ok(())
+    .await;
+    match res {
+        Ok(_) => Ok(()),
+        Err(mut errors) if errors.len() == 1 => Err(errors.pop().unwrap()),
+        Err(errors) => Err(anyhow::anyhow!(
+            "{} artifacts failed to build:\n{}",
+            errors.len(),
+            errors
+                .iter()
+                .map(|e| format!("{:#}", e))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )),
+    }
 }
 
 fn run_tests<'a, 'b>(
@@ -853,8 +967,102 @@ impl TestLabelFiltering {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::testing::CellResolverExt;
+    use buck2_core::cells::CellName;
+    use buck2_core::configuration::Configuration;
+    use buck2_core::provider::label::testing::ProvidersLabelTestExt;
+    use buck2_test_api::data::ExternalRunnerSpec;
+    use dice::testing::DiceBuilder;
+    use dice::UserComputationData;
+
+    use super::*;
     use crate::command::TestLabelFiltering;
 
+    struct NoExecutor;
+
+    #[async_trait]
+    impl TestExecutor for NoExecutor {
+        async fn external_runner_spec(&self, _: ExternalRunnerSpec) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn end_of_test_requests(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Drives a build failure along with some other work, returning whether that work ran.
+    async fn other_work_runs_after_build_failure(
+        options: TestSessionOptions,
+    ) -> anyhow::Result<bool> {
+        let dice = DiceBuilder::new().build(UserComputationData::new())?;
+        let label_filtering = Arc::new(TestLabelFiltering::new(
+            Vec::new(),
+            Vec::new(),
+            false,
+            false,
+        ));
+        let session = TestSession::new(options);
+        let test_executor: Arc<dyn TestExecutor> = Arc::new(NoExecutor);
+        let ran = AtomicBool::new(false);
+        let cell_resolver = CellResolver::of_names_and_paths(&[(
+            CellName::unchecked_new("cell".to_owned()),
+            CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell".to_owned())),
+        )]);
+        let mut driver = TestDriver::new(TestDriverState {
+            ctx: &dice,
+            label_filtering: &label_filtering,
+            global_target_platform: &None,
+            session: &session,
+            test_executor: &test_executor,
+            cell_resolver: &cell_resolver,
+        });
+
+        driver.work.push(
+            future::ready(Ok(TestDriverTask::BuildFailed {
+                label: ProvidersLabel::testing_new("cell", "pkg", "broken", None)
+                    .configure(Configuration::testing_new()),
+                error: anyhow::anyhow!("broken"),
+            }))
+            .boxed(),
+        );
+        driver.work.push(
+            async {
+                // Still pending when the build failure comes in.
+                tokio::task::yield_now().await;
+                ran.store(true, Ordering::SeqCst);
+                Ok(TestDriverTask::Done)
+            }
+            .boxed(),
+        );
+        driver.drive_to_completion().await;
+
+        assert_eq!(driver.build_failed, vec!["cell//pkg:broken (<testing>)"]);
+        Ok(ran.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn build_failures_keep_going_by_default() -> anyhow::Result<()> {
+        assert!(other_work_runs_after_build_failure(TestSessionOptions::default()).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn build_failures_stop_with_fail_fast() -> anyhow::Result<()> {
+        assert!(
+            !other_work_runs_after_build_failure(TestSessionOptions {
+                fail_fast: true,
+                ..Default::default()
+            })
+            .await?
+        );
+        Ok(())
+    }
+
     #[test]
     fn only_include_labels_in_includes() {
         let filter = TestLabelFiltering::new(
@@ -17,6 +17,7 @@ pub mod downward_api;
 pub mod executor_launcher;
 pub mod orchestrator;
 pub mod session;
+pub mod shard;
 pub(crate) mod tcp;
 pub mod translations;
 #[cfg(unix)]
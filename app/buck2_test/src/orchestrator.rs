@@ -13,6 +13,7 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::SystemTime;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
@@ -89,6 +90,7 @@ use buck2_test_api::data::ExternalRunnerSpecValue;
 use buck2_test_api::data::Output;
 use buck2_test_api::data::PrepareForLocalExecutionResult;
 use buck2_test_api::data::TestResult;
+use buck2_test_api::data::TestStatus;
 use buck2_test_api::protocol::TestOrchestrator;
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
@@ -196,6 +198,21 @@ impl TestOrchestrator for BuckTestOrchestrator {
     ) -> anyhow::Result<ExecutionResult2> {
         self.liveliness_observer.require_alive().await?;
 
+        if let DisplayMetadata::Testing { testcases, .. } = &metadata {
+            // Test cases of other shards are not run. Their results are dropped in
+            // `report_test_result` anyway.
+            if !self.session.runs_any_test(test_target, testcases) {
+                return Ok(ExecutionResult2 {
+                    status: ExecutionStatus::Skipped,
+                    stdout: ExecutionStream::Inline(Vec::new()),
+                    stderr: ExecutionStream::Inline(Vec::new()),
+                    outputs: HashMap::new(),
+                    start_time: SystemTime::now(),
+                    execution_time: Duration::ZERO,
+                });
+            }
+        }
+
         let test_target = self.session.get(test_target)?;
 
         let fs = self.dice.get_artifact_fs().await?;
@@ -291,8 +308,18 @@ impl TestOrchestrator for BuckTestOrchestrator {
     }
 
     async fn report_test_result(&self, r: TestResult) -> anyhow::Result<()> {
+        if !self.session.runs_test(r.target, &r.name) {
+            return Ok(());
+        }
+        if matches!(
+            r.status,
+            TestStatus::FAIL | TestStatus::FATAL | TestStatus::TIMEOUT
+        ) {
+            self.session.record_failure();
+        }
         let event = buck2_data::instant_event::Data::TestResult(translations::convert_test_result(
             r.clone(),
+            &self.session,
         )?);
         self.events.instant_event(event);
         self.results_channel
@@ -303,11 +330,14 @@ impl TestOrchestrator for BuckTestOrchestrator {
 
     async fn report_tests_discovered(
         &self,
-        // FIXME(bobyf): Should be using this
-        _target: ConfiguredTargetHandle,
+        target: ConfiguredTargetHandle,
         suite: String,
         names: Vec<String>,
     ) -> anyhow::Result<()> {
+        let names = names
+            .into_iter()
+            .filter(|name| self.session.runs_test(target, name))
+            .collect();
         self.events.instant_event(TestDiscovery {
             data: Some(buck2_data::test_discovery::Data::Tests(TestSuite {
                 suite_name: suite,
@@ -501,7 +531,8 @@ impl BuckTestOrchestrator {
                 timing,
                 outputs,
             ),
-            CommandExecutionStatus::Failure { .. } => (
+            CommandExecutionStatus::Failure { .. }
+            | CommandExecutionStatus::SandboxViolation { .. } => (
                 stdout,
                 stderr,
                 ExecutionStatus::Finished {
@@ -547,7 +578,12 @@ impl BuckTestOrchestrator {
         };
 
         let executor = self.dice.get_command_executor(fs, executor_config)?;
-        let executor = CommandExecutor::new(executor, fs.clone(), executor_config.path_separator);
+        let executor = CommandExecutor::new(
+            executor,
+            fs.clone(),
+            executor_config.path_separator,
+            executor_config.default_action_timeout,
+        );
         Ok(executor)
     }
 
@@ -968,7 +1004,6 @@ mod tests {
     use buck2_core::fs::project::ProjectRootTemp;
     use buck2_events::dispatch::EventDispatcher;
     use buck2_test_api::data::testing::ConfiguredTargetHandleExt;
-    use buck2_test_api::data::TestStatus;
     use dice::testing::DiceBuilder;
     use dice::UserComputationData;
     use futures::channel::mpsc;
@@ -977,8 +1012,11 @@ mod tests {
     use futures::stream::TryStreamExt;
 
     use super::*;
+    use crate::session::TestSessionOptions;
 
-    fn make() -> anyhow::Result<(
+    fn make(
+        options: TestSessionOptions,
+    ) -> anyhow::Result<(
         BuckTestOrchestrator,
         UnboundedReceiver<anyhow::Result<TestResultOrExitCode>>,
     )> {
@@ -1002,7 +1040,7 @@ mod tests {
         Ok((
             BuckTestOrchestrator::from_parts(
                 dice,
-                Arc::new(TestSession::new(Default::default())),
+                Arc::new(TestSession::new(options)),
                 NoopLivelinessObserver::create(),
                 sender,
                 EventDispatcher::null(),
@@ -1013,7 +1051,7 @@ mod tests {
 
     #[tokio::test]
     async fn orchestrator_results() -> anyhow::Result<()> {
-        let (orchestrator, channel) = make()?;
+        let (orchestrator, channel) = make(Default::default())?;
 
         let jobs = async {
             orchestrator
@@ -1073,9 +1111,44 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_orchestrator_reaches_max_failures() -> anyhow::Result<()> {
+        let (orchestrator, _channel) = make(TestSessionOptions {
+            max_failures: Some(2),
+            ..Default::default()
+        })?;
+
+        let result = |name: &str, status| TestResult {
+            target: ConfiguredTargetHandle::testing_new(0),
+            status,
+            msg: None,
+            name: name.to_owned(),
+            duration: None,
+            details: String::new(),
+        };
+
+        orchestrator
+            .report_test_result(result("a", TestStatus::FAIL))
+            .await?;
+        orchestrator
+            .report_test_result(result("b", TestStatus::PASS))
+            .await?;
+        orchestrator
+            .report_test_result(result("c", TestStatus::SKIP))
+            .await?;
+        assert!(!orchestrator.session.reached_max_failures());
+
+        orchestrator
+            .report_test_result(result("d", TestStatus::TIMEOUT))
+            .await?;
+        assert!(orchestrator.session.reached_max_failures());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_orchestrator_channel_drop() -> anyhow::Result<()> {
-        let (orchestrator, channel) = make()?;
+        let (orchestrator, channel) = make(Default::default())?;
         drop(orchestrator);
 
         let res = channel.try_collect::<Vec<_>>().await;
@@ -1086,7 +1159,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_orchestrator_closes_channel() -> anyhow::Result<()> {
-        let (orchestrator, channel) = make()?;
+        let (orchestrator, channel) = make(Default::default())?;
         let sender = orchestrator.results_channel.clone();
         orchestrator.end_of_test_results(1).await?;
 
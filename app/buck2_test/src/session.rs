@@ -17,14 +17,24 @@ use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_test_api::data::ConfiguredTargetHandle;
 use chrono::Local;
 use dashmap::DashMap;
+use dashmap::DashSet;
 use dupe::Dupe;
 
+use crate::shard::ShardBy;
+use crate::shard::TestShard;
+
 #[derive(Debug, Clone, Copy, Dupe, Default)]
 pub struct TestSessionOptions {
     /// Whether this session should allow things to run on RE.
     pub allow_re: bool,
     pub force_use_project_relative_paths: bool,
     pub force_run_from_project_root: bool,
+    /// Whether to keep building the other artifacts of a test target when one of them fails.
+    pub keep_going: bool,
+    /// Whether to stop testing the other targets when a test target fails to build.
+    pub fail_fast: bool,
+    /// Stop starting new tests once this many tests or test targets have failed.
+    pub max_failures: Option<u64>,
 }
 
 /// The state of a buck2 test command.
@@ -41,6 +51,13 @@ pub struct TestSession {
     /// Options overriding the behavior of tests executed in this session. This is primarily
     /// intended for unstable or debugging features.
     options: TestSessionOptions,
+    /// How many tests and test targets have failed so far.
+    failures: AtomicU64,
+    /// The part of the tests this session runs, if it doesn't run all of them.
+    shard: Option<TestShard>,
+    /// The targets whose test cases weren't listed when executing them, so they couldn't be split
+    /// by test case.
+    unsplit: DashSet<ConfiguredTargetHandle>,
 }
 
 impl TestSession {
@@ -57,6 +74,17 @@ impl TestSession {
             labels: DashMap::new(),
             prefix,
             options,
+            failures: AtomicU64::new(0),
+            shard: None,
+            unsplit: DashSet::new(),
+        }
+    }
+
+    /// Only run the tests in `shard`.
+    pub fn with_shard(self, shard: TestShard) -> Self {
+        Self {
+            shard: Some(shard),
+            ..self
         }
     }
 
@@ -68,6 +96,60 @@ impl TestSession {
         self.prefix.as_ref()
     }
 
+    /// Record that a test, or the build of a test target, failed.
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether enough failures were recorded that no new tests should be started.
+    pub fn reached_max_failures(&self) -> bool {
+        match self.options.max_failures {
+            Some(max) => self.failures.load(Ordering::Relaxed) >= max,
+            None => false,
+        }
+    }
+
+    /// Whether the tests of this target are run, as far as sharding is concerned.
+    pub fn runs_target(&self, label: &ConfiguredProvidersLabel) -> bool {
+        match &self.shard {
+            Some(shard) => shard.contains_target(label.target().unconfigured()),
+            None => true,
+        }
+    }
+
+    /// Whether the test `name` of this target is run, as far as sharding is concerned.
+    pub fn runs_test(&self, id: ConfiguredTargetHandle, name: &str) -> bool {
+        match (&self.shard, self.labels.get(&id)) {
+            (Some(shard), Some(label)) if self.unsplit.contains(&id) => {
+                shard.contains_unsplit_target(label.target().unconfigured())
+            }
+            (Some(shard), Some(label)) => shard.contains_test(label.target().unconfigured(), name),
+            _ => true,
+        }
+    }
+
+    /// Whether any of the tests `names` of this target, which the test executor is about to run,
+    /// are run as far as sharding is concerned. If the executor didn't list them, the target is
+    /// run as a whole by one shard.
+    pub fn runs_any_test(&self, id: ConfiguredTargetHandle, names: &[String]) -> bool {
+        if !names.is_empty() {
+            return names.iter().any(|name| self.runs_test(id, name));
+        }
+        match (&self.shard, self.labels.get(&id)) {
+            (Some(shard), Some(label)) => {
+                if shard.by() == ShardBy::TestCase && self.unsplit.insert(id) {
+                    tracing::warn!(
+                        "Running the tests of `{}` in a single shard: the test executor doesn't \
+                        list their test cases, so they can't be sharded by test case",
+                        *label
+                    );
+                }
+                shard.contains_unsplit_target(label.target().unconfigured())
+            }
+            _ => true,
+        }
+    }
+
     /// Insert a new provider and retrieve the matching handle.
     pub fn register(&self, label: ConfiguredProvidersLabel) -> ConfiguredTargetHandle {
         let id = self.next_id.fetch_add(1, Ordering::Relaxed).into();
@@ -0,0 +1,185 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Splitting the tests of a test command across machines. Every machine is given the same
+//! inputs, so each of them decides on its own which tests are its share.
+
+use std::collections::HashMap;
+
+use buck2_core::target::TargetLabel;
+use buck2_test_api::data::test_case_key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardBy {
+    /// All the tests of a target run in the same shard, and targets of other shards aren't built.
+    Target,
+    /// Test cases are split individually. Every shard builds all the test targets. The test cases
+    /// of a target whose test executor doesn't list them, like the bundled runner, can't be
+    /// split, so such targets are split as a whole.
+    TestCase,
+}
+
+#[derive(Debug)]
+pub struct TestShard {
+    index: u32,
+    count: u32,
+    by: ShardBy,
+    /// The shards of the targets or test cases that ran before, picked to balance their durations.
+    assignments: HashMap<String, u32>,
+}
+
+impl TestShard {
+    /// `weights` are the durations of targets, or of test cases as keyed by `test_case_key`.
+    pub fn new(
+        index: u32,
+        count: u32,
+        by: ShardBy,
+        weights: &HashMap<String, u64>,
+    ) -> anyhow::Result<Self> {
+        if index >= count {
+            return Err(anyhow::anyhow!(
+                "Shard index {} must be less than the shard count {}",
+                index,
+                count
+            ));
+        }
+        Ok(Self {
+            index,
+            count,
+            by,
+            assignments: balance(count, weights),
+        })
+    }
+
+    pub fn from_proto(shard: &buck2_cli_proto::TestShard) -> anyhow::Result<Self> {
+        Self::new(
+            shard.index,
+            shard.count,
+            if shard.by_test_case {
+                ShardBy::TestCase
+            } else {
+                ShardBy::Target
+            },
+            &shard.weights_ms,
+        )
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        let shard = match self.assignments.get(key) {
+            Some(shard) => *shard,
+            None => {
+                let hash = blake3::hash(key.as_bytes());
+                let bytes: [u8; 8] = hash.as_bytes()[..8].try_into().unwrap();
+                (u64::from_le_bytes(bytes) % u64::from(self.count)) as u32
+            }
+        };
+        shard == self.index
+    }
+
+    pub fn contains_target(&self, target: &TargetLabel) -> bool {
+        match self.by {
+            ShardBy::Target => self.contains(&target.to_string()),
+            ShardBy::TestCase => true,
+        }
+    }
+
+    pub fn contains_test(&self, target: &TargetLabel, name: &str) -> bool {
+        match self.by {
+            ShardBy::Target => true,
+            ShardBy::TestCase => self.contains(&test_case_key(&target.to_string(), name)),
+        }
+    }
+
+    /// Whether the tests of `target` run here when they can't be split by test case.
+    pub fn contains_unsplit_target(&self, target: &TargetLabel) -> bool {
+        match self.by {
+            ShardBy::Target => true,
+            ShardBy::TestCase => self.contains(&target.to_string()),
+        }
+    }
+
+    pub fn by(&self) -> ShardBy {
+        self.by
+    }
+}
+
+/// Assigns the heaviest keys first, each to the shard with the least weight so far.
+fn balance(count: u32, weights: &HashMap<String, u64>) -> HashMap<String, u32> {
+    let mut weights: Vec<_> = weights.iter().collect();
+    // Break ties by key so every machine comes up with the same assignments.
+    weights.sort_by(|(a_key, a), (b_key, b)| b.cmp(a).then_with(|| a_key.cmp(b_key)));
+
+    let mut loads = vec![0u64; count as usize];
+    weights
+        .into_iter()
+        .map(|(key, weight)| {
+            let (shard, _) = loads
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, load)| **load)
+                .unwrap();
+            loads[shard] += weight;
+            (key.clone(), shard as u32)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::target::testing::TargetLabelExt;
+
+    use super::*;
+
+    #[test]
+    fn test_balance() {
+        let weights = HashMap::from_iter(
+            [("a", 10), ("b", 6), ("c", 5), ("d", 4), ("e", 1)].map(|(k, w)| (k.to_owned(), w)),
+        );
+        let assignments = balance(2, &weights);
+        let shard = |k: &str| assignments[k];
+        assert_eq!(0, shard("a"));
+        assert_eq!(1, shard("b"));
+        assert_eq!(1, shard("c"));
+        assert_eq!(0, shard("d"));
+        assert_eq!(1, shard("e"));
+    }
+
+    #[test]
+    fn test_shards_partition_keys() -> anyhow::Result<()> {
+        let shards = (0..3)
+            .map(|i| TestShard::new(i, 3, ShardBy::TestCase, &HashMap::new()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for i in 0..100 {
+            let key = format!("root//foo:test_{}", i);
+            assert_eq!(1, shards.iter().filter(|s| s.contains(&key)).count());
+        }
+        assert!(TestShard::new(3, 3, ShardBy::Target, &HashMap::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsplit_targets_run_in_one_shard() -> anyhow::Result<()> {
+        let target = TargetLabel::testing_parse("root//foo:test");
+        let shards = |by| {
+            (0..3)
+                .map(|i| TestShard::new(i, 3, by, &HashMap::new()))
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        let count = |shards: Vec<TestShard>| {
+            shards
+                .iter()
+                .filter(|s| s.contains_unsplit_target(&target))
+                .count()
+        };
+        assert_eq!(1, count(shards(ShardBy::TestCase)?));
+        // Only the shard of the target built it in the first place.
+        assert_eq!(3, count(shards(ShardBy::Target)?));
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@
 use anyhow::Context;
 use buck2_core::cells::CellResolver;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_data::ToProtoMessage;
 use buck2_test_api::data::ConfiguredTarget;
 
 use crate::session::TestSession;
@@ -51,14 +52,15 @@ fn test_target_name(target: &ConfiguredProvidersLabel) -> String {
 
 pub fn convert_test_result(
     test_result: buck2_test_api::data::TestResult,
+    session: &TestSession,
 ) -> anyhow::Result<buck2_data::TestResult> {
     let buck2_test_api::data::TestResult {
+        target,
         name,
         status,
         msg,
         duration,
         details,
-        ..
     } = test_result;
     Ok(buck2_data::TestResult {
         name,
@@ -66,5 +68,11 @@ pub fn convert_test_result(
         msg: msg.map(|msg| buck2_data::test_result::OptionalMsg { msg }),
         duration: duration.and_then(|d| d.try_into().ok()),
         details,
+        // The label is only informational, so a result for a target this session doesn't know
+        // about is still reported.
+        target_label: session
+            .get(target)
+            .ok()
+            .map(|label| label.target().as_proto()),
     })
 }
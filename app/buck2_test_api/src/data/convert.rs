@@ -106,6 +106,7 @@ impl TryFrom<buck2_test_proto::ExecutionStatus> for ExecutionStatus {
             Status::TimedOut(duration) => Self::TimedOut {
                 duration: convert::to_std_duration(duration)?,
             },
+            Status::Skipped(buck2_test_proto::Empty {}) => Self::Skipped,
         })
     }
 }
@@ -119,6 +120,7 @@ impl TryInto<buck2_test_proto::ExecutionStatus> for ExecutionStatus {
         let status = match self {
             Self::Finished { exitcode } => Status::Finished(exitcode),
             Self::TimedOut { duration } => Status::TimedOut(duration.try_into()?),
+            Self::Skipped => Status::Skipped(buck2_test_proto::Empty {}),
         };
 
         Ok(buck2_test_proto::ExecutionStatus {
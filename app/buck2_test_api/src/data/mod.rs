@@ -72,8 +72,14 @@ impl Debug for ExecutionStream {
 
 #[derive(Clone, Debug, Dupe, PartialEq)]
 pub enum ExecutionStatus {
-    Finished { exitcode: i32 },
-    TimedOut { duration: Duration },
+    Finished {
+        exitcode: i32,
+    },
+    TimedOut {
+        duration: Duration,
+    },
+    /// Not run, because the tests belong to another shard.
+    Skipped,
 }
 
 /// The result of running a test
@@ -222,6 +228,12 @@ pub struct PrepareForLocalExecutionResult {
     pub cwd: AbsNormPathBuf,
 }
 
+/// How a test case is identified in test durations and when sharding test cases: its target
+/// and name, separated by a space.
+pub fn test_case_key(target: &str, name: &str) -> String {
+    format!("{} {}", target, name)
+}
+
 pub mod testing {
     use crate::data::ConfiguredTargetHandle;
 
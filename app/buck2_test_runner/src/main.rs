@@ -0,0 +1,165 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A test executor for `buck2 test` that runs every test target as a single test.
+//!
+//! Buck launches it with the two ends of the test protocol (see `buck2_test_api`), either as
+//! inherited file descriptors or as TCP addresses to connect to, followed by `--` and the
+//! arguments given after `--` to `buck2 test`, which configure the runner.
+
+#![cfg_attr(feature = "gazebo_lint", feature(plugin))]
+#![cfg_attr(feature = "gazebo_lint", allow(deprecated))] // :(
+#![cfg_attr(feature = "gazebo_lint", plugin(gazebo_lint))]
+
+mod runner;
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use buck2_test_api::grpc::spawn_executor_server;
+use buck2_test_api::grpc::TestOrchestratorClient;
+use buck2_test_api::protocol::TestOrchestrator;
+use clap::Parser;
+use dupe::Dupe;
+use tokio::sync::mpsc;
+
+use crate::runner::Runner;
+use crate::runner::RunnerOptions;
+
+/// The exit code reported to Buck when any test did not pass.
+const TESTS_FAILED_EXIT_CODE: i32 = 32;
+
+#[derive(Parser)]
+struct Opt {
+    #[clap(long, help = "File descriptor to serve the executor API on")]
+    executor_fd: Option<i32>,
+
+    #[clap(long, help = "File descriptor to reach the orchestrator API on")]
+    orchestrator_fd: Option<i32>,
+
+    #[clap(
+        long,
+        conflicts_with = "executor-fd",
+        help = "Address to serve the executor API on"
+    )]
+    executor_addr: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with = "orchestrator-fd",
+        help = "Address to reach the orchestrator API on"
+    )]
+    orchestrator_addr: Option<String>,
+
+    #[clap(last = true)]
+    runner_args: Vec<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Opt {
+        executor_fd,
+        orchestrator_fd,
+        executor_addr,
+        orchestrator_addr,
+        runner_args,
+    } = Opt::parse();
+
+    // Buck passes a placeholder where the executable name would be.
+    let options = RunnerOptions::try_parse_from(runner_args)?;
+
+    let (tasks_sender, mut tasks) = mpsc::unbounded_channel();
+
+    let (orchestrator, server) = match (
+        executor_fd,
+        orchestrator_fd,
+        executor_addr,
+        orchestrator_addr,
+    ) {
+        (Some(executor_fd), Some(orchestrator_fd), None, None) => {
+            let executor_io = unix_stream(executor_fd).context("Invalid `--executor-fd`")?;
+            let orchestrator_io =
+                unix_stream(orchestrator_fd).context("Invalid `--orchestrator-fd`")?;
+
+            let orchestrator = connect_orchestrator(orchestrator_io).await?;
+            let server = spawn_executor_server(
+                executor_io,
+                Runner::new(orchestrator.dupe(), options, tasks_sender)?,
+            );
+            (orchestrator, server)
+        }
+        (None, None, Some(executor_addr), Some(orchestrator_addr)) => {
+            let (executor_io, orchestrator_io) = tokio::try_join!(
+                tokio::net::TcpStream::connect(&executor_addr),
+                tokio::net::TcpStream::connect(&orchestrator_addr),
+            )
+            .context("Failed to connect to Buck")?;
+
+            let orchestrator = connect_orchestrator(orchestrator_io).await?;
+            let server = spawn_executor_server(
+                executor_io,
+                Runner::new(orchestrator.dupe(), options, tasks_sender)?,
+            );
+            (orchestrator, server)
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Expected either `--executor-fd` and `--orchestrator-fd`, or `--executor-addr` and `--orchestrator-addr`"
+            ));
+        }
+    };
+
+    // The runner hands over a task per test target, until Buck reports there are no more.
+    let mut all_passed = true;
+    while let Some(task) = tasks.recv().await {
+        all_passed &= task.await.context("Test task panicked")??;
+    }
+
+    let exit_code = if all_passed {
+        0
+    } else {
+        TESTS_FAILED_EXIT_CODE
+    };
+    orchestrator
+        .end_of_test_results(exit_code)
+        .await
+        .context("Failed to report end of test results")?;
+
+    server
+        .shutdown()
+        .await
+        .context("Failed to shutdown executor server")?;
+
+    Ok(())
+}
+
+async fn connect_orchestrator<T>(io: T) -> anyhow::Result<Arc<dyn TestOrchestrator>>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + Sync + Unpin + 'static,
+{
+    let client = TestOrchestratorClient::new(io)
+        .await
+        .context("Failed to connect to orchestrator")?;
+    Ok(Arc::new(client))
+}
+
+#[cfg(unix)]
+fn unix_stream(fd: i32) -> anyhow::Result<tokio::net::UnixStream> {
+    use std::os::unix::io::FromRawFd;
+
+    // SAFETY: Buck hands this descriptor over to us and doesn't use it in this process.
+    let stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+    stream.set_nonblocking(true)?;
+    Ok(tokio::net::UnixStream::from_std(stream)?)
+}
+
+#[cfg(not(unix))]
+fn unix_stream(_fd: i32) -> anyhow::Result<tokio::net::TcpStream> {
+    Err(anyhow::anyhow!("Passing file descriptors is UNIX only"))
+}
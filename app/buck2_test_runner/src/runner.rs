@@ -0,0 +1,355 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use buck2_test_api::data::ArgValue;
+use buck2_test_api::data::ArgValueContent;
+use buck2_test_api::data::DisplayMetadata;
+use buck2_test_api::data::ExecutionResult2;
+use buck2_test_api::data::ExecutionStatus;
+use buck2_test_api::data::ExecutionStream;
+use buck2_test_api::data::ExternalRunnerSpec;
+use buck2_test_api::data::ExternalRunnerSpecValue;
+use buck2_test_api::data::TestResult;
+use buck2_test_api::data::TestStatus;
+use buck2_test_api::protocol::TestExecutor;
+use buck2_test_api::protocol::TestOrchestrator;
+use clap::Parser;
+use dupe::Dupe;
+use host_sharing::HostSharingRequirements;
+use parking_lot::Mutex;
+use regex::RegexSet;
+use tokio::sync::mpsc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// A test target being run, which resolves to whether it passed.
+pub(crate) type TestTask = JoinHandle<anyhow::Result<bool>>;
+
+/// Options given after `--` to `buck2 test`.
+#[derive(Parser, Debug)]
+pub(crate) struct RunnerOptions {
+    /// Passed by Buck for compatibility with other executors.
+    #[clap(long, hide = true)]
+    #[allow(unused)]
+    buck_test_info: Option<String>,
+
+    #[clap(
+        long,
+        short = 'j',
+        help = "How many tests to run concurrently [default: the number of CPUs]"
+    )]
+    jobs: Option<usize>,
+
+    #[clap(
+        long,
+        default_value = "600",
+        help = "Seconds after which a test is killed and reported as timed out"
+    )]
+    timeout: u64,
+
+    #[clap(
+        long,
+        default_value = "0",
+        help = "How many times to rerun a test that failed or timed out"
+    )]
+    retries: usize,
+
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        help = "Only run tests whose name matches one of these regexes"
+    )]
+    filter: Vec<String>,
+
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        help = "Don't run tests whose name matches one of these regexes"
+    )]
+    exclude: Vec<String>,
+}
+
+/// Which tests to run, by name.
+struct NameFilter {
+    include: Option<RegexSet>,
+    exclude: Option<RegexSet>,
+}
+
+impl NameFilter {
+    fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        let set = |patterns: &[String]| -> anyhow::Result<Option<RegexSet>> {
+            if patterns.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(RegexSet::new(patterns)?))
+            }
+        };
+        Ok(Self {
+            include: set(include).context("Invalid `--filter`")?,
+            exclude: set(exclude).context("Invalid `--exclude`")?,
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.include.as_ref().map_or(true, |s| s.is_match(name))
+            && !self.exclude.as_ref().map_or(false, |s| s.is_match(name))
+    }
+}
+
+struct RunnerConfig {
+    timeout: Duration,
+    retries: usize,
+    filter: NameFilter,
+}
+
+/// Runs each test target as one test: its command is run through the orchestrator, and the test
+/// passes if that exits successfully.
+pub(crate) struct Runner {
+    orchestrator: Arc<dyn TestOrchestrator>,
+    config: Arc<RunnerConfig>,
+    jobs: Arc<Semaphore>,
+    /// Dropped once Buck reports there are no more tests, which closes the channel.
+    tasks: Mutex<Option<mpsc::UnboundedSender<TestTask>>>,
+}
+
+impl Runner {
+    pub(crate) fn new(
+        orchestrator: Arc<dyn TestOrchestrator>,
+        options: RunnerOptions,
+        tasks: mpsc::UnboundedSender<TestTask>,
+    ) -> anyhow::Result<Self> {
+        let RunnerOptions {
+            buck_test_info: _,
+            jobs,
+            timeout,
+            retries,
+            filter,
+            exclude,
+        } = options;
+
+        Ok(Self {
+            orchestrator,
+            config: Arc::new(RunnerConfig {
+                timeout: Duration::from_secs(timeout),
+                retries,
+                filter: NameFilter::new(&filter, &exclude)?,
+            }),
+            jobs: Arc::new(Semaphore::new(jobs.unwrap_or_else(num_cpus::get).max(1))),
+            tasks: Mutex::new(Some(tasks)),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TestExecutor for Runner {
+    async fn external_runner_spec(&self, spec: ExternalRunnerSpec) -> anyhow::Result<()> {
+        let task = tokio::spawn(run_test(
+            self.orchestrator.dupe(),
+            self.config.dupe(),
+            self.jobs.dupe(),
+            spec,
+        ));
+
+        self.tasks
+            .lock()
+            .as_ref()
+            .context("Received a test after the end of tests")?
+            .send(task)
+            .ok()
+            .context("Runner is shutting down")?;
+
+        Ok(())
+    }
+
+    async fn end_of_test_requests(&self) -> anyhow::Result<()> {
+        self.tasks.lock().take();
+        Ok(())
+    }
+}
+
+async fn run_test(
+    orchestrator: Arc<dyn TestOrchestrator>,
+    config: Arc<RunnerConfig>,
+    jobs: Arc<Semaphore>,
+    spec: ExternalRunnerSpec,
+) -> anyhow::Result<bool> {
+    let name = spec.target.name.clone();
+    let target = spec.target.handle;
+
+    if !config.filter.matches(&name) {
+        orchestrator
+            .report_test_result(TestResult {
+                target,
+                name,
+                status: TestStatus::OMITTED,
+                msg: Some("Excluded by test name filters".to_owned()),
+                duration: None,
+                details: String::new(),
+            })
+            .await?;
+        return Ok(true);
+    }
+
+    let arg = |value: ExternalRunnerSpecValue| ArgValue {
+        content: ArgValueContent::ExternalRunnerSpecValue(value),
+        format: None,
+    };
+    let cmd: Vec<ArgValue> = spec.command.into_iter().map(arg).collect();
+    let env: HashMap<String, ArgValue> = spec.env.into_iter().map(|(k, v)| (k, arg(v))).collect();
+
+    let _permit = jobs.acquire().await?;
+
+    let mut attempt = 0;
+    loop {
+        let (status, msg, duration, details) = match orchestrator
+            .execute2(
+                DisplayMetadata::Testing {
+                    suite: name.clone(),
+                    testcases: Vec::new(),
+                },
+                target,
+                cmd.clone(),
+                env.clone(),
+                config.timeout,
+                HostSharingRequirements::default(),
+                Vec::new(),
+                None,
+            )
+            .await
+        {
+            Ok(result) => {
+                let (status, msg) = match result.status {
+                    ExecutionStatus::Finished { exitcode: 0 } => (TestStatus::PASS, None),
+                    ExecutionStatus::Finished { exitcode } => (
+                        TestStatus::FAIL,
+                        Some(format!("Exited with code {}", exitcode)),
+                    ),
+                    ExecutionStatus::TimedOut { duration } => (
+                        TestStatus::TIMEOUT,
+                        Some(format!("Timed out after {:.1}s", duration.as_secs_f64())),
+                    ),
+                    ExecutionStatus::Skipped => {
+                        (TestStatus::SKIP, Some("Runs in another shard".to_owned()))
+                    }
+                };
+                (
+                    status,
+                    msg,
+                    Some(result.execution_time),
+                    combined_output(&result),
+                )
+            }
+            Err(e) => (
+                TestStatus::FATAL,
+                Some(format!("Failed to execute test: {:#}", e)),
+                None,
+                String::new(),
+            ),
+        };
+
+        let retry =
+            matches!(status, TestStatus::FAIL | TestStatus::TIMEOUT) && attempt < config.retries;
+        // Tests of other shards don't fail this one.
+        let passed = matches!(status, TestStatus::PASS | TestStatus::SKIP);
+
+        orchestrator
+            .report_test_result(TestResult {
+                target,
+                name: name.clone(),
+                // Attempts that are retried don't count towards the outcome.
+                status: if retry { TestStatus::RERUN } else { status },
+                msg: match (retry, msg) {
+                    (true, Some(msg)) => Some(format!(
+                        "{} (attempt {} of {})",
+                        msg,
+                        attempt + 1,
+                        config.retries + 1
+                    )),
+                    (_, msg) => msg,
+                },
+                duration,
+                details,
+            })
+            .await
+            .context("Failed to report test result")?;
+
+        if !retry {
+            return Ok(passed);
+        }
+        attempt += 1;
+    }
+}
+
+fn combined_output(result: &ExecutionResult2) -> String {
+    let ExecutionStream::Inline(stdout) = &result.stdout;
+    let ExecutionStream::Inline(stderr) = &result.stderr;
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(stdout),
+        String::from_utf8_lossy(stderr)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_filter() -> anyhow::Result<()> {
+        let names = &["root//foo:unit", "root//foo:integration", "root//bar:unit"];
+        let matching = |include: &[&str], exclude: &[&str]| -> anyhow::Result<Vec<&str>> {
+            let owned = |s: &[&str]| s.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>();
+            let filter = NameFilter::new(&owned(include), &owned(exclude))?;
+            Ok(names
+                .iter()
+                .copied()
+                .filter(|n| filter.matches(n))
+                .collect())
+        };
+
+        assert_eq!(names.to_vec(), matching(&[], &[])?);
+        assert_eq!(
+            vec!["root//foo:unit", "root//bar:unit"],
+            matching(&[":unit$"], &[])?
+        );
+        assert_eq!(
+            vec!["root//foo:integration"],
+            matching(&["//foo:"], &[":unit$"])?
+        );
+        assert!(NameFilter::new(&["(".to_owned()], &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_options() -> anyhow::Result<()> {
+        let options = RunnerOptions::try_parse_from([
+            "ignored",
+            "--buck-test-info",
+            "ignored",
+            "-j",
+            "4",
+            "--retries",
+            "2",
+            "--filter",
+            "a",
+            "--filter",
+            "b",
+        ])?;
+        assert_eq!(Some(4), options.jobs);
+        assert_eq!(600, options.timeout);
+        assert_eq!(2, options.retries);
+        assert_eq!(vec!["a", "b"], options.filter);
+        Ok(())
+    }
+}
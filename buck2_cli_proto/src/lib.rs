@@ -271,5 +271,6 @@ define_request!(MaterializeRequest, has(context));
 define_request!(AllocativeRequest, has(context));
 define_request!(CleanStaleRequest, has(context));
 define_request!(FileStatusRequest, has(context));
+define_request!(WhyRecomputedRequest, has(context));
 
 define_request!(InstallRequest, has(context, build_options));
@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::path::PathBuf;
+
 use buck2_client_ctx::client_ctx::ClientCommandContext;
 use buck2_core::logging::LogConfigurationReloadHandle;
 
@@ -21,6 +23,10 @@ type RawFd = String;
 pub(crate) struct ForkserverCommand {
     #[clap(long)]
     fd: RawFd,
+
+    /// The cgroup to create the cgroups of commands in, relative to the cgroup root.
+    #[clap(long)]
+    action_cgroup: Option<PathBuf>,
 }
 
 impl ForkserverCommand {
@@ -43,12 +49,13 @@ impl ForkserverCommand {
             rt.block_on(buck2_forkserver::unix::run_forkserver(
                 self.fd,
                 log_reload_handle,
+                self.action_cgroup,
             ))
         }
 
         #[cfg(not(unix))]
         {
-            let _ignored = log_reload_handle;
+            let _ignored = (log_reload_handle, self.action_cgroup);
             Err(anyhow::anyhow!("The forkserver is only available on UNIX"))
         }
     }
@@ -40,6 +40,7 @@ use crate::Dice;
 use crate::DiceResult;
 use crate::Key;
 use crate::ProjectionKey;
+use crate::WhyRecomputed;
 
 /// Includes all user related computation-specific data.
 #[derive(Allocative)]
@@ -165,6 +166,18 @@ impl DiceTransaction {
     pub fn version(&self) -> u64 {
         self.0.0.transaction_ctx.get_version().0 as u64
     }
+
+    /// The keys of type `K` that have been computed or injected and that match.
+    /// See `Dice::keys`.
+    pub fn keys<K: Key>(&self, matches: impl Fn(&K) -> bool) -> Vec<K> {
+        self.0.0.dice.keys(matches)
+    }
+
+    /// Explains why each key whose `Display` matches was last invalidated.
+    /// See `Dice::why_recomputed`.
+    pub fn why_recomputed(&self, matches: impl Fn(&str) -> bool) -> Vec<WhyRecomputed> {
+        self.0.0.dice.why_recomputed(matches)
+    }
 }
 
 impl Deref for DiceTransaction {
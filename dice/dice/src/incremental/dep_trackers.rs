@@ -103,6 +103,21 @@ impl BothDeps {
             rdeps: Vec::from_iter([node.into_dyn()]),
         }
     }
+
+    /// records a dependency on a node that is already known, without going through a tracker
+    pub(crate) fn add<S: IncrementalComputeProperties>(
+        &mut self,
+        version: VersionNumber,
+        node: GraphNode<S>,
+        incremental_engine: &Arc<IncrementalEngine<S>>,
+    ) {
+        self.deps.insert(box ComputedDep::<S> {
+            engine: Arc::downgrade(incremental_engine),
+            version,
+            node: node.dupe(),
+        });
+        self.rdeps.push(node.into_dyn());
+    }
 }
 
 #[derive(Allocative)]
@@ -303,6 +318,13 @@ mod internals {
             }
         }
 
+        fn lookup_latest(&self) -> Option<(VersionNumber, Arc<dyn GraphNodeDyn>)> {
+            self.engine()
+                .versioned_cache
+                .latest(&self.k)
+                .map(|(v, node)| (v, node.into_dyn()))
+        }
+
         fn dirty(&self, v: VersionNumber) {
             self.engine().dirty(self.k.clone(), v, false)
         }
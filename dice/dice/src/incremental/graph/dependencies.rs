@@ -47,6 +47,10 @@ pub(crate) trait Dependency: Allocative + Debug + Display + Send + Sync {
     /// was evicted from the storage.
     fn lookup_node(&self, v: VersionNumber, mv: MinorVersion) -> Option<Arc<dyn GraphNodeDyn>>;
 
+    /// looks up the most recent valid node of this dependency at any version, along with the
+    /// version at which its value became valid.
+    fn lookup_latest(&self) -> Option<(VersionNumber, Arc<dyn GraphNodeDyn>)>;
+
     fn dirty(&self, v: VersionNumber);
 
     fn get_key_equality(&self) -> PartialEqAny;
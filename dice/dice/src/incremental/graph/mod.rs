@@ -267,6 +267,9 @@ pub(crate) trait GraphNodeDyn: Allocative + Send + Sync + 'static {
 
     fn read_rdeps(&self) -> VersionedRevDependencies;
 
+    /// the deps of the most recent computation of this node
+    fn read_deps(&self) -> Option<Arc<Vec<Box<dyn Dependency>>>>;
+
     fn add_rdep(&self, dependent: Weak<dyn GraphNodeDyn>, v: VersionNumber);
 
     fn writable(&self) -> WritableMetadata;
@@ -291,6 +294,10 @@ where
         self.metadata.read().rdeps.dupe()
     }
 
+    fn read_deps(&self) -> Option<Arc<Vec<Box<dyn Dependency>>>> {
+        self.metadata.read().deps.deps()
+    }
+
     fn add_rdep(&self, dependent: Weak<dyn GraphNodeDyn>, v: VersionNumber) {
         // we only need to hold a read lock on `metadata` since adding `rdep` does not affect
         // the versioning/history of this node at all, which means that any other threads holding
@@ -520,6 +527,10 @@ where
         self.meta.read().rdeps.dupe()
     }
 
+    fn read_deps(&self) -> Option<Arc<Vec<Box<dyn Dependency>>>> {
+        self.meta.read().deps.deps()
+    }
+
     fn add_rdep(&self, _dependent: Weak<dyn GraphNodeDyn>, _v: VersionNumber) {
         // do nothing, since this is an transient entry, the nodes that depend on this must also be
         // transient, therefore, does not need rdeps invalidation
@@ -1086,6 +1097,15 @@ where
     pub(crate) fn len(&self) -> usize {
         self.last_n.len()
     }
+
+    /// the most recent valid entry of the key, whatever version it is at, along with the version
+    /// at which its value became valid
+    pub(crate) fn latest(&self, k: &K::Key) -> Option<(VersionNumber, GraphNode<K>)> {
+        self.last_n.get(k)?.iter().rev().find_map(|(v, e)| match e {
+            VersionedGraphNodeInternal::Occupied(e) => Some((*v, GraphNode::occupied(e.dupe()))),
+            _ => None,
+        })
+    }
 }
 
 struct EntryUpdater<'a, K: StorageProperties> {
@@ -39,6 +39,9 @@ pub(crate) struct CellHistory {
     /// versions of dirty, mapping ot whether or not it's a forced dirty (which means recompute
     /// regardless of node changed)
     dirtied: SortedVectorMap<VersionNumber, bool>,
+    /// the most recent dirty, and whether it was forced. Unlike `dirtied`, this is kept once the
+    /// node is verified again, so that we can explain why it was recomputed.
+    last_dirtied: Option<(VersionNumber, bool)>,
 }
 
 impl CellHistory {
@@ -46,6 +49,7 @@ impl CellHistory {
         Self {
             verified: sorted_vector_set![verified],
             dirtied: SortedVectorMap::new(),
+            last_dirtied: None,
         }
     }
 
@@ -56,6 +60,7 @@ impl CellHistory {
         Self {
             verified: SortedVectorSet::new(),
             dirtied: sorted_vector_map![dirty => force],
+            last_dirtied: Some((dirty, force)),
         }
     }
 
@@ -63,6 +68,7 @@ impl CellHistory {
         Self {
             verified: SortedVectorSet::new(),
             dirtied: SortedVectorMap::new(),
+            last_dirtied: None,
         }
     }
 
@@ -129,7 +135,11 @@ impl CellHistory {
         verified.insert(since);
         dirtied.remove(&since);
 
-        let new = CellHistory { verified, dirtied };
+        let new = CellHistory {
+            verified,
+            dirtied,
+            last_dirtied: self.last_dirtied,
+        };
 
         (since, up_to, new)
     }
@@ -300,6 +310,12 @@ impl CellHistory {
         self.dirtied.iter().max().map(|d| *d.0)
     }
 
+    /// the most recent version this was dirtied at, even if it has been verified since, and
+    /// whether that was a forced dirty
+    pub(crate) fn last_dirtied(&self) -> Option<(VersionNumber, bool)> {
+        self.last_dirtied
+    }
+
     pub(crate) fn latest_verified_before(&self, v: VersionNumber) -> Option<VersionNumber> {
         self.verified
             .range((Bound::Unbounded, Bound::Included(v)))
@@ -359,6 +375,7 @@ impl CellHistory {
 
         if let Some(min_dirty) = min_dirty {
             self.dirtied.insert(min_dirty, false);
+            self.record_dirtied(min_dirty, false);
         }
     }
 
@@ -395,6 +412,13 @@ impl CellHistory {
         );
 
         self.dirtied.insert(v, force);
+        self.record_dirtied(v, force);
+    }
+
+    fn record_dirtied(&mut self, v: VersionNumber, force: bool) {
+        if self.last_dirtied.map_or(true, |(last, _)| last <= v) {
+            self.last_dirtied = Some((v, force));
+        }
     }
 }
 
@@ -430,6 +454,7 @@ pub(crate) mod testing {
             CellHistory {
                 verified: verified.iter().copied().collect(),
                 dirtied: dirtied.iter().map(|v| (*v, false)).collect(),
+                last_dirtied: dirtied.iter().max().map(|v| (*v, false)),
             }
         }
 
@@ -527,6 +552,7 @@ mod tests {
         let mut hist = CellHistory {
             verified: sorted_vector_set![VersionNumber::new(0), VersionNumber::new(2)],
             dirtied: Default::default(),
+            last_dirtied: None,
         };
         // we should ignore dirties that occur after the known version
         hist.propagate_from_deps(
@@ -559,6 +585,7 @@ mod tests {
         let mut hist = CellHistory {
             verified: sorted_vector_set![VersionNumber::new(0), VersionNumber::new(2)],
             dirtied: Default::default(),
+            last_dirtied: None,
         };
         // we should ignore dirties that occur after the known version
         hist.propagate_from_deps(
@@ -19,8 +19,10 @@ pub(crate) mod evaluator;
 pub(crate) mod graph;
 mod history;
 pub(crate) mod introspection;
+pub(crate) mod snapshot;
 pub(crate) mod transaction_ctx;
 pub(crate) mod versions;
+pub(crate) mod why_recomputed;
 
 use std::borrow::Cow;
 use std::fmt::Debug;
@@ -54,6 +56,7 @@ use crate::incremental::evaluator::Evaluator;
 pub(crate) use crate::incremental::graph::dependencies::ComputedDependency;
 pub(crate) use crate::incremental::graph::dependencies::Dependency;
 use crate::incremental::graph::GraphNode;
+use crate::incremental::graph::GraphNodeDyn;
 pub(crate) use crate::incremental::graph::StorageType;
 use crate::incremental::graph::VersionedGraph;
 use crate::incremental::graph::VersionedGraphKey;
@@ -64,6 +67,7 @@ use crate::incremental::history::CellHistory;
 use crate::incremental::transaction_ctx::TransactionCtx;
 use crate::incremental::versions::VersionNumber;
 use crate::incremental::versions::VersionRanges;
+use crate::introspection::graph::AnyKey;
 use crate::introspection::graph::EngineForIntrospection;
 use crate::projection::ProjectionKeyAsKey;
 use crate::projection::ProjectionKeyProperties;
@@ -124,6 +128,13 @@ pub(crate) trait ErasedEngine: Allocative {
     fn introspect(&self) -> &dyn EngineForIntrospection;
 
     fn gc_version(&self, v: VersionNumber);
+
+    /// the most recent valid nodes of the keys that match, along with the versions at which their
+    /// values became valid
+    fn latest_nodes(
+        &self,
+        matches: &dyn Fn(&AnyKey) -> bool,
+    ) -> Vec<(VersionNumber, Arc<dyn GraphNodeDyn>)>;
 }
 
 impl<K> ErasedEngine for IncrementalEngine<K>
@@ -139,6 +150,22 @@ where
         running_map.remove(&v);
         running_map.shrink_to_fit();
     }
+
+    fn latest_nodes(
+        &self,
+        matches: &dyn Fn(&AnyKey) -> bool,
+    ) -> Vec<(VersionNumber, Arc<dyn GraphNodeDyn>)> {
+        let keys: Vec<K::Key> = self
+            .versioned_cache
+            .iter()
+            .map(|e| e.key().clone())
+            .filter(|k| matches(&AnyKey::new(k.clone())))
+            .collect();
+        keys.iter()
+            .filter_map(|k| self.versioned_cache.latest(k))
+            .map(|(v, node)| (v, node.into_dyn()))
+            .collect()
+    }
 }
 
 pub(crate) trait Computable:
@@ -162,6 +189,14 @@ where
         })
     }
 
+    /// All the keys that have an entry in the cache, at any version.
+    pub(crate) fn keys(&self) -> Vec<K::Key> {
+        self.versioned_cache
+            .iter()
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
     /// Dirties the value at K
     #[instrument(level = "info", skip(self), fields(k = %k, version = %version))]
     pub(crate) fn dirty(&self, k: K::Key, version: VersionNumber, force_dirty: bool) {
@@ -1455,6 +1490,10 @@ mod tests {
                 self.0.read().rdeps.dupe()
             }
 
+            fn read_deps(&self) -> Option<Arc<Vec<Box<dyn Dependency>>>> {
+                self.0.read().deps.deps()
+            }
+
             fn add_rdep(&self, dependent: Weak<dyn GraphNodeDyn>, v: VersionNumber) {
                 self.0.read().rdeps.add_rdep(dependent, v)
             }
@@ -1516,6 +1555,10 @@ mod tests {
                 Some(self.1.dupe())
             }
 
+            fn lookup_latest(&self) -> Option<(VersionNumber, Arc<dyn GraphNodeDyn>)> {
+                Some((VersionNumber::new(0), self.1.dupe()))
+            }
+
             fn dirty(&self, v: VersionNumber) {
                 self.1.0.write().hist.mark_invalidated(v);
             }
@@ -1618,6 +1661,10 @@ mod tests {
                 None
             }
 
+            fn lookup_latest(&self) -> Option<(VersionNumber, Arc<dyn GraphNodeDyn>)> {
+                None
+            }
+
             fn dirty(&self, _v: VersionNumber) {}
 
             fn get_key_equality(&self) -> PartialEqAny {
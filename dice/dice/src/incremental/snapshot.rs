@@ -0,0 +1,588 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//!
+//! On-disk snapshots of the computation graph, so that a new Dice can start from the state of a
+//! previous one rather than from nothing.
+//!
+//! Only keys that were registered as persisted (see `DiceDataBuilder::persist`) are written, and
+//! only the nodes that are valid at the current version. A node whose dependencies cannot all be
+//! written is dropped, along with everything that depends on it, since restoring it without its
+//! dependencies would mean it could never be invalidated.
+//!
+//! Nodes are written in dependency order, so that restoring them one by one always finds their
+//! dependencies already on the graph, with the reverse edges needed to dirty them.
+//!
+//! The snapshot knows nothing about what changed while it was on disk. The caller records an
+//! opaque token when saving (e.g. the file watcher's clock), and after restoring must mark as
+//! changed everything that changed since that token, or discard the snapshot if it can't tell.
+//!
+
+use std::any::type_name;
+use std::io::Read;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use anyhow::Context;
+use dupe::Dupe;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::incremental::dep_trackers::BothDeps;
+use crate::incremental::graph::GraphNode;
+use crate::incremental::graph::VersionedGraphKey;
+use crate::incremental::graph::VersionedGraphKeyRef;
+use crate::incremental::graph::VersionedGraphResult;
+use crate::incremental::versions::MinorVersion;
+use crate::incremental::versions::VersionNumber;
+use crate::incremental::IncrementalEngine;
+use crate::introspection::graph::AnyKey;
+use crate::Dice;
+use crate::HashMap;
+use crate::Key;
+use crate::StoragePropertiesForKey;
+
+/// Bumped whenever the layout of `DiceSnapshot` changes.
+const SNAPSHOT_FORMAT: u32 = 1;
+
+#[derive(Debug, Error)]
+enum SnapshotError {
+    #[error("Snapshot has format version {0}, but only version {SNAPSHOT_FORMAT} is supported")]
+    Format(u32),
+    #[error("Snapshots can only be restored into a Dice that has not computed anything yet")]
+    NotEmpty,
+    #[error("Snapshot node {0} depends on node {1}, which is not before it")]
+    Order(usize, u32),
+    #[error("Snapshot node {0} has unknown key type {1}")]
+    KeyType(usize, u32),
+}
+
+/// The valid persisted nodes of a Dice at some version, along with the token that the caller
+/// saved them with.
+#[derive(Serialize, Deserialize)]
+pub struct DiceSnapshot {
+    format: u32,
+    token: String,
+    key_types: Vec<String>,
+    nodes: Vec<SnapshotNode>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SnapshotNode {
+    key_type: u32,
+    key: Vec<u8>,
+    value: Vec<u8>,
+    /// indices of nodes that come before this one
+    deps: Vec<u32>,
+}
+
+impl DiceSnapshot {
+    pub(crate) fn new(dice: &Arc<Dice>, token: String) -> anyhow::Result<Self> {
+        let current = dice.global_versions.current();
+
+        let mut collected = Vec::new();
+        let mut key_types = Vec::new();
+        for (i, ty) in dice.snapshot_keys.iter().enumerate() {
+            ty.collect(
+                dice,
+                current.version,
+                *current.minor_version_guard,
+                i as u32,
+                &mut collected,
+            )?;
+            key_types.push(ty.key_type_name().to_owned());
+        }
+
+        Ok(Self {
+            format: SNAPSHOT_FORMAT,
+            token,
+            key_types,
+            nodes: order_nodes(collected),
+        })
+    }
+
+    /// The token given when this snapshot was taken.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The number of nodes in this snapshot.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn write(&self, out: impl Write) -> anyhow::Result<()> {
+        bincode::serialize_into(out, self).context("Error writing DICE snapshot")
+    }
+
+    /// Reads a snapshot written by `write`. Nothing is decoded beyond the token and the layout of
+    /// the graph until the snapshot is restored.
+    pub fn read(input: impl Read) -> anyhow::Result<Self> {
+        let snapshot: Self =
+            bincode::deserialize_from(input).context("Error reading DICE snapshot")?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return Err(SnapshotError::Format(snapshot.format).into());
+        }
+        for (i, node) in snapshot.nodes.iter().enumerate() {
+            if node.key_type as usize >= snapshot.key_types.len() {
+                return Err(SnapshotError::KeyType(i, node.key_type).into());
+            }
+            if let Some(dep) = node.deps.iter().find(|d| **d as usize >= i) {
+                return Err(SnapshotError::Order(i, *dep).into());
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Puts the nodes of this snapshot onto the graph of a new Dice, returning how many were
+    /// restored. Nodes of key types that this Dice doesn't persist are skipped, along with
+    /// everything that depends on them.
+    ///
+    /// If this fails, the Dice may hold part of the snapshot and should be discarded.
+    pub(crate) fn restore(&self, dice: &Arc<Dice>) -> anyhow::Result<usize> {
+        if !dice.map.read().engines().is_empty() {
+            return Err(SnapshotError::NotEmpty.into());
+        }
+
+        let key_types: Vec<_> = self
+            .key_types
+            .iter()
+            .map(|name| {
+                dice.snapshot_keys
+                    .iter()
+                    .find(|ty| ty.key_type_name() == name)
+            })
+            .collect();
+
+        // Everything is restored as verified at a single new version, which is committed when
+        // this is dropped.
+        let version = dice.global_versions.write();
+        let v = version.get();
+
+        let mut restored: Vec<Option<Box<dyn RestoredNode>>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let deps: Option<Vec<&dyn RestoredNode>> = node
+                .deps
+                .iter()
+                .map(|d| restored[*d as usize].as_deref())
+                .collect();
+            let new = match (key_types[node.key_type as usize], deps) {
+                (Some(ty), Some(deps)) => {
+                    let mut both_deps = BothDeps::default();
+                    for dep in deps {
+                        dep.add_to(&mut both_deps);
+                    }
+                    Some(ty.restore(dice, v, node, both_deps)?)
+                }
+                _ => None,
+            };
+            restored.push(new);
+        }
+
+        Ok(restored.iter().filter(|x| x.is_some()).count())
+    }
+}
+
+/// A node collected from the graph, before we know whether all of its dependencies were also
+/// collected.
+pub(crate) struct CollectedNode {
+    key: AnyKey,
+    deps: Vec<AnyKey>,
+    node: SnapshotNode,
+}
+
+/// Drops nodes with missing dependencies and sorts the rest so that dependencies come first.
+fn order_nodes(collected: Vec<CollectedNode>) -> Vec<SnapshotNode> {
+    #[derive(Clone, Copy)]
+    enum Visit {
+        New,
+        Visiting,
+        Dropped,
+        Written(u32),
+    }
+
+    let index: HashMap<&AnyKey, usize> = collected
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (&n.key, i))
+        .collect();
+    let deps: Vec<Vec<Option<usize>>> = collected
+        .iter()
+        .map(|n| n.deps.iter().map(|d| index.get(d).copied()).collect())
+        .collect();
+    drop(index);
+
+    let mut nodes: Vec<Option<SnapshotNode>> =
+        collected.into_iter().map(|n| Some(n.node)).collect();
+    let mut visits = vec![Visit::New; nodes.len()];
+    let mut ordered = Vec::new();
+
+    for root in 0..nodes.len() {
+        if !matches!(visits[root], Visit::New) {
+            continue;
+        }
+        visits[root] = Visit::Visiting;
+        let mut stack = vec![(root, 0)];
+        while let Some((i, next)) = stack.last_mut() {
+            let i = *i;
+            if let Some(dep) = deps[i].get(*next) {
+                *next += 1;
+                if let Some(dep) = *dep {
+                    if matches!(visits[dep], Visit::New) {
+                        visits[dep] = Visit::Visiting;
+                        stack.push((dep, 0));
+                    }
+                }
+                continue;
+            }
+            stack.pop();
+
+            // A dependency that is still being visited would be a cycle, which Dice doesn't allow,
+            // but it can't be written before us either way.
+            let written: Option<Vec<u32>> = deps[i]
+                .iter()
+                .map(|d| match d.map(|d| visits[d]) {
+                    Some(Visit::Written(id)) => Some(id),
+                    _ => None,
+                })
+                .collect();
+            visits[i] = match written {
+                Some(written) => {
+                    let mut node = nodes[i].take().unwrap();
+                    node.deps = written;
+                    ordered.push(node);
+                    Visit::Written((ordered.len() - 1) as u32)
+                }
+                None => Visit::Dropped,
+            };
+        }
+    }
+
+    ordered
+}
+
+/// A restored node, as a dependency of the nodes restored after it.
+pub(crate) trait RestoredNode {
+    fn add_to(&self, deps: &mut BothDeps);
+}
+
+struct RestoredGraphNode<K: Key> {
+    engine: Arc<IncrementalEngine<StoragePropertiesForKey<K>>>,
+    version: VersionNumber,
+    node: GraphNode<StoragePropertiesForKey<K>>,
+}
+
+impl<K: Key> RestoredNode for RestoredGraphNode<K> {
+    fn add_to(&self, deps: &mut BothDeps) {
+        deps.add(self.version, self.node.dupe(), &self.engine)
+    }
+}
+
+/// A key type that is written to snapshots.
+pub(crate) trait SnapshotKeyType: Send + Sync + 'static {
+    /// Identifies the key type within a snapshot, so must be unique among persisted keys.
+    fn key_type_name(&self) -> &'static str;
+
+    fn collect(
+        &self,
+        dice: &Arc<Dice>,
+        v: VersionNumber,
+        mv: MinorVersion,
+        key_type: u32,
+        out: &mut Vec<CollectedNode>,
+    ) -> anyhow::Result<()>;
+
+    fn restore(
+        &self,
+        dice: &Arc<Dice>,
+        v: VersionNumber,
+        node: &SnapshotNode,
+        deps: BothDeps,
+    ) -> anyhow::Result<Box<dyn RestoredNode>>;
+}
+
+pub(crate) struct SnapshotKey<K>(PhantomData<fn(K)>);
+
+impl<K> SnapshotKey<K>
+where
+    K: Key + Serialize + DeserializeOwned,
+    K::Value: Serialize + DeserializeOwned,
+{
+    pub(crate) fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<K> SnapshotKeyType for SnapshotKey<K>
+where
+    K: Key + Serialize + DeserializeOwned,
+    K::Value: Serialize + DeserializeOwned,
+{
+    fn key_type_name(&self) -> &'static str {
+        type_name::<K>()
+    }
+
+    fn collect(
+        &self,
+        dice: &Arc<Dice>,
+        v: VersionNumber,
+        mv: MinorVersion,
+        key_type: u32,
+        out: &mut Vec<CollectedNode>,
+    ) -> anyhow::Result<()> {
+        let engine = match dice
+            .map
+            .read()
+            .find_cache_opt::<StoragePropertiesForKey<K>>()
+        {
+            Some(engine) => engine,
+            None => return Ok(()),
+        };
+
+        let keys: Vec<K> = engine
+            .versioned_cache
+            .iter()
+            .map(|e| e.key().clone())
+            .collect();
+        for k in keys {
+            let node = match engine
+                .versioned_cache
+                .get(VersionedGraphKeyRef::new(v, &k), mv)
+            {
+                VersionedGraphResult::Match(node) if node.is_valid() => node,
+                _ => continue,
+            };
+            let deps = match node.read_meta().deps.deps() {
+                Some(deps) => deps.iter().map(|d| d.introspect()).collect(),
+                None => Vec::new(),
+            };
+            let key =
+                bincode::serialize(&k).with_context(|| format!("Error serializing key `{}`", k))?;
+            let value = bincode::serialize(node.val())
+                .with_context(|| format!("Error serializing the value of key `{}`", k))?;
+            out.push(CollectedNode {
+                key: AnyKey::new(k),
+                deps,
+                node: SnapshotNode {
+                    key_type,
+                    key,
+                    value,
+                    deps: Vec::new(),
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn restore(
+        &self,
+        dice: &Arc<Dice>,
+        v: VersionNumber,
+        node: &SnapshotNode,
+        deps: BothDeps,
+    ) -> anyhow::Result<Box<dyn RestoredNode>> {
+        let k: K = bincode::deserialize(&node.key)
+            .with_context(|| format!("Error deserializing key of type `{}`", type_name::<K>()))?;
+        let value: K::Value = bincode::deserialize(&node.value)
+            .with_context(|| format!("Error deserializing the value of key `{}`", k))?;
+
+        let engine = dice.find_cache::<K>();
+        let (node, _) = engine.versioned_cache.update_computed_value(
+            VersionedGraphKey::new(v, k),
+            MinorVersion::ZERO,
+            value,
+            deps,
+        );
+        Ok(box RestoredGraphNode {
+            engine,
+            version: v,
+            node,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use allocative::Allocative;
+    use async_trait::async_trait;
+    use derive_more::Display;
+    use dupe::Dupe;
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use crate::cycles::DetectCycles;
+    use crate::Dice;
+    use crate::DiceComputations;
+    use crate::DiceSnapshot;
+    use crate::InjectedKey;
+    use crate::Key;
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[derive(Serialize, Deserialize)]
+    #[display(fmt = "{:?}", self)]
+    struct Source(u32);
+
+    impl InjectedKey for Source {
+        type Value = u32;
+
+        fn compare(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    /// Counts the computations of `Sum`, stored in the Dice data.
+    type Sums = Arc<AtomicUsize>;
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[derive(Serialize, Deserialize)]
+    #[display(fmt = "{:?}", self)]
+    struct Sum;
+
+    #[async_trait]
+    impl Key for Sum {
+        type Value = u32;
+
+        async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+            ctx.global_data()
+                .get::<Sums>()
+                .unwrap()
+                .fetch_add(1, Ordering::SeqCst);
+            ctx.compute(&Source(0)).await.unwrap() + ctx.compute(&Source(1)).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    /// Not persisted.
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "{:?}", self)]
+    struct Ephemeral;
+
+    #[async_trait]
+    impl Key for Ephemeral {
+        type Value = u32;
+
+        async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+            ctx.compute(&Source(0)).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[derive(Serialize, Deserialize)]
+    #[display(fmt = "{:?}", self)]
+    struct Doubled;
+
+    #[async_trait]
+    impl Key for Doubled {
+        type Value = u32;
+
+        async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+            ctx.compute(&Ephemeral).await.unwrap() * 2
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    fn new_dice(sums: &Sums) -> Arc<Dice> {
+        let mut builder = Dice::builder();
+        builder.set(sums.dupe());
+        builder.persist::<Source>();
+        builder.persist::<Sum>();
+        builder.persist::<Doubled>();
+        builder.build(DetectCycles::Enabled)
+    }
+
+    #[tokio::test]
+    async fn snapshot_restores_persisted_keys() -> anyhow::Result<()> {
+        let sums = Sums::default();
+        let dice = new_dice(&sums);
+        let ctx = dice.ctx();
+        ctx.changed_to(vec![(Source(0), 1), (Source(1), 2)])?;
+        let ctx = ctx.commit();
+        assert_eq!(3, ctx.compute(&Sum).await?);
+        assert_eq!(2, ctx.compute(&Doubled).await?);
+        assert_eq!(1, sums.load(Ordering::SeqCst));
+        drop(ctx);
+
+        // `Doubled` depends on a key that isn't persisted, so can't be written.
+        let snapshot = dice.snapshot("token".to_owned())?;
+        assert_eq!(3, snapshot.len());
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes)?;
+        let snapshot = DiceSnapshot::read(bytes.as_slice())?;
+        assert_eq!("token", snapshot.token());
+
+        let dice = new_dice(&sums);
+        assert_eq!(3, dice.restore_snapshot(&snapshot)?);
+        assert!(dice.restore_snapshot(&snapshot).is_err());
+
+        let ctx = dice.ctx();
+        assert_eq!(3, ctx.compute(&Sum).await?);
+        assert_eq!(1, sums.load(Ordering::SeqCst));
+        assert_eq!(2, ctx.compute(&Doubled).await?);
+        drop(ctx);
+
+        // Changes made after restoring invalidate the restored nodes that depend on them.
+        let ctx = dice.ctx();
+        ctx.changed_to(vec![(Source(1), 5)])?;
+        let ctx = ctx.commit();
+        assert_eq!(6, ctx.compute(&Sum).await?);
+        assert_eq!(2, sums.load(Ordering::SeqCst));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_skips_unknown_key_types() -> anyhow::Result<()> {
+        let dice = new_dice(&Sums::default());
+        let ctx = dice.ctx();
+        ctx.changed_to(vec![(Source(0), 1), (Source(1), 2)])?;
+        let ctx = ctx.commit();
+        ctx.compute(&Sum).await?;
+        drop(ctx);
+        let snapshot = dice.snapshot("token".to_owned())?;
+
+        // Without `Source`, nothing that depends on it can be restored either.
+        let mut builder = Dice::builder();
+        builder.persist::<Sum>();
+        let dice = builder.build(DetectCycles::Enabled);
+        assert_eq!(0, dice.restore_snapshot(&snapshot)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_rejects_other_formats() -> anyhow::Result<()> {
+        let mut snapshot = new_dice(&Sums::default()).snapshot("token".to_owned())?;
+        snapshot.format += 1;
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes)?;
+        assert!(DiceSnapshot::read(bytes.as_slice()).is_err());
+        Ok(())
+    }
+}
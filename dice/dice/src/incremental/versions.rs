@@ -81,7 +81,7 @@ impl Sub for VersionNumber {
 pub(crate) struct MinorVersion(usize);
 
 impl MinorVersion {
-    const ZERO: MinorVersion = MinorVersion(0);
+    pub(crate) const ZERO: MinorVersion = MinorVersion(0);
 
     pub(crate) fn next(&self) -> MinorVersion {
         MinorVersion(self.0 + 1)
@@ -0,0 +1,292 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//!
+//! Explains why a node was last invalidated.
+//!
+//! When a change is committed at some version, the changed node, and every node that transitively
+//! depends on it, is dirtied at that version. So starting from a node that was dirtied at `v`, we
+//! walk back through the dependencies that were also invalidated at `v` until we reach the nodes
+//! that were changed at `v`: either forcibly dirtied, or given a new value with none of their
+//! own dependencies invalidated.
+//!
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::Arc;
+
+use dupe::Dupe;
+
+use crate::incremental::graph::GraphNodeDyn;
+use crate::introspection::graph::AnyKey;
+use crate::HashSet;
+
+/// Why a key was last invalidated, and so recomputed (or checked for whether it needed to be) when
+/// it was next requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhyRecomputed {
+    /// The key, as `KeyType(key)`.
+    pub key: String,
+    /// The version at which the key was last invalidated, or `None` if it hasn't been since it was
+    /// first computed.
+    pub invalidated_at: Option<u64>,
+    /// For each change that caused the invalidation, the keys from `key` down to the changed key.
+    pub causes: Vec<Vec<String>>,
+}
+
+impl Display for WhyRecomputed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.invalidated_at {
+            None => writeln!(f, "{} has not been invalidated", self.key)?,
+            Some(v) if self.causes.is_empty() => writeln!(
+                f,
+                "{} was invalidated at v{}, but the change is no longer in the graph",
+                self.key, v
+            )?,
+            Some(v) => {
+                writeln!(f, "{} was invalidated at v{} by:", self.key, v)?;
+                for cause in &self.causes {
+                    writeln!(f, "  {}", cause.join(" -> "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(key: &AnyKey) -> String {
+    format!("{}({})", key.short_type_name(), key)
+}
+
+pub(crate) fn why_recomputed(node: Arc<dyn GraphNodeDyn>) -> WhyRecomputed {
+    let key = describe(&node.key());
+    let last_dirtied = node.get_history().last_dirtied();
+    let (v, forced) = match last_dirtied {
+        Some(last) => last,
+        None => {
+            return WhyRecomputed {
+                key,
+                invalidated_at: None,
+                causes: Vec::new(),
+            };
+        }
+    };
+
+    struct Visited {
+        node: Arc<dyn GraphNodeDyn>,
+        parent: Option<usize>,
+        /// forcibly dirtied at `v`
+        forced: bool,
+        /// given a new value at `v`
+        changed: bool,
+    }
+
+    // Breadth first, so that each change is reached by one of the shortest paths to it.
+    let mut visited = vec![Visited {
+        node: node.dupe(),
+        parent: None,
+        forced,
+        changed: false,
+    }];
+    let mut seen: HashSet<usize> = HashSet::default();
+    seen.insert(node.id());
+    let mut roots = Vec::new();
+
+    let mut i = 0;
+    while i < visited.len() {
+        let mut invalidated_deps = false;
+        for dep in visited[i]
+            .node
+            .read_deps()
+            .iter()
+            .flat_map(|deps| deps.iter())
+        {
+            let (since, dep) = match dep.lookup_latest() {
+                Some(latest) => latest,
+                None => continue,
+            };
+            let dirtied = match dep.get_history().last_dirtied() {
+                Some((last, forced)) if last == v => Some(forced),
+                _ => None,
+            };
+            if since != v && dirtied.is_none() {
+                continue;
+            }
+            invalidated_deps = true;
+            if seen.insert(dep.id()) {
+                visited.push(Visited {
+                    node: dep,
+                    parent: Some(i),
+                    forced: dirtied == Some(true),
+                    changed: since == v,
+                });
+            }
+        }
+
+        let x = &visited[i];
+        if x.forced || (x.changed && !invalidated_deps) {
+            roots.push(i);
+        }
+        i += 1;
+    }
+
+    let causes = roots
+        .into_iter()
+        .map(|root| {
+            let mut path = Vec::new();
+            let mut next = Some(root);
+            while let Some(i) = next {
+                path.push(describe(&visited[i].node.key()));
+                next = visited[i].parent;
+            }
+            path.reverse();
+            path
+        })
+        .collect();
+
+    WhyRecomputed {
+        key,
+        invalidated_at: Some(v.0 as u64),
+        causes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use allocative::Allocative;
+    use async_trait::async_trait;
+    use derive_more::Display;
+    use dupe::Dupe;
+
+    use crate::cycles::DetectCycles;
+    use crate::Dice;
+    use crate::DiceComputations;
+    use crate::InjectedKey;
+    use crate::Key;
+    use crate::WhyRecomputed;
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "{}", _0)]
+    struct Source(u32);
+
+    impl InjectedKey for Source {
+        type Value = u32;
+
+        fn compare(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "mid")]
+    struct Mid;
+
+    #[async_trait]
+    impl Key for Mid {
+        type Value = u32;
+
+        async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+            ctx.compute(&Source(0)).await.unwrap() * 2
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    #[derive(Clone, Dupe, Display, Debug, Eq, Hash, PartialEq, Allocative)]
+    #[display(fmt = "top")]
+    struct Top;
+
+    #[async_trait]
+    impl Key for Top {
+        type Value = u32;
+
+        async fn compute(&self, ctx: &DiceComputations) -> Self::Value {
+            ctx.compute(&Mid).await.unwrap() + ctx.compute(&Source(1)).await.unwrap()
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+    }
+
+    fn path(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|x| (*x).to_owned()).collect()
+    }
+
+    #[tokio::test]
+    async fn why_recomputed_finds_changes() -> anyhow::Result<()> {
+        let dice = Dice::builder().build(DetectCycles::Enabled);
+        let ctx = dice.ctx();
+        ctx.changed_to(vec![(Source(0), 1), (Source(1), 2)])?;
+        let ctx = ctx.commit();
+        assert_eq!(4, ctx.compute(&Top).await?);
+        drop(ctx);
+
+        assert_eq!(
+            vec![WhyRecomputed {
+                key: "Top(top)".to_owned(),
+                invalidated_at: None,
+                causes: Vec::new(),
+            }],
+            dice.why_recomputed(|x| x == "top")
+        );
+
+        let ctx = dice.ctx();
+        ctx.changed_to(vec![(Source(0), 3)])?;
+        let ctx = ctx.commit();
+        let v = ctx.version();
+        assert_eq!(8, ctx.compute(&Top).await?);
+        drop(ctx);
+
+        let why = dice.why_recomputed(|x| x == "top");
+        assert_eq!(
+            vec![WhyRecomputed {
+                key: "Top(top)".to_owned(),
+                invalidated_at: Some(v),
+                causes: vec![path(&["Top(top)", "Mid(mid)", "Source(0)"])],
+            }],
+            why
+        );
+        assert_eq!(
+            format!(
+                "Top(top) was invalidated at v{} by:\n  Top(top) -> Mid(mid) -> Source(0)\n",
+                v
+            ),
+            why[0].to_string()
+        );
+
+        // Forcing a key to be recomputed is also a change, even if its value is unchanged.
+        let ctx = dice.ctx();
+        ctx.changed(vec![Mid])?;
+        ctx.changed_to(vec![(Source(1), 5)])?;
+        let ctx = ctx.commit();
+        let v = ctx.version();
+        assert_eq!(11, ctx.compute(&Top).await?);
+        drop(ctx);
+
+        let mut why = dice.why_recomputed(|x| x == "top");
+        why[0].causes.sort();
+        assert_eq!(
+            vec![WhyRecomputed {
+                key: "Top(top)".to_owned(),
+                invalidated_at: Some(v),
+                causes: vec![
+                    path(&["Top(top)", "Mid(mid)"]),
+                    path(&["Top(top)", "Source(1)"]),
+                ],
+            }],
+            why
+        );
+
+        Ok(())
+    }
+}
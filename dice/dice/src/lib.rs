@@ -241,6 +241,8 @@ use gazebo::prelude::*;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde::Serializer;
 use thiserror::Error;
 
@@ -258,8 +260,13 @@ use crate::future_handle::WeakDiceFutureHandle;
 use crate::incremental::evaluator::Evaluator;
 use crate::incremental::graph::storage_properties::StorageProperties;
 use crate::incremental::graph::GraphNode;
+pub use crate::incremental::snapshot::DiceSnapshot;
+use crate::incremental::snapshot::SnapshotKey;
+use crate::incremental::snapshot::SnapshotKeyType;
 use crate::incremental::transaction_ctx::TransactionCtx;
 use crate::incremental::versions::VersionTracker;
+use crate::incremental::why_recomputed::why_recomputed;
+pub use crate::incremental::why_recomputed::WhyRecomputed;
 use crate::incremental::IncrementalComputeProperties;
 use crate::incremental::IncrementalEngine;
 use crate::incremental::StorageType;
@@ -320,6 +327,9 @@ pub struct Dice {
     /// Number of active transactions.
     /// Or more precisely, the number of alive transaction context objects.
     active_transaction_count: AtomicU32,
+    /// Key types written to snapshots.
+    #[allocative(skip)]
+    snapshot_keys: Vec<Box<dyn SnapshotKeyType>>,
 }
 
 impl Debug for Dice {
@@ -335,7 +345,11 @@ impl Dice {
         DiceDataBuilder::new()
     }
 
-    fn new(data: DiceData, detect_cycles: DetectCycles) -> Arc<Self> {
+    fn new(
+        data: DiceData,
+        detect_cycles: DetectCycles,
+        snapshot_keys: Vec<Box<dyn SnapshotKeyType>>,
+    ) -> Arc<Self> {
         let map = Arc::new(RwLock::new(DiceMap::new()));
         let weak_map = Arc::downgrade(&map);
         Arc::new(Dice {
@@ -348,6 +362,7 @@ impl Dice {
             }),
             detect_cycles,
             active_transaction_count: AtomicU32::new(0),
+            snapshot_keys,
         })
     }
 
@@ -435,6 +450,49 @@ impl Dice {
         Ok(())
     }
 
+    /// Takes a snapshot of the persisted keys that are valid at the current version. The `token`
+    /// should identify both the state of the inputs at this version (e.g. a file watcher clock)
+    /// and anything that affects how values are serialized (e.g. the build of the binary).
+    pub fn snapshot(self: &Arc<Dice>, token: String) -> anyhow::Result<DiceSnapshot> {
+        DiceSnapshot::new(self, token)
+    }
+
+    /// Restores a snapshot into this Dice, which must not have computed anything yet, returning
+    /// the number of nodes restored. They are restored as if they were valid, so the caller must
+    /// then mark as changed everything that changed since the snapshot's token.
+    pub fn restore_snapshot(self: &Arc<Dice>, snapshot: &DiceSnapshot) -> anyhow::Result<usize> {
+        snapshot.restore(self)
+    }
+
+    /// The keys of type `K` that have been computed or injected at any version and that match.
+    /// This lets callers mark as changed a whole family of keys when they can't tell which of
+    /// them changed.
+    pub fn keys<K: Key>(&self, matches: impl Fn(&K) -> bool) -> Vec<K> {
+        let engine = self
+            .map
+            .read()
+            .find_cache_opt::<StoragePropertiesForKey<K>>();
+        match engine {
+            Some(engine) => engine.keys().into_iter().filter(|k| matches(k)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Explains why each key whose `Display` matches was last invalidated, tracing back to the
+    /// changes that caused it.
+    pub fn why_recomputed(&self, matches: impl Fn(&str) -> bool) -> Vec<WhyRecomputed> {
+        let mut res: Vec<_> = self
+            .map
+            .read()
+            .engines()
+            .iter()
+            .flat_map(|e| e.latest_nodes(&|k| matches(&k.to_string())))
+            .map(|(_, n)| why_recomputed(n))
+            .collect();
+        res.sort_by(|x, y| x.key.cmp(&y.key));
+        res
+    }
+
     pub fn detect_cycles(&self) -> &DetectCycles {
         &self.detect_cycles
     }
@@ -444,19 +502,42 @@ impl Dice {
     }
 }
 
-pub struct DiceDataBuilder(DiceData);
+pub struct DiceDataBuilder {
+    data: DiceData,
+    snapshot_keys: Vec<Box<dyn SnapshotKeyType>>,
+}
 
 impl DiceDataBuilder {
     fn new() -> Self {
-        Self(DiceData::new())
+        Self {
+            data: DiceData::new(),
+            snapshot_keys: Vec::new(),
+        }
     }
 
     pub fn set<K: Send + Sync + 'static>(&mut self, val: K) {
-        self.0.set(val);
+        self.data.set(val);
+    }
+
+    /// Writes keys of type `K` to snapshots, and restores them from snapshots. Keys that depend on
+    /// keys that are not persisted are never written.
+    pub fn persist<K>(&mut self)
+    where
+        K: Key + Serialize + DeserializeOwned,
+        K::Value: Serialize + DeserializeOwned,
+    {
+        let ty = SnapshotKey::<K>::new();
+        if !self
+            .snapshot_keys
+            .iter()
+            .any(|x| x.key_type_name() == ty.key_type_name())
+        {
+            self.snapshot_keys.push(box ty);
+        }
     }
 
     pub fn build(self, detect_cycles: DetectCycles) -> Arc<Dice> {
-        Dice::new(self.0, detect_cycles)
+        Dice::new(self.data, detect_cycles, self.snapshot_keys)
     }
 }
 
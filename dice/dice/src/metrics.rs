@@ -40,7 +40,11 @@ mod tests {
 
     #[test]
     fn test_active_transaction_count() {
-        let dice = Arc::new(Dice::new(DiceData::new(), DetectCycles::Enabled));
+        let dice = Arc::new(Dice::new(
+            DiceData::new(),
+            DetectCycles::Enabled,
+            Vec::new(),
+        ));
         assert_eq!(0, dice.metrics().active_transaction_count);
         let ctx = dice.ctx();
         assert_eq!(1, dice.metrics().active_transaction_count);
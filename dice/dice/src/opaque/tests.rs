@@ -112,7 +112,7 @@ async fn key_does_not_read_opaque() -> anyhow::Result<()> {
 
     let mut dice_data = DiceData::new();
     dice_data.set(tracker.dupe());
-    let dice = Dice::new(dice_data, DetectCycles::Enabled);
+    let dice = Dice::new(dice_data, DetectCycles::Enabled, Vec::new());
 
     // Part 1: compute key which requests on opaque key, but does not use it.
 
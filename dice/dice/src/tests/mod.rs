@@ -544,7 +544,7 @@ async fn invalid_results_are_not_cached() -> anyhow::Result<()> {
         }
     }
 
-    let dice = Dice::new(DiceData::new(), DetectCycles::Enabled);
+    let dice = Dice::new(DiceData::new(), DetectCycles::Enabled, Vec::new());
     let is_ran = Arc::new(AtomicBool::new(false));
     {
         let ctx = dice.ctx();
@@ -656,3 +656,19 @@ async fn demo_with_transient() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn keys_lists_computed_keys() -> anyhow::Result<()> {
+    let dice = Dice::builder().build(DetectCycles::Enabled);
+    let ctx = dice.ctx();
+    ctx.compute(&K(3))
+        .await?
+        .map_err(|e| anyhow::anyhow!(format!("{:#}", e)))?;
+
+    let mut keys = ctx.keys::<K>(|k| k.0 > 0);
+    keys.sort_by_key(|k| k.0);
+    assert_eq!(vec![K(1), K(2), K(3)], keys);
+    assert!(ctx.keys::<Foo>(|_| true).is_empty());
+
+    Ok(())
+}
@@ -7,7 +7,10 @@
 
 load("@prelude//utils:utils.bzl", "expect", "value_or")
 
-# Flags to apply to decompress the various types of archives.
+# Types of archives that `ctx.actions.extract_archive` can unpack.
+_NATIVE_TYPES = ["tar", "tar.gz", "tar.zst", "zip"]
+
+# Flags to apply to decompress the various types of archives with `tar`.
 _FLAGS = {
     "tar.gz": "-z",
     "tar.xz": "-J",
@@ -16,13 +19,12 @@ _FLAGS = {
 
 def _type(ctx: "context") -> str.type:
     typ = value_or(ctx.attrs.type, "tar.gz")
-    if typ not in _FLAGS:
+    if typ not in _FLAGS and typ not in _NATIVE_TYPES:
         fail("unsupported `type`: {}".format(typ))
     return typ
 
 def http_archive_impl(ctx: "context") -> ["provider"]:
     expect(len(ctx.attrs.urls) == 1, "multiple `urls` not support: {}".format(ctx.attrs.urls))
-    expect(ctx.attrs.strip_prefix == None, "`strip_prefix` not supported: {}".format(ctx.attrs.strip_prefix))
 
     # The HTTP download is local so it makes little sense to run actions
     # remotely, unless we can defer them.
@@ -33,8 +35,23 @@ def http_archive_impl(ctx: "context") -> ["provider"]:
     url = ctx.attrs.urls[0]
     ctx.actions.download_file(archive.as_output(), url, sha1 = ctx.attrs.sha1, sha256 = ctx.attrs.sha256, is_deferrable = True)
 
+    # `excludes` are regexes, which `extract_archive` doesn't take, so those
+    # archives still go through `tar`.
+    typ = _type(ctx)
+    if typ in _NATIVE_TYPES and not ctx.attrs.excludes:
+        output = ctx.actions.extract_archive(
+            value_or(ctx.attrs.out, ctx.label.name),
+            archive,
+            format = typ,
+            strip_prefix = ctx.attrs.strip_prefix,
+        )
+        return [DefaultInfo(default_outputs = [output])]
+
+    expect(ctx.attrs.strip_prefix == None, "`strip_prefix` not supported with `excludes` or `type = \"{}\"`".format(typ))
+    expect(typ in _FLAGS, "`excludes` not supported with `type = \"{}\"`".format(typ))
+
     # Unpack archive to output directory.
-    compress_flag = _FLAGS[_type(ctx)]
+    compress_flag = _FLAGS[typ]
 
     exclude_flags = []
     exclude_hidden = []
@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Streaming transfers of blobs that are too large to fit in a batch request, using the
+//! `google.bytestream.ByteStream` service as described by the REAPI.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use futures::stream;
+use futures::Stream;
+use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
+use re_grpc_proto::google::bytestream::QueryWriteStatusRequest;
+use re_grpc_proto::google::bytestream::ReadRequest;
+use re_grpc_proto::google::bytestream::WriteRequest;
+use sha1::Digest as _;
+use sha1::Sha1;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncSeekExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+use crate::client::GrpcService;
+use crate::digest::TDigest;
+
+/// Size of the data sent in each `WriteRequest`.
+const WRITE_CHUNK_SIZE: usize = 1 << 20;
+
+/// How many times an interrupted write is resumed before giving up.
+const WRITE_RETRIES: usize = 3;
+
+/// Where the contents of a blob being uploaded come from.
+#[derive(Clone)]
+pub(crate) enum BlobSource {
+    Bytes(Arc<Vec<u8>>),
+    File(String),
+}
+
+fn resource_name_prefix(instance_name: &str) -> String {
+    if instance_name.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", instance_name)
+    }
+}
+
+pub(crate) fn read_resource_name(instance_name: &str, digest: &TDigest) -> String {
+    format!(
+        "{}blobs/{}/{}",
+        resource_name_prefix(instance_name),
+        digest.hash,
+        digest.size_in_bytes
+    )
+}
+
+pub(crate) fn write_resource_name(instance_name: &str, digest: &TDigest) -> String {
+    format!(
+        "{}uploads/{}/blobs/{}/{}",
+        resource_name_prefix(instance_name),
+        uuid::Uuid::new_v4(),
+        digest.hash,
+        digest.size_in_bytes
+    )
+}
+
+/// Reads `source` in chunks, starting from `offset`. An empty chunk marks the end.
+struct ChunkReader {
+    source: BlobSource,
+    file: Option<tokio::fs::File>,
+    offset: usize,
+}
+
+impl ChunkReader {
+    async fn new(source: BlobSource, offset: usize) -> anyhow::Result<Self> {
+        let file = match &source {
+            BlobSource::Bytes(..) => None,
+            BlobSource::File(path) => {
+                let mut file = tokio::fs::File::open(path)
+                    .await
+                    .with_context(|| format!("Error opening `{}` for upload", path))?;
+                file.seek(std::io::SeekFrom::Start(offset as u64))
+                    .await
+                    .with_context(|| format!("Error seeking `{}` for upload", path))?;
+                Some(file)
+            }
+        };
+        Ok(Self {
+            source,
+            file,
+            offset,
+        })
+    }
+
+    async fn next_chunk(&mut self) -> anyhow::Result<Vec<u8>> {
+        let chunk = match (&self.source, &mut self.file) {
+            (BlobSource::Bytes(bytes), _) => {
+                let start = std::cmp::min(self.offset, bytes.len());
+                let end = std::cmp::min(start + WRITE_CHUNK_SIZE, bytes.len());
+                bytes[start..end].to_vec()
+            }
+            (BlobSource::File(path), Some(file)) => {
+                let mut chunk = Vec::with_capacity(WRITE_CHUNK_SIZE);
+                (&mut *file)
+                    .take(WRITE_CHUNK_SIZE as u64)
+                    .read_to_end(&mut chunk)
+                    .await
+                    .with_context(|| format!("Error reading `{}` for upload", path))?;
+                chunk
+            }
+            (BlobSource::File(..), None) => unreachable!("File is opened in ChunkReader::new"),
+        };
+        self.offset += chunk.len();
+        Ok(chunk)
+    }
+}
+
+/// Produces the `WriteRequest`s for the data of `source` from `offset` onwards. Errors reading
+/// the source end the stream early and are stored in `error`, the server will then see an
+/// unfinished write.
+fn write_requests(
+    resource_name: String,
+    reader: ChunkReader,
+    size: usize,
+    error: Arc<Mutex<Option<anyhow::Error>>>,
+) -> impl Stream<Item = WriteRequest> + Send + 'static {
+    stream::unfold(Some(reader), move |reader| {
+        let resource_name = resource_name.clone();
+        let error = error.clone();
+        async move {
+            let mut reader = reader?;
+            let write_offset = reader.offset;
+            match reader.next_chunk().await {
+                Ok(data) => {
+                    let finish_write = reader.offset >= size || data.is_empty();
+                    let request = WriteRequest {
+                        // Only required on the first request, but allowed on all of them.
+                        resource_name,
+                        write_offset: write_offset as i64,
+                        finish_write,
+                        data,
+                    };
+                    Some((request, if finish_write { None } else { Some(reader) }))
+                }
+                Err(e) => {
+                    *error.lock().unwrap() = Some(e);
+                    None
+                }
+            }
+        }
+    })
+}
+
+/// Uploads a blob through `ByteStream.Write`. If the write is interrupted, the committed size is
+/// queried from the server and the write resumes from there.
+pub(crate) async fn write_blob(
+    client: &ByteStreamClient<GrpcService>,
+    instance_name: &str,
+    digest: &TDigest,
+    source: BlobSource,
+) -> anyhow::Result<()> {
+    let mut client = client.clone();
+    let resource_name = write_resource_name(instance_name, digest);
+    let size: usize = digest
+        .size_in_bytes
+        .try_into()
+        .with_context(|| format!("Invalid digest size: {}", digest))?;
+
+    let mut offset = 0;
+    let mut attempt = 0;
+
+    loop {
+        let error = Arc::new(Mutex::new(None));
+        let reader = ChunkReader::new(source.clone(), offset).await?;
+        let res = client
+            .write(write_requests(
+                resource_name.clone(),
+                reader,
+                size,
+                error.clone(),
+            ))
+            .await;
+
+        if let Some(e) = error.lock().unwrap().take() {
+            return Err(e);
+        }
+
+        let (committed_size, reason) = match res {
+            Ok(response) => {
+                let committed_size = response.into_inner().committed_size;
+                // A negative size is returned if the blob was already present and the server
+                // stopped the write early.
+                if committed_size < 0 || committed_size as usize >= size {
+                    return Ok(());
+                }
+                (
+                    committed_size,
+                    "the server did not commit all the data".to_owned(),
+                )
+            }
+            Err(status) => {
+                let reason = format!(
+                    "rpc status code: {:?}, message: \"{}\"",
+                    status.code(),
+                    status.message()
+                );
+                match client
+                    .query_write_status(QueryWriteStatusRequest {
+                        resource_name: resource_name.clone(),
+                    })
+                    .await
+                {
+                    Ok(response) if response.get_ref().complete => return Ok(()),
+                    Ok(response) => (response.into_inner().committed_size, reason),
+                    // Nothing reached the server, start over.
+                    Err(status) if status.code() == tonic::Code::NotFound => (0, reason),
+                    Err(_) => {
+                        return Err(anyhow::anyhow!(
+                            "Unable to upload blob '{}', {}",
+                            digest,
+                            reason
+                        ));
+                    }
+                }
+            }
+        };
+
+        attempt += 1;
+        if attempt > WRITE_RETRIES {
+            return Err(anyhow::anyhow!(
+                "Unable to upload blob '{}' after {} attempts, {} bytes committed, last error: {}",
+                digest,
+                attempt,
+                committed_size,
+                reason
+            ));
+        }
+
+        offset = committed_size
+            .try_into()
+            .with_context(|| format!("Invalid committed size for blob '{}'", digest))?;
+    }
+}
+
+/// Downloads a blob through `ByteStream.Read`, writing its contents to `out`.
+pub(crate) async fn read_blob<W: AsyncWrite + Unpin>(
+    client: &ByteStreamClient<GrpcService>,
+    instance_name: &str,
+    digest: &TDigest,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    let mut client = client.clone();
+
+    let mut response = client
+        .read(ReadRequest {
+            resource_name: read_resource_name(instance_name, digest),
+            read_offset: 0,
+            read_limit: 0,
+        })
+        .await
+        .with_context(|| format!("Unable to download blob '{}'", digest))?
+        .into_inner();
+
+    let mut received = 0;
+    let mut hasher = Sha1::new();
+    while let Some(chunk) = response
+        .message()
+        .await
+        .with_context(|| format!("Error downloading blob '{}'", digest))?
+    {
+        received += chunk.data.len() as i64;
+        hasher.update(&chunk.data);
+        out.write_all(&chunk.data).await?;
+    }
+    out.flush().await?;
+
+    if received != digest.size_in_bytes {
+        return Err(anyhow::anyhow!(
+            "Downloaded blob '{}' has size {}, expected {}",
+            digest,
+            received,
+            digest.size_in_bytes
+        ));
+    }
+    let hash = hex::encode(hasher.finalize());
+    if hash != digest.hash {
+        return Err(anyhow::anyhow!(
+            "Downloaded blob '{}' has hash {}",
+            digest,
+            hash
+        ));
+    }
+
+    Ok(())
+}
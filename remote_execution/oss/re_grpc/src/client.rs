@@ -7,23 +7,57 @@
  * of this source tree.
  */
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::future;
 use std::pin::Pin;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use buck2_core::fs::fs_util;
 use futures::stream;
 use futures::Stream;
 use gazebo::prelude::*;
+use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_client::ActionCacheClient;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_client::CapabilitiesClient;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
 use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_client::ContentAddressableStorageClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::execution_client::ExecutionClient;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
 use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputDirectory;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputFile;
+use re_grpc_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
+use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
+use sha1::Digest as _;
+use sha1::Sha1;
 use slog::*;
+use tokio::io::AsyncWriteExt;
+use tonic::metadata::AsciiMetadataKey;
+use tonic::metadata::AsciiMetadataValue;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Certificate;
 use tonic::transport::Channel;
+use tonic::transport::ClientTlsConfig;
+use tonic::transport::Identity;
 
+use crate::bytestream;
+use crate::bytestream::BlobSource;
 use crate::config::*;
 use crate::error::*;
+use crate::find_missing_cache::FindMissingCache;
 use crate::metadata::*;
 use crate::request::*;
 use crate::response::*;
@@ -31,6 +65,29 @@ use crate::response::*;
 // TODO(aloiscochard): Get instance_name from settings, what key? need a new one?
 const INSTANCE_NAME: &str = "";
 
+/// Blobs larger than this are transferred through ByteStream, and batch requests are split so
+/// their total size stays under it. This matches the default gRPC message size limit, servers may
+/// advertise a different one.
+const DEFAULT_MAX_TOTAL_BATCH_SIZE: usize = 4 * 1000 * 1000;
+
+/// How long a blob is assumed to stay in the CAS once we know it is there. The REAPI does not
+/// expose real TTLs, this needs to be comfortably above the leeway buck2 asks for when deciding
+/// whether to upload inputs.
+const DIGEST_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Digests with less than this left of their `DIGEST_TTL` are checked again with
+/// `FindMissingBlobs`. This must be above the leeway buck2 asks for, otherwise the blobs would be
+/// uploaded again instead.
+const DIGEST_REFRESH_BELOW: Duration = Duration::from_secs(20 * 60);
+
+const ZSTD_COMPRESSION_LEVEL: i32 = 3;
+
+/// Bounds the memory used to remember the digests known to be in the CAS.
+const FIND_MISSING_CACHE_MAX_ENTRIES: usize = 1_000_000;
+
+/// Maximum number of digests checked in a single `FindMissingBlobs` request.
+const FIND_MISSING_BLOBS_CHUNK_SIZE: usize = 10_000;
+
 #[derive(Default)]
 pub struct REClientBuilder {
     logger: Option<slog::Logger>,
@@ -71,6 +128,362 @@ fn ttimestamp_from(ts: Option<::prost_types::Timestamp>) -> TTimestamp {
     }
 }
 
+/// The digest of `blob`, as computed by buck2.
+fn blob_digest(blob: &[u8]) -> TDigest {
+    TDigest {
+        hash: hex::encode(Sha1::digest(blob)),
+        size_in_bytes: blob.len() as i64,
+        ..Default::default()
+    }
+}
+
+/// Decompresses a blob returned by the CAS and checks that it matches `digest`.
+fn decode_blob(digest: &TDigest, compressor_id: i32, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    let blob = if compressor_id == compressor::Value::Identity as i32 {
+        data
+    } else if compressor_id == compressor::Value::Zstd as i32 {
+        zstd::bulk::decompress(&data, digest.size_in_bytes as usize)
+            .with_context(|| format!("Error decompressing blob '{}'", digest))?
+    } else {
+        return Err(anyhow::anyhow!(
+            "Blob '{}' uses unsupported compressor {}",
+            digest,
+            compressor_id
+        ));
+    };
+
+    let actual = blob_digest(&blob);
+    if actual.size_in_bytes != digest.size_in_bytes || actual.hash != digest.hash {
+        return Err(anyhow::anyhow!(
+            "Blob '{}' does not match its digest, got '{}'",
+            digest,
+            actual
+        ));
+    }
+    Ok(blob)
+}
+
+/// Splits `items` into batches whose total size does not exceed `max_total_size`. Items larger
+/// than that end up in a batch of their own.
+fn split_batches<T>(
+    items: Vec<T>,
+    max_total_size: usize,
+    size: impl Fn(&T) -> usize,
+) -> Vec<Vec<T>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_size = 0;
+
+    for item in items {
+        let item_size = size(&item);
+        if !batch.is_empty() && batch_size + item_size > max_total_size {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch_size += item_size;
+        batch.push(item);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+/// Creates (or truncates) the file a downloaded blob is written to.
+async fn create_file(f: &NamedDigestWithPermissions) -> anyhow::Result<tokio::fs::File> {
+    let mut opts = tokio::fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    opts.mode(if f.is_executable { 0o755 } else { 0o644 });
+
+    opts.open(&f.named_digest.name)
+        .await
+        .with_context(|| format!("Error creating `{}`", f.named_digest.name))
+}
+
+fn ttimestamp_to(ts: TTimestamp) -> Option<::prost_types::Timestamp> {
+    Some(::prost_types::Timestamp {
+        seconds: ts.seconds,
+        nanos: ts.nanos,
+    })
+}
+
+fn convert_execute_response(
+    execute_response: GExecuteResponse,
+    action_digest: TDigest,
+) -> anyhow::Result<ExecuteResponse> {
+    // note: the execute_response.status field is undefined when response is successful
+    let status = execute_response.status.unwrap_or_default();
+    let code = TCode(status.code);
+    let message = if code == TCode::OK {
+        execute_response.message
+    } else {
+        status.message
+    };
+
+    // Executions that failed (e.g. by exceeding the action's timeout) may not have a
+    // result at all.
+    let action_result = match execute_response.result {
+        Some(action_result) => convert_action_result(action_result)?,
+        None if code != TCode::OK => TActionResult2::default(),
+        None => return Err(anyhow::anyhow!("The action result is not defined.")),
+    };
+
+    Ok(ExecuteResponse {
+        action_result,
+        action_result_digest: TDigest::default(),
+        action_result_ttl: 0,
+        error: REError {
+            code,
+            message,
+            error_location: ErrorLocation(0),
+        },
+        cached_result: execute_response.cached_result,
+        action_digest,
+    })
+}
+
+fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
+    let execution_metadata = action_result
+        .execution_metadata
+        .with_context(|| "The execution metadata are not defined.")?;
+
+    let output_files = action_result.output_files.into_try_map(|output_file| {
+        let output_file_digest = output_file.digest.with_context(|| "Digest not found.")?;
+
+        anyhow::Ok(TFile {
+            digest: DigestWithStatus {
+                status: tstatus_ok(),
+                digest: tdigest_from(output_file_digest),
+                _dot_dot_default: (),
+            },
+            name: output_file.path,
+            existed: false,
+            executable: output_file.is_executable,
+            ttl: 0,
+            _dot_dot_default: (),
+        })
+    })?;
+
+    let output_directories = action_result
+        .output_directories
+        .into_try_map(|output_directory| {
+            let digest = tdigest_from(
+                output_directory
+                    .tree_digest
+                    .with_context(|| "Tree digest not defined.")?,
+            );
+            anyhow::Ok(TDirectory2 {
+                path: output_directory.path,
+                tree_digest: digest.clone(),
+                root_directory_digest: digest,
+                _dot_dot_default: (),
+            })
+        })?;
+
+    Ok(TActionResult2 {
+        output_files,
+        output_directories,
+        exit_code: action_result.exit_code,
+        stdout_raw: Some(action_result.stdout_raw),
+        stdout_digest: action_result.stdout_digest.map(tdigest_from),
+        stderr_raw: Some(action_result.stderr_raw),
+        stderr_digest: action_result.stderr_digest.map(tdigest_from),
+
+        execution_metadata: TExecutedActionMetadata {
+            worker: execution_metadata.worker,
+            queued_timestamp: ttimestamp_from(execution_metadata.queued_timestamp),
+            worker_start_timestamp: ttimestamp_from(execution_metadata.worker_start_timestamp),
+            worker_completed_timestamp: ttimestamp_from(
+                execution_metadata.worker_completed_timestamp,
+            ),
+            input_fetch_start_timestamp: ttimestamp_from(
+                execution_metadata.input_fetch_start_timestamp,
+            ),
+            input_fetch_completed_timestamp: ttimestamp_from(
+                execution_metadata.input_fetch_completed_timestamp,
+            ),
+            execution_start_timestamp: ttimestamp_from(
+                execution_metadata.execution_start_timestamp,
+            ),
+            execution_completed_timestamp: ttimestamp_from(
+                execution_metadata.execution_completed_timestamp,
+            ),
+            output_upload_start_timestamp: ttimestamp_from(
+                execution_metadata.output_upload_start_timestamp,
+            ),
+            output_upload_completed_timestamp: ttimestamp_from(
+                execution_metadata.output_upload_completed_timestamp,
+            ),
+            input_analyzing_start_timestamp: Default::default(),
+            input_analyzing_completed_timestamp: Default::default(),
+            execution_dir: "".to_owned(),
+            execution_attempts: 0,
+            last_queued_timestamp: Default::default(),
+            _dot_dot_default: (),
+        },
+        _dot_dot_default: (),
+    })
+}
+
+fn convert_t_action_result2(t_action_result: TActionResult2) -> ActionResult {
+    let t_execution_metadata = t_action_result.execution_metadata;
+    let execution_metadata = Some(ExecutedActionMetadata {
+        worker: t_execution_metadata.worker,
+        queued_timestamp: ttimestamp_to(t_execution_metadata.queued_timestamp),
+        worker_start_timestamp: ttimestamp_to(t_execution_metadata.worker_start_timestamp),
+        worker_completed_timestamp: ttimestamp_to(t_execution_metadata.worker_completed_timestamp),
+        input_fetch_start_timestamp: ttimestamp_to(
+            t_execution_metadata.input_fetch_start_timestamp,
+        ),
+        input_fetch_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.input_fetch_completed_timestamp,
+        ),
+        execution_start_timestamp: ttimestamp_to(t_execution_metadata.execution_start_timestamp),
+        execution_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.execution_completed_timestamp,
+        ),
+        virtual_execution_duration: None,
+        output_upload_start_timestamp: ttimestamp_to(
+            t_execution_metadata.output_upload_start_timestamp,
+        ),
+        output_upload_completed_timestamp: ttimestamp_to(
+            t_execution_metadata.output_upload_completed_timestamp,
+        ),
+        auxiliary_metadata: Vec::new(),
+    });
+
+    let output_files = t_action_result
+        .output_files
+        .into_map(|output_file| OutputFile {
+            digest: Some(tdigest_to(output_file.digest.digest)),
+            path: output_file.name,
+            is_executable: output_file.executable,
+            contents: Vec::new(),
+            node_properties: None,
+        });
+
+    let output_directories = t_action_result
+        .output_directories
+        .into_map(|output_directory| OutputDirectory {
+            path: output_directory.path,
+            tree_digest: Some(tdigest_to(output_directory.tree_digest)),
+            is_topologically_sorted: false,
+        });
+
+    ActionResult {
+        output_files,
+        output_file_symlinks: Vec::new(),
+        output_symlinks: Vec::new(),
+        output_directories,
+        output_directory_symlinks: Vec::new(),
+        exit_code: t_action_result.exit_code,
+        stdout_raw: t_action_result.stdout_raw.unwrap_or_default(),
+        stdout_digest: t_action_result.stdout_digest.map(tdigest_to),
+        stderr_raw: t_action_result.stderr_raw.unwrap_or_default(),
+        stderr_digest: t_action_result.stderr_digest.map(tdigest_to),
+        execution_metadata,
+    }
+}
+
+/// How often `http_headers_command` is re-run by default.
+const DEFAULT_HTTP_HEADERS_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Runs the configured `http_headers_command` and parses the headers it prints, one per line.
+async fn run_http_headers_command(command: &[String]) -> anyhow::Result<Vec<HttpHeader>> {
+    let (program, args) = command
+        .split_first()
+        .context("The HTTP headers command is empty")?;
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("Error running HTTP headers command `{}`", program))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "HTTP headers command `{}` failed with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .with_context(|| format!("HTTP headers command `{}` printed invalid UTF-8", program))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(HttpHeader::from_str)
+        .collect()
+}
+
+async fn create_tls_config(cfg: &TLSCfg) -> anyhow::Result<ClientTlsConfig> {
+    let mut tls_config = ClientTlsConfig::new();
+    if let Some(ca_certs) = &cfg.ca_certs {
+        let pem = tokio::fs::read(ca_certs)
+            .await
+            .with_context(|| format!("Error reading CA certificates `{}`", ca_certs))?;
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(pem));
+    }
+    match (&cfg.client_cert, &cfg.client_key) {
+        (Some(client_cert), Some(client_key)) => {
+            let cert = tokio::fs::read(client_cert)
+                .await
+                .with_context(|| format!("Error reading client certificate `{}`", client_cert))?;
+            let key = tokio::fs::read(client_key)
+                .await
+                .with_context(|| format!("Error reading client key `{}`", client_key))?;
+            tls_config = tls_config.identity(Identity::from_pem(cert, key));
+        }
+        (None, None) => {}
+        _ => {
+            return Err(anyhow::anyhow!(
+                "A client certificate and a client key must be configured together"
+            ));
+        }
+    }
+    Ok(tls_config)
+}
+
+/// The cache capabilities of the server, or `None` if it does not implement the Capabilities
+/// service.
+async fn get_cache_capabilities(service: GrpcService) -> Option<CacheCapabilities> {
+    CapabilitiesClient::new(service)
+        .get_capabilities(GetCapabilitiesRequest {
+            instance_name: INSTANCE_NAME.into(),
+        })
+        .await
+        .ok()?
+        .into_inner()
+        .cache_capabilities
+}
+
+/// Connects to `address`. TLS is used for `https://` addresses, `grpc://` and `grpcs://` are
+/// accepted as aliases of `http://` and `https://`.
+async fn create_channel(address: &str, tls: &TLSCfg) -> anyhow::Result<Channel> {
+    let address = if let Some(rest) = address.strip_prefix("grpc://") {
+        format!("http://{}", rest)
+    } else if let Some(rest) = address.strip_prefix("grpcs://") {
+        format!("https://{}", rest)
+    } else {
+        address.to_owned()
+    };
+
+    let mut endpoint = Channel::from_shared(address.clone())
+        .with_context(|| format!("Invalid address `{}`", address))?;
+    if address.starts_with("https://") {
+        endpoint = endpoint
+            .tls_config(create_tls_config(tls).await?)
+            .with_context(|| format!("Invalid TLS configuration for `{}`", address))?;
+    }
+
+    endpoint
+        .connect()
+        .await
+        .with_context(|| format!("Error connecting to `{}`", address))
+}
+
 impl REClientBuilder {
     pub fn new<T>(_fb_init: T) -> Self {
         REClientBuilder::default()
@@ -97,17 +510,77 @@ impl REClientBuilder {
         };
 
         let cfg = self.cfg.unwrap_or_default();
-        let address = cfg
-            .execution_client_config
+
+        let CASDaemonClientCfg::embedded_config(cas_config) = &cfg.cas_client_config;
+        let execution_address = cfg.execution_client_config.address.clone();
+        let cas_address = cas_config
+            .address
+            .clone()
+            .or_else(|| execution_address.clone())
+            .context("Neither a CAS nor an execution address is defined")?;
+        // The action cache is usually served next to the CAS, and every service is usually
+        // served from a single address, so only the addresses that differ need configuring.
+        let action_cache_address = cfg
+            .action_cache_client_config
             .address
-            .context("Execution client address not defined")?;
+            .clone()
+            .unwrap_or_else(|| cas_address.clone());
+        let execution_address = execution_address.unwrap_or_else(|| cas_address.clone());
+
+        let mut headers = cfg.http_headers.clone();
+        if let Some(command) = &cfg.http_headers_command {
+            headers.extend(run_http_headers_command(command).await?);
+        }
+        let interceptor = InjectHeadersInterceptor::new(&headers)?;
+        if let Some(command) = &cfg.http_headers_command {
+            spawn_http_headers_refresh(
+                &interceptor,
+                logger.clone(),
+                cfg.http_headers.clone(),
+                command.clone(),
+                cfg.http_headers_refresh_interval
+                    .unwrap_or(DEFAULT_HTTP_HEADERS_REFRESH_INTERVAL),
+            );
+        }
+
+        let mut channels: HashMap<&str, Channel> = HashMap::new();
+        for address in [&cas_address, &action_cache_address, &execution_address] {
+            if !channels.contains_key(address.as_str()) {
+                channels.insert(address, create_channel(address, &cfg.tls_config).await?);
+            }
+        }
+        let service =
+            |address: &str| InterceptedService::new(channels[address].clone(), interceptor.clone());
 
         let grpc_clients = GRPCClients {
-            cas_client: ContentAddressableStorageClient::connect(address.clone()).await?,
-            execution_client: ExecutionClient::connect(address).await?,
+            cas_client: ContentAddressableStorageClient::new(service(&cas_address)),
+            bytestream_client: ByteStreamClient::new(service(&cas_address)),
+            execution_client: ExecutionClient::new(service(&execution_address)),
+            action_cache_client: ActionCacheClient::new(service(&action_cache_address)),
         };
 
-        Ok(REClient::new(logger, grpc_clients))
+        let cache_capabilities = get_cache_capabilities(service(&cas_address)).await;
+
+        let mut client = REClient::new(logger, grpc_clients);
+        if let Some(capabilities) = &cache_capabilities {
+            if capabilities.max_batch_total_size_bytes > 0 {
+                client.max_total_batch_size = std::cmp::min(
+                    client.max_total_batch_size,
+                    capabilities.max_batch_total_size_bytes as usize,
+                );
+            }
+        }
+        if cfg.use_zstd_compression {
+            // Servers return blobs uncompressed if they don't support any of the acceptable
+            // compressors, but uploads must only use the compressors they advertise.
+            client.zstd_batch_reads = true;
+            client.zstd_batch_updates = cache_capabilities.map_or(false, |c| {
+                c.supported_batch_update_compressors
+                    .contains(&(compressor::Value::Zstd as i32))
+            });
+        }
+
+        Ok(client)
     }
 
     pub fn with_config(mut self, cfg: ClientCfg) -> Self {
@@ -121,9 +594,94 @@ impl REClientBuilder {
     }
 }
 
+/// Re-runs `command` every `interval` for as long as `interceptor` is in use, so that the
+/// requests carry fresh tokens. Failures are logged, and the previous headers kept.
+fn spawn_http_headers_refresh(
+    interceptor: &InjectHeadersInterceptor,
+    logger: Logger,
+    static_headers: Vec<HttpHeader>,
+    command: Vec<String>,
+    interval: Duration,
+) {
+    let headers = Arc::downgrade(&interceptor.headers);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            let interceptor = match headers.upgrade() {
+                Some(headers) => InjectHeadersInterceptor { headers },
+                None => break,
+            };
+            let res = run_http_headers_command(&command).await.and_then(|extra| {
+                let mut headers = static_headers.clone();
+                headers.extend(extra);
+                interceptor.set(&headers)
+            });
+            if let Err(e) = res {
+                warn!(logger, "Error refreshing the HTTP headers: {:#}", e);
+            }
+        }
+    });
+}
+
+type ParsedHeaders = Vec<(AsciiMetadataKey, AsciiMetadataValue)>;
+
+/// Adds the configured headers, e.g. authentication tokens, to every request.
+#[derive(Clone)]
+pub(crate) struct InjectHeadersInterceptor {
+    headers: Arc<RwLock<Arc<ParsedHeaders>>>,
+}
+
+impl InjectHeadersInterceptor {
+    fn new(headers: &[HttpHeader]) -> anyhow::Result<Self> {
+        Ok(Self {
+            headers: Arc::new(RwLock::new(Arc::new(Self::parse(headers)?))),
+        })
+    }
+
+    /// Replaces the headers of the requests made from now on.
+    fn set(&self, headers: &[HttpHeader]) -> anyhow::Result<()> {
+        let headers = Arc::new(Self::parse(headers)?);
+        *self.headers.write().unwrap() = headers;
+        Ok(())
+    }
+
+    fn parse(headers: &[HttpHeader]) -> anyhow::Result<ParsedHeaders> {
+        headers.try_map(|header| {
+            anyhow::Ok((
+                header
+                    .key
+                    .parse::<AsciiMetadataKey>()
+                    .with_context(|| format!("Invalid header name `{}`", header.key))?,
+                header
+                    .value
+                    .parse::<AsciiMetadataValue>()
+                    .with_context(|| format!("Invalid value for header `{}`", header.key))?,
+            ))
+        })
+    }
+}
+
+impl Interceptor for InjectHeadersInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> std::result::Result<tonic::Request<()>, tonic::Status> {
+        let headers = self.headers.read().unwrap().clone();
+        for (key, value) in headers.iter() {
+            request.metadata_mut().insert(key.clone(), value.clone());
+        }
+        Ok(request)
+    }
+}
+
+pub(crate) type GrpcService = InterceptedService<Channel, InjectHeadersInterceptor>;
+
 pub struct GRPCClients {
-    cas_client: ContentAddressableStorageClient<Channel>,
-    execution_client: ExecutionClient<Channel>,
+    cas_client: ContentAddressableStorageClient<GrpcService>,
+    bytestream_client: ByteStreamClient<GrpcService>,
+    execution_client: ExecutionClient<GrpcService>,
+    action_cache_client: ActionCacheClient<GrpcService>,
 }
 
 #[derive(Default)]
@@ -137,6 +695,12 @@ pub struct REClient {
     logger: Logger,
     grpc_clients: GRPCClients,
     state: Mutex<REState>,
+    max_total_batch_size: usize,
+    find_missing_cache: FindMissingCache,
+    /// Accept zstd compressed blobs in `BatchReadBlobs` responses.
+    zstd_batch_reads: bool,
+    /// Compress blobs with zstd in `BatchUpdateBlobs` requests.
+    zstd_batch_updates: bool,
 }
 
 impl Drop for REClient {
@@ -152,6 +716,14 @@ impl REClient {
             logger,
             grpc_clients,
             state: Mutex::new(REState::default()),
+            max_total_batch_size: DEFAULT_MAX_TOTAL_BATCH_SIZE,
+            find_missing_cache: FindMissingCache::new(
+                DIGEST_TTL,
+                DIGEST_REFRESH_BELOW,
+                FIND_MISSING_CACHE_MAX_ENTRIES,
+            ),
+            zstd_batch_reads: false,
+            zstd_batch_updates: false,
         }
     }
 
@@ -160,15 +732,68 @@ impl REClient {
         _metadata: RemoteExecutionMetadata,
         request: ActionResultRequest,
     ) -> anyhow::Result<ActionResultResponse> {
-        Err(anyhow::anyhow!("Not found: {}", request.digest))
+        let mut client = self.grpc_clients.action_cache_client.clone();
+
+        let res = client
+            .get_action_result(GetActionResultRequest {
+                instance_name: INSTANCE_NAME.into(),
+                action_digest: Some(tdigest_to(request.digest.clone())),
+                inline_stdout: false,
+                inline_stderr: false,
+                inline_output_files: Vec::new(),
+            })
+            .await;
+
+        let action_result = match res {
+            Ok(r) => r.into_inner(),
+            // A cache miss is reported as NOT_FOUND, callers expect it as a `REClientError` so
+            // they can tell it apart from actual failures.
+            Err(status) if status.code() == tonic::Code::NotFound => {
+                return Err(REClientError {
+                    code: TCode::NOT_FOUND,
+                    message: format!("Action result not found: {}", request.digest),
+                }
+                .into());
+            }
+            Err(status) => {
+                return Err(anyhow::anyhow!(
+                    "Unable to get action result for '{}', rpc status code: {:?}, message: \"{}\"",
+                    request.digest,
+                    status.code(),
+                    status.message()
+                ));
+            }
+        };
+
+        Ok(ActionResultResponse {
+            action_result: convert_action_result(action_result)?,
+            ttl: 0,
+        })
     }
 
     pub async fn write_action_result(
         &self,
         _metadata: RemoteExecutionMetadata,
-        _request: WriteActionResultRequest,
+        request: WriteActionResultRequest,
     ) -> anyhow::Result<WriteActionResultResponse> {
-        Err(anyhow::anyhow!("Not supported"))
+        let mut client = self.grpc_clients.action_cache_client.clone();
+
+        client
+            .update_action_result(UpdateActionResultRequest {
+                instance_name: INSTANCE_NAME.into(),
+                action_digest: Some(tdigest_to(request.action_digest.clone())),
+                action_result: Some(convert_t_action_result2(request.action_result)),
+                results_cache_policy: None,
+            })
+            .await
+            .with_context(|| {
+                format!(
+                    "Unable to write action result for '{}'",
+                    request.action_digest
+                )
+            })?;
+
+        Ok(WriteActionResultResponse {})
     }
 
     pub async fn execute_with_progress(
@@ -180,7 +805,6 @@ impl REClient {
     > {
         use prost::Message;
         use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest as GExecuteRequest;
-        use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
         // TODO(aloiscochard): Map those properly in the request
         // use crate::proto::build::bazel::remote::execution::v2::ExecutionPolicy;
         use re_grpc_proto::build::bazel::remote::execution::v2::ResultsCachePolicy;
@@ -219,106 +843,8 @@ impl REClient {
             OpResult::Response(any) => {
                 let execute_response_grpc: GExecuteResponse =
                     GExecuteResponse::decode(&any.value[..])?;
-                // note: the execute_response_grpc.status field is undefined when response is successful
-                let action_result = execute_response_grpc
-                    .result
-                    .with_context(|| "The action result is not defined.")?;
-
-                let execution_metadata = action_result
-                    .execution_metadata
-                    .with_context(|| "The execution metadata are not defined.")?;
-
-                let output_files = action_result.output_files.into_try_map(|output_file| {
-                    let output_file_digest =
-                        output_file.digest.with_context(|| "Digest not found.")?;
-
-                    anyhow::Ok(TFile {
-                        digest: DigestWithStatus {
-                            status: tstatus_ok(),
-                            digest: tdigest_from(output_file_digest),
-                            _dot_dot_default: (),
-                        },
-                        name: output_file.path,
-                        existed: false,
-                        executable: output_file.is_executable,
-                        ttl: 0,
-                        _dot_dot_default: (),
-                    })
-                })?;
-
-                let output_directories =
-                    action_result
-                        .output_directories
-                        .into_try_map(|output_directory| {
-                            let digest = tdigest_from(
-                                output_directory
-                                    .tree_digest
-                                    .with_context(|| "Tree digest not defined.")?,
-                            );
-                            anyhow::Ok(TDirectory2 {
-                                path: output_directory.path,
-                                tree_digest: digest.clone(),
-                                root_directory_digest: digest,
-                                _dot_dot_default: (),
-                            })
-                        })?;
-
-                let execute_response = ExecuteResponse {
-                    action_result: TActionResult2 {
-                        output_files,
-                        output_directories,
-                        exit_code: action_result.exit_code,
-                        stdout_raw: Some(action_result.stdout_raw),
-                        stdout_digest: action_result.stdout_digest.map(tdigest_from),
-                        stderr_raw: Some(action_result.stderr_raw),
-                        stderr_digest: action_result.stderr_digest.map(tdigest_from),
-
-                        execution_metadata: TExecutedActionMetadata {
-                            worker: execution_metadata.worker,
-                            queued_timestamp: ttimestamp_from(execution_metadata.queued_timestamp),
-                            worker_start_timestamp: ttimestamp_from(
-                                execution_metadata.worker_start_timestamp,
-                            ),
-                            worker_completed_timestamp: ttimestamp_from(
-                                execution_metadata.worker_completed_timestamp,
-                            ),
-                            input_fetch_start_timestamp: ttimestamp_from(
-                                execution_metadata.input_fetch_start_timestamp,
-                            ),
-                            input_fetch_completed_timestamp: ttimestamp_from(
-                                execution_metadata.input_fetch_completed_timestamp,
-                            ),
-                            execution_start_timestamp: ttimestamp_from(
-                                execution_metadata.execution_start_timestamp,
-                            ),
-                            execution_completed_timestamp: ttimestamp_from(
-                                execution_metadata.execution_completed_timestamp,
-                            ),
-                            output_upload_start_timestamp: ttimestamp_from(
-                                execution_metadata.output_upload_start_timestamp,
-                            ),
-                            output_upload_completed_timestamp: ttimestamp_from(
-                                execution_metadata.output_upload_completed_timestamp,
-                            ),
-                            input_analyzing_start_timestamp: Default::default(),
-                            input_analyzing_completed_timestamp: Default::default(),
-                            execution_dir: "".to_owned(),
-                            execution_attempts: 0,
-                            last_queued_timestamp: Default::default(),
-                            _dot_dot_default: (),
-                        },
-                        _dot_dot_default: (),
-                    },
-                    action_result_digest: TDigest::default(),
-                    action_result_ttl: 0,
-                    error: REError {
-                        code: TCode::OK,
-                        message: execute_response_grpc.message,
-                        error_location: ErrorLocation(0),
-                    },
-                    cached_result: execute_response_grpc.cached_result,
-                    action_digest: action_tdigest.clone(),
-                };
+                let execute_response =
+                    convert_execute_response(execute_response_grpc, action_tdigest)?;
 
                 Ok(Box::pin(stream::once(future::ready(Ok(
                     ExecuteWithProgressResponse {
@@ -335,39 +861,95 @@ impl REClient {
         _metadata: RemoteExecutionMetadata,
         request: UploadRequest,
     ) -> anyhow::Result<UploadResponse> {
+        let mut batched_requests = Vec::new();
+        let mut streamed_blobs = Vec::new();
+        let mut uploaded_digests = Vec::new();
+
+        for x in request.inlined_blobs_with_digest.unwrap_or_default() {
+            uploaded_digests.push(x.digest.clone());
+            if self.is_streamed(&x.digest) {
+                streamed_blobs.push((x.digest, BlobSource::Bytes(Arc::new(x.blob))));
+            } else {
+                batched_requests.push(self.batch_update_request(x.digest, x.blob)?);
+            }
+        }
+
+        for x in request.files_with_digest.unwrap_or_default() {
+            uploaded_digests.push(x.digest.clone());
+            if self.is_streamed(&x.digest) {
+                streamed_blobs.push((x.digest, BlobSource::File(x.name)));
+            } else {
+                // FIXME: This could do a lot of blocking reads
+                let data = fs_util::read(&x.name)?;
+                batched_requests.push(self.batch_update_request(x.digest, data)?);
+            }
+        }
+
+        let batches = split_batches(batched_requests, self.max_total_batch_size, |r| {
+            r.data.len()
+        });
+
+        let batched_uploads =
+            futures::future::try_join_all(batches.into_map(|batch| self.batch_update_blobs(batch)));
+        let streamed_uploads =
+            futures::future::try_join_all(streamed_blobs.into_map(|(digest, source)| async move {
+                bytestream::write_blob(
+                    &self.grpc_clients.bytestream_client,
+                    INSTANCE_NAME,
+                    &digest,
+                    source,
+                )
+                .await?;
+                debug!(self.logger, "uploaded (streamed): {}", digest);
+                anyhow::Ok(())
+            }));
+
+        futures::future::try_join(batched_uploads, streamed_uploads).await?;
+        self.find_missing_cache
+            .insert_all(uploaded_digests, Instant::now());
+
+        // TODO(aloiscochard): Add something interesting in UploadResponse?
+        Ok(UploadResponse {})
+    }
+
+    fn batch_update_request(
+        &self,
+        digest: TDigest,
+        data: Vec<u8>,
+    ) -> anyhow::Result<
+        re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_request::Request,
+    > {
         use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_request::Request;
-        use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+
+        let (data, compressor) = if self.zstd_batch_updates {
+            let data = zstd::bulk::compress(&data, ZSTD_COMPRESSION_LEVEL)
+                .with_context(|| format!("Error compressing blob '{}'", digest))?;
+            (data, compressor::Value::Zstd)
+        } else {
+            (data, compressor::Value::Identity)
+        };
+
+        Ok(Request {
+            digest: Some(tdigest_to(digest)),
+            data,
+            compressor: compressor as i32,
+        })
+    }
+
+    async fn batch_update_blobs(
+        &self,
+        requests: Vec<
+            re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_request::Request,
+        >,
+    ) -> anyhow::Result<()> {
         use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsRequest;
         use re_grpc_proto::google::rpc::Code;
 
         let mut client = self.grpc_clients.cas_client.clone();
 
-        let files_with_digest: Vec<Request> = request
-            .files_with_digest
-            .unwrap_or_default()
-            .into_try_map(|x| {
-                anyhow::Ok(Request {
-                    digest: Some(tdigest_to(x.digest)),
-                    // FIXME: This could do a lot of blocking reads
-                    data: fs_util::read(&x.name)?,
-                    compressor: compressor::Value::Identity as i32,
-                })
-            })?;
-
         let re_request = BatchUpdateBlobsRequest {
             instance_name: INSTANCE_NAME.into(),
-            requests: [
-                request
-                    .inlined_blobs_with_digest
-                    .unwrap_or_default()
-                    .into_map(|x| Request {
-                        digest: Some(tdigest_to(x.digest)),
-                        data: x.blob,
-                        compressor: compressor::Value::Identity as i32,
-                    }),
-                files_with_digest,
-            ]
-            .concat(),
+            requests,
         };
 
         let blob_hashes = re_request
@@ -399,8 +981,7 @@ impl REClient {
 
         if failures.is_empty() {
             debug!(self.logger, "uploaded: {:?}", blob_hashes);
-            // TODO(aloiscochard): Add something interesting in UploadResponse?
-            Ok(UploadResponse {})
+            Ok(())
         } else {
             Err(anyhow::anyhow!("Batch upload failed: {:?}", failures))
         }
@@ -408,11 +989,25 @@ impl REClient {
 
     pub async fn upload_blob(
         &self,
-        _blob: Vec<u8>,
-        _metadata: RemoteExecutionMetadata,
+        blob: Vec<u8>,
+        metadata: RemoteExecutionMetadata,
     ) -> anyhow::Result<TDigest> {
-        // TODO(aloiscochard)
-        Err(anyhow::anyhow!("Not implemented (RE upload_blob)"))
+        let digest = blob_digest(&blob);
+
+        self.upload(
+            metadata,
+            UploadRequest {
+                inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                    blob,
+                    digest: digest.clone(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(digest)
     }
 
     pub async fn download(
@@ -420,56 +1015,267 @@ impl REClient {
         _metadata: RemoteExecutionMetadata,
         request: DownloadRequest,
     ) -> anyhow::Result<DownloadResponse> {
-        use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+        let (streamed_inlined_digests, batched_inlined_digests): (Vec<_>, Vec<_>) = request
+            .inlined_digests
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|d| self.is_streamed(d));
+
+        let (streamed_file_digests, batched_file_digests): (Vec<_>, Vec<_>) = request
+            .file_digests
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|f| self.is_streamed(&f.named_digest.digest));
+
+        let batched_inlined = futures::future::try_join_all(
+            split_batches(batched_inlined_digests, self.max_total_batch_size, |d| {
+                d.size_in_bytes as usize
+            })
+            .into_map(|batch| self.batch_read_blobs(batch)),
+        );
+
+        let streamed_inlined =
+            futures::future::try_join_all(streamed_inlined_digests.into_map(|digest| async move {
+                let mut blob = Vec::with_capacity(digest.size_in_bytes as usize);
+                bytestream::read_blob(
+                    &self.grpc_clients.bytestream_client,
+                    INSTANCE_NAME,
+                    &digest,
+                    &mut blob,
+                )
+                .await?;
+                anyhow::Ok(InlinedDigestWithStatus {
+                    digest,
+                    status: tstatus_ok(),
+                    blob,
+                })
+            }));
+
+        let batched_files = futures::future::try_join_all(
+            split_batches(batched_file_digests, self.max_total_batch_size, |f| {
+                f.named_digest.digest.size_in_bytes as usize
+            })
+            .into_map(|batch| async move {
+                let blobs = self
+                    .batch_read_blobs(batch.map(|f| f.named_digest.digest.clone()))
+                    .await?
+                    .into_iter()
+                    .map(|b| (b.digest.clone(), b))
+                    .collect::<HashMap<_, _>>();
+
+                for f in batch {
+                    let blob = blobs.get(&f.named_digest.digest).with_context(|| {
+                        format!("Blob '{}' missing from response", f.named_digest.digest)
+                    })?;
+                    if blob.status.code != TCode::OK {
+                        return Err(REClientError {
+                            code: blob.status.code.clone(),
+                            message: format!(
+                                "Unable to download blob '{}' to `{}`: {}",
+                                f.named_digest.digest, f.named_digest.name, blob.status.message
+                            ),
+                        }
+                        .into());
+                    }
+                    let blob = &blob.blob;
+                    let mut file = create_file(&f).await?;
+                    file.write_all(blob).await?;
+                    file.flush().await?;
+                }
+
+                anyhow::Ok(())
+            }),
+        );
+
+        let streamed_files =
+            futures::future::try_join_all(streamed_file_digests.into_map(|f| async move {
+                let mut file = create_file(&f).await?;
+                bytestream::read_blob(
+                    &self.grpc_clients.bytestream_client,
+                    INSTANCE_NAME,
+                    &f.named_digest.digest,
+                    &mut file,
+                )
+                .await
+                .with_context(|| format!("Error downloading `{}`", f.named_digest.name))
+            }));
+
+        let (batched_inlined, streamed_inlined, _, _) = futures::future::try_join4(
+            batched_inlined,
+            streamed_inlined,
+            batched_files,
+            streamed_files,
+        )
+        .await?;
+
+        let mut blobs = batched_inlined.into_iter().flatten().collect::<Vec<_>>();
+        blobs.extend(streamed_inlined);
+
+        Ok(DownloadResponse {
+            inlined_blobs: Some(blobs),
+            directories: None,
+        })
+    }
+
+    /// Reads blobs from the CAS. Blobs that could not be read, or that don't match their digest,
+    /// are returned with an error status.
+    async fn batch_read_blobs(
+        &self,
+        digests: Vec<TDigest>,
+    ) -> anyhow::Result<Vec<InlinedDigestWithStatus>> {
         use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsRequest;
+        use re_grpc_proto::google::rpc::Code;
 
         let mut client = self.grpc_clients.cas_client.clone();
 
+        let mut acceptable_compressors = vec![compressor::Value::Identity as i32];
+        if self.zstd_batch_reads {
+            acceptable_compressors.push(compressor::Value::Zstd as i32);
+        }
+
         let re_request = BatchReadBlobsRequest {
             instance_name: INSTANCE_NAME.into(),
-            digests: [
-                request
-                    .inlined_digests
-                    .unwrap_or_default()
-                    .into_map(tdigest_to),
-                request
-                    .file_digests
-                    .unwrap_or_default()
-                    .into_map(|x| tdigest_to(x.named_digest.digest)),
-            ]
-            .concat(),
-            acceptable_compressors: vec![compressor::Value::Identity as i32],
+            digests: digests.into_map(tdigest_to),
+            acceptable_compressors,
         };
 
         let response = client.batch_read_blobs(re_request).await?;
 
-        let blobs: Vec<InlinedDigestWithStatus> =
-            response.into_inner().responses.into_try_map(|r| {
-                anyhow::Ok(InlinedDigestWithStatus {
-                    // TODO(aloiscochard): Here we should check if r.status is ok!
-                    digest: tdigest_from(r.digest.with_context(|| "Response digest not found.")?),
+        response.into_inner().responses.into_try_map(|r| {
+            let digest = tdigest_from(r.digest.with_context(|| "Response digest not found.")?);
+            let status = r.status.unwrap_or_default();
+
+            let res = if status.code == Code::Ok as i32 {
+                decode_blob(&digest, r.compressor, r.data).map_err(|e| TStatus {
+                    code: TCode::DATA_LOSS,
+                    message: format!("{:#}", e),
+                    ..Default::default()
+                })
+            } else {
+                Err(TStatus {
+                    code: TCode(status.code),
+                    message: status.message,
+                    ..Default::default()
+                })
+            };
+
+            anyhow::Ok(match res {
+                Ok(blob) => InlinedDigestWithStatus {
+                    digest,
                     status: tstatus_ok(),
-                    blob: r.data,
+                    blob,
+                },
+                Err(status) => InlinedDigestWithStatus {
+                    digest,
+                    status,
+                    blob: Vec::new(),
+                },
+            })
+        })
+    }
+
+    /// Whether this blob is too large to be transferred in batch requests and must go through
+    /// ByteStream.
+    fn is_streamed(&self, digest: &TDigest) -> bool {
+        digest.size_in_bytes as usize > self.max_total_batch_size
+    }
+
+    /// Returns the digests the CAS does not have. This always asks the server, see
+    /// `get_digests_ttl` for a cached alternative.
+    pub async fn find_missing_blobs(
+        &self,
+        _metadata: RemoteExecutionMetadata,
+        request: FindMissingBlobsRequest,
+    ) -> anyhow::Result<FindMissingBlobsResponse> {
+        use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest as GFindMissingBlobsRequest;
+
+        let chunks = request
+            .digests
+            .chunks(FIND_MISSING_BLOBS_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+
+        let responses = futures::future::try_join_all(chunks.into_map(|chunk| async move {
+            let mut client = self.grpc_clients.cas_client.clone();
+            let response = client
+                .find_missing_blobs(GFindMissingBlobsRequest {
+                    instance_name: INSTANCE_NAME.into(),
+                    blob_digests: chunk.into_map(tdigest_to),
                 })
-            })?;
+                .await
+                .map_err(|status| {
+                    anyhow::anyhow!(
+                        "Unable to find missing blobs, rpc status code: {:?}, message: \"{}\"",
+                        status.code(),
+                        status.message()
+                    )
+                })?;
+            anyhow::Ok(response.into_inner().missing_blob_digests)
+        }))
+        .await?;
 
-        Ok(DownloadResponse {
-            inlined_blobs: Some(blobs),
-            directories: None,
+        Ok(FindMissingBlobsResponse {
+            missing_digests: responses.into_iter().flatten().map(tdigest_from).collect(),
         })
     }
 
+    /// Returns how long each digest is expected to remain in the CAS. Digests that are missing
+    /// have a TTL of 0. The REAPI has no TTLs, so present digests are given a fixed one, and are
+    /// not checked again with `FindMissingBlobs` until it has nearly elapsed.
     pub async fn get_digests_ttl(
         &self,
-        _metadata: RemoteExecutionMetadata,
+        metadata: RemoteExecutionMetadata,
         request: GetDigestsTtlRequest,
     ) -> anyhow::Result<GetDigestsTtlResponse> {
-        // TODO(aloiscochard): For now we just say all digests have expired,
-        // we should fetch and return real TTL instead
+        let now = Instant::now();
+
+        let mut ttls = HashMap::new();
+        let mut unknown = Vec::new();
+        for digest in &request.digests {
+            if ttls.contains_key(digest) {
+                continue;
+            }
+            match self.find_missing_cache.remaining_ttl(digest, now) {
+                Some(ttl) => {
+                    ttls.insert(digest.clone(), ttl.as_secs() as i64);
+                }
+                None => {
+                    ttls.insert(digest.clone(), 0);
+                    unknown.push(digest.clone());
+                }
+            }
+        }
+
+        if !unknown.is_empty() {
+            let missing = self
+                .find_missing_blobs(
+                    metadata,
+                    FindMissingBlobsRequest {
+                        digests: unknown.clone(),
+                        ..Default::default()
+                    },
+                )
+                .await?
+                .missing_digests
+                .into_iter()
+                .collect::<HashSet<_>>();
+
+            let present = unknown
+                .into_iter()
+                .filter(|digest| !missing.contains(digest))
+                .collect::<Vec<_>>();
+            let ttl = self.find_missing_cache.ttl().as_secs() as i64;
+            for digest in &present {
+                ttls.insert(digest.clone(), ttl);
+            }
+            self.find_missing_cache.insert_all(present, now);
+        }
+
         Ok(GetDigestsTtlResponse {
-            digests_with_ttl: request
-                .digests
-                .into_map(|digest| DigestWithTtl { digest, ttl: 0 }),
+            digests_with_ttl: request.digests.into_map(|digest| {
+                let ttl = ttls[&digest];
+                DigestWithTtl { digest, ttl }
+            }),
         })
     }
 
@@ -507,3 +1313,612 @@ impl REClient {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use re_grpc_proto::google::rpc::Status;
+
+    use super::*;
+    use crate::test_server::spawn_and_connect;
+    use crate::test_server::spawn_and_connect_with;
+    use crate::test_server::TestServerState;
+
+    fn tdigest(hash: &str, size_in_bytes: i64) -> TDigest {
+        TDigest {
+            hash: hash.to_owned(),
+            size_in_bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_convert_execute_response_timeout() -> anyhow::Result<()> {
+        // Executors report actions that exceeded their timeout without a result.
+        let response = convert_execute_response(
+            GExecuteResponse {
+                status: Some(Status {
+                    code: TCode::DEADLINE_EXCEEDED.0,
+                    message: "Action timed out".to_owned(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            tdigest("aa", 1),
+        )?;
+        assert_eq!(TCode::DEADLINE_EXCEEDED, response.error.code);
+        assert_eq!("Action timed out", response.error.message);
+        assert_eq!(tdigest("aa", 1), response.action_digest);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_execute_response_ok() -> anyhow::Result<()> {
+        let response = convert_execute_response(
+            GExecuteResponse {
+                result: Some(ActionResult {
+                    exit_code: 1,
+                    execution_metadata: Some(ExecutedActionMetadata::default()),
+                    ..Default::default()
+                }),
+                message: "Done".to_owned(),
+                ..Default::default()
+            },
+            tdigest("aa", 1),
+        )?;
+        assert_eq!(TCode::OK, response.error.code);
+        assert_eq!("Done", response.error.message);
+        assert_eq!(1, response.action_result.exit_code);
+
+        // Successful executions must have a result.
+        assert!(convert_execute_response(GExecuteResponse::default(), tdigest("aa", 1)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_action_result_not_found() -> anyhow::Result<()> {
+        let (_state, client) = spawn_and_connect().await?;
+
+        let err = match client
+            .get_action_result(
+                RemoteExecutionMetadata::default(),
+                ActionResultRequest {
+                    digest: tdigest("aa", 1),
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(_) => panic!("Expected a cache miss"),
+            Err(e) => e,
+        };
+
+        assert_eq!(
+            err.downcast_ref::<REClientError>().map(|e| e.code.clone()),
+            Some(TCode::NOT_FOUND)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_then_get_action_result() -> anyhow::Result<()> {
+        let (state, client) = spawn_and_connect().await?;
+
+        let action_result = TActionResult2 {
+            output_files: vec![TFile {
+                digest: DigestWithStatus {
+                    digest: tdigest("bb", 3),
+                    status: tstatus_ok(),
+                    ..Default::default()
+                },
+                name: "out/file".to_owned(),
+                executable: true,
+                ..Default::default()
+            }],
+            output_directories: vec![TDirectory2 {
+                path: "out/dir".to_owned(),
+                tree_digest: tdigest("cc", 4),
+                root_directory_digest: tdigest("cc", 4),
+                ..Default::default()
+            }],
+            exit_code: 1,
+            stderr_digest: Some(tdigest("dd", 5)),
+            execution_metadata: TExecutedActionMetadata {
+                worker: "worker".to_owned(),
+                execution_start_timestamp: TTimestamp {
+                    seconds: 10,
+                    nanos: 0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        client
+            .write_action_result(
+                RemoteExecutionMetadata::default(),
+                WriteActionResultRequest {
+                    action_digest: tdigest("aa", 1),
+                    action_result,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        assert_eq!(state.action_results.lock().unwrap().len(), 1);
+
+        let res = client
+            .get_action_result(
+                RemoteExecutionMetadata::default(),
+                ActionResultRequest {
+                    digest: tdigest("aa", 1),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .action_result;
+
+        assert_eq!(res.exit_code, 1);
+        assert_eq!(res.output_files.len(), 1);
+        assert_eq!(res.output_files[0].name, "out/file");
+        assert_eq!(res.output_files[0].digest.digest, tdigest("bb", 3));
+        assert!(res.output_files[0].executable);
+        assert_eq!(res.output_directories.len(), 1);
+        assert_eq!(res.output_directories[0].path, "out/dir");
+        assert_eq!(res.output_directories[0].tree_digest, tdigest("cc", 4));
+        assert_eq!(res.stderr_digest, Some(tdigest("dd", 5)));
+        assert_eq!(res.stdout_digest, None);
+        assert_eq!(res.execution_metadata.worker, "worker");
+        assert_eq!(res.execution_metadata.execution_start_timestamp.seconds, 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_batches() {
+        assert_eq!(
+            split_batches(vec![1, 2, 3, 4, 10, 1], 5, |x| *x),
+            vec![vec![1, 2], vec![3], vec![4], vec![10], vec![1]]
+        );
+        assert_eq!(
+            split_batches(Vec::<usize>::new(), 5, |x| *x),
+            Vec::<Vec<usize>>::new()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_download() -> anyhow::Result<()> {
+        let (state, mut client) = spawn_and_connect().await?;
+        client.max_total_batch_size = 10;
+        *state.max_batch_total_size.lock().unwrap() = Some(10);
+
+        let tempdir = tempfile::tempdir()?;
+        let path = |name: &str| tempdir.path().join(name).to_str().unwrap().to_owned();
+
+        let small = b"small".to_vec();
+        let large = vec![42u8; 2500];
+        let small_file = b"file".to_vec();
+        let large_file = vec![7u8; 3000];
+        fs_util::write(path("small_file"), &small_file)?;
+        fs_util::write(path("large_file"), &large_file)?;
+
+        client
+            .upload(
+                RemoteExecutionMetadata::default(),
+                UploadRequest {
+                    inlined_blobs_with_digest: Some(vec![
+                        InlinedBlobWithDigest {
+                            blob: small.clone(),
+                            digest: blob_digest(&small),
+                            ..Default::default()
+                        },
+                        InlinedBlobWithDigest {
+                            blob: large.clone(),
+                            digest: blob_digest(&large),
+                            ..Default::default()
+                        },
+                    ]),
+                    files_with_digest: Some(vec![
+                        NamedDigest {
+                            name: path("small_file"),
+                            digest: blob_digest(&small_file),
+                            ..Default::default()
+                        },
+                        NamedDigest {
+                            name: path("large_file"),
+                            digest: blob_digest(&large_file),
+                            ..Default::default()
+                        },
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        assert_eq!(state.blobs.lock().unwrap().len(), 4);
+        assert_eq!(*state.write_calls.lock().unwrap(), 2);
+
+        let res = client
+            .download(
+                RemoteExecutionMetadata::default(),
+                DownloadRequest {
+                    inlined_digests: Some(vec![blob_digest(&small), blob_digest(&large)]),
+                    file_digests: Some(vec![
+                        NamedDigestWithPermissions {
+                            named_digest: NamedDigest {
+                                name: path("small_out"),
+                                digest: blob_digest(&small_file),
+                                ..Default::default()
+                            },
+                            is_executable: false,
+                            ..Default::default()
+                        },
+                        NamedDigestWithPermissions {
+                            named_digest: NamedDigest {
+                                name: path("large_out"),
+                                digest: blob_digest(&large_file),
+                                ..Default::default()
+                            },
+                            is_executable: true,
+                            ..Default::default()
+                        },
+                    ]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let blobs = res
+            .inlined_blobs
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.digest, b.blob))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs.get(&blob_digest(&small)), Some(&small));
+        assert_eq!(blobs.get(&blob_digest(&large)), Some(&large));
+
+        assert_eq!(fs_util::read(path("small_out"))?, small_file);
+        assert_eq!(fs_util::read(path("large_out"))?, large_file);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path("large_out"))?.permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamed_upload_resumes() -> anyhow::Result<()> {
+        let (state, mut client) = spawn_and_connect().await?;
+        client.max_total_batch_size = 10;
+        *state.fail_next_write_after.lock().unwrap() = Some(1200);
+
+        let blob = (0..3000).map(|i| i as u8).collect::<Vec<_>>();
+
+        client
+            .upload(
+                RemoteExecutionMetadata::default(),
+                UploadRequest {
+                    inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                        blob: blob.clone(),
+                        digest: tdigest("05", 3000),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        assert_eq!(*state.write_calls.lock().unwrap(), 2);
+        assert_eq!(
+            state.blobs.lock().unwrap().get(&("05".to_owned(), 3000)),
+            Some(&blob)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_blob() -> anyhow::Result<()> {
+        let (state, client) = spawn_and_connect().await?;
+
+        let digest = client
+            .upload_blob(b"hello".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+
+        assert_eq!(
+            digest,
+            tdigest("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d", 5)
+        );
+        assert_eq!(
+            state
+                .blobs
+                .lock()
+                .unwrap()
+                .get(&(digest.hash, 5))
+                .map(|b| b.as_slice()),
+            Some(b"hello".as_slice())
+        );
+
+        Ok(())
+    }
+
+    async fn get_authorization(cfg: ClientCfg) -> anyhow::Result<Option<String>> {
+        let (state, client) = spawn_and_connect_with(TestServerState::default(), cfg).await?;
+        let _ignored = client
+            .get_action_result(
+                RemoteExecutionMetadata::default(),
+                ActionResultRequest {
+                    digest: tdigest("00", 0),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let authorization = state.last_authorization.lock().unwrap().clone();
+        Ok(authorization)
+    }
+
+    #[tokio::test]
+    async fn test_http_headers() -> anyhow::Result<()> {
+        let cfg = ClientCfg {
+            http_headers: vec!["Authorization: Bearer static".parse()?],
+            ..Default::default()
+        };
+        assert_eq!(
+            get_authorization(cfg).await?.as_deref(),
+            Some("Bearer static")
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_http_headers_command() -> anyhow::Result<()> {
+        let cfg = ClientCfg {
+            http_headers_command: Some(vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                "echo 'Authorization: Bearer from-command'".to_owned(),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_authorization(cfg).await?.as_deref(),
+            Some("Bearer from-command")
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_http_headers_command_refresh() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let count = tempdir.path().join("count");
+        let cfg = ClientCfg {
+            // Prints a new token every time.
+            http_headers_command: Some(vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                "n=$(($(cat \"$0\" 2>/dev/null || echo 0) + 1)); echo $n > \"$0\"; echo \"Authorization: Bearer $n\"".to_owned(),
+                count.to_str().unwrap().to_owned(),
+            ]),
+            http_headers_refresh_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let (state, client) = spawn_and_connect_with(TestServerState::default(), cfg).await?;
+        let authorization = || async {
+            let _ignored = client
+                .get_action_result(
+                    RemoteExecutionMetadata::default(),
+                    ActionResultRequest {
+                        digest: tdigest("00", 0),
+                        ..Default::default()
+                    },
+                )
+                .await;
+            state.last_authorization.lock().unwrap().clone()
+        };
+
+        assert_eq!(authorization().await.as_deref(), Some("Bearer 1"));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while authorization().await.as_deref() == Some("Bearer 1") {
+            assert!(
+                Instant::now() < deadline,
+                "The headers were never refreshed"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_cert_requires_key() {
+        let tls = TLSCfg {
+            client_cert: Some("cert.pem".to_owned()),
+            ..Default::default()
+        };
+        let err = create_channel("grpcs://127.0.0.1:1", &tls)
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("must be configured together"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_digests_ttl() -> anyhow::Result<()> {
+        let (state, client) = spawn_and_connect().await?;
+
+        let get_ttls = |digests: Vec<TDigest>| {
+            let client = &client;
+            async move {
+                anyhow::Ok(
+                    client
+                        .get_digests_ttl(
+                            RemoteExecutionMetadata::default(),
+                            GetDigestsTtlRequest {
+                                digests,
+                                ..Default::default()
+                            },
+                        )
+                        .await?
+                        .digests_with_ttl
+                        .into_map(|d| (d.digest.hash, d.ttl)),
+                )
+            }
+        };
+
+        // Uploaded digests are known to be present without asking the server.
+        let uploaded = client
+            .upload_blob(b"uploaded".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        let missing = tdigest("01", 7);
+        let ttls = get_ttls(vec![uploaded.clone(), missing.clone(), missing.clone()]).await?;
+        assert_eq!(ttls.len(), 3);
+        assert!(ttls[0].1 > 600);
+        assert_eq!(ttls[1], ("01".to_owned(), 0));
+        assert_eq!(ttls[2], ("01".to_owned(), 0));
+        assert_eq!(*state.find_missing_calls.lock().unwrap(), 1);
+
+        // Missing digests are checked again, and cached once found.
+        state
+            .blobs
+            .lock()
+            .unwrap()
+            .insert(("01".to_owned(), 7), b"missing".to_vec());
+        let ttls = get_ttls(vec![missing.clone()]).await?;
+        assert!(ttls[0].1 > 600);
+        assert_eq!(*state.find_missing_calls.lock().unwrap(), 2);
+
+        let ttls = get_ttls(vec![missing]).await?;
+        assert!(ttls[0].1 > 600);
+        assert_eq!(*state.find_missing_calls.lock().unwrap(), 2);
+
+        Ok(())
+    }
+
+    async fn download_inlined(
+        client: &REClient,
+        digests: Vec<TDigest>,
+    ) -> anyhow::Result<Vec<InlinedDigestWithStatus>> {
+        Ok(client
+            .download(
+                RemoteExecutionMetadata::default(),
+                DownloadRequest {
+                    inlined_digests: Some(digests),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .inlined_blobs
+            .unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_download_statuses() -> anyhow::Result<()> {
+        let (state, client) = spawn_and_connect().await?;
+
+        let present = client
+            .upload_blob(b"present".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        let missing = blob_digest(b"missing");
+        let corrupted = blob_digest(b"corrupted");
+        state
+            .blobs
+            .lock()
+            .unwrap()
+            .insert((corrupted.hash.clone(), 9), b"CORRUPTED".to_vec());
+
+        let blobs = download_inlined(&client, vec![present, missing, corrupted.clone()]).await?;
+        assert_eq!(blobs.len(), 3);
+        assert_eq!(blobs[0].status.code, TCode::OK);
+        assert_eq!(blobs[0].blob, b"present");
+        assert_eq!(blobs[1].status.code, TCode::NOT_FOUND);
+        assert_eq!(blobs[2].status.code, TCode::DATA_LOSS);
+        assert!(blobs[2].blob.is_empty());
+
+        // A file that can't be downloaded is an error rather than an empty file.
+        let tempdir = tempfile::tempdir()?;
+        let path = tempdir.path().join("out").to_str().unwrap().to_owned();
+        let err = match client
+            .download(
+                RemoteExecutionMetadata::default(),
+                DownloadRequest {
+                    file_digests: Some(vec![NamedDigestWithPermissions {
+                        named_digest: NamedDigest {
+                            name: path,
+                            digest: corrupted,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(_) => panic!("Downloading a corrupted blob should fail"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            err.downcast_ref::<REClientError>().map(|e| e.code.clone()),
+            Some(TCode::DATA_LOSS)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstd_compression() -> anyhow::Result<()> {
+        let state = TestServerState::default();
+        *state.zstd.lock().unwrap() = true;
+        let cfg = ClientCfg {
+            use_zstd_compression: true,
+            ..Default::default()
+        };
+        let (state, client) = spawn_and_connect_with(state, cfg).await?;
+
+        let blob = b"compressible ".repeat(100);
+        let digest = client
+            .upload_blob(blob.clone(), RemoteExecutionMetadata::default())
+            .await?;
+        assert_eq!(*state.compressed_updates.lock().unwrap(), 1);
+        assert_eq!(
+            state
+                .blobs
+                .lock()
+                .unwrap()
+                .get(&(digest.hash.clone(), digest.size_in_bytes)),
+            Some(&blob)
+        );
+
+        let blobs = download_inlined(&client, vec![digest]).await?;
+        assert_eq!(blobs[0].status.code, TCode::OK);
+        assert_eq!(blobs[0].blob, blob);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstd_requires_server_support() -> anyhow::Result<()> {
+        let cfg = ClientCfg {
+            use_zstd_compression: true,
+            ..Default::default()
+        };
+        let (state, client) = spawn_and_connect_with(TestServerState::default(), cfg).await?;
+
+        client
+            .upload_blob(b"blob".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        assert_eq!(*state.compressed_updates.lock().unwrap(), 0);
+
+        Ok(())
+    }
+}
@@ -7,6 +7,11 @@
  * of this source tree.
  */
 
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+
 pub enum CASDaemonClientCfg {
     #[allow(non_camel_case_types)]
     embedded_config(EmbeddedCASDaemonClientCfg),
@@ -73,11 +78,56 @@ pub struct GRPCClientCfg {
     pub connection_count: i32,
 }
 
+#[derive(Default)]
+pub struct TLSCfg {
+    /// PEM file with the certificate authorities used to verify the server, the system roots are
+    /// used if unset.
+    pub ca_certs: Option<String>,
+    /// PEM files with the certificate and private key used for client authentication.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+/// A header sent with every request, e.g. `Authorization: Bearer <token>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpHeader {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for HttpHeader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (key, value) = s
+            .split_once(':')
+            .with_context(|| format!("Invalid header `{}`, expected `Name: Value`", s))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow::anyhow!("Invalid header `{}`, the name is empty", s));
+        }
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.trim().to_owned(),
+        })
+    }
+}
+
 #[derive(Default)]
 pub struct ClientCfg {
     pub action_cache_client_config: GRPCClientCfg,
     pub cas_client_config: CASDaemonClientCfg,
     pub execution_client_config: GRPCClientCfg,
+    pub tls_config: TLSCfg,
+    pub http_headers: Vec<HttpHeader>,
+    /// A command printing extra headers on its stdout, one `Name: Value` per line, typically to
+    /// fetch an authentication token. It is run when connecting, and again every
+    /// `http_headers_refresh_interval` since such tokens expire.
+    pub http_headers_command: Option<Vec<String>>,
+    /// Defaults to 5 minutes.
+    pub http_headers_refresh_interval: Option<Duration>,
+    /// Compress batched transfers with zstd, when the server supports it.
+    pub use_zstd_compression: bool,
     pub quiet_mode: bool,
     pub log_max_file_size: i64,
     pub log_rollup_window_size: i32,
@@ -88,3 +138,22 @@ pub struct ClientCfg {
 pub fn create_default_config() -> ClientCfg {
     ClientCfg::default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_header() -> anyhow::Result<()> {
+        assert_eq!(
+            "Authorization: Bearer a:b".parse::<HttpHeader>()?,
+            HttpHeader {
+                key: "Authorization".to_owned(),
+                value: "Bearer a:b".to_owned(),
+            }
+        );
+        assert!("Authorization".parse::<HttpHeader>().is_err());
+        assert!(": value".parse::<HttpHeader>().is_err());
+        Ok(())
+    }
+}
@@ -42,7 +42,9 @@ pub struct TCode(pub i32);
 impl TCode {
     pub const OK: Self = TCode(0i32);
     pub const INVALID_ARGUMENT: Self = TCode(3i32);
+    pub const DEADLINE_EXCEEDED: Self = TCode(4i32);
     pub const NOT_FOUND: Self = TCode(5i32);
+    pub const DATA_LOSS: Self = TCode(15i32);
 }
 
 impl Display for TCode {
@@ -51,6 +53,12 @@ impl Display for TCode {
             write!(f, "OK")
         } else if self == &TCode::INVALID_ARGUMENT {
             write!(f, "INVALID_ARGUMENT")
+        } else if self == &TCode::DEADLINE_EXCEEDED {
+            write!(f, "DEADLINE_EXCEEDED")
+        } else if self == &TCode::NOT_FOUND {
+            write!(f, "NOT_FOUND")
+        } else if self == &TCode::DATA_LOSS {
+            write!(f, "DATA_LOSS")
         } else {
             write!(f, "UNKNOWN")
         }
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The REAPI has no notion of digest TTLs, only `FindMissingBlobs`. This keeps track of the
+//! digests recently confirmed to be in the CAS (found or uploaded) and assumes they stay there
+//! for a fixed time, so they don't need to be checked again until they get close to the end of it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::digest::TDigest;
+
+pub(crate) struct FindMissingCache {
+    /// How long a digest is assumed to stay in the CAS after it was confirmed to be there.
+    ttl: Duration,
+    /// Entries with less than this left are checked again rather than reported with a short TTL,
+    /// which callers would take as a reason to upload the blob again.
+    refresh_below: Duration,
+    /// Above this many entries, expired entries are dropped, and if that is not enough, the
+    /// whole cache is.
+    max_entries: usize,
+    confirmed: Mutex<HashMap<TDigest, Instant>>,
+}
+
+impl FindMissingCache {
+    pub(crate) fn new(ttl: Duration, refresh_below: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            refresh_below,
+            max_entries,
+            confirmed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// How long `digest` is still assumed to be in the CAS, if it was confirmed recently enough
+    /// not to need checking again.
+    pub(crate) fn remaining_ttl(&self, digest: &TDigest, now: Instant) -> Option<Duration> {
+        let confirmed_at = *self.confirmed.lock().unwrap().get(digest)?;
+        let remaining = (confirmed_at + self.ttl).checked_duration_since(now)?;
+        if remaining >= self.refresh_below {
+            Some(remaining)
+        } else {
+            None
+        }
+    }
+
+    /// Record that `digests` were confirmed to be in the CAS at `now`.
+    pub(crate) fn insert_all(&self, digests: impl IntoIterator<Item = TDigest>, now: Instant) {
+        let mut confirmed = self.confirmed.lock().unwrap();
+        for digest in digests {
+            confirmed.insert(digest, now);
+        }
+
+        if confirmed.len() > self.max_entries {
+            let ttl = self.ttl;
+            confirmed.retain(|_, confirmed_at| *confirmed_at + ttl > now);
+            if confirmed.len() > self.max_entries {
+                confirmed.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tdigest(hash: &str) -> TDigest {
+        TDigest {
+            hash: hash.to_owned(),
+            size_in_bytes: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_remaining_ttl() {
+        let cache = FindMissingCache::new(Duration::from_secs(100), Duration::from_secs(20), 10);
+        let now = Instant::now();
+
+        assert_eq!(cache.remaining_ttl(&tdigest("a"), now), None);
+
+        cache.insert_all(vec![tdigest("a")], now);
+        assert_eq!(
+            cache.remaining_ttl(&tdigest("a"), now + Duration::from_secs(40)),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            cache.remaining_ttl(&tdigest("a"), now + Duration::from_secs(100)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_refreshes_entries_near_expiry() {
+        let cache = FindMissingCache::new(Duration::from_secs(100), Duration::from_secs(20), 10);
+        let now = Instant::now();
+
+        cache.insert_all(vec![tdigest("a")], now);
+        assert_eq!(
+            cache.remaining_ttl(&tdigest("a"), now + Duration::from_secs(80)),
+            Some(Duration::from_secs(20))
+        );
+        // Still in the CAS for a while, but close enough to expiring that it's checked again.
+        assert_eq!(
+            cache.remaining_ttl(&tdigest("a"), now + Duration::from_secs(81)),
+            None
+        );
+
+        // Checking it again resets its TTL.
+        cache.insert_all(vec![tdigest("a")], now + Duration::from_secs(81));
+        assert_eq!(
+            cache.remaining_ttl(&tdigest("a"), now + Duration::from_secs(90)),
+            Some(Duration::from_secs(91))
+        );
+    }
+
+    #[test]
+    fn test_evicts_expired_entries() {
+        let cache = FindMissingCache::new(Duration::from_secs(100), Duration::from_secs(20), 2);
+        let now = Instant::now();
+
+        cache.insert_all(vec![tdigest("a"), tdigest("b")], now);
+        let later = now + Duration::from_secs(150);
+        cache.insert_all(vec![tdigest("c")], later);
+
+        assert_eq!(cache.confirmed.lock().unwrap().len(), 1);
+        assert!(cache.remaining_ttl(&tdigest("c"), later).is_some());
+    }
+}
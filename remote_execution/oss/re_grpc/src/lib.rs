@@ -12,14 +12,18 @@
 #![cfg_attr(feature = "gazebo_lint", allow(deprecated))] // :(
 #![cfg_attr(feature = "gazebo_lint", plugin(gazebo_lint))]
 
+mod bytestream;
 mod client;
 mod config;
 mod digest;
 mod error;
+mod find_missing_cache;
 mod grpc;
 mod metadata;
 mod request;
 mod response;
+#[cfg(test)]
+mod test_server;
 pub use client::*;
 pub use config::*;
 pub use digest::*;
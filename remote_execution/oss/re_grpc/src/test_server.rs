@@ -0,0 +1,425 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A minimal in-process stand-in for a REAPI server, used to test the client.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::stream;
+use futures::Stream;
+use gazebo::prelude::*;
+use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_server::ActionCache;
+use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_server::ActionCacheServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::batch_read_blobs_response;
+use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_response;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_server::Capabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_server::CapabilitiesServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorage;
+use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorageServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetTreeRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetTreeResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ServerCapabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
+use re_grpc_proto::google::bytestream::byte_stream_server::ByteStream;
+use re_grpc_proto::google::bytestream::byte_stream_server::ByteStreamServer;
+use re_grpc_proto::google::bytestream::QueryWriteStatusRequest;
+use re_grpc_proto::google::bytestream::QueryWriteStatusResponse;
+use re_grpc_proto::google::bytestream::ReadRequest;
+use re_grpc_proto::google::bytestream::ReadResponse;
+use re_grpc_proto::google::bytestream::WriteRequest;
+use re_grpc_proto::google::bytestream::WriteResponse;
+use re_grpc_proto::google::rpc::Code;
+use re_grpc_proto::google::rpc::Status as RpcStatus;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+use tonic::Streaming;
+
+use crate::config::ClientCfg;
+use crate::REClient;
+use crate::REClientBuilder;
+
+type DigestKey = (String, i64);
+
+fn digest_key(digest: Option<Digest>) -> Result<DigestKey, Status> {
+    let digest = digest.ok_or_else(|| Status::invalid_argument("Missing digest"))?;
+    Ok((digest.hash, digest.size_bytes))
+}
+
+/// Parses the `{hash}/{size}` suffix of a ByteStream resource name.
+fn resource_key(resource_name: &str) -> Result<DigestKey, Status> {
+    let mut parts = resource_name.rsplit('/');
+    match (parts.next(), parts.next()) {
+        (Some(size), Some(hash)) => Ok((
+            hash.to_owned(),
+            size.parse()
+                .map_err(|_| Status::invalid_argument("Invalid size"))?,
+        )),
+        _ => Err(Status::invalid_argument("Invalid resource name")),
+    }
+}
+
+fn rpc_status(code: Code) -> Option<RpcStatus> {
+    Some(RpcStatus {
+        code: code as i32,
+        ..Default::default()
+    })
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct TestServerState {
+    pub(crate) action_results: Arc<Mutex<HashMap<DigestKey, ActionResult>>>,
+    pub(crate) blobs: Arc<Mutex<HashMap<DigestKey, Vec<u8>>>>,
+    /// Data committed so far for ongoing ByteStream writes, by resource name.
+    pub(crate) partial_writes: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    /// Batch requests carrying more data than this are rejected.
+    pub(crate) max_batch_total_size: Arc<Mutex<Option<usize>>>,
+    /// The next ByteStream write fails once this many bytes have been committed.
+    pub(crate) fail_next_write_after: Arc<Mutex<Option<usize>>>,
+    pub(crate) write_calls: Arc<Mutex<usize>>,
+    pub(crate) find_missing_calls: Arc<Mutex<usize>>,
+    /// Advertise zstd for batch updates and use it for batch reads when acceptable.
+    pub(crate) zstd: Arc<Mutex<bool>>,
+    pub(crate) compressed_updates: Arc<Mutex<usize>>,
+    /// The `authorization` header of the last `GetActionResult` request.
+    pub(crate) last_authorization: Arc<Mutex<Option<String>>>,
+}
+
+impl TestServerState {
+    fn check_batch_size(&self, size: usize) -> Result<(), Status> {
+        match *self.max_batch_total_size.lock().unwrap() {
+            Some(max) if size > max => Err(Status::invalid_argument("Batch is too large")),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct TestActionCache {
+    state: TestServerState,
+}
+
+#[tonic::async_trait]
+impl ActionCache for TestActionCache {
+    async fn get_action_result(
+        &self,
+        request: Request<GetActionResultRequest>,
+    ) -> Result<Response<ActionResult>, Status> {
+        *self.state.last_authorization.lock().unwrap() = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        let key = digest_key(request.into_inner().action_digest)?;
+        match self.state.action_results.lock().unwrap().get(&key) {
+            Some(action_result) => Ok(Response::new(action_result.clone())),
+            None => Err(Status::not_found(format!("{}:{}", key.0, key.1))),
+        }
+    }
+
+    async fn update_action_result(
+        &self,
+        request: Request<UpdateActionResultRequest>,
+    ) -> Result<Response<ActionResult>, Status> {
+        let request = request.into_inner();
+        let key = digest_key(request.action_digest)?;
+        let action_result = request
+            .action_result
+            .ok_or_else(|| Status::invalid_argument("Missing action result"))?;
+        self.state
+            .action_results
+            .lock()
+            .unwrap()
+            .insert(key, action_result.clone());
+        Ok(Response::new(action_result))
+    }
+}
+
+struct TestCas {
+    state: TestServerState,
+}
+
+#[tonic::async_trait]
+impl ContentAddressableStorage for TestCas {
+    async fn find_missing_blobs(
+        &self,
+        request: Request<FindMissingBlobsRequest>,
+    ) -> Result<Response<FindMissingBlobsResponse>, Status> {
+        *self.state.find_missing_calls.lock().unwrap() += 1;
+        let blobs = self.state.blobs.lock().unwrap();
+        let missing_blob_digests = request
+            .into_inner()
+            .blob_digests
+            .into_iter()
+            .filter(|d| !blobs.contains_key(&(d.hash.clone(), d.size_bytes)))
+            .collect();
+        Ok(Response::new(FindMissingBlobsResponse {
+            missing_blob_digests,
+        }))
+    }
+
+    async fn batch_update_blobs(
+        &self,
+        request: Request<BatchUpdateBlobsRequest>,
+    ) -> Result<Response<BatchUpdateBlobsResponse>, Status> {
+        let request = request.into_inner();
+        self.state
+            .check_batch_size(request.requests.iter().map(|r| r.data.len()).sum())?;
+
+        let mut blobs = self.state.blobs.lock().unwrap();
+        let mut responses = Vec::new();
+        for r in request.requests {
+            let key = digest_key(r.digest.clone())?;
+            let data = if r.compressor == compressor::Value::Zstd as i32 {
+                *self.state.compressed_updates.lock().unwrap() += 1;
+                zstd::bulk::decompress(&r.data, key.1 as usize)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?
+            } else {
+                r.data
+            };
+            blobs.insert(key, data);
+            responses.push(batch_update_blobs_response::Response {
+                digest: r.digest,
+                status: rpc_status(Code::Ok),
+            });
+        }
+        Ok(Response::new(BatchUpdateBlobsResponse { responses }))
+    }
+
+    async fn batch_read_blobs(
+        &self,
+        request: Request<BatchReadBlobsRequest>,
+    ) -> Result<Response<BatchReadBlobsResponse>, Status> {
+        let request = request.into_inner();
+        self.state
+            .check_batch_size(request.digests.iter().map(|d| d.size_bytes as usize).sum())?;
+
+        let zstd = *self.state.zstd.lock().unwrap()
+            && request
+                .acceptable_compressors
+                .contains(&(compressor::Value::Zstd as i32));
+
+        let blobs = self.state.blobs.lock().unwrap();
+        let responses =
+            request
+                .digests
+                .into_map(|d| match blobs.get(&(d.hash.clone(), d.size_bytes)) {
+                    Some(data) if zstd => batch_read_blobs_response::Response {
+                        digest: Some(d),
+                        data: zstd::bulk::compress(data, 0).unwrap(),
+                        compressor: compressor::Value::Zstd as i32,
+                        status: rpc_status(Code::Ok),
+                    },
+                    Some(data) => batch_read_blobs_response::Response {
+                        digest: Some(d),
+                        data: data.clone(),
+                        compressor: 0,
+                        status: rpc_status(Code::Ok),
+                    },
+                    None => batch_read_blobs_response::Response {
+                        digest: Some(d),
+                        data: Vec::new(),
+                        compressor: 0,
+                        status: rpc_status(Code::NotFound),
+                    },
+                });
+        Ok(Response::new(BatchReadBlobsResponse { responses }))
+    }
+
+    type GetTreeStream = Pin<Box<dyn Stream<Item = Result<GetTreeResponse, Status>> + Send>>;
+
+    async fn get_tree(
+        &self,
+        _request: Request<GetTreeRequest>,
+    ) -> Result<Response<Self::GetTreeStream>, Status> {
+        Err(Status::unimplemented("get_tree"))
+    }
+}
+
+struct TestCapabilities {
+    state: TestServerState,
+}
+
+#[tonic::async_trait]
+impl Capabilities for TestCapabilities {
+    async fn get_capabilities(
+        &self,
+        _request: Request<GetCapabilitiesRequest>,
+    ) -> Result<Response<ServerCapabilities>, Status> {
+        let supported_batch_update_compressors = if *self.state.zstd.lock().unwrap() {
+            vec![compressor::Value::Zstd as i32]
+        } else {
+            Vec::new()
+        };
+        Ok(Response::new(ServerCapabilities {
+            cache_capabilities: Some(CacheCapabilities {
+                supported_batch_update_compressors,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }))
+    }
+}
+
+struct TestByteStream {
+    state: TestServerState,
+}
+
+#[tonic::async_trait]
+impl ByteStream for TestByteStream {
+    type ReadStream = Pin<Box<dyn Stream<Item = Result<ReadResponse, Status>> + Send>>;
+
+    async fn read(
+        &self,
+        request: Request<ReadRequest>,
+    ) -> Result<Response<Self::ReadStream>, Status> {
+        let key = resource_key(&request.into_inner().resource_name)?;
+        let data = match self.state.blobs.lock().unwrap().get(&key) {
+            Some(data) => data.clone(),
+            None => return Err(Status::not_found(format!("{}:{}", key.0, key.1))),
+        };
+        // Use small chunks so that clients have to reassemble the blob.
+        let chunks = data
+            .chunks(1000)
+            .map(|c| Ok(ReadResponse { data: c.to_vec() }));
+        Ok(Response::new(Box::pin(stream::iter(
+            chunks.collect::<Vec<_>>(),
+        ))))
+    }
+
+    async fn write(
+        &self,
+        request: Request<Streaming<WriteRequest>>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        *self.state.write_calls.lock().unwrap() += 1;
+        let fail_after = self.state.fail_next_write_after.lock().unwrap().take();
+
+        let mut stream = request.into_inner();
+        let mut resource_name = None;
+
+        while let Some(req) = stream.message().await? {
+            let resource_name = resource_name.get_or_insert_with(|| req.resource_name.clone());
+            let mut partial_writes = self.state.partial_writes.lock().unwrap();
+            let committed = partial_writes.entry(resource_name.clone()).or_default();
+
+            if req.write_offset as usize != committed.len() {
+                return Err(Status::invalid_argument(format!(
+                    "Write offset {} does not match committed size {}",
+                    req.write_offset,
+                    committed.len()
+                )));
+            }
+
+            match fail_after {
+                Some(n) if committed.len() + req.data.len() > n => {
+                    let keep = n.saturating_sub(committed.len());
+                    committed.extend_from_slice(&req.data[..keep]);
+                    return Err(Status::unavailable("Simulated failure"));
+                }
+                _ => committed.extend_from_slice(&req.data),
+            }
+
+            if req.finish_write {
+                let key = resource_key(resource_name)?;
+                let data = partial_writes.remove(resource_name).unwrap_or_default();
+                if data.len() as i64 != key.1 {
+                    return Err(Status::invalid_argument("Size mismatch"));
+                }
+                let committed_size = data.len() as i64;
+                self.state.blobs.lock().unwrap().insert(key, data);
+                return Ok(Response::new(WriteResponse { committed_size }));
+            }
+        }
+
+        Err(Status::invalid_argument("Write was not finished"))
+    }
+
+    async fn query_write_status(
+        &self,
+        request: Request<QueryWriteStatusRequest>,
+    ) -> Result<Response<QueryWriteStatusResponse>, Status> {
+        let resource_name = request.into_inner().resource_name;
+        if let Some(committed) = self
+            .state
+            .partial_writes
+            .lock()
+            .unwrap()
+            .get(&resource_name)
+        {
+            return Ok(Response::new(QueryWriteStatusResponse {
+                committed_size: committed.len() as i64,
+                complete: false,
+            }));
+        }
+        let key = resource_key(&resource_name)?;
+        if self.state.blobs.lock().unwrap().contains_key(&key) {
+            return Ok(Response::new(QueryWriteStatusResponse {
+                committed_size: key.1,
+                complete: true,
+            }));
+        }
+        Err(Status::not_found(resource_name))
+    }
+}
+
+/// Start a server on a random local port and return its state along with a client connected to
+/// it.
+pub(crate) async fn spawn_and_connect() -> anyhow::Result<(TestServerState, REClient)> {
+    spawn_and_connect_with(TestServerState::default(), ClientCfg::default()).await
+}
+
+/// Like `spawn_and_connect`, with an initial server state and using `cfg` for the client. The
+/// execution address is overwritten.
+pub(crate) async fn spawn_and_connect_with(
+    state: TestServerState,
+    mut cfg: ClientCfg,
+) -> anyhow::Result<(TestServerState, REClient)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = format!("http://{}", listener.local_addr()?);
+
+    let router = tonic::transport::Server::builder()
+        .add_service(ActionCacheServer::new(TestActionCache {
+            state: state.clone(),
+        }))
+        .add_service(ContentAddressableStorageServer::new(TestCas {
+            state: state.clone(),
+        }))
+        .add_service(ByteStreamServer::new(TestByteStream {
+            state: state.clone(),
+        }))
+        .add_service(CapabilitiesServer::new(TestCapabilities {
+            state: state.clone(),
+        }));
+    tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+    cfg.execution_client_config.address = Some(address);
+
+    let client = REClientBuilder::new(())
+        .with_config(cfg)
+        .build_and_connect()
+        .await?;
+
+    Ok((state, client))
+}
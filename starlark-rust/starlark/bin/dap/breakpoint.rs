@@ -0,0 +1,181 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use debugserver_types::SourceBreakpoint;
+use starlark::syntax::AstModule;
+use starlark::syntax::Dialect;
+
+/// When a breakpoint stops, given the number of times that it has been hit, e.g. `>= 3`.
+/// A bare number means the breakpoint stops on exactly that hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HitCondition {
+    Equal(usize),
+    Greater(usize),
+    GreaterOrEqual(usize),
+    Less(usize),
+    LessOrEqual(usize),
+    /// Stop on every nth hit.
+    Multiple(usize),
+}
+
+impl HitCondition {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        let ops: &[(&str, fn(usize) -> HitCondition)] = &[
+            (">=", HitCondition::GreaterOrEqual),
+            ("<=", HitCondition::LessOrEqual),
+            ("==", HitCondition::Equal),
+            (">", HitCondition::Greater),
+            ("<", HitCondition::Less),
+            ("%", HitCondition::Multiple),
+            ("=", HitCondition::Equal),
+        ];
+        let (op, count): (fn(usize) -> HitCondition, _) = ops
+            .iter()
+            .find_map(|(prefix, op)| s.strip_prefix(prefix).map(|rest| (*op, rest)))
+            .unwrap_or((HitCondition::Equal, s));
+        match count.trim().parse() {
+            Ok(0) if s.starts_with('%') => Err(anyhow::anyhow!(
+                "Hit condition `{}` must be a multiple of a positive number",
+                s
+            )),
+            Ok(count) => Ok(op(count)),
+            Err(_) => Err(anyhow::anyhow!(
+                "Hit condition `{}` must be a number, optionally after one of {}",
+                s,
+                ">, >=, <, <=, == or %"
+            )),
+        }
+    }
+
+    pub(crate) fn matches(self, hits: usize) -> bool {
+        match self {
+            HitCondition::Equal(n) => hits == n,
+            HitCondition::Greater(n) => hits > n,
+            HitCondition::GreaterOrEqual(n) => hits >= n,
+            HitCondition::Less(n) => hits < n,
+            HitCondition::LessOrEqual(n) => hits <= n,
+            HitCondition::Multiple(n) => hits % n == 0,
+        }
+    }
+}
+
+/// A breakpoint on a statement, along with the options that decide what it does when the
+/// statement is reached.
+#[derive(Debug)]
+pub(crate) struct LineBreakpoint {
+    /// Only stop if this expression is true.
+    pub(crate) condition: Option<String>,
+    /// Only stop if the number of hits (where the condition was true) matches.
+    pub(crate) hit_condition: Option<HitCondition>,
+    /// Log this message rather than stopping, interpolating the expressions in `{}`.
+    pub(crate) log_message: Option<String>,
+    pub(crate) hits: usize,
+}
+
+impl LineBreakpoint {
+    pub(crate) fn new(x: &SourceBreakpoint) -> anyhow::Result<Self> {
+        let condition = x.condition.clone().filter(|x| !x.trim().is_empty());
+        if let Some(condition) = &condition {
+            AstModule::parse("condition", condition.clone(), &Dialect::Extended)?;
+        }
+        Ok(Self {
+            condition,
+            hit_condition: x
+                .hit_condition
+                .as_deref()
+                .filter(|x| !x.trim().is_empty())
+                .map(HitCondition::parse)
+                .transpose()?,
+            log_message: x.log_message.clone(),
+            hits: 0,
+        })
+    }
+
+    /// Record a hit, and decide whether it stops.
+    pub(crate) fn hit(&mut self) -> bool {
+        self.hits += 1;
+        self.hit_condition.map_or(true, |x| x.matches(self.hits))
+    }
+}
+
+/// Replace the expressions in `{}` in a log message with the results of `eval`. Braces can be
+/// escaped by doubling them.
+pub(crate) fn interpolate(message: &str, mut eval: impl FnMut(&str) -> String) -> String {
+    let mut res = String::new();
+    let mut rest = message;
+    while let Some(i) = rest.find(|c| c == '{' || c == '}') {
+        res.push_str(&rest[..i]);
+        let c = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if let Some(escaped) = rest.strip_prefix(c) {
+            res.push_str(c);
+            rest = escaped;
+        } else if c == "}" {
+            res.push('}');
+        } else {
+            match rest.find('}') {
+                Some(end) => {
+                    res.push_str(&eval(&rest[..end]));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    res.push('{');
+                }
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_condition() {
+        assert_eq!(HitCondition::Equal(3), HitCondition::parse("3").unwrap());
+        assert_eq!(HitCondition::Equal(3), HitCondition::parse("== 3").unwrap());
+        assert_eq!(
+            HitCondition::GreaterOrEqual(2),
+            HitCondition::parse(" >=2 ").unwrap()
+        );
+        assert_eq!(HitCondition::Less(4), HitCondition::parse("<4").unwrap());
+        assert_eq!(
+            HitCondition::Multiple(5),
+            HitCondition::parse("% 5").unwrap()
+        );
+        assert!(HitCondition::parse("% 0").is_err());
+        assert!(HitCondition::parse("lots").is_err());
+
+        let hits = |x: HitCondition| (1..=6).filter(|n| x.matches(*n)).collect::<Vec<_>>();
+        assert_eq!(vec![3], hits(HitCondition::Equal(3)));
+        assert_eq!(vec![4, 5, 6], hits(HitCondition::Greater(3)));
+        assert_eq!(vec![1, 2], hits(HitCondition::LessOrEqual(2)));
+        assert_eq!(vec![2, 4, 6], hits(HitCondition::Multiple(2)));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let eval = |x: &str| format!("<{}>", x);
+        assert_eq!("x is <x>", interpolate("x is {x}", eval));
+        assert_eq!("<a> and <b.c>!", interpolate("{a} and {b.c}!", eval));
+        assert_eq!("{x} is }", interpolate("{{x}} is }", eval));
+        assert_eq!("unclosed {x", interpolate("unclosed {x", eval));
+    }
+}
@@ -15,8 +15,8 @@
  * limitations under the License.
  */
 
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicUsize;
@@ -30,21 +30,23 @@ use std::thread;
 
 use debugserver_types::*;
 use dupe::Dupe;
-use dupe::OptionDupedExt;
-use gazebo::prelude::*;
 pub(crate) use library::*;
 use serde_json::Map;
 use serde_json::Value;
 use starlark::codemap::FileSpan;
 use starlark::codemap::FileSpanRef;
+use starlark::codemap::ResolvedSpan;
 use starlark::environment::Module;
 use starlark::eval::Evaluator;
 use starlark::syntax::AstModule;
 use starlark::syntax::Dialect;
 
+use crate::dap::breakpoint::interpolate;
+use crate::dap::breakpoint::LineBreakpoint;
 use crate::eval::dialect;
 use crate::eval::globals;
 
+mod breakpoint;
 mod library;
 
 #[derive(Debug)]
@@ -53,8 +55,10 @@ struct Backend {
     file: Mutex<Option<String>>,
 
     // These breakpoints must all match statements as per before_stmt.
-    // Those values for which we abort the execution.
-    breakpoints: Arc<Mutex<HashMap<String, HashSet<FileSpan>>>>,
+    // Those values for which we might abort the execution, depending on their options.
+    // They are keyed by location rather than FileSpan, since the file is parsed separately to
+    // set them, and code maps compare by identity.
+    breakpoints: Arc<Mutex<HashMap<String, HashMap<ResolvedSpan, LineBreakpoint>>>>,
     // Set while we are doing evaluate calls (>= 1 means disable)
     disable_breakpoints: Arc<AtomicUsize>,
 
@@ -64,9 +68,135 @@ struct Backend {
 
 enum Next {
     Continue,
+    /// Continue until a statement where the step finishes.
+    Step(StepKind),
     RemainPaused,
 }
 
+#[derive(Clone, Copy)]
+enum StepKind {
+    /// Stop at the next statement.
+    In,
+    /// Stop at the next statement in the same function, or after it returns.
+    Over,
+    /// Stop at the next statement after the current function returns.
+    Out,
+}
+
+/// A step that started at a statement with `depth` frames on the call stack.
+#[derive(Clone, Copy)]
+struct Step {
+    kind: StepKind,
+    depth: usize,
+}
+
+impl Step {
+    fn finishes_at(self, depth: usize) -> bool {
+        match self.kind {
+            StepKind::In => true,
+            StepKind::Over => depth <= self.depth,
+            StepKind::Out => depth < self.depth,
+        }
+    }
+}
+
+fn stack_depth(eval: &Evaluator) -> usize {
+    eval.call_stack().into_frames().len()
+}
+
+/// Evaluate `expression` where execution is paused. Breakpoints are disabled while doing so,
+/// not least because we currently don't allow reenterant evaluate.
+fn evaluate<'v, T>(
+    disable_breakpoints: &AtomicUsize,
+    eval: &mut Evaluator<'v, '_>,
+    expression: &str,
+    f: impl FnOnce(starlark::values::Value<'v>) -> T,
+) -> anyhow::Result<T> {
+    disable_breakpoints.fetch_add(1, Ordering::SeqCst);
+    let res = AstModule::parse("interactive", expression.to_owned(), &Dialect::Extended)
+        .and_then(|ast| eval.eval_statements(ast))
+        .map(f);
+    disable_breakpoints.fetch_sub(1, Ordering::SeqCst);
+    res
+}
+
+fn output(output: String) -> OutputEventBody {
+    OutputEventBody {
+        output,
+        category: None,
+        column: None,
+        data: None,
+        line: None,
+        source: None,
+        variables_reference: None,
+    }
+}
+
+/// Decide whether a breakpoint on the statement at `span` stops execution. Breakpoints with
+/// log messages never stop, but log their message instead.
+fn breakpoint_hit(
+    breakpoints: &Mutex<HashMap<String, HashMap<ResolvedSpan, LineBreakpoint>>>,
+    disable_breakpoints: &AtomicUsize,
+    client: &Client,
+    span: FileSpanRef,
+    eval: &mut Evaluator,
+) -> bool {
+    let resolved = span.resolve_span();
+    // Don't hold the lock while evaluating, as that runs arbitrary code.
+    let condition = match breakpoints
+        .lock()
+        .unwrap()
+        .get(span.filename())
+        .and_then(|x| x.get(&resolved))
+    {
+        None => return false,
+        Some(x) => x.condition.clone(),
+    };
+    if let Some(condition) = condition {
+        match evaluate(disable_breakpoints, eval, &condition, |x| x.to_bool()) {
+            Ok(true) => {}
+            Ok(false) => return false,
+            // Stop, so the user can see what went wrong.
+            Err(e) => client.event_output(output(format!(
+                "Error evaluating breakpoint condition `{}`: {:#}\n",
+                condition, e
+            ))),
+        }
+    }
+
+    let log_message = match breakpoints
+        .lock()
+        .unwrap()
+        .get_mut(span.filename())
+        .and_then(|x| x.get_mut(&resolved))
+    {
+        Some(x) => {
+            if !x.hit() {
+                return false;
+            }
+            x.log_message.clone()
+        }
+        // The breakpoint was removed while evaluating its condition.
+        None => return false,
+    };
+    match log_message {
+        None => true,
+        Some(message) => {
+            let message = interpolate(&message, |expression| {
+                match evaluate(disable_breakpoints, eval, expression, |x| x.to_string()) {
+                    Ok(x) => x,
+                    Err(e) => format!("{:#}", e),
+                }
+            });
+            client.event_output(OutputEventBody {
+                category: Some("console".to_owned()),
+                ..output(message + "\n")
+            });
+            false
+        }
+    }
+}
+
 impl Backend {
     fn inject<T: 'static + Send>(
         &self,
@@ -87,6 +217,10 @@ impl Backend {
         self.inject(Box::new(|_, _| (Next::Continue, ())))
     }
 
+    fn inject_step(&self, kind: StepKind) {
+        self.inject(Box::new(move |_, _| (Next::Step(kind), ())))
+    }
+
     fn with_ctx<T: 'static + Send>(
         &self,
         f: Box<dyn Fn(FileSpanRef, &mut Evaluator) -> T + Send>,
@@ -110,31 +244,49 @@ impl Backend {
             let module = Module::new();
             let globals = globals();
             let mut eval = Evaluator::new(&module);
+            let step: Cell<Option<Step>> = Cell::new(None);
             let fun = |span_loc: FileSpanRef, eval: &mut Evaluator| {
-                let stop = if disable_breakpoints.load(Ordering::SeqCst) > 0 {
-                    false
+                if disable_breakpoints.load(Ordering::SeqCst) > 0 {
+                    return;
+                }
+                let reason = if breakpoint_hit(
+                    &breakpoints,
+                    &disable_breakpoints,
+                    &client,
+                    span_loc,
+                    eval,
+                ) {
+                    "breakpoint"
+                } else if step
+                    .get()
+                    .map_or(false, |step| step.finishes_at(stack_depth(eval)))
+                {
+                    "step"
                 } else {
-                    let breaks = breakpoints.lock().unwrap();
-                    breaks
-                        .get(span_loc.filename())
-                        .map(|set| set.contains(&span_loc.to_file_span()))
-                        .unwrap_or_default()
+                    return;
                 };
-                if stop {
-                    client.event_stopped(StoppedEventBody {
-                        reason: "breakpoint".to_owned(),
-                        thread_id: Some(0),
-                        description: Some("Hello".to_owned()),
-                        all_threads_stopped: Some(true),
-                        preserve_focus_hint: None,
-                        text: None,
-                    });
-                    loop {
-                        let msg = receiver.lock().unwrap().recv().unwrap();
-                        match msg(span_loc, eval) {
-                            Next::Continue => break,
-                            Next::RemainPaused => continue,
+
+                client.event_stopped(StoppedEventBody {
+                    reason: reason.to_owned(),
+                    thread_id: Some(0),
+                    description: None,
+                    all_threads_stopped: Some(true),
+                    preserve_focus_hint: None,
+                    text: None,
+                });
+                step.set(None);
+                loop {
+                    let msg = receiver.lock().unwrap().recv().unwrap();
+                    match msg(span_loc, eval) {
+                        Next::Continue => break,
+                        Next::Step(kind) => {
+                            step.set(Some(Step {
+                                kind,
+                                depth: stack_depth(eval),
+                            }));
+                            break;
                         }
+                        Next::RemainPaused => continue,
                     }
                 }
             };
@@ -153,15 +305,7 @@ impl Backend {
                 Err(e) => format!("{:#}", e),
                 Ok(v) => v.to_owned(),
             };
-            client2.event_output(OutputEventBody {
-                output,
-                category: None,
-                column: None,
-                data: None,
-                line: None,
-                source: None,
-                variables_reference: None,
-            });
+            client2.event_output(self::output(output));
             client2.event_exited(ExitedEventBody {
                 exit_code: if res.is_ok() { 0 } else { 1 },
             });
@@ -188,6 +332,9 @@ impl DebugServer for Backend {
         self.client.event_initialized(None);
         Ok(Some(Capabilities {
             supports_configuration_done_request: Some(true),
+            supports_conditional_breakpoints: Some(true),
+            supports_hit_conditional_breakpoints: Some(true),
+            supports_log_points: Some(true),
             supports_evaluate_for_hovers: Some(true),
             supports_set_variable: Some(true),
             supports_step_in_targets_request: Some(true),
@@ -216,19 +363,31 @@ impl DebugServer for Backend {
                     })
                 }
                 Ok(ast) => {
-                    let poss: HashMap<usize, FileSpan> = ast
+                    let poss: HashMap<usize, ResolvedSpan> = ast
                         .stmt_locations()
                         .iter()
-                        .map(|span| (span.resolve_span().begin_line, span.dupe()))
+                        .map(|span| span.resolve_span())
+                        .map(|span| (span.begin_line, span))
                         .collect();
-                    let list = breakpoints.map(|x| poss.get(&(x.line as usize - 1)));
-                    self.breakpoints
-                        .lock()
-                        .unwrap()
-                        .insert(source, list.iter().filter_map(|x| x.duped()).collect());
-                    Ok(SetBreakpointsResponseBody {
-                        breakpoints: list.map(|x| breakpoint(x.is_some())),
-                    })
+                    let mut breaks = HashMap::new();
+                    let list = breakpoints
+                        .iter()
+                        .map(
+                            |x| match (poss.get(&(x.line as usize - 1)), LineBreakpoint::new(x)) {
+                                (None, _) => breakpoint(false),
+                                (Some(_), Err(e)) => Breakpoint {
+                                    message: Some(format!("{:#}", e)),
+                                    ..breakpoint(false)
+                                },
+                                (Some(span), Ok(x)) => {
+                                    breaks.insert(*span, x);
+                                    breakpoint(true)
+                                }
+                            },
+                        )
+                        .collect();
+                    self.breakpoints.lock().unwrap().insert(source, breaks);
+                    Ok(SetBreakpointsResponseBody { breakpoints: list })
                 }
             }
         }
@@ -361,18 +520,28 @@ impl DebugServer for Backend {
         Ok(ContinueResponseBody::default())
     }
 
+    fn next(&self, _: NextArguments) -> anyhow::Result<()> {
+        self.inject_step(StepKind::Over);
+        Ok(())
+    }
+
+    fn step_in(&self, _: StepInArguments) -> anyhow::Result<()> {
+        self.inject_step(StepKind::In);
+        Ok(())
+    }
+
+    fn step_out(&self, _: StepOutArguments) -> anyhow::Result<()> {
+        self.inject_step(StepKind::Out);
+        Ok(())
+    }
+
     fn evaluate(&self, x: EvaluateArguments) -> anyhow::Result<EvaluateResponseBody> {
         let disable_breakpoints = self.disable_breakpoints.dupe();
         self.with_ctx(Box::new(move |_, eval| {
-            // We don't want to trigger breakpoints during an evaluate,
-            // not least because we currently don't allow reenterant evaluate
-            disable_breakpoints.fetch_add(1, Ordering::SeqCst);
-            let ast = AstModule::parse("interactive", x.expression.clone(), &Dialect::Extended);
-            let s = match ast.and_then(|ast| eval.eval_statements(ast)) {
+            let s = match evaluate(&disable_breakpoints, eval, &x.expression, |v| v.to_string()) {
                 Err(e) => format!("{:#}", e),
-                Ok(v) => v.to_string(),
+                Ok(v) => v,
             };
-            disable_breakpoints.fetch_sub(1, Ordering::SeqCst);
             Ok(EvaluateResponseBody {
                 indexed_variables: None,
                 named_variables: None,
@@ -30,6 +30,8 @@ use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::docs::Identifier;
+use starlark::docs::Member;
 use starlark::environment::FrozenModule;
 use starlark::environment::Globals;
 use starlark::environment::Module;
@@ -66,6 +68,7 @@ pub(crate) struct Context {
     pub(crate) module: Option<Module>,
     pub(crate) builtin_docs: HashMap<LspUrl, String>,
     pub(crate) builtin_symbols: HashMap<String, LspUrl>,
+    pub(crate) global_docs: Vec<Doc>,
 }
 
 /// The outcome of evaluating (checking, parsing or running) given starlark code.
@@ -122,6 +125,24 @@ impl Context {
             .into_iter()
             .map(|(u, ds)| (u, render_docs_as_code(&ds)))
             .collect();
+        let global_docs = match globals.documentation() {
+            DocItem::Object(globals) => globals
+                .members
+                .into_iter()
+                .filter_map(|(name, member)| match member {
+                    Member::Function(function) => Some(Doc {
+                        id: Identifier {
+                            name,
+                            location: None,
+                        },
+                        item: DocItem::Function(function),
+                        custom_attrs: HashMap::new(),
+                    }),
+                    Member::Property(_) => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
 
         Ok(Self {
             mode,
@@ -130,6 +151,7 @@ impl Context {
             module,
             builtin_docs,
             builtin_symbols,
+            global_docs,
         })
     }
 
@@ -329,6 +351,10 @@ impl LspContext for Context {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_global_symbols(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<Doc>> {
+        Ok(self.global_docs.clone())
+    }
 }
 
 pub(crate) fn globals() -> Globals {
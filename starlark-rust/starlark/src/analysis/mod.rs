@@ -21,6 +21,8 @@ pub(crate) use definition::Definition;
 pub(crate) use definition::DottedDefinition;
 pub(crate) use definition::IdentifierDefinition;
 pub(crate) use definition::LspModule;
+pub(crate) use symbols::NameKind;
+pub(crate) use symbols::Symbol;
 pub use types::EvalMessage;
 pub use types::EvalSeverity;
 pub use types::Lint;
@@ -36,6 +38,7 @@ mod flow;
 mod incompatible;
 mod names;
 mod performance;
+mod symbols;
 mod types;
 
 impl AstModule {
@@ -0,0 +1,708 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Resolution of the names used in a module to the symbols they refer to, for things like
+//! hover documentation, completion and finding references in the LSP.
+
+use std::collections::HashMap;
+
+use dupe::Dupe;
+use gazebo::prelude::*;
+
+use crate::analysis::bind::scope;
+use crate::analysis::bind::Assigner;
+use crate::analysis::bind::Bind;
+use crate::analysis::bind::Scope;
+use crate::analysis::LspModule;
+use crate::codemap::Pos;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::codemap::Spanned;
+use crate::collections::SmallMap;
+use crate::docs;
+use crate::docs::Doc;
+use crate::docs::DocItem;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
+use crate::docs::Identifier;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::Stmt;
+use crate::syntax::uniplate::Visit;
+
+/// The symbol that a name in a module refers to. See [`LspModule::find_symbol_at`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum Symbol {
+    /// The symbol is bound in this module, and `binding` is where it is first bound in its scope.
+    /// Only symbols bound at the top level can be loaded by other modules.
+    Local {
+        name: String,
+        binding: ResolvedSpan,
+        top_level: bool,
+    },
+    /// The symbol was loaded from the module at `path`, where it is called `name`.
+    Loaded { path: String, name: String },
+    /// The symbol is not bound in this module, so it should be a global symbol.
+    Global { name: String },
+}
+
+/// How a name that is in scope was bound. See [`LspModule::names_in_scope_at`].
+#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq)]
+pub(crate) enum NameKind {
+    /// A `def` statement.
+    Function,
+    /// An assignment, or the variable of a `for` loop.
+    Variable,
+    /// A parameter of an enclosing function.
+    Parameter,
+    /// A `load()` statement.
+    Loaded,
+}
+
+/// A call of a function by name, e.g. `foo(...)`. See [`LspModule::find_call_at`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct NamedCall {
+    /// The location of the name of the called function.
+    pub(crate) function: ResolvedSpan,
+    /// The arguments that are already passed by name.
+    pub(crate) named_args: Vec<String>,
+}
+
+/// A place where a name is bound or used, along with the binding that it resolves to.
+struct Reference<'a> {
+    span: Span,
+    name: &'a str,
+    /// The binding in the innermost scope that binds `name`, or `None` if no scope does.
+    binding: Option<&'a (Assigner, Span)>,
+    /// Whether `binding` is in the top level scope of the module.
+    top_level: bool,
+}
+
+/// Find all the places in `scope` (and its inner scopes) that bind or use a name.
+fn references(scope: &Scope) -> Vec<Reference> {
+    fn resolve<'a>(scopes: &[&'a Scope], name: &str) -> (Option<&'a (Assigner, Span)>, bool) {
+        for (i, scope) in scopes.iter().enumerate().rev() {
+            if let Some(binding) = scope.bound.get(name) {
+                return (Some(binding), i == 0);
+            }
+        }
+        (None, false)
+    }
+
+    fn visit<'a>(scopes: &mut Vec<&'a Scope>, res: &mut Vec<Reference<'a>>) {
+        let scope = *scopes.last().expect("at least one scope");
+        for bind in &scope.inner {
+            let (span, name) = match bind {
+                Bind::Set(_, x) => (x.span, x.0.as_str()),
+                Bind::Get(x) => (x.span, x.node.as_str()),
+                Bind::GetDotted(x) => {
+                    let x = x.root_identifier();
+                    (x.span, x.node.as_str())
+                }
+                Bind::Scope(inner) => {
+                    scopes.push(inner);
+                    visit(scopes, res);
+                    scopes.pop();
+                    continue;
+                }
+                Bind::Flow => continue,
+            };
+            let (binding, top_level) = resolve(scopes, name);
+            res.push(Reference {
+                span,
+                name,
+                binding,
+                top_level,
+            });
+        }
+    }
+
+    let mut res = Vec::new();
+    visit(&mut vec![scope], &mut res);
+    res
+}
+
+impl LspModule {
+    fn symbol(&self, reference: &Reference) -> Symbol {
+        match reference.binding {
+            Some((Assigner::Load { path, name }, _)) => Symbol::Loaded {
+                path: path.node.clone(),
+                name: name.node.clone(),
+            },
+            Some((_, span)) => Symbol::Local {
+                name: reference.name.to_owned(),
+                binding: self.ast.codemap.resolve_span(*span),
+                top_level: reference.top_level,
+            },
+            None => Symbol::Global {
+                name: reference.name.to_owned(),
+            },
+        }
+    }
+
+    fn pos(&self, line: u32, col: u32) -> Pos {
+        let line_span = self.ast.codemap.line_span(line as usize);
+        std::cmp::min(line_span.begin() + col, line_span.end())
+    }
+
+    /// Calls `f` with the path, the symbol name and the location of the string that names the
+    /// symbol for each symbol loaded in a `load()` statement. This is not the location of the
+    /// (possibly different) name that the symbol is bound to in this module.
+    fn visit_loaded_names<'a>(&'a self, mut f: impl FnMut(&'a str, &'a str, Span)) {
+        self.ast.statement.visit_stmt(|x| {
+            if let Stmt::Load(load) = &x.node {
+                for (_, name) in &load.args {
+                    f(&load.module.node, &name.node, name.span);
+                }
+            }
+        });
+    }
+
+    /// Find the symbol that the name at `line` and `col` (both zero based) refers to, along
+    /// with the location of that name. Only the leftmost name of a dotted expression is
+    /// considered, as the symbols that members refer to are not known.
+    pub(crate) fn find_symbol_at(&self, line: u32, col: u32) -> Option<(ResolvedSpan, Symbol)> {
+        let pos = self.pos(line, col);
+        let scope = scope(&self.ast);
+        if let Some(reference) = references(&scope).iter().find(|r| r.span.contains(pos)) {
+            return Some((
+                self.ast.codemap.resolve_span(reference.span),
+                self.symbol(reference),
+            ));
+        }
+
+        let mut res = None;
+        self.visit_loaded_names(|path, name, span| {
+            if res.is_none() && span.contains(pos) {
+                res = Some((
+                    self.ast.codemap.resolve_span(span),
+                    Symbol::Loaded {
+                        path: path.to_owned(),
+                        name: name.to_owned(),
+                    },
+                ));
+            }
+        });
+        res
+    }
+
+    /// Find the symbol that is bound to `name` at the top level of this module, if it is not
+    /// just loaded from another module.
+    pub(crate) fn find_top_level_symbol(&self, name: &str) -> Option<Symbol> {
+        match scope(&self.ast).bound.get(name) {
+            Some((Assigner::Load { .. }, _)) | None => None,
+            Some((_, span)) => Some(Symbol::Local {
+                name: name.to_owned(),
+                binding: self.ast.codemap.resolve_span(*span),
+                top_level: true,
+            }),
+        }
+    }
+
+    /// Find all the places in this module that bind or use `symbol`, which is a symbol that
+    /// was found in this module.
+    pub(crate) fn find_references(&self, symbol: &Symbol) -> Vec<ResolvedSpan> {
+        match symbol {
+            Symbol::Loaded { path, name } => {
+                self.find_loaded_references(name, |loaded_path| loaded_path == path)
+            }
+            _ => references(&scope(&self.ast))
+                .iter()
+                .filter(|r| self.symbol(r) == *symbol)
+                .map(|r| self.ast.codemap.resolve_span(r.span))
+                .collect(),
+        }
+    }
+
+    /// Find all the places in this module that load or use `name`, which is bound at the top
+    /// level of another module. `is_module` is called with the paths in `load()` statements,
+    /// and decides whether they refer to that module.
+    pub(crate) fn find_loaded_references(
+        &self,
+        name: &str,
+        is_module: impl Fn(&str) -> bool,
+    ) -> Vec<ResolvedSpan> {
+        let mut spans = Vec::new();
+        self.visit_loaded_names(|path, loaded_name, span| {
+            if loaded_name == name && is_module(path) {
+                spans.push(span);
+            }
+        });
+        for reference in references(&scope(&self.ast)) {
+            if let Some((
+                Assigner::Load {
+                    path,
+                    name: loaded_name,
+                },
+                _,
+            )) = reference.binding
+            {
+                if loaded_name.node == name
+                    && is_module(&path.node)
+                    && !spans.contains(&reference.span)
+                {
+                    spans.push(reference.span);
+                }
+            }
+        }
+        spans.sort_by_key(|span| span.begin());
+        spans.into_map(|span| self.ast.codemap.resolve_span(span))
+    }
+
+    /// Get the documentation for `symbol`, which is a symbol that was found in this module.
+    ///
+    /// Documentation is only available for functions that are defined in this module.
+    pub(crate) fn find_symbol_docs(&self, symbol: &Symbol) -> Option<Doc> {
+        fn find_def<'a>(
+            stmt: &'a AstStmt,
+            is_binding: &impl Fn(Span) -> bool,
+            res: &mut Option<&'a DefP<AstNoPayload>>,
+        ) {
+            match &stmt.node {
+                Stmt::Def(def) if is_binding(def.name.span) => *res = Some(def),
+                _ => stmt.visit_stmt(|x| find_def(x, is_binding, res)),
+            }
+        }
+
+        let (name, binding) = match symbol {
+            Symbol::Local { name, binding, .. } => (name, binding),
+            Symbol::Loaded { .. } | Symbol::Global { .. } => return None,
+        };
+        let mut def = None;
+        find_def(
+            &self.ast.statement,
+            &|span| self.ast.codemap.resolve_span(span) == *binding,
+            &mut def,
+        );
+
+        def.map(|def| Doc {
+            id: Identifier {
+                name: name.clone(),
+                location: None,
+            },
+            item: DocItem::Function(def_documentation(def)),
+            custom_attrs: HashMap::new(),
+        })
+    }
+
+    /// Get the names that are in scope at `line` and `col` (both zero based), along with how
+    /// they were bound. These are the names bound at the top level of the module, and in any
+    /// functions that enclose the position. Inner bindings come after, and take precedence
+    /// over, outer bindings of the same name.
+    pub(crate) fn names_in_scope_at(&self, line: u32, col: u32) -> Vec<(String, NameKind)> {
+        fn collect(stmt: &AstStmt, pos: Pos, names: &mut SmallMap<String, NameKind>) {
+            let mut insert = |name: &str, kind| {
+                names.remove(name);
+                names.insert(name.to_owned(), kind);
+            };
+            match &stmt.node {
+                Stmt::Def(def) => {
+                    insert(&def.name.0, NameKind::Function);
+                    if stmt.span.contains(pos) {
+                        for param in &def.params {
+                            if let (Some(name), _, _) = param.split() {
+                                insert(&name.0, NameKind::Parameter);
+                            }
+                        }
+                        collect(&def.body, pos, names);
+                    }
+                }
+                Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => {
+                    lhs.visit_lvalue(|x| insert(&x.0, NameKind::Variable))
+                }
+                Stmt::For(var, over_body) => {
+                    var.visit_lvalue(|x| insert(&x.0, NameKind::Variable));
+                    collect(&over_body.1, pos, names);
+                }
+                Stmt::Load(load) => {
+                    for (name, _) in &load.args {
+                        insert(&name.0, NameKind::Loaded);
+                    }
+                }
+                _ => stmt.visit_stmt(|x| collect(x, pos, names)),
+            }
+        }
+
+        let mut names = SmallMap::new();
+        collect(&self.ast.statement, self.pos(line, col), &mut names);
+        names.into_iter().collect()
+    }
+
+    /// If `line` and `col` (both zero based) are within the arguments of a call of a function
+    /// by name, but not within the value of one of the arguments, get that call. This is where
+    /// the names of the function's parameters can be completed.
+    pub(crate) fn find_call_at(&self, line: u32, col: u32) -> Option<NamedCall> {
+        fn visit_node(
+            module: &LspModule,
+            pos: Pos,
+            ret: &mut Option<NamedCall>,
+            node: Visit<AstNoPayload>,
+        ) {
+            if let Visit::Expr(Spanned {
+                node: ExprP::Call(function, args),
+                span,
+            }) = node
+            {
+                // A bare name might be the start of an argument name that is being typed.
+                let in_value = args.iter().any(|arg| match &arg.node {
+                    ArgumentP::Positional(Spanned {
+                        node: Expr::Identifier(..),
+                        ..
+                    }) => false,
+                    arg => arg.expr().span.contains(pos),
+                });
+                if let ExprP::Identifier(..) = &function.node {
+                    if pos > function.span.end() && pos < span.end() && !in_value {
+                        *ret = Some(NamedCall {
+                            function: module.ast.codemap.resolve_span(function.span),
+                            named_args: args
+                                .iter()
+                                .filter_map(|arg| match &arg.node {
+                                    ArgumentP::Named(name, _) => Some(name.node.clone()),
+                                    _ => None,
+                                })
+                                .collect(),
+                        });
+                    }
+                }
+            }
+            node.visit_children(|node| visit_node(module, pos, ret, node));
+        }
+
+        let mut ret = None;
+        visit_node(
+            self,
+            self.pos(line, col),
+            &mut ret,
+            Visit::Stmt(&self.ast.statement),
+        );
+        ret
+    }
+
+    /// Find the places in this module that bind or use the name at `line` and `col` (both zero
+    /// based), if that name was given to a symbol by a `load()` statement that renames it, like
+    /// `x` in `load("foo.star", x = "y")`. These are the places that need updating to change just
+    /// that name, rather than the name of the symbol that was loaded.
+    pub(crate) fn find_load_alias_references(
+        &self,
+        line: u32,
+        col: u32,
+    ) -> Option<Vec<ResolvedSpan>> {
+        let pos = self.pos(line, col);
+        let scope = scope(&self.ast);
+        let references = references(&scope);
+        let binding = match references.iter().find(|r| r.span.contains(pos))?.binding {
+            // The parser uses the span of the string as the span of the name if it isn't renamed.
+            Some((Assigner::Load { name, .. }, span)) if *span != name.span => *span,
+            _ => return None,
+        };
+        Some(
+            references
+                .iter()
+                .filter(|r| matches!(r.binding, Some((Assigner::Load { .. }, span)) if *span == binding))
+                .map(|r| self.ast.codemap.resolve_span(r.span))
+                .collect(),
+        )
+    }
+
+    /// Find the edits to this module that rename `name`, which is bound at the top level of
+    /// another module, to `new_name`. `is_module` decides whether the paths in `load()`
+    /// statements refer to that module. The strings that name the symbol in `load()` statements
+    /// are always updated, but the uses of the symbol only are if the load didn't rename it.
+    pub(crate) fn find_loaded_renames(
+        &self,
+        name: &str,
+        new_name: &str,
+        is_module: impl Fn(&str) -> bool,
+    ) -> Vec<(ResolvedSpan, String)> {
+        let mut strings = Vec::new();
+        self.visit_loaded_names(|path, loaded_name, span| {
+            if loaded_name == name && is_module(path) {
+                strings.push(span);
+            }
+        });
+
+        let mut edits = strings.map(|span| {
+            let quote = &self.ast.codemap.source_span(*span)[..1];
+            (*span, format!("{}{}{}", quote, new_name, quote))
+        });
+        for reference in references(&scope(&self.ast)) {
+            if let Some((
+                Assigner::Load {
+                    path,
+                    name: loaded_name,
+                },
+                binding,
+            )) = reference.binding
+            {
+                if loaded_name.node == name
+                    && *binding == loaded_name.span
+                    && is_module(&path.node)
+                    && !strings.contains(&reference.span)
+                {
+                    edits.push((reference.span, new_name.to_owned()));
+                }
+            }
+        }
+        edits.sort_by_key(|(span, _)| span.begin());
+        edits.into_map(|(span, new_name)| (self.ast.codemap.resolve_span(span), new_name))
+    }
+
+    /// If `symbol` is a parameter of a function defined with `def`, get the symbol that the
+    /// function is bound to.
+    pub(crate) fn find_parameter_function(&self, symbol: &Symbol) -> Option<Symbol> {
+        fn find_def<'a>(
+            stmt: &'a AstStmt,
+            is_binding: &impl Fn(Span) -> bool,
+            res: &mut Option<&'a DefP<AstNoPayload>>,
+        ) {
+            match &stmt.node {
+                Stmt::Def(def)
+                    if def.params.iter().any(|param| match param.split() {
+                        (Some(name), _, _) => is_binding(name.span),
+                        _ => false,
+                    }) =>
+                {
+                    *res = Some(def)
+                }
+                _ => stmt.visit_stmt(|x| find_def(x, is_binding, res)),
+            }
+        }
+
+        let binding = match symbol {
+            Symbol::Local {
+                binding,
+                top_level: false,
+                ..
+            } => binding,
+            _ => return None,
+        };
+        let mut def = None;
+        find_def(
+            &self.ast.statement,
+            &|span| self.ast.codemap.resolve_span(span) == *binding,
+            &mut def,
+        );
+        let def = def?;
+        references(&scope(&self.ast))
+            .iter()
+            .find(|r| r.span == def.name.span)
+            .map(|r| self.symbol(r))
+    }
+
+    /// Find the names of the arguments that are passed by `name` in calls of a function by name,
+    /// where `is_function` decides whether the function called is the one of interest.
+    pub(crate) fn find_keyword_arguments(
+        &self,
+        name: &str,
+        is_function: impl Fn(&Symbol) -> bool,
+    ) -> Vec<ResolvedSpan> {
+        fn visit_exprs<'a>(node: Visit<'a, AstNoPayload>, f: &mut impl FnMut(&'a AstExpr)) {
+            if let Visit::Expr(x) = node {
+                f(x);
+            }
+            node.visit_children(|node| visit_exprs(node, f));
+        }
+
+        let scope = scope(&self.ast);
+        let references = references(&scope);
+        let mut spans = Vec::new();
+        visit_exprs(Visit::Stmt(&self.ast.statement), &mut |x| {
+            if let ExprP::Call(function, args) = &x.node {
+                let is_call = matches!(function.node, ExprP::Identifier(..))
+                    && references
+                        .iter()
+                        .find(|r| r.span == function.span)
+                        .map_or(false, |r| is_function(&self.symbol(r)));
+                if is_call {
+                    for arg in args {
+                        if let ArgumentP::Named(arg_name, _) = &arg.node {
+                            if arg_name.node == name {
+                                spans.push(self.ast.codemap.resolve_span(arg_name.span));
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        spans
+    }
+}
+
+/// Build the documentation of a function from its definition and docstring.
+fn def_documentation(def: &DefP<AstNoPayload>) -> docs::Function {
+    let typ = |x: Option<&Spanned<Expr>>| {
+        x.map(|x| docs::Type {
+            raw_type: x.node.to_string(),
+        })
+    };
+    let params = def.params.map(|param| match &param.node {
+        ParameterP::Normal(name, t) => docs::Param::Arg {
+            name: name.0.clone(),
+            docs: None,
+            typ: typ(t.as_deref()),
+            default_value: None,
+        },
+        ParameterP::WithDefaultValue(name, t, default) => docs::Param::Arg {
+            name: name.0.clone(),
+            docs: None,
+            typ: typ(t.as_deref()),
+            default_value: Some(default.node.to_string()),
+        },
+        ParameterP::NoArgs => docs::Param::NoArgs,
+        ParameterP::Args(name, t) => docs::Param::Args {
+            name: name.0.clone(),
+            docs: None,
+            typ: typ(t.as_deref()),
+        },
+        ParameterP::KwArgs(name, t) => docs::Param::Kwargs {
+            name: name.0.clone(),
+            docs: None,
+            typ: typ(t.as_deref()),
+        },
+    });
+    docs::Function::from_docstring(
+        DocStringKind::Starlark,
+        params,
+        typ(def.return_type.as_deref()),
+        DocString::extract_raw_starlark_docstring(&def.body).as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use textwrap::dedent;
+
+    use super::NameKind;
+    use super::Symbol;
+    use crate::analysis::definition::helpers::FixtureWithRanges;
+
+    #[test]
+    fn finds_symbols_and_references() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("bar.star", <bar_load>"bar"</bar_load>)
+
+            <x_binding>x</x_binding> = 1
+
+            def f(x):
+                return x + <bar_use>bar</bar_use>
+
+            <x_use>x</x_use> + f(2) + <print>print</print>(3)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let (span, symbol) = module
+            .find_symbol_at(parsed.begin_line("x_use"), parsed.begin_column("x_use"))
+            .unwrap();
+        assert_eq!(parsed.span("x_use"), span);
+        assert_eq!(
+            Symbol::Local {
+                name: "x".to_owned(),
+                binding: parsed.span("x_binding"),
+                top_level: true,
+            },
+            symbol
+        );
+        assert_eq!(
+            vec![parsed.span("x_binding"), parsed.span("x_use")],
+            module.find_references(&symbol)
+        );
+
+        let loaded = Symbol::Loaded {
+            path: "bar.star".to_owned(),
+            name: "bar".to_owned(),
+        };
+        assert_eq!(
+            Some((parsed.span("bar_use"), loaded.clone())),
+            module.find_symbol_at(parsed.begin_line("bar_use"), parsed.begin_column("bar_use"))
+        );
+        assert_eq!(
+            Some((parsed.span("bar_load"), loaded.clone())),
+            module.find_symbol_at(
+                parsed.begin_line("bar_load"),
+                parsed.begin_column("bar_load")
+            )
+        );
+        assert_eq!(
+            vec![parsed.span("bar_load"), parsed.span("bar_use")],
+            module.find_references(&loaded)
+        );
+
+        assert_eq!(
+            Some((
+                parsed.span("print"),
+                Symbol::Global {
+                    name: "print".to_owned()
+                }
+            )),
+            module.find_symbol_at(parsed.begin_line("print"), parsed.begin_column("print"))
+        );
+        assert_eq!(None, module.find_top_level_symbol("bar"));
+        Ok(())
+    }
+
+    #[test]
+    fn finds_names_in_scope_and_calls() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("bar.star", "bar")
+
+            x = 1
+
+            def f(x, y):
+                z = x
+                bar(a = z, <click></click>)
+
+            def g():
+                pass
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+        let line = parsed.begin_line("click");
+        let col = parsed.begin_column("click");
+
+        assert_eq!(
+            vec![
+                ("bar".to_owned(), NameKind::Loaded),
+                ("f".to_owned(), NameKind::Function),
+                ("x".to_owned(), NameKind::Parameter),
+                ("y".to_owned(), NameKind::Parameter),
+                ("z".to_owned(), NameKind::Variable),
+                ("g".to_owned(), NameKind::Function),
+            ],
+            module.names_in_scope_at(line, col)
+        );
+
+        let call = module.find_call_at(line, col).unwrap();
+        assert_eq!(vec!["a".to_owned()], call.named_args);
+        Ok(())
+    }
+}
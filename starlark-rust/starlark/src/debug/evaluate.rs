@@ -81,7 +81,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         }
 
         let orig_module_variables = mem::replace(&mut self.module_variables, None);
-        let globals = self.top_second_frame_def_info_for_debugger()?.globals;
+        let globals = self.debugger_frame_def_info()?.globals;
         let res = self.eval_module(statements, &globals);
         self.module_variables = orig_module_variables;
 
@@ -109,12 +109,18 @@ impl<'v, 'a> Evaluator<'v, 'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    use std::cell::RefCell;
+
     use itertools::Itertools;
 
     use super::*;
     use crate as starlark;
     use crate::assert;
+    use crate::codemap::FileSpanRef;
+    use crate::environment::Globals;
     use crate::environment::GlobalsBuilder;
+    use crate::environment::Module;
     use crate::syntax::Dialect;
 
     #[starlark_module]
@@ -186,4 +192,36 @@ def bar(y):
         );
         a.pass("load('test', 'bar'); assert_eq(bar(4), 4 + 7 + 2)");
     }
+
+    #[test]
+    fn test_debug_evaluate_before_stmt() {
+        // Debuggers evaluate code where execution is paused, before a statement, where there is
+        // no native function on the top of the stack.
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        let results = RefCell::new(Vec::new());
+        let evaluating = Cell::new(false);
+        let before_stmt = |span: FileSpanRef, eval: &mut Evaluator<'_, '_>| {
+            let code = match span.to_file_span().source_span() {
+                "return x" => "x + y",
+                "z = y" => "y",
+                _ => return,
+            };
+            if !evaluating.replace(true) {
+                let ast =
+                    AstModule::parse("interactive", code.to_owned(), &Dialect::Extended).unwrap();
+                results
+                    .borrow_mut()
+                    .push(eval.eval_statements(ast).unwrap().to_string());
+                evaluating.set(false);
+            }
+        };
+        eval.before_stmt(&before_stmt);
+
+        let program = "y = 10\ndef f(x):\n    return x\nf(1)\nf(2)\nz = y\n";
+        let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+        assert_eq!(vec!["11", "12", "10"], *results.borrow());
+    }
 }
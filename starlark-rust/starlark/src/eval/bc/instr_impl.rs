@@ -27,6 +27,7 @@ use gazebo::coerce::coerce;
 use crate::collections::symbol_map::Symbol;
 use crate::collections::Hashed;
 use crate::collections::SmallMap;
+use crate::collections::SmallSet;
 use crate::const_frozen_string;
 use crate::environment::slots::ModuleSlotId;
 use crate::eval::bc::addr::BcAddrOffset;
@@ -78,6 +79,7 @@ use crate::eval::ParametersSpec;
 use crate::values::dict::Dict;
 use crate::values::int::PointerI32;
 use crate::values::layout::value_not_special::FrozenValueNotSpecial;
+use crate::values::set::Set;
 use crate::values::string::interpolation::format_one;
 use crate::values::string::interpolation::percent_s_one;
 use crate::values::types::known_methods::KnownMethod;
@@ -901,6 +903,7 @@ pub(crate) struct InstrDictConstKeysImpl;
 pub(crate) struct InstrDictNPopImpl;
 pub(crate) struct InstrListNewImpl;
 pub(crate) struct InstrDictNewImpl;
+pub(crate) struct InstrSetNPopImpl;
 
 pub(crate) type InstrTupleNPop = InstrNoFlow<InstrTupleNPopImpl>;
 pub(crate) type InstrListNew = InstrNoFlow<InstrListNewImpl>;
@@ -910,6 +913,7 @@ pub(crate) type InstrDictNew = InstrNoFlow<InstrDictNewImpl>;
 pub(crate) type InstrDictOfConsts = InstrNoFlow<InstrDictOfConstsImpl>;
 pub(crate) type InstrDictConstKeys = InstrNoFlow<InstrDictConstKeysImpl>;
 pub(crate) type InstrDictNPop = InstrNoFlow<InstrDictNPopImpl>;
+pub(crate) type InstrSetNPop = InstrNoFlow<InstrSetNPopImpl>;
 
 impl InstrNoFlowImpl for InstrTupleNPopImpl {
     type Arg = (BcSlotInRange, BcSlotOut);
@@ -1012,6 +1016,33 @@ impl InstrNoFlowImpl for InstrDictNPopImpl {
     }
 }
 
+impl InstrNoFlowImpl for InstrSetNPopImpl {
+    type Arg = (BcSlotInRange, BcSlotOut);
+
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        frame: BcFramePtr<'v>,
+        ip: BcPtrAddr,
+        (npops, target): &(BcSlotInRange, BcSlotOut),
+    ) -> anyhow::Result<()> {
+        let items = frame.get_bc_slot_range(*npops);
+        let mut set = SmallSet::with_capacity(items.len());
+        for (i, x) in items.iter().enumerate() {
+            let x = match x.get_hashed() {
+                Ok(x) => x,
+                Err(e) => {
+                    let spans = &Bc::slow_arg_at_ptr(ip).spans;
+                    return Err(add_span_to_expr_error(e, spans[i], eval).0);
+                }
+            };
+            set.insert_hashed(x);
+        }
+        let set = eval.heap().alloc(Set::new(set));
+        frame.set_bc_slot(*target, set);
+        Ok(())
+    }
+}
+
 impl InstrNoFlowImpl for InstrDictConstKeysImpl {
     type Arg = (Box<[Hashed<FrozenValue>]>, BcSlotInRangeFrom, BcSlotOut);
 
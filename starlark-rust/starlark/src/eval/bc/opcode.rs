@@ -87,6 +87,7 @@ pub(crate) enum BcOpcode {
     DictNPop,
     DictOfConsts,
     DictConstKeys,
+    SetNPop,
     ComprListAppend,
     ComprDictInsert,
     CheckType,
@@ -236,6 +236,7 @@ pub(crate) enum ExprCompiled {
     Tuple(Vec<IrSpanned<ExprCompiled>>),
     List(Vec<IrSpanned<ExprCompiled>>),
     Dict(Vec<(IrSpanned<ExprCompiled>, IrSpanned<ExprCompiled>)>),
+    Set(Vec<IrSpanned<ExprCompiled>>),
     /// Comprehension.
     Compr(ComprCompiled),
     If(
@@ -471,6 +472,7 @@ impl IrSpanned<ExprCompiled> {
             ExprCompiled::Dict(kvs) => {
                 ExprCompiled::Dict(kvs.map(|(k, v)| (k.optimize(ctx), v.optimize(ctx))))
             }
+            ExprCompiled::Set(xs) => ExprCompiled::Set(xs.map(|e| e.optimize(ctx))),
             ExprCompiled::Compr(compr) => compr.optimize(ctx),
             ExprCompiled::If(cond_t_f) => {
                 let (cond, t, f) = &**cond_t_f;
@@ -1199,6 +1201,10 @@ impl<'v, 'a, 'e> Compiler<'v, 'a, 'e> {
                 let xs = exprs.into_map(|(k, v)| (self.expr(k), self.expr(v)));
                 ExprCompiled::Dict(xs)
             }
+            ExprP::Set(exprs) => {
+                let xs = exprs.into_map(|x| self.expr(x));
+                ExprCompiled::Set(xs)
+            }
             ExprP::If(cond_then_expr_else_expr) => {
                 let (cond, then_expr, else_expr) = *cond_then_expr_else_expr;
                 let cond = self.expr(cond);
@@ -59,6 +59,9 @@ use crate::syntax::ast::StmtP;
 use crate::values::dict::Dict;
 use crate::values::dict::DictMut;
 use crate::values::dict::DictRef;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
 use crate::values::types::list::value::ListData;
 use crate::values::FrozenHeap;
 use crate::values::FrozenValue;
@@ -576,14 +579,14 @@ pub(crate) fn possible_gc(eval: &mut Evaluator) {
     }
 }
 
-/// Implement lhs |= rhs, which is special in Starlark, because dicts are mutated,
+/// Implement lhs |= rhs, which is special in Starlark, because dicts and sets are mutated,
 /// while all other types are not.
 pub(crate) fn bit_or_assign<'v>(
     lhs: Value<'v>,
     rhs: Value<'v>,
     heap: &'v Heap,
 ) -> anyhow::Result<Value<'v>> {
-    // The Starlark spec says dict |= mutates, while nothing else does.
+    // The Starlark spec says dict |= mutates, and so does set |=, while nothing else does.
     // When mutating, be careful if they alias, so we don't have `lhs`
     // mutably borrowed when we iterate over `rhs`, as they might alias.
 
@@ -604,6 +607,20 @@ pub(crate) fn bit_or_assign<'v>(
             }
         }
         Ok(lhs)
+    } else if Set::is_set_type(lhs_ty) {
+        let mut set = SetMut::from_value(lhs)?;
+        if lhs.ptr_eq(rhs) {
+            // Nothing to do as union is idempotent
+        } else {
+            let rhs = SetRef::from_value(rhs).map_or_else(
+                || ValueError::unsupported_owned(lhs_aref.get_type(), "|=", Some(rhs.get_type())),
+                Ok,
+            )?;
+            for x in rhs.iter_hashed() {
+                set.insert_hashed(x);
+            }
+        }
+        Ok(lhs)
     } else {
         lhs_aref.bit_or(rhs, heap)
     }
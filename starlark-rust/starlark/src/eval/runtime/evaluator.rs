@@ -92,8 +92,6 @@ pub(crate) enum EvaluatorError {
     TopFrameNotDef,
     #[error("Top second frame is not def (internal error)")]
     TopSecondFrameNotDef,
-    #[error("Top frame is not native (internal error)")]
-    TopFrameNotNative,
     #[error(
         "Coverage profile generation not implemented (but can be obtained with `.coverage()` function)"
     )]
@@ -639,17 +637,16 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         }
     }
 
-    /// When top frame is `breakpoint` or `debug_evaluate` function, skip it.
-    pub(crate) fn top_second_frame_def_info_for_debugger(
-        &self,
-    ) -> anyhow::Result<FrozenRef<DefInfo>> {
-        // Self-check: we are in `breakpoint` or `debug_evaluate` function.
-        let breakpoint = self.call_stack.top_nth_function(0)?;
-        breakpoint
-            .downcast_ref::<NativeFunction>()
-            .ok_or(EvaluatorError::TopFrameNotNative)?;
-
-        let func = self.call_stack.top_nth_function(1)?;
+    /// Get the `DefInfo` of the function (or module) that the debugger is stopped in. That is
+    /// the top frame when stopped by `before_stmt`, or the frame below it when stopped by
+    /// a native function like `breakpoint` or `debug_evaluate`.
+    pub(crate) fn debugger_frame_def_info(&self) -> anyhow::Result<FrozenRef<DefInfo>> {
+        let mut func = self.call_stack.top_nth_function(0)?;
+        let mut error = EvaluatorError::TopFrameNotDef;
+        if func.downcast_ref::<NativeFunction>().is_some() {
+            func = self.call_stack.top_nth_function(1)?;
+            error = EvaluatorError::TopSecondFrameNotDef;
+        }
         if let Some(func) = func.downcast_ref::<Def>() {
             Ok(func.def_info)
         } else if let Some(func) = func.downcast_ref::<FrozenDef>() {
@@ -658,7 +655,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             // For module, it is `None`.
             Ok(self.module_def_info)
         } else {
-            Err(EvaluatorError::TopSecondFrameNotDef.into())
+            Err(error.into())
         }
     }
 
@@ -18,6 +18,7 @@
 //! Based on the reference lsp-server example at <https://github.com/rust-analyzer/lsp-server/blob/master/examples/goto_def.rs>.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 use std::path::PathBuf;
@@ -36,30 +37,62 @@ use lsp_server::RequestId;
 use lsp_server::Response;
 use lsp_server::ResponseError;
 use lsp_types::notification::DidChangeTextDocument;
+use lsp_types::notification::DidChangeWatchedFiles;
 use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::Completion;
+use lsp_types::request::Formatting;
 use lsp_types::request::GotoDefinition;
+use lsp_types::request::HoverRequest;
+use lsp_types::request::References;
+use lsp_types::request::RegisterCapability;
+use lsp_types::request::Rename;
+use lsp_types::CompletionItem;
+use lsp_types::CompletionItemKind;
+use lsp_types::CompletionOptions;
+use lsp_types::CompletionParams;
+use lsp_types::CompletionResponse;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
+use lsp_types::DidChangeWatchedFilesParams;
+use lsp_types::DidChangeWatchedFilesRegistrationOptions;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentFormattingParams;
+use lsp_types::Documentation;
+use lsp_types::FileChangeType;
+use lsp_types::FileSystemWatcher;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
+use lsp_types::Hover;
+use lsp_types::HoverContents;
+use lsp_types::HoverParams;
+use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
+use lsp_types::Location;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
 use lsp_types::MessageType;
 use lsp_types::OneOf;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::Registration;
+use lsp_types::RegistrationParams;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
+use lsp_types::TextEdit;
 use lsp_types::Url;
+use lsp_types::WatchKind;
 use lsp_types::WorkDoneProgressOptions;
+use lsp_types::WorkspaceEdit;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -70,8 +103,14 @@ use crate::analysis::Definition;
 use crate::analysis::DottedDefinition;
 use crate::analysis::IdentifierDefinition;
 use crate::analysis::LspModule;
+use crate::analysis::NameKind;
+use crate::analysis::Symbol;
 use crate::codemap::ResolvedSpan;
+use crate::docs::Doc;
+use crate::docs::DocItem;
+use crate::docs::Param;
 use crate::lsp::server::LoadContentsError::WrongScheme;
+use crate::syntax::lexer::Token;
 use crate::syntax::AstModule;
 
 /// The request to get the file contents for a starlark: URI
@@ -293,6 +332,22 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// Get the documentation for the global symbols that are available in `current_file`.
+    ///
+    /// These are shown when hovering over global symbols, and offered as completions.
+    fn get_global_symbols(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<Doc>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the starlark files in the workspace, which are searched when finding references to
+    /// a symbol. `workspace_roots` are the root directories that the client has open, if any.
+    ///
+    /// Files that are open in the client are always searched, whether or not they are returned.
+    /// The result is reused until the client reports that files were created or deleted.
+    fn get_workspace_files(&self, _workspace_roots: &[LspUrl]) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -311,12 +366,31 @@ pub(crate) enum LoadContentsError {
     WrongScheme(String, LspUrl),
 }
 
+/// Errors when a symbol cannot be renamed.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    /// The new name is not a valid identifier.
+    #[error("`{0}` is not a valid name")]
+    InvalidName(String),
+    /// Global symbols are not defined in the workspace, so can't be renamed.
+    #[error("`{0}` is a global symbol, so can't be renamed")]
+    Global(String),
+}
+
 struct Backend<T: LspContext> {
     connection: Connection,
     context: T,
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// The open files whose contents didn't parse the last time that they changed, so the
+    /// entries in `last_valid_parse` for them are out of date.
+    outdated_parses: RwLock<HashSet<LspUrl>>,
+    /// The root directories that the client has open.
+    workspace_roots: Vec<LspUrl>,
+    /// The files from [`LspContext::get_workspace_files()`], which are kept until the client
+    /// says that files were created or deleted.
+    workspace_files: RwLock<Option<Arc<Vec<LspUrl>>>>,
 }
 
 /// The logic implementations of stuff
@@ -332,6 +406,11 @@ impl<T: LspContext> Backend<T> {
         ServerCapabilities {
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
             definition_provider,
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            completion_provider: Some(CompletionOptions::default()),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         }
     }
@@ -359,6 +438,9 @@ impl<T: LspContext> Backend<T> {
             let module = Arc::new(LspModule::new(ast));
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
             last_valid_parse.insert(uri.clone(), module);
+            self.outdated_parses.write().unwrap().remove(&uri);
+        } else {
+            self.outdated_parses.write().unwrap().insert(uri.clone());
         }
         self.publish_diagnostics(uri.try_into()?, eval_result.diagnostics, version);
         Ok(())
@@ -382,10 +464,21 @@ impl<T: LspContext> Backend<T> {
         )
     }
 
+    fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        // Edits to existing files don't change which files there are.
+        if params.changes.iter().any(|change| {
+            change.typ == FileChangeType::CREATED || change.typ == FileChangeType::DELETED
+        }) {
+            *self.workspace_files.write().unwrap() = None;
+        }
+    }
+
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
+            let uri: LspUrl = params.text_document.uri.clone().try_into()?;
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
+            self.outdated_parses.write().unwrap().remove(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
@@ -587,6 +680,475 @@ impl<T: LspContext> Backend<T> {
         };
         Ok(GotoDefinitionResponse::Link(response))
     }
+
+    /// Show the documentation for the symbol at the current cursor, if there is any.
+    fn hover(&self, id: RequestId, params: HoverParams) {
+        self.send_response(new_response(id, self.hover_info(params)));
+    }
+
+    /// Offer the names that are in scope at the current cursor, the global symbols, and if the
+    /// cursor is within the arguments of a call, the parameter names of the called function.
+    fn completion(&self, id: RequestId, params: CompletionParams) {
+        self.send_response(new_response(id, self.completion_options(params)));
+    }
+
+    /// Find all the places in the workspace that refer to the symbol at the current cursor.
+    fn references(&self, id: RequestId, params: ReferenceParams) {
+        self.send_response(new_response(id, self.find_references(params)));
+    }
+
+    /// Rename the symbol at the current cursor, everywhere that it is used.
+    fn rename(&self, id: RequestId, params: RenameParams) {
+        self.send_response(new_response(id, self.rename_symbol(params)));
+    }
+
+    /// Format a whole file.
+    fn formatting(&self, id: RequestId, params: DocumentFormattingParams) {
+        self.send_response(new_response(id, self.format_document(params)));
+    }
+
+    /// Get the documentation for a symbol that was found in `module`, which is at `uri`.
+    ///
+    /// Symbols loaded from other modules are looked up in those modules, and global symbols
+    /// are looked up in [`LspContext::get_global_symbols`].
+    fn get_symbol_docs(
+        &self,
+        uri: &LspUrl,
+        module: &LspModule,
+        symbol: &Symbol,
+    ) -> anyhow::Result<Option<Doc>> {
+        let doc = match symbol {
+            Symbol::Local { .. } => module.find_symbol_docs(symbol),
+            Symbol::Loaded { path, name } => {
+                let load_uri = self.resolve_load_path(path, uri)?;
+                self.get_ast_or_load_from_disk(&load_uri)?
+                    .and_then(|loaded| {
+                        loaded
+                            .find_top_level_symbol(name)
+                            .and_then(|symbol| loaded.find_symbol_docs(&symbol))
+                    })
+            }
+            Symbol::Global { name } => self
+                .context
+                .get_global_symbols(uri)?
+                .into_iter()
+                .find(|doc| doc.id.name == *name),
+        };
+        Ok(doc)
+    }
+
+    fn hover_info(&self, params: HoverParams) -> anyhow::Result<Option<Hover>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let (source, symbol) = match ast.find_symbol_at(position.line, position.character) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        Ok(self.get_symbol_docs(&uri, &ast, &symbol)?.map(|doc| Hover {
+            contents: HoverContents::Markup(doc_markup(&doc)),
+            range: Some(source.into()),
+        }))
+    }
+
+    fn completion_options(&self, params: CompletionParams) -> anyhow::Result<CompletionResponse> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+
+        let mut items = Vec::new();
+        let mut names = Vec::new();
+        if let Some(ast) = self.get_ast(&uri) {
+            if let Some(call) = ast.find_call_at(position.line, position.character) {
+                let function = ast.find_symbol_at(
+                    call.function.begin_line as u32,
+                    call.function.begin_column as u32,
+                );
+                let function_docs = match function {
+                    Some((_, symbol)) => self.get_symbol_docs(&uri, &ast, &symbol)?,
+                    None => None,
+                };
+                if let Some(Doc {
+                    item: DocItem::Function(function),
+                    ..
+                }) = function_docs
+                {
+                    for param in function.params {
+                        if let Param::Arg { name, docs, .. } = param {
+                            if call.named_args.contains(&name) {
+                                continue;
+                            }
+                            items.push(CompletionItem {
+                                insert_text: Some(format!("{} = ", name)),
+                                label: name,
+                                kind: Some(CompletionItemKind::PROPERTY),
+                                documentation: docs.map(|docs| {
+                                    Documentation::String(match docs.details {
+                                        Some(details) => {
+                                            format!("{}\n\n{}", docs.summary, details)
+                                        }
+                                        None => docs.summary,
+                                    })
+                                }),
+                                ..CompletionItem::default()
+                            });
+                        }
+                    }
+                }
+            }
+
+            for (name, kind) in ast.names_in_scope_at(position.line, position.character) {
+                names.push(name.clone());
+                items.push(CompletionItem {
+                    label: name,
+                    kind: Some(match kind {
+                        NameKind::Function => CompletionItemKind::FUNCTION,
+                        NameKind::Variable | NameKind::Parameter => CompletionItemKind::VARIABLE,
+                        NameKind::Loaded => CompletionItemKind::REFERENCE,
+                    }),
+                    ..CompletionItem::default()
+                });
+            }
+        }
+
+        for doc in self.context.get_global_symbols(&uri)? {
+            // Names bound in the module shadow the global symbols.
+            if names.contains(&doc.id.name) {
+                continue;
+            }
+            items.push(CompletionItem {
+                kind: Some(match doc.item {
+                    DocItem::Function(_) => CompletionItemKind::FUNCTION,
+                    DocItem::Module(_) => CompletionItemKind::MODULE,
+                    DocItem::Object(_) => CompletionItemKind::STRUCT,
+                }),
+                documentation: Some(Documentation::MarkupContent(doc_markup(&doc))),
+                label: doc.id.name,
+                ..CompletionItem::default()
+            });
+        }
+
+        Ok(CompletionResponse::Array(items))
+    }
+
+    fn workspace_files(&self) -> anyhow::Result<Arc<Vec<LspUrl>>> {
+        if let Some(files) = self.workspace_files.read().unwrap().as_ref() {
+            return Ok(files.dupe());
+        }
+        let files = Arc::new(self.context.get_workspace_files(&self.workspace_roots)?);
+        *self.workspace_files.write().unwrap() = Some(files.dupe());
+        Ok(files)
+    }
+
+    /// Get the files to search for references to a symbol defined in `uri`: that file, the files
+    /// that are open in the client, and the files in the workspace.
+    fn files_to_search(&self, uri: &LspUrl) -> anyhow::Result<Vec<LspUrl>> {
+        let mut files = vec![uri.clone()];
+        files.extend(self.last_valid_parse.read().unwrap().keys().cloned());
+        files.extend(self.workspace_files()?.iter().cloned());
+
+        let mut seen = HashSet::new();
+        files.retain(|file| seen.insert(file.clone()));
+        Ok(files)
+    }
+
+    /// Find the references to `name`, which is bound at the top level of the module at `uri`,
+    /// in that module and in all the modules that load it.
+    fn find_exported_references(
+        &self,
+        uri: &LspUrl,
+        name: &str,
+    ) -> anyhow::Result<Vec<(LspUrl, ResolvedSpan)>> {
+        let mut references = Vec::new();
+        for file in self.files_to_search(uri)? {
+            // Files that can't be loaded or parsed can't refer to the symbol.
+            let module = match self.get_ast_or_load_from_disk(&file) {
+                Ok(Some(module)) => module,
+                _ => continue,
+            };
+            let mut spans = Vec::new();
+            if file == *uri {
+                if let Some(symbol) = module.find_top_level_symbol(name) {
+                    spans.extend(module.find_references(&symbol));
+                }
+            }
+            spans.extend(module.find_loaded_references(name, |path| {
+                self.resolve_load_path(path, &file)
+                    .map_or(false, |load_uri| load_uri == *uri)
+            }));
+            references.extend(spans.into_iter().map(|span| (file.clone(), span)));
+        }
+        Ok(references)
+    }
+
+    fn find_references(&self, params: ReferenceParams) -> anyhow::Result<Vec<Location>> {
+        let uri: LspUrl = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(Vec::new()),
+        };
+        let symbol = match ast.find_symbol_at(position.line, position.character) {
+            Some((_, symbol)) => symbol,
+            None => return Ok(Vec::new()),
+        };
+
+        let (declaration, references) = match &symbol {
+            // Names that start with an underscore are private, so can't be loaded elsewhere.
+            Symbol::Local {
+                name,
+                binding,
+                top_level,
+            } if *top_level && !name.starts_with('_') => (
+                Some((uri.clone(), *binding)),
+                self.find_exported_references(&uri, name)?,
+            ),
+            Symbol::Local { binding, .. } => (
+                Some((uri.clone(), *binding)),
+                ast.find_references(&symbol)
+                    .into_iter()
+                    .map(|span| (uri.clone(), span))
+                    .collect(),
+            ),
+            Symbol::Loaded { path, name } => {
+                let load_uri = self.resolve_load_path(path, &uri)?;
+                let declaration = self
+                    .get_ast_or_load_from_disk(&load_uri)?
+                    .and_then(|loaded| loaded.find_top_level_symbol(name))
+                    .and_then(|symbol| match symbol {
+                        Symbol::Local { binding, .. } => Some((load_uri.clone(), binding)),
+                        _ => None,
+                    });
+                (declaration, self.find_exported_references(&load_uri, name)?)
+            }
+            Symbol::Global { .. } => {
+                let mut references = Vec::new();
+                for file in self.files_to_search(&uri)? {
+                    if let Ok(Some(module)) = self.get_ast_or_load_from_disk(&file) {
+                        references.extend(
+                            module
+                                .find_references(&symbol)
+                                .into_iter()
+                                .map(|span| (file.clone(), span)),
+                        );
+                    }
+                }
+                (None, references)
+            }
+        };
+
+        references
+            .into_iter()
+            .filter(|reference| {
+                params.context.include_declaration || Some(reference) != declaration.as_ref()
+            })
+            .map(|(file, span)| {
+                Ok(Location {
+                    uri: (&file).try_into()?,
+                    range: span.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Find the edits that rename `name`, which is bound at the top level of the module at
+    /// `uri`, in that module and in all the modules that load it.
+    fn rename_exported(
+        &self,
+        uri: &LspUrl,
+        name: &str,
+        new_name: &str,
+    ) -> anyhow::Result<Vec<(LspUrl, ResolvedSpan, String)>> {
+        let mut edits = Vec::new();
+        for file in self.files_to_search(uri)? {
+            // Files that can't be loaded or parsed can't refer to the symbol.
+            let module = match self.get_ast_or_load_from_disk(&file) {
+                Ok(Some(module)) => module,
+                _ => continue,
+            };
+            if file == *uri {
+                if let Some(symbol) = module.find_top_level_symbol(name) {
+                    edits.extend(
+                        module
+                            .find_references(&symbol)
+                            .into_iter()
+                            .map(|span| (file.clone(), span, new_name.to_owned())),
+                    );
+                }
+            }
+            edits.extend(
+                module
+                    .find_loaded_renames(name, new_name, |path| {
+                        self.resolve_load_path(path, &file)
+                            .map_or(false, |load_uri| load_uri == *uri)
+                    })
+                    .into_iter()
+                    .map(|(span, new_name)| (file.clone(), span, new_name)),
+            );
+        }
+        Ok(edits)
+    }
+
+    /// Find the names of the arguments that are passed by the name `param` to `function`, which
+    /// is a symbol in the module at `uri`, everywhere that `function` can be called.
+    fn find_keyword_arguments(
+        &self,
+        uri: &LspUrl,
+        function: &Symbol,
+        param: &str,
+    ) -> anyhow::Result<Vec<(LspUrl, ResolvedSpan)>> {
+        let exported_name = match function {
+            Symbol::Local {
+                name, top_level, ..
+            } if *top_level && !name.starts_with('_') => name,
+            _ => {
+                let ast = self.get_ast_or_load_from_disk(uri)?;
+                return Ok(ast
+                    .map(|ast| ast.find_keyword_arguments(param, |symbol| symbol == function))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|span| (uri.clone(), span))
+                    .collect());
+            }
+        };
+
+        let mut arguments = Vec::new();
+        for file in self.files_to_search(uri)? {
+            let module = match self.get_ast_or_load_from_disk(&file) {
+                Ok(Some(module)) => module,
+                _ => continue,
+            };
+            let spans = module.find_keyword_arguments(param, |symbol| match symbol {
+                Symbol::Loaded { path, name } => {
+                    name == exported_name
+                        && self
+                            .resolve_load_path(path, &file)
+                            .map_or(false, |load_uri| load_uri == *uri)
+                }
+                symbol => file == *uri && symbol == function,
+            });
+            arguments.extend(spans.into_iter().map(|span| (file.clone(), span)));
+        }
+        Ok(arguments)
+    }
+
+    /// Rename the symbol at a position, and everything that refers to it. Symbols that are bound
+    /// at the top level of a module are also renamed in the modules that load them, and renaming
+    /// a parameter of a function also renames the arguments passed by that name to the function.
+    /// Renaming a name that a `load()` statement gave to a symbol just renames that name.
+    fn rename_symbol(&self, params: RenameParams) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let uri: LspUrl = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+        if !Token::is_identifier(&new_name) {
+            return Err(RenameError::InvalidName(new_name).into());
+        }
+
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let symbol = match ast.find_symbol_at(position.line, position.character) {
+            Some((_, symbol)) => symbol,
+            None => return Ok(None),
+        };
+
+        let edits = match &symbol {
+            Symbol::Global { name } => return Err(RenameError::Global(name.clone()).into()),
+            // Names that start with an underscore are private, so can't be loaded elsewhere.
+            Symbol::Local {
+                name, top_level, ..
+            } if *top_level && !name.starts_with('_') => {
+                self.rename_exported(&uri, name, &new_name)?
+            }
+            Symbol::Local { .. } => {
+                let mut edits: Vec<_> = ast
+                    .find_references(&symbol)
+                    .into_iter()
+                    .map(|span| (uri.clone(), span, new_name.clone()))
+                    .collect();
+                if let (Some(function), Symbol::Local { name, .. }) =
+                    (ast.find_parameter_function(&symbol), &symbol)
+                {
+                    edits.extend(
+                        self.find_keyword_arguments(&uri, &function, name)?
+                            .into_iter()
+                            .map(|(file, span)| (file, span, new_name.clone())),
+                    );
+                }
+                edits
+            }
+            Symbol::Loaded { path, name } => {
+                match ast.find_load_alias_references(position.line, position.character) {
+                    Some(spans) => spans
+                        .into_iter()
+                        .map(|span| (uri.clone(), span, new_name.clone()))
+                        .collect(),
+                    None => {
+                        let load_uri = self.resolve_load_path(path, &uri)?;
+                        self.rename_exported(&load_uri, name, &new_name)?
+                    }
+                }
+            }
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for (file, span, new_text) in edits {
+            changes
+                .entry((&file).try_into()?)
+                .or_default()
+                .push(TextEdit {
+                    range: span.into(),
+                    new_text,
+                });
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+
+    /// Format a whole file with the canonical printer for starlark. Files whose current contents
+    /// don't parse are left alone.
+    fn format_document(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let uri: LspUrl = params.text_document.uri.try_into()?;
+        if self.outdated_parses.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+
+        let formatted = ast.ast.format();
+        let codemap = &ast.ast.codemap;
+        if formatted == codemap.source() {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: codemap.resolve_span(codemap.full_span()).into(),
+            new_text: formatted,
+        }]))
+    }
+}
+
+/// Render documentation as the starlark code that it describes, for hovers and completions.
+fn doc_markup(doc: &Doc) -> MarkupContent {
+    MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: format!("```python\n{}\n```", doc.render_as_code()),
+    }
 }
 
 /// The library style pieces
@@ -615,8 +1177,46 @@ impl<T: LspContext> Backend<T> {
         ));
     }
 
-    fn main_loop(&self, _params: InitializeParams) -> anyhow::Result<()> {
+    /// Ask the client to tell us when files are created or deleted, so that the cached
+    /// workspace files can be refreshed.
+    fn register_watched_files(&self, params: &InitializeParams) {
+        let supported = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        if !supported {
+            return;
+        }
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: "**/*".to_owned(),
+                kind: Some(WatchKind::Create | WatchKind::Delete),
+            }],
+        };
+        let method = <DidChangeWatchedFiles as lsp_types::notification::Notification>::METHOD;
+        let params = RegistrationParams {
+            registrations: vec![Registration {
+                id: method.to_owned(),
+                method: method.to_owned(),
+                register_options: Some(serde_json::to_value(options).unwrap()),
+            }],
+        };
+        // The client's response is ignored by `main_loop`.
+        self.connection
+            .sender
+            .send(Message::Request(new_request::<RegisterCapability>(
+                RequestId::from("register_watched_files".to_owned()),
+                params,
+            )))
+            .unwrap()
+    }
+
+    fn main_loop(&self, params: InitializeParams) -> anyhow::Result<()> {
         self.log_message(MessageType::INFO, "Starlark server initialised");
+        self.register_watched_files(&params);
         for msg in &self.connection.receiver {
             match msg {
                 Message::Request(req) => {
@@ -624,6 +1224,16 @@ impl<T: LspContext> Backend<T> {
                     //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params);
+                    } else if let Some(params) = as_request::<HoverRequest>(&req) {
+                        self.hover(req.id, params);
+                    } else if let Some(params) = as_request::<Completion>(&req) {
+                        self.completion(req.id, params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename(req.id, params);
+                    } else if let Some(params) = as_request::<Formatting>(&req) {
+                        self.formatting(req.id, params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
                         self.get_starlark_file_contents(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
@@ -638,6 +1248,8 @@ impl<T: LspContext> Backend<T> {
                         self.did_change(params)?;
                     } else if let Some(params) = as_notification::<DidCloseTextDocument>(&x) {
                         self.did_close(params)?;
+                    } else if let Some(params) = as_notification::<DidChangeWatchedFiles>(&x) {
+                        self.did_change_watched_files(params);
                     }
                 }
                 Message::Response(_) => {
@@ -685,10 +1297,25 @@ pub fn server_with_connection<T: LspContext>(
     });
     connection.initialize_finish(init_request_id, initialize_data)?;
 
+    let workspace_roots = match &initialization_params.workspace_folders {
+        Some(folders) => folders
+            .iter()
+            .filter_map(|folder| folder.uri.clone().try_into().ok())
+            .collect(),
+        None => initialization_params
+            .root_uri
+            .iter()
+            .filter_map(|uri| uri.clone().try_into().ok())
+            .collect(),
+    };
+
     Backend {
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        outdated_parses: RwLock::default(),
+        workspace_roots,
+        workspace_files: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -738,6 +1365,18 @@ where
     }
 }
 
+/// Create a new `Request` object with the correct name from the given params.
+fn new_request<T>(id: RequestId, params: T::Params) -> Request
+where
+    T: lsp_types::request::Request,
+{
+    Request {
+        id,
+        method: T::METHOD.to_owned(),
+        params: serde_json::to_value(&params).unwrap(),
+    }
+}
+
 fn new_response<T>(id: RequestId, params: anyhow::Result<T>) -> Response
 where
     T: serde::Serialize,
@@ -770,19 +1409,42 @@ mod test {
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::notification::DidChangeWatchedFiles;
+    use lsp_types::request::Completion;
+    use lsp_types::request::Formatting;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::HoverRequest;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::CompletionParams;
+    use lsp_types::CompletionResponse;
+    use lsp_types::DidChangeWatchedFilesParams;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::FileChangeType;
+    use lsp_types::FileEvent;
+    use lsp_types::FormattingOptions;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Hover;
+    use lsp_types::HoverContents;
+    use lsp_types::HoverParams;
+    use lsp_types::Location;
     use lsp_types::LocationLink;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
     use textwrap::dedent;
 
     use crate::analysis::FixtureWithRanges;
     use crate::codemap::ResolvedSpan;
+    use crate::lsp::server::new_notification;
     use crate::lsp::server::LspServerSettings;
     use crate::lsp::server::LspUrl;
     use crate::lsp::server::StarlarkFileContentsParams;
@@ -857,6 +1519,106 @@ mod test {
         }
     }
 
+    fn text_document_position(uri: Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position { line, character },
+        }
+    }
+
+    fn hover(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Option<(String, Option<Range>)>> {
+        let req = server.new_request::<HoverRequest>(HoverParams {
+            text_document_position_params: text_document_position(uri, line, character),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        match server.get_response::<Option<Hover>>(request_id)? {
+            Some(Hover {
+                contents: HoverContents::Markup(markup),
+                range,
+            }) => Ok(Some((markup.value, range))),
+            Some(hover) => Err(anyhow::anyhow!("Unexpected hover contents: {:?}", hover)),
+            None => Ok(None),
+        }
+    }
+
+    fn references(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        include_declaration: bool,
+    ) -> anyhow::Result<Vec<Location>> {
+        let req = server.new_request::<References>(ReferenceParams {
+            text_document_position: text_document_position(uri, line, character),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration,
+            },
+        });
+        let request_id = server.send_request(req)?;
+        let mut locations = server.get_response::<Vec<Location>>(request_id)?;
+        locations.sort_by_key(|l| {
+            (
+                l.uri.to_string(),
+                l.range.start.line,
+                l.range.start.character,
+            )
+        });
+        Ok(locations)
+    }
+
+    /// Rename the symbol at a position, returning the edits sorted by file and position.
+    fn rename(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> anyhow::Result<Vec<(Url, TextEdit)>> {
+        let req = server.new_request::<Rename>(RenameParams {
+            text_document_position: text_document_position(uri, line, character),
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        let edit = server.get_response::<Option<WorkspaceEdit>>(request_id)?;
+        let mut edits = edit
+            .and_then(|edit| edit.changes)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|(uri, edits)| edits.into_iter().map(move |edit| (uri.clone(), edit)))
+            .collect::<Vec<_>>();
+        edits.sort_by_key(|(uri, edit)| {
+            (
+                uri.to_string(),
+                edit.range.start.line,
+                edit.range.start.character,
+            )
+        });
+        Ok(edits)
+    }
+
+    fn format(server: &mut TestServer, uri: Url) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let req = server.new_request::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..FormattingOptions::default()
+            },
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(req)?;
+        server.get_response(request_id)
+    }
+
     #[cfg(windows)]
     fn temp_file_uri(rel_path: &str) -> Url {
         Url::from_file_path(&PathBuf::from("C:/tmp").join(rel_path)).unwrap()
@@ -1805,4 +2567,533 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn hover_shows_docs() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("{load}", "loaded_function")
+
+            def local_function(a, b = 1):
+                """Local docs"""
+                return a + b
+
+            <local>local_function</local>(1)
+            <loaded>loaded_function</loaded>()
+            <native>native_function1</native>()
+            <none>x</none> = 1
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            def loaded_function():
+                """Loaded docs"""
+                pass
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents)?;
+
+        let (local, local_range) = hover(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("local"),
+            foo.begin_column("local"),
+        )?
+        .context("local function hover")?;
+        assert!(local.contains("def local_function(a, b = 1):"), "{}", local);
+        assert!(local.contains("Local docs"), "{}", local);
+        assert_eq!(Some(Range::from(foo.span("local"))), local_range);
+
+        let (loaded, _) = hover(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("loaded"),
+            foo.begin_column("loaded"),
+        )?
+        .context("loaded function hover")?;
+        assert!(loaded.contains("Loaded docs"), "{}", loaded);
+
+        let (native, _) = hover(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("native"),
+            foo.begin_column("native"),
+        )?
+        .context("native function hover")?;
+        assert!(native.contains("def native_function1():"), "{}", native);
+
+        assert_eq!(
+            None,
+            hover(
+                &mut server,
+                foo_uri,
+                foo.begin_line("none"),
+                foo.begin_column("none"),
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn completes_names_globals_and_arguments() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("bar.star", "loaded")
+
+            x = loaded
+
+            def f(param):
+                y = param
+                native_rule(name = y, <click></click>)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let req = server.new_request::<Completion>(CompletionParams {
+            text_document_position: text_document_position(
+                foo_uri,
+                foo.begin_line("click"),
+                foo.begin_column("click"),
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let request_id = server.send_request(req)?;
+        let items = match server.get_response::<CompletionResponse>(request_id)? {
+            CompletionResponse::Array(items) => items,
+            response => return Err(anyhow::anyhow!("Unexpected response: {:?}", response)),
+        };
+
+        assert_eq!(Some("srcs = "), items[0].insert_text.as_deref());
+        let mut labels = items
+            .iter()
+            .map(|item| item.label.as_str())
+            .collect::<Vec<_>>();
+        labels[6..].sort_unstable();
+        assert_eq!(
+            vec![
+                "srcs",
+                "loaded",
+                "x",
+                "f",
+                "param",
+                "y",
+                "native_function1",
+                "native_function2",
+                "native_rule",
+                "prelude_function",
+            ],
+            labels
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_across_files() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let baz_uri = temp_file_uri("baz.star");
+
+        let foo_contents = dedent(
+            r#"
+            def <def>exported</def>():
+                pass
+
+            <foo_use>exported</foo_use>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            load("{load}", <bar_load>"exported"</bar_load>)
+            <bar_use>exported</bar_use>()
+            "#,
+        )
+        .replace("{load}", foo_uri.path())
+        .trim()
+        .to_owned();
+        let baz_contents = dedent(
+            r#"
+            load("{load}", <baz_alias>e</baz_alias> = <baz_load>"exported"</baz_load>)
+            <baz_use>e</baz_use>()
+            "#,
+        )
+        .replace("{load}", foo_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        let bar = FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?;
+        let baz = FixtureWithRanges::from_fixture(baz_uri.path(), &baz_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.open_file(baz_uri.clone(), baz.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+
+        let location = |uri: &Url, fixture: &FixtureWithRanges, id: &str| Location {
+            uri: uri.clone(),
+            range: fixture.span(id).into(),
+        };
+        let expected = vec![
+            location(&bar_uri, &bar, "bar_load"),
+            location(&bar_uri, &bar, "bar_use"),
+            location(&baz_uri, &baz, "baz_alias"),
+            location(&baz_uri, &baz, "baz_load"),
+            location(&baz_uri, &baz, "baz_use"),
+            location(&foo_uri, &foo, "def"),
+            location(&foo_uri, &foo, "foo_use"),
+        ];
+
+        assert_eq!(
+            expected,
+            references(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                true,
+            )?
+        );
+        assert_eq!(
+            expected,
+            references(
+                &mut server,
+                baz_uri,
+                baz.begin_line("baz_use"),
+                baz.begin_column("baz_use"),
+                true,
+            )?
+        );
+
+        let without_declaration = expected
+            .iter()
+            .filter(|l| **l != location(&foo_uri, &foo, "def"))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            without_declaration,
+            references(
+                &mut server,
+                foo_uri,
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                false,
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn searches_created_files_after_the_client_reports_them() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let foo_contents = dedent(
+            r#"
+            def <def>exported</def>():
+                pass
+
+            <foo_use>exported</foo_use>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            load("{load}", <bar_load>"exported"</bar_load>)
+            "#,
+        )
+        .replace("{load}", foo_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        let bar = FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let location = |uri: &Url, fixture: &FixtureWithRanges, id: &str| Location {
+            uri: uri.clone(),
+            range: fixture.span(id).into(),
+        };
+        let find = |server: &mut TestServer| {
+            references(
+                server,
+                foo_uri.clone(),
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                true,
+            )
+        };
+        let in_foo = vec![
+            location(&foo_uri, &foo, "def"),
+            location(&foo_uri, &foo, "foo_use"),
+        ];
+        assert_eq!(in_foo, find(&mut server)?);
+
+        // The workspace files are cached, so a new file isn't searched until the client
+        // says that it was created.
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+        assert_eq!(in_foo, find(&mut server)?);
+
+        server.send_notification(new_notification::<DidChangeWatchedFiles>(
+            DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: bar_uri.clone(),
+                    typ: FileChangeType::CREATED,
+                }],
+            },
+        ))?;
+        let mut expected = vec![location(&bar_uri, &bar, "bar_load")];
+        expected.extend(in_foo);
+        assert_eq!(expected, find(&mut server)?);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_to_local_variables() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+
+        let foo_contents = dedent(
+            r#"
+            x = 1
+
+            def f(<param>x</param>):
+                return <use>x</use> + 1
+
+            f(x)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let expected = vec![
+            Location {
+                uri: foo_uri.clone(),
+                range: foo.span("param").into(),
+            },
+            Location {
+                uri: foo_uri.clone(),
+                range: foo.span("use").into(),
+            },
+        ];
+        assert_eq!(
+            expected,
+            references(
+                &mut server,
+                foo_uri,
+                foo.begin_line("use"),
+                foo.begin_column("use"),
+                true,
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn renames_across_files() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+        let baz_uri = temp_file_uri("baz.star");
+
+        let foo_contents = dedent(
+            r#"
+            def <def>exported</def>():
+                pass
+
+            <foo_use>exported</foo_use>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            load("{load}", <bar_load>'exported'</bar_load>)
+            <bar_use>exported</bar_use>()
+            "#,
+        )
+        .replace("{load}", foo_uri.path())
+        .trim()
+        .to_owned();
+        let baz_contents = dedent(
+            r#"
+            load("{load}", <baz_alias>e</baz_alias> = <baz_load>"exported"</baz_load>)
+            <baz_use>e</baz_use>()
+            "#,
+        )
+        .replace("{load}", foo_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+        let bar = FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?;
+        let baz = FixtureWithRanges::from_fixture(baz_uri.path(), &baz_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.open_file(baz_uri.clone(), baz.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+
+        let edit = |uri: &Url, fixture: &FixtureWithRanges, id: &str, new_text: &str| {
+            (
+                uri.clone(),
+                TextEdit {
+                    range: fixture.span(id).into(),
+                    new_text: new_text.to_owned(),
+                },
+            )
+        };
+        // Loads keep their quotes, and uses of an alias aren't renamed.
+        let expected = vec![
+            edit(&bar_uri, &bar, "bar_load", "'renamed'"),
+            edit(&bar_uri, &bar, "bar_use", "renamed"),
+            edit(&baz_uri, &baz, "baz_load", "\"renamed\""),
+            edit(&foo_uri, &foo, "def", "renamed"),
+            edit(&foo_uri, &foo, "foo_use", "renamed"),
+        ];
+        assert_eq!(
+            expected,
+            rename(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                "renamed",
+            )?
+        );
+        assert_eq!(
+            expected,
+            rename(
+                &mut server,
+                baz_uri.clone(),
+                baz.begin_line("baz_load"),
+                baz.begin_column("baz_load"),
+                "renamed",
+            )?
+        );
+
+        // Renaming an alias only renames it in the file that loads it.
+        assert_eq!(
+            vec![
+                edit(&baz_uri, &baz, "baz_alias", "alias"),
+                edit(&baz_uri, &baz, "baz_use", "alias"),
+            ],
+            rename(
+                &mut server,
+                baz_uri,
+                baz.begin_line("baz_use"),
+                baz.begin_column("baz_use"),
+                "alias",
+            )?
+        );
+
+        assert!(
+            rename(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                "not valid",
+            )
+            .is_err()
+        );
+        assert!(
+            rename(
+                &mut server,
+                foo_uri,
+                foo.begin_line("foo_use"),
+                foo.begin_column("foo_use"),
+                "def",
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn renames_parameters_and_keyword_arguments() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+
+        let foo_contents = dedent(
+            r#"
+            x = 1
+
+            def f(<param>x</param>, y):
+                return <use>x</use> + y
+
+            f(<arg>x</arg> = x, y = 2)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+
+        let edit = |id: &str| {
+            (
+                foo_uri.clone(),
+                TextEdit {
+                    range: foo.span(id).into(),
+                    new_text: "z".to_owned(),
+                },
+            )
+        };
+        assert_eq!(
+            vec![edit("param"), edit("use"), edit("arg")],
+            rename(
+                &mut server,
+                foo_uri.clone(),
+                foo.begin_line("use"),
+                foo.begin_column("use"),
+                "z",
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn formats_documents() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x=[1,\n  2]\n".to_owned())?;
+        server.open_file(bar_uri.clone(), "x = 1\n".to_owned())?;
+
+        assert_eq!(
+            Some(vec![TextEdit {
+                range: Range::new(Position::new(0, 0), Position::new(2, 0)),
+                new_text: "x = [1, 2]\n".to_owned(),
+            }]),
+            format(&mut server, foo_uri)?
+        );
+        assert_eq!(Some(Vec::new()), format(&mut server, bar_uri)?);
+        Ok(())
+    }
 }
@@ -60,9 +60,12 @@ use serde::de::DeserializeOwned;
 use crate::docs::render_docs_as_code;
 use crate::docs::Doc;
 use crate::docs::DocItem;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
 use crate::docs::Function;
 use crate::docs::Identifier;
 use crate::docs::Location;
+use crate::docs::Param;
 use crate::errors::EvalMessage;
 use crate::lsp::server::new_notification;
 use crate::lsp::server::server_with_connection;
@@ -119,6 +122,7 @@ struct TestServerContext {
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
+    global_docs: Arc<Vec<Doc>>,
 }
 
 impl LspContext for TestServerContext {
@@ -226,6 +230,19 @@ impl LspContext for TestServerContext {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_global_symbols(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<Doc>> {
+        Ok((*self.global_docs).clone())
+    }
+
+    fn get_workspace_files(&self, _workspace_roots: &[LspUrl]) -> anyhow::Result<Vec<LspUrl>> {
+        self.file_contents
+            .read()
+            .unwrap()
+            .keys()
+            .map(|path| Ok(Url::from_file_path(path).unwrap().try_into()?))
+            .collect()
+    }
 }
 
 /// A server for use in testing that provides helpers for sending requests, correlating
@@ -309,6 +326,32 @@ impl TestServer {
                     item: DocItem::Function(Function::default()),
                     custom_attrs: Default::default(),
                 },
+                Doc {
+                    id: Identifier {
+                        name: "native_rule".to_owned(),
+                        location: None,
+                    },
+                    item: DocItem::Function(Function {
+                        docs: DocString::from_docstring(DocStringKind::Rust, "A rule"),
+                        params: vec![
+                            Param::NoArgs,
+                            Param::Arg {
+                                name: "name".to_owned(),
+                                docs: DocString::from_docstring(DocStringKind::Rust, "The name"),
+                                typ: None,
+                                default_value: None,
+                            },
+                            Param::Arg {
+                                name: "srcs".to_owned(),
+                                docs: None,
+                                typ: None,
+                                default_value: Some("[]".to_owned()),
+                            },
+                        ],
+                        ret: Default::default(),
+                    }),
+                    custom_attrs: Default::default(),
+                },
             ],
             LspUrl::try_from(Url::from_file_path(prelude_path).unwrap())? => vec![
                 Doc {
@@ -356,11 +399,13 @@ impl TestServer {
         let builtin = Self::testing_builtins(&std::env::current_dir()?)?;
         let mut builtin_docs = HashMap::with_capacity(builtin.len());
         let mut builtin_symbols = HashMap::new();
+        let mut global_docs = Vec::new();
 
         for (u, ds) in builtin {
             builtin_docs.insert(u.clone(), render_docs_as_code(&ds));
             for d in ds {
-                builtin_symbols.insert(d.id.name, u.clone());
+                builtin_symbols.insert(d.id.name.clone(), u.clone());
+                global_docs.push(d);
             }
         }
 
@@ -381,6 +426,7 @@ impl TestServer {
             dirs: dirs.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
+            global_docs: Arc::new(global_docs),
         };
 
         let server_thread = std::thread::spawn(|| {
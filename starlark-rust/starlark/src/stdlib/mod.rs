@@ -31,6 +31,7 @@ pub(crate) mod json;
 
 pub(crate) mod list;
 pub(crate) mod record;
+pub(crate) mod set;
 pub(crate) mod string;
 pub(crate) mod structs;
 pub(crate) mod util;
@@ -55,6 +56,8 @@ pub enum LibraryExtension {
     RecordType,
     /// Definitions to support the `enum` type, the `enum()` constructor.
     EnumType,
+    /// Definitions to support the `set` type, the `set()` constructor.
+    SetType,
     /// A function `map(f, xs)` which applies `f` to each element of `xs` and returns the result.
     Map,
     /// A function `filter(f, xs)` which applies `f` to each element of `xs` and returns those for which `f` returns `True`.
@@ -89,6 +92,7 @@ impl LibraryExtension {
             StructType,
             RecordType,
             EnumType,
+            SetType,
             Map,
             Filter,
             Partial,
@@ -109,6 +113,7 @@ impl LibraryExtension {
             StructType => structs::global(builder),
             RecordType => record::global(builder),
             EnumType => enumeration::global(builder),
+            SetType => set::global(builder),
             Map => extra::map(builder),
             Filter => extra::filter(builder),
             Partial => extra::partial(builder),
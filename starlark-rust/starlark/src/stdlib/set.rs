@@ -0,0 +1,391 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `set()` constructor and methods for the `set` type.
+
+use crate as starlark;
+use crate::collections::SmallSet;
+use crate::environment::GlobalsBuilder;
+use crate::environment::MethodsBuilder;
+use crate::values::none::NoneType;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
+use crate::values::Heap;
+use crate::values::Value;
+
+/// Collect the elements of an iterable into a new set.
+fn iterable_to_set<'v>(x: Value<'v>, heap: &'v Heap) -> anyhow::Result<Set<'v>> {
+    if let Some(x) = SetRef::from_value(x) {
+        return Ok((*x).clone());
+    }
+    x.with_iterator(heap, |it| -> anyhow::Result<_> {
+        let mut content = SmallSet::with_capacity(it.size_hint().0);
+        for x in it {
+            content.insert_hashed(x.get_hashed()?);
+        }
+        Ok(Set::new(content))
+    })?
+}
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Create a set.
+    ///
+    /// `set()` returns a new empty set, and `set(x)` returns a new set containing the elements
+    /// of the iterable `x`, in the order they are first seen.
+    /// Fails if any element is unhashable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// len(set()) == 0
+    /// set([1, 2, 1]) == {1, 2}
+    /// list(set("abc".elems())) == ["a", "b", "c"]
+    /// # "#);
+    /// ```
+    #[starlark(type = Set::TYPE, speculative_exec_safe)]
+    fn set<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] x: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        match x {
+            None => Ok(Set::default()),
+            Some(x) => iterable_to_set(x, heap),
+        }
+    }
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// `S.add(x)` adds `x` to the set S, and returns `None`.
+    ///
+    /// Fails if `x` is unhashable, or if the set is frozen or has active iterators.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1}
+    /// x.add(2)
+    /// x.add(1)
+    /// x == {1, 2}
+    /// # "#);
+    /// ```
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] x: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let x = x.get_hashed()?;
+        SetMut::from_value(this)?.insert_hashed(x);
+        Ok(NoneType)
+    }
+
+    /// `S.clear()` removes all the elements of the set S, and returns `None`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1, 2}
+    /// x.clear()
+    /// x == set()
+    /// # "#);
+    /// ```
+    fn clear(this: Value) -> anyhow::Result<NoneType> {
+        SetMut::from_value(this)?.clear();
+        Ok(NoneType)
+    }
+
+    /// `S.copy()` returns a new set with the same elements as S.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1, 2}
+    /// y = x.copy()
+    /// y.add(3)
+    /// x == {1, 2} and y == {1, 2, 3}
+    /// # "#);
+    /// ```
+    fn copy<'v>(this: SetRef<'v>) -> anyhow::Result<Set<'v>> {
+        Ok((*this).clone())
+    }
+
+    /// `S.difference(x)` returns a new set with the elements of S which are not in the iterable
+    /// `x`. Like `S - x`, but `x` can be any iterable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// {1, 2, 3}.difference([2, 4]) == {1, 3}
+    /// # "#);
+    /// ```
+    fn difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.difference(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.discard(x)` removes `x` from the set S if it is present, and returns `None`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1, 2}
+    /// x.discard(2)
+    /// x.discard(3)
+    /// x == {1}
+    /// # "#);
+    /// ```
+    fn discard<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] x: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let x = x.get_hashed()?;
+        SetMut::from_value(this)?.remove_hashed(x);
+        Ok(NoneType)
+    }
+
+    /// `S.intersection(x)` returns a new set with the elements of S which are also in the
+    /// iterable `x`. Like `S & x`, but `x` can be any iterable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// {1, 2, 3}.intersection([2, 3, 4]) == {2, 3}
+    /// # "#);
+    /// ```
+    fn intersection<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.intersection(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.isdisjoint(x)` returns `True` if S has no elements in common with the iterable `x`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// {1, 2}.isdisjoint([3, 4])
+    /// not {1, 2}.isdisjoint([2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn isdisjoint<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_disjoint(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.issubset(x)` returns `True` if every element of S is in the iterable `x`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// {1, 2}.issubset([1, 2, 3])
+    /// not {1, 4}.issubset([1, 2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_subset(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.issuperset(x)` returns `True` if every element of the iterable `x` is in S.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// {1, 2, 3}.issuperset([1, 2])
+    /// not {1, 2}.issuperset([1, 4])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issuperset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(iterable_to_set(other, heap)?.is_subset(&this))
+    }
+
+    /// `S.pop()` removes the most recently added element of the set S, and returns it.
+    ///
+    /// Fails if the set is empty, frozen, or has active iterators.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1, 2}
+    /// x.pop() == 2 and x == {1}
+    /// # "#);
+    /// ```
+    fn pop<'v>(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+        match SetMut::from_value(this)?.pop() {
+            Some(x) => Ok(x),
+            None => Err(anyhow::anyhow!("Cannot pop from an empty set")),
+        }
+    }
+
+    /// `S.remove(x)` removes `x` from the set S, and returns `None`.
+    ///
+    /// Fails if `x` is not in the set, or if the set is frozen or has active iterators.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1, 2}
+    /// x.remove(2)
+    /// x == {1}
+    /// # "#);
+    /// ```
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// {1, 2}.remove(3) # error: not found
+    /// # "#, "not found");
+    /// ```
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] x: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let hashed = x.get_hashed()?;
+        if SetMut::from_value(this)?.remove_hashed(hashed) {
+            Ok(NoneType)
+        } else {
+            Err(anyhow::anyhow!(
+                "Element `{}` not found in set `{}`",
+                x.to_repr(),
+                this.to_repr()
+            ))
+        }
+    }
+
+    /// `S.symmetric_difference(x)` returns a new set with the elements which are in exactly
+    /// one of S and the iterable `x`. Like `S ^ x`, but `x` can be any iterable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// {1, 2}.symmetric_difference([2, 3]) == {1, 3}
+    /// # "#);
+    /// ```
+    fn symmetric_difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.symmetric_difference(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.union(x)` returns a new set with the elements of S, followed by the elements of the
+    /// iterable `x` which are not in S. Like `S | x`, but `x` can be any iterable.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// list({1, 2}.union([3, 1])) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    fn union<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        Ok(this.union(&iterable_to_set(other, heap)?))
+    }
+
+    /// `S.update(x)` adds the elements of the iterable `x` to the set S, and returns `None`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = {1}
+    /// x.update([2, 1, 3])
+    /// list(x) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    fn update<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        // Collect first, as `other` may be `this`, which can't be iterated while it's mutated.
+        let other = iterable_to_set(other, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        this.reserve(other.len());
+        for x in other.iter_hashed() {
+            this.insert_hashed(x);
+        }
+        Ok(NoneType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_error_codes() {
+        assert::fail("{1, 2}.remove(3)", "not found in set");
+        assert::fail("set().pop()", "empty set");
+        assert::fail("set([[1]])", "not hashable");
+    }
+
+    #[test]
+    fn test_update_self() {
+        assert::is_true("x = {1, 2}; x.update(x); x == {1, 2}");
+    }
+
+    #[test]
+    fn test_mutate_while_iterating() {
+        assert::fail(
+            r#"
+def f():
+    x = {1, 2}
+    for y in x:
+        x.add(3)
+f()
+"#,
+            "mutate an iterable",
+        );
+    }
+}
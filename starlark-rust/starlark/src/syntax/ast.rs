@@ -195,6 +195,7 @@ pub(crate) enum ExprP<P: AstPayload> {
     If(Box<(AstExprP<P>, AstExprP<P>, AstExprP<P>)>), // Order: condition, v1, v2 <=> v1 if condition else v2
     List(Vec<AstExprP<P>>),
     Dict(Vec<(AstExprP<P>, AstExprP<P>)>),
+    Set(Vec<AstExprP<P>>),
     ListComprehension(Box<AstExprP<P>>, Box<ForClauseP<P>>, Vec<ClauseP<P>>),
     DictComprehension(
         Box<(AstExprP<P>, AstExprP<P>)>,
@@ -376,7 +377,7 @@ impl Display for AssignOp {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
             AssignOp::Add => f.write_str(" += "),
-            AssignOp::Subtract => f.write_str(" += "),
+            AssignOp::Subtract => f.write_str(" -= "),
             AssignOp::Multiply => f.write_str(" *= "),
             AssignOp::Divide => f.write_str(" /= "),
             AssignOp::FloorDivide => f.write_str(" //= "),
@@ -504,6 +505,11 @@ impl Display for Expr {
                 comma_separated_fmt(f, v, |x, f| write!(f, "{}: {}", x.0.node, x.1.node), false)?;
                 f.write_str("}")
             }
+            Expr::Set(v) => {
+                f.write_str("{")?;
+                comma_separated_fmt(f, v, |x, f| write!(f, "{}", x.node), false)?;
+                f.write_str("}")
+            }
             Expr::ListComprehension(e, for_, c) => {
                 write!(f, "[{}", e.node)?;
                 write!(f, "{}", for_)?;
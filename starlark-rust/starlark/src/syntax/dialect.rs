@@ -37,6 +37,8 @@ pub(crate) enum DialectError {
     KeywordOnlyArguments,
     #[error("type annotations are not allowed in this dialect")]
     Types,
+    #[error("set literals are not allowed in this dialect")]
+    Set,
 }
 
 /// How to handle type annotations in Starlark.
@@ -78,6 +80,10 @@ pub struct Dialect {
     /// Are `for`, `if` and other statements allowed at the top level.
     /// Only enabled in [`Extended`](Dialect::Extended).
     pub enable_top_level_stmt: bool,
+    /// Are set literals such as `{1, 2}` permitted. The `set` type itself is available
+    /// through [`LibraryExtension::SetType`](crate::environment::LibraryExtension::SetType).
+    /// Only enabled in [`Extended`](Dialect::Extended).
+    pub enable_set: bool,
 }
 
 // These are morally enumerations, so give them enumeration-like names
@@ -94,6 +100,7 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true, // But they plan to change it
         enable_top_level_stmt: false,
+        enable_set: false,
     };
 
     /// A superset of [`Standard`](Dialect::Standard), including extra features (types, top-level statements etc).
@@ -106,6 +113,7 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true,
         enable_top_level_stmt: true,
+        enable_set: true,
     };
 }
 
@@ -165,6 +173,18 @@ impl Dialect {
         }
     }
 
+    pub(crate) fn check_set<T>(
+        &self,
+        codemap: &CodeMap,
+        x: Spanned<T>,
+    ) -> anyhow::Result<Spanned<T>> {
+        if self.enable_set {
+            Ok(x)
+        } else {
+            err(codemap, x.span, DialectError::Set)
+        }
+    }
+
     pub(crate) fn load_visibility(&self) -> Visibility {
         if self.enable_load_reexport {
             Visibility::Public
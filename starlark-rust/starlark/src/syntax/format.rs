@@ -0,0 +1,843 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A printer for [`AstModule`] that lays the module out in a canonical way, for formatting files.
+//!
+//! The AST doesn't record comments, so they are recovered from the source, and printed next
+//! to the closest statement or element of a bracketed list.
+
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::syntax::ast::Argument;
+use crate::syntax::ast::Assign;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstAssignIdent;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::LambdaP;
+use crate::syntax::ast::Parameter;
+use crate::syntax::ast::Stmt;
+use crate::syntax::lexer::Lexer;
+use crate::syntax::lexer::Token;
+use crate::syntax::AstModule;
+
+const INDENT: &str = "    ";
+
+impl AstModule {
+    /// Print the module in a canonical layout: one statement per line, four spaces of
+    /// indentation, a single blank line wherever the source had any, and minimal parentheses.
+    /// Bracketed lists are printed with one element per line if they were split over multiple
+    /// lines in the source, or contain comments. String and number literals are printed as they
+    /// were written.
+    pub(crate) fn format(&self) -> String {
+        let mut printer = Printer {
+            codemap: &self.codemap,
+            comments: comments(self),
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            line_start: true,
+        };
+        printer.block(&self.statement);
+        // Everything should have been printed already, but never drop comments.
+        printer.leading_comments(self.codemap.full_span().end(), &mut None);
+        printer.out
+    }
+}
+
+/// A `#` comment in the source.
+struct Comment {
+    /// The span of the comment, excluding the line terminator.
+    span: Span,
+    /// Whether the comment is the only thing on its line.
+    own_line: bool,
+}
+
+/// Find the comments in a module. The lexer skips comments, so they are found in the gaps between
+/// its tokens, which otherwise only contain whitespace and line continuations.
+fn comments(module: &AstModule) -> Vec<Comment> {
+    let source = module.codemap.source();
+    let mut res = Vec::new();
+    let mut visit_gap = |begin: usize, end: usize| {
+        let mut pos = begin;
+        while let Some(offset) = source[pos..end].find('#') {
+            let comment_begin = pos + offset;
+            let line_end = source[comment_begin..end]
+                .find('\n')
+                .map_or(end, |x| comment_begin + x);
+            let comment_end = comment_begin + source[comment_begin..line_end].trim_end().len();
+            let line_begin = source[..comment_begin].rfind('\n').map_or(0, |x| x + 1);
+            res.push(Comment {
+                span: Span::new(Pos::new(comment_begin as u32), Pos::new(comment_end as u32)),
+                own_line: source[line_begin..comment_begin].trim().is_empty(),
+            });
+            pos = line_end;
+        }
+    };
+
+    let mut last = 0;
+    for lexeme in Lexer::new(source, &module.dialect, module.codemap.dupe()) {
+        // The module was parsed from this source, so it lexes.
+        let (begin, token, end) = match lexeme {
+            Ok(x) => x,
+            Err(_) => break,
+        };
+        // The layout tokens may cover comments on the lines that they span.
+        if matches!(token, Token::Newline | Token::Indent | Token::Dedent) {
+            continue;
+        }
+        visit_gap(last, begin);
+        last = end;
+    }
+    visit_gap(last, source.len());
+    res
+}
+
+/// The precedence of an expression, from the loosest to the tightest binding. An expression
+/// needs parentheses if its precedence is lower than what the context it is in allows.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Lambda,
+    If,
+    Or,
+    And,
+    Not,
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Arith,
+    Product,
+    Unary,
+    Primary,
+}
+
+impl Prec {
+    fn of_op(op: BinOp) -> Prec {
+        match op {
+            BinOp::Or => Prec::Or,
+            BinOp::And => Prec::And,
+            BinOp::Equal
+            | BinOp::NotEqual
+            | BinOp::Less
+            | BinOp::Greater
+            | BinOp::LessOrEqual
+            | BinOp::GreaterOrEqual
+            | BinOp::In
+            | BinOp::NotIn => Prec::Comparison,
+            BinOp::BitOr => Prec::BitOr,
+            BinOp::BitXor => Prec::BitXor,
+            BinOp::BitAnd => Prec::BitAnd,
+            BinOp::LeftShift | BinOp::RightShift => Prec::Shift,
+            BinOp::Add | BinOp::Subtract => Prec::Arith,
+            BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => Prec::Product,
+        }
+    }
+
+    fn of(expr: &Expr) -> Prec {
+        match expr {
+            Expr::Lambda(..) => Prec::Lambda,
+            Expr::If(..) => Prec::If,
+            Expr::Not(..) => Prec::Not,
+            Expr::Op(_, op, _) => Prec::of_op(*op),
+            Expr::Minus(..) | Expr::Plus(..) | Expr::BitNot(..) => Prec::Unary,
+            // Tuples are always printed in parentheses.
+            _ => Prec::Primary,
+        }
+    }
+
+    /// The next tighter binding precedence.
+    fn next(self) -> Prec {
+        match self {
+            Prec::Lambda => Prec::If,
+            Prec::If => Prec::Or,
+            Prec::Or => Prec::And,
+            Prec::And => Prec::Not,
+            Prec::Not => Prec::Comparison,
+            Prec::Comparison => Prec::BitOr,
+            Prec::BitOr => Prec::BitXor,
+            Prec::BitXor => Prec::BitAnd,
+            Prec::BitAnd => Prec::Shift,
+            Prec::Shift => Prec::Arith,
+            Prec::Arith => Prec::Product,
+            Prec::Product => Prec::Unary,
+            Prec::Unary | Prec::Primary => Prec::Primary,
+        }
+    }
+}
+
+/// An element of the argument list of a `load()`.
+enum LoadItem<'a> {
+    Module(&'a AstString),
+    Symbol(&'a AstAssignIdent, &'a AstString),
+}
+
+impl<'a> LoadItem<'a> {
+    fn span(&self) -> Span {
+        match self {
+            LoadItem::Module(module) => module.span,
+            LoadItem::Symbol(local, name) => local.span.merge(name.span),
+        }
+    }
+}
+
+struct Printer<'a> {
+    codemap: &'a CodeMap,
+    comments: Vec<Comment>,
+    /// The index of the first comment that hasn't been printed yet.
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    /// Whether nothing has been written to the current line, so it still needs indenting.
+    line_start: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn write(&mut self, s: &str) {
+        if self.line_start && !s.is_empty() {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+            self.line_start = false;
+        }
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    fn source(&self, span: Span) -> &'a str {
+        self.codemap.source_span(span)
+    }
+
+    fn line(&self, pos: Pos) -> usize {
+        self.codemap.find_line(pos)
+    }
+
+    fn column(&self, pos: Pos) -> usize {
+        let line = self.codemap.line_span(self.line(pos));
+        self.source(Span::new(line.begin(), pos)).len()
+    }
+
+    /// The next comment that hasn't been printed, if it starts before `pos`.
+    fn next_comment_before(&self, pos: Pos) -> Option<&Comment> {
+        self.comments
+            .get(self.next_comment)
+            .filter(|c| c.span.begin() < pos)
+    }
+
+    /// Whether there are comments that haven't been printed within `span`.
+    fn has_comments_in(&self, span: Span) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .take_while(|c| c.span.begin() < span.end())
+            .any(|c| c.span.begin() >= span.begin())
+    }
+
+    /// Whether there is anything other than whitespace and comments between `begin` and `end`.
+    fn code_between(&self, begin: Pos, end: Pos) -> bool {
+        let mut in_comment = false;
+        for c in self.source(Span::new(begin, end)).chars() {
+            match c {
+                '\n' => in_comment = false,
+                '#' => in_comment = true,
+                c if !in_comment && !c.is_whitespace() => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Print the next comment, which is expected to be the next thing on its line.
+    fn comment(&mut self) {
+        let span = self.comments[self.next_comment].span;
+        self.next_comment += 1;
+        self.write(self.source(span));
+    }
+
+    fn blank_line(&mut self, last_line: Option<usize>, line: usize) {
+        if let Some(last_line) = last_line {
+            if line > last_line + 1 {
+                self.newline();
+            }
+        }
+    }
+
+    /// Print the comments before `pos` on lines of their own, keeping single blank lines
+    /// between them. `last_line` is the source line of whatever was printed last in this block.
+    fn leading_comments(&mut self, pos: Pos, last_line: &mut Option<usize>) {
+        while let Some(c) = self.next_comment_before(pos) {
+            let line = self.line(c.span.begin());
+            self.blank_line(*last_line, line);
+            self.comment();
+            self.newline();
+            *last_line = Some(line);
+        }
+    }
+
+    /// Print the comments at the end of the current line, which ends at `end` in the source. That
+    /// is the comment after `end` on the same line, and any comments before `end` that haven't
+    /// been printed, since they must have been within something printed on a single line.
+    fn trailing_comments(&mut self, end: Pos) {
+        let mut first = true;
+        while let Some(c) = self.comments.get(self.next_comment) {
+            let after = c.span.begin() >= end;
+            if after
+                && !self
+                    .source(Span::new(end, c.span.begin()))
+                    .chars()
+                    .all(|x| x == ',' || x == ';' || (x.is_whitespace() && x != '\n'))
+            {
+                break;
+            }
+            if first && !self.line_start {
+                self.write("  ");
+            } else if !self.line_start {
+                self.newline();
+            }
+            first = false;
+            self.comment();
+            if after {
+                break;
+            }
+        }
+    }
+
+    /// The position after the `:` that ends the header of a compound statement, given the start
+    /// of its body.
+    fn header_end(&self, body: Pos) -> Pos {
+        let source = self.codemap.source();
+        let mut end = body;
+        loop {
+            let trimmed = Pos::new(source[..end.get() as usize].trim_end().len() as u32);
+            match self.comments.iter().find(|c| c.span.end() == trimmed) {
+                Some(c) => end = c.span.begin(),
+                None => return trimmed,
+            }
+        }
+    }
+
+    /// The position of the last `keyword` before `pos`.
+    fn keyword_before(&self, keyword: &str, pos: Pos) -> Pos {
+        let source = &self.codemap.source()[..pos.get() as usize];
+        Pos::new(source.rfind(keyword).unwrap_or(source.len()) as u32)
+    }
+
+    fn block(&mut self, block: &AstStmt) {
+        fn flatten<'b>(stmt: &'b AstStmt, res: &mut Vec<&'b AstStmt>) {
+            match &stmt.node {
+                Stmt::Statements(xs) => xs.iter().for_each(|x| flatten(x, res)),
+                _ => res.push(stmt),
+            }
+        }
+
+        let mut stmts = Vec::new();
+        flatten(block, &mut stmts);
+        let column = stmts.first().map_or(0, |x| self.column(x.span.begin()));
+        let mut last_line = None;
+        let mut end = block.span.begin();
+        for stmt in stmts {
+            self.leading_comments(stmt.span.begin(), &mut last_line);
+            self.blank_line(last_line, self.line(stmt.span.begin()));
+            self.stmt(stmt);
+            if !self.line_start {
+                self.newline();
+            }
+            last_line = Some(self.line(stmt.span.end()));
+            end = stmt.span.end();
+        }
+
+        // The comments after the last statement belong to the block if they are indented as far.
+        // An empty module has a statement at its end, so everything is before it.
+        while let Some(c) = self.comments.get(self.next_comment) {
+            if !c.own_line
+                || self.column(c.span.begin()) < column
+                || (c.span.begin() > end && self.code_between(end, c.span.begin()))
+            {
+                break;
+            }
+            let line = self.line(c.span.begin());
+            self.blank_line(last_line, line);
+            self.comment();
+            self.newline();
+            last_line = Some(line);
+        }
+    }
+
+    /// Print the `:` and the body of a compound statement.
+    fn suite(&mut self, body: &AstStmt) {
+        self.write(":");
+        self.trailing_comments(self.header_end(body.span.begin()));
+        self.newline();
+        self.indent += 1;
+        self.block(body);
+        self.indent -= 1;
+    }
+
+    fn if_(&mut self, keyword: &str, cond: &AstExpr, then: &AstStmt, else_: Option<&AstStmt>) {
+        self.write(keyword);
+        self.write(" ");
+        self.expr(cond, Prec::Lambda);
+        self.suite(then);
+        match else_.map(|x| (x, &x.node)) {
+            None => {}
+            // An `elif` is parsed as an `else` containing just an `if` statement, which can't
+            // otherwise happen, since an indented `if` statement would be in a `Statements`.
+            Some((else_, Stmt::If(cond, then))) => {
+                let keyword = self.keyword_before("elif", else_.span.begin());
+                self.leading_comments(keyword, &mut None);
+                self.if_("elif", cond, then, None);
+            }
+            Some((else_, Stmt::IfElse(cond, then_else))) => {
+                let keyword = self.keyword_before("elif", else_.span.begin());
+                self.leading_comments(keyword, &mut None);
+                self.if_("elif", cond, &then_else.0, Some(&then_else.1));
+            }
+            Some((else_, _)) => {
+                let colon = self.header_end(else_.span.begin());
+                let keyword = self.keyword_before("else", colon);
+                self.leading_comments(keyword, &mut None);
+                self.write("else");
+                self.suite(else_);
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &AstStmt) {
+        match &stmt.node {
+            Stmt::Break => self.write("break"),
+            Stmt::Continue => self.write("continue"),
+            Stmt::Pass => self.write("pass"),
+            Stmt::Return(None) => self.write("return"),
+            Stmt::Return(Some(e)) => {
+                self.write("return ");
+                self.expr(e, Prec::Lambda);
+            }
+            Stmt::Expression(e) => self.expr(e, Prec::Lambda),
+            Stmt::Assign(lhs, ty_rhs) => {
+                let (ty, rhs) = &**ty_rhs;
+                self.assign(lhs, true);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.expr(ty, Prec::Lambda);
+                }
+                self.write(" = ");
+                self.expr(rhs, Prec::Lambda);
+            }
+            Stmt::AssignModify(lhs, op, rhs) => {
+                self.assign(lhs, true);
+                self.write(&op.to_string());
+                self.expr(rhs, Prec::Lambda);
+            }
+            Stmt::Load(load) => {
+                let mut items = vec![LoadItem::Module(&load.module)];
+                items.extend(
+                    load.args
+                        .iter()
+                        .map(|(local, name)| LoadItem::Symbol(local, name)),
+                );
+                self.write("load");
+                self.list(
+                    "(",
+                    ")",
+                    Span::new(stmt.span.begin(), stmt.span.end()),
+                    &items,
+                    LoadItem::span,
+                    false,
+                    |p, item| match item {
+                        LoadItem::Module(module) => p.write(p.source(module.span)),
+                        // The parser uses the span of the string as the span of the local name
+                        // if the symbol is not renamed.
+                        LoadItem::Symbol(local, name) => {
+                            if local.span != name.span {
+                                p.write(&local.0);
+                                p.write(" = ");
+                            }
+                            p.write(p.source(name.span));
+                        }
+                    },
+                );
+            }
+            Stmt::If(cond, then) => self.if_("if", cond, then, None),
+            Stmt::IfElse(cond, then_else) => self.if_("if", cond, &then_else.0, Some(&then_else.1)),
+            Stmt::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                self.write("for ");
+                self.assign(var, true);
+                self.write(" in ");
+                self.expr(over, Prec::Lambda);
+                self.suite(body);
+            }
+            Stmt::Def(DefP {
+                name,
+                params,
+                return_type,
+                body,
+                payload: _,
+            }) => {
+                self.write("def ");
+                self.write(&name.0);
+                let header_end = self.header_end(body.span.begin());
+                self.list(
+                    "(",
+                    ")",
+                    Span::new(name.span.end(), header_end),
+                    params,
+                    |x| x.span,
+                    false,
+                    |p, x| p.param(x),
+                );
+                if let Some(return_type) = return_type {
+                    self.write(" -> ");
+                    self.expr(return_type, Prec::Lambda);
+                }
+                self.suite(body);
+            }
+            Stmt::Statements(_) => unreachable!("statements are flattened by `block`"),
+        }
+        if !matches!(
+            &stmt.node,
+            Stmt::If(..) | Stmt::IfElse(..) | Stmt::For(..) | Stmt::Def(..)
+        ) {
+            self.trailing_comments(stmt.span.end());
+        }
+    }
+
+    /// Print the elements of a bracketed list, which is in `span` in the source (starting
+    /// from the opening bracket). If the list is split over multiple lines in the source, or has
+    /// comments in it, each element is printed on its own line, followed by a comma.
+    fn list<T>(
+        &mut self,
+        open: &str,
+        close: &str,
+        span: Span,
+        items: &[T],
+        item_span: impl Fn(&T) -> Span,
+        single_comma: bool,
+        print: impl Fn(&mut Self, &T),
+    ) {
+        let multi_line = self.has_comments_in(span)
+            || match (items.first(), items.last()) {
+                (Some(first), Some(last)) => {
+                    self.line(span.begin()) != self.line(item_span(first).begin())
+                        || self.line(item_span(last).end()) != self.line(span.end())
+                }
+                _ => false,
+            };
+
+        self.write(open);
+        if multi_line {
+            self.indent += 1;
+            for item in items {
+                self.newline();
+                self.leading_comments(item_span(item).begin(), &mut None);
+                print(self, item);
+                self.write(",");
+                self.trailing_comments(item_span(item).end());
+            }
+            self.newline();
+            self.leading_comments(span.end(), &mut None);
+            self.indent -= 1;
+        } else {
+            for (i, item) in items.iter().enumerate() {
+                if i != 0 {
+                    self.write(", ");
+                }
+                print(self, item);
+            }
+            if single_comma && items.len() == 1 {
+                self.write(",");
+            }
+        }
+        self.write(close);
+    }
+
+    /// Print an assignment target. Tuples at the top level don't need parentheses.
+    fn assign(&mut self, assign: &AstAssign, top_level: bool) {
+        match &assign.node {
+            Assign::Tuple(xs) if top_level && !xs.is_empty() => {
+                for (i, x) in xs.iter().enumerate() {
+                    if i != 0 {
+                        self.write(", ");
+                    }
+                    self.assign(x, false);
+                }
+                if xs.len() == 1 {
+                    self.write(",");
+                }
+            }
+            Assign::Tuple(xs) => self.list(
+                "(",
+                ")",
+                assign.span,
+                xs,
+                |x| x.span,
+                true,
+                |p, x| p.assign(x, false),
+            ),
+            Assign::ArrayIndirection(array_index) => {
+                let (array, index) = &**array_index;
+                self.expr(array, Prec::Primary);
+                self.write("[");
+                self.expr(index, Prec::Lambda);
+                self.write("]");
+            }
+            Assign::Dot(object, field) => {
+                self.expr(object, Prec::Primary);
+                self.write(".");
+                self.write(&field.node);
+            }
+            Assign::Identifier(x) => self.write(&x.0),
+        }
+    }
+
+    fn param(&mut self, param: &AstParameter) {
+        let (prefix, name, ty, default) = match &param.node {
+            Parameter::Normal(name, ty) => ("", name, ty, None),
+            Parameter::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            Parameter::NoArgs => return self.write("*"),
+            Parameter::Args(name, ty) => ("*", name, ty, None),
+            Parameter::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        self.write(prefix);
+        self.write(&name.0);
+        if let Some(ty) = ty {
+            self.write(": ");
+            self.expr(ty, Prec::Lambda);
+        }
+        if let Some(default) = default {
+            self.write(" = ");
+            self.expr(default, Prec::Lambda);
+        }
+    }
+
+    fn arg(&mut self, arg: &AstArgument) {
+        match &arg.node {
+            Argument::Positional(e) => self.expr(e, Prec::Lambda),
+            Argument::Named(name, e) => {
+                self.write(&name.node);
+                self.write(" = ");
+                self.expr(e, Prec::Lambda);
+            }
+            Argument::Args(e) => {
+                self.write("*");
+                self.expr(e, Prec::Lambda);
+            }
+            Argument::KwArgs(e) => {
+                self.write("**");
+                self.expr(e, Prec::Lambda);
+            }
+        }
+    }
+
+    fn clauses(&mut self, for_: &ForClause, clauses: &[Clause]) {
+        self.for_clause(for_);
+        for clause in clauses {
+            match clause {
+                Clause::For(for_) => self.for_clause(for_),
+                Clause::If(cond) => {
+                    self.write(" if ");
+                    self.expr(cond, Prec::Or);
+                }
+            }
+        }
+    }
+
+    fn for_clause(&mut self, for_: &ForClause) {
+        self.write(" for ");
+        self.assign(&for_.var, true);
+        self.write(" in ");
+        self.expr(&for_.over, Prec::Or);
+    }
+
+    /// Print an expression, in parentheses if it binds looser than `min`.
+    fn expr(&mut self, expr: &AstExpr, min: Prec) {
+        if Prec::of(&expr.node) < min {
+            self.write("(");
+            self.expr(expr, Prec::Lambda);
+            self.write(")");
+            return;
+        }
+
+        match &expr.node {
+            Expr::Tuple(xs) => self.list(
+                "(",
+                ")",
+                expr.span,
+                xs,
+                |x| x.span,
+                true,
+                |p, x| p.expr(x, Prec::Lambda),
+            ),
+            Expr::Dot(object, field) => {
+                self.expr(object, Prec::Primary);
+                self.write(".");
+                self.write(&field.node);
+            }
+            Expr::Call(function, args) => {
+                self.expr(function, Prec::Primary);
+                self.list(
+                    "(",
+                    ")",
+                    Span::new(function.span.end(), expr.span.end()),
+                    args,
+                    |x| x.span,
+                    false,
+                    |p, x| p.arg(x),
+                );
+            }
+            Expr::ArrayIndirection(array_index) => {
+                let (array, index) = &**array_index;
+                self.expr(array, Prec::Primary);
+                self.write("[");
+                self.expr(index, Prec::Lambda);
+                self.write("]");
+            }
+            Expr::Slice(array, start, stop, step) => {
+                self.expr(array, Prec::Primary);
+                self.write("[");
+                if let Some(start) = start {
+                    self.expr(start, Prec::Lambda);
+                }
+                self.write(":");
+                if let Some(stop) = stop {
+                    self.expr(stop, Prec::Lambda);
+                }
+                if let Some(step) = step {
+                    self.write(":");
+                    self.expr(step, Prec::Lambda);
+                }
+                self.write("]");
+            }
+            Expr::Identifier(name, _) => self.write(&name.node),
+            Expr::Lambda(LambdaP { params, body, .. }) => {
+                self.write("lambda");
+                for (i, param) in params.iter().enumerate() {
+                    self.write(if i == 0 { " " } else { ", " });
+                    self.param(param);
+                }
+                self.write(": ");
+                self.expr(body, Prec::Lambda);
+            }
+            Expr::Literal(x) => {
+                let span = match x {
+                    AstLiteral::Int(x) => x.span,
+                    AstLiteral::Float(x) => x.span,
+                    AstLiteral::String(x) => x.span,
+                };
+                self.write(self.source(span));
+            }
+            Expr::Not(x) => {
+                self.write("not ");
+                self.expr(x, Prec::Not);
+            }
+            Expr::Minus(x) => {
+                self.write("-");
+                self.expr(x, Prec::Unary);
+            }
+            Expr::Plus(x) => {
+                self.write("+");
+                self.expr(x, Prec::Unary);
+            }
+            Expr::BitNot(x) => {
+                self.write("~");
+                self.expr(x, Prec::Unary);
+            }
+            Expr::Op(lhs, op, rhs) => {
+                let prec = Prec::of_op(*op);
+                // Comparisons don't chain, the other operators are left associative.
+                let lhs_prec = if prec == Prec::Comparison {
+                    prec.next()
+                } else {
+                    prec
+                };
+                self.expr(lhs, lhs_prec);
+                self.write(&op.to_string());
+                self.expr(rhs, prec.next());
+            }
+            Expr::If(cond_then_else) => {
+                let (cond, then, else_) = &**cond_then_else;
+                self.expr(then, Prec::Or);
+                self.write(" if ");
+                self.expr(cond, Prec::Or);
+                self.write(" else ");
+                self.expr(else_, Prec::Lambda);
+            }
+            Expr::List(xs) => self.list(
+                "[",
+                "]",
+                expr.span,
+                xs,
+                |x| x.span,
+                false,
+                |p, x| p.expr(x, Prec::Lambda),
+            ),
+            Expr::Dict(xs) => self.list(
+                "{",
+                "}",
+                expr.span,
+                xs,
+                |(k, v)| k.span.merge(v.span),
+                false,
+                |p, (k, v)| {
+                    p.expr(k, Prec::Lambda);
+                    p.write(": ");
+                    p.expr(v, Prec::Lambda);
+                },
+            ),
+            Expr::Set(xs) => self.list(
+                "{",
+                "}",
+                expr.span,
+                xs,
+                |x| x.span,
+                false,
+                |p, x| p.expr(x, Prec::Lambda),
+            ),
+            Expr::ListComprehension(x, for_, clauses) => {
+                self.write("[");
+                self.expr(x, Prec::Lambda);
+                self.clauses(for_, clauses);
+                self.write("]");
+            }
+            Expr::DictComprehension(k_v, for_, clauses) => {
+                let (k, v) = &**k_v;
+                self.write("{");
+                self.expr(k, Prec::Lambda);
+                self.write(": ");
+                self.expr(v, Prec::Lambda);
+                self.clauses(for_, clauses);
+                self.write("}");
+            }
+        }
+    }
+}
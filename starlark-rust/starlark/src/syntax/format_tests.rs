@@ -0,0 +1,215 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use textwrap::dedent;
+
+use crate::assert;
+
+/// Check that `program` formats as `expected`, that the formatting doesn't change what the
+/// program means, and that formatting it again doesn't change it.
+fn assert_format(program: &str, expected: &str) {
+    let program = dedent(program).trim_start().to_owned();
+    let expected = dedent(expected).trim_start().to_owned();
+    let formatted = assert::parse_ast(&program).format();
+    assert_eq!(expected, formatted);
+    assert_eq!(assert::parse(&program), assert::parse(&formatted));
+    assert_eq!(formatted, assert::parse_ast(&formatted).format());
+}
+
+#[test]
+fn test_format_statements() {
+    assert_format(
+        r#"
+        x=1;y =2
+        def  f(a,b=1,*args,**kwargs):
+          if a: return b
+          elif b:
+            pass
+          else:
+            for i,j in [ ]: continue
+        z  += x
+        "#,
+        r#"
+        x = 1
+        y = 2
+        def f(a, b = 1, *args, **kwargs):
+            if a:
+                return b
+            elif b:
+                pass
+            else:
+                for i, j in []:
+                    continue
+        z += x
+        "#,
+    );
+}
+
+#[test]
+fn test_format_blank_lines() {
+    assert_format(
+        r#"
+
+
+        x = 1
+
+
+
+        def f():
+
+            y = 1
+
+            return y
+        "#,
+        r#"
+        x = 1
+
+        def f():
+            y = 1
+
+            return y
+        "#,
+    );
+}
+
+#[test]
+fn test_format_parentheses() {
+    assert_format(
+        r#"
+        x = ((a + b) * c) - (d - (e + f))
+        y = (not a) == b and (c or d)
+        z = (a if b else c) if (lambda: d) else (lambda x: x)
+        w = -(a.b) if 0 else (-1).x
+        t = (1, (2,), ())[0]
+        "#,
+        r#"
+        x = (a + b) * c - (d - (e + f))
+        y = (not a) == b and (c or d)
+        z = (a if b else c) if (lambda: d) else lambda x: x
+        w = -a.b if 0 else (-1).x
+        t = (1, (2,), ())[0]
+        "#,
+    );
+}
+
+#[test]
+fn test_format_literals() {
+    assert_format(
+        r#"
+        def f():
+            '''Docs
+                that are indented'''
+            return [0x10, 1.5e3, r'\d', "a\"b"]
+        "#,
+        r#"
+        def f():
+            '''Docs
+                that are indented'''
+            return [0x10, 1.5e3, r'\d', "a\"b"]
+        "#,
+    );
+}
+
+#[test]
+fn test_format_lists() {
+    assert_format(
+        r#"
+        load(
+          "foo.star", "a", b = "c")
+        x = [1,
+          2]
+        y = foo(1, bar = [
+          2, 3], **kwargs)
+        z = {1: 2,
+        3: 4
+        }
+        "#,
+        r#"
+        load(
+            "foo.star",
+            "a",
+            b = "c",
+        )
+        x = [1, 2]
+        y = foo(1, bar = [
+            2,
+            3,
+        ], **kwargs)
+        z = {
+            1: 2,
+            3: 4,
+        }
+        "#,
+    );
+}
+
+#[test]
+fn test_format_comments() {
+    assert_format(
+        r#"
+        # A comment.
+        load("foo.star", "a")  # Trailing.
+
+        # About f.
+        def f(): # On the header.
+            x = [
+              1, # One.
+              # Before two.
+              2,
+              # At the end.
+            ]
+            # After x.
+            if x: # On the if.
+                pass
+                # In the if.
+            # Before the else.
+            else:
+                pass
+        y = (1 +  # Inside.
+             2)
+        # At the end of the file.
+        "#,
+        r#"
+        # A comment.
+        load("foo.star", "a")  # Trailing.
+
+        # About f.
+        def f():  # On the header.
+            x = [
+                1,  # One.
+                # Before two.
+                2,
+                # At the end.
+            ]
+            # After x.
+            if x:  # On the if.
+                pass
+                # In the if.
+            # Before the else.
+            else:
+                pass
+        y = 1 + 2  # Inside.
+        # At the end of the file.
+        "#,
+    );
+}
+
+#[test]
+fn test_format_empty() {
+    assert_format("", "");
+    assert_format("# Just a comment\n", "# Just a comment\n");
+}
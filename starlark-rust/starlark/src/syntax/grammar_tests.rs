@@ -114,6 +114,19 @@ fn test_top_level_statements() {
     assert_eq!(assert::parse("pass"), "pass\n");
 }
 
+#[test]
+fn test_set_literals() {
+    assert_eq!(assert::parse("x = {1, 2,}"), "x = {1, 2}\n");
+    assert_eq!(assert::parse("x = {1}"), "x = {1}\n");
+    assert_eq!(assert::parse("x = {}"), "x = {}\n");
+    assert_eq!(assert::parse("x = {1: 2}"), "x = {1: 2}\n");
+
+    let mut a = Assert::new();
+    a.dialect_set(|x| x.enable_set = false);
+    a.parse_fail("x = !{1, 2}!");
+    assert_eq!(a.parse("x = {1: 2}"), "x = {1: 2}\n");
+}
+
 #[test]
 fn test_top_level_def_with_docstring() {
     assert_eq!(
@@ -691,6 +691,14 @@ pub enum Token {
 }
 
 impl Token {
+    /// Whether `s` is a valid name for a variable, i.e. a single identifier and not a keyword.
+    pub(crate) fn is_identifier(s: &str) -> bool {
+        let mut lexer = Token::lexer(s);
+        matches!(lexer.next(), Some(Token::Identifier(_)))
+            && lexer.span() == (0..s.len())
+            && lexer.next().is_none()
+    }
+
     /// Used for testing
     pub(crate) fn unlex(&self) -> String {
         match self {
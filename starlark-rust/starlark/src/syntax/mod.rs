@@ -21,6 +21,8 @@ pub use ast::AstModule;
 pub use dialect::Dialect;
 pub use dialect::DialectTypes;
 
+#[cfg(test)]
+mod format_tests;
 #[cfg(test)]
 mod grammar_tests;
 #[cfg(test)]
@@ -31,6 +33,7 @@ mod testcases;
 pub(crate) mod ast;
 pub(crate) mod cursors;
 mod dialect;
+pub(crate) mod format;
 pub(crate) mod lexer;
 pub(crate) mod payload_map;
 pub(crate) mod validate;
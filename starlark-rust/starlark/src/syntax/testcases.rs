@@ -215,3 +215,22 @@ fn parsing_testcases() {
         assert::parse(content);
     }
 }
+
+#[test]
+fn formatting_testcases() {
+    for (name, content) in TESTCASE_FILES {
+        let formatted = assert::parse_ast(content).format();
+        assert_eq!(
+            assert::parse(content),
+            assert::parse(&formatted),
+            "formatting changed the meaning of {}",
+            name
+        );
+        assert_eq!(
+            formatted,
+            assert::parse_ast(&formatted).format(),
+            "formatting {} again changed it",
+            name
+        );
+    }
+}
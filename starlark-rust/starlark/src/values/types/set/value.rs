@@ -0,0 +1,478 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use allocative::Allocative;
+use gazebo::any::ProvidesStaticType;
+use gazebo::cell::ARef;
+use gazebo::coerce::coerce;
+use gazebo::coerce::Coerce;
+use gazebo::display::display_container;
+use serde::Serialize;
+
+use crate as starlark;
+use crate::collections::Hashed;
+use crate::collections::SmallSet;
+use crate::environment::Methods;
+use crate::environment::MethodsStatic;
+use crate::values::error::ValueError;
+use crate::values::iter::ARefIterator;
+use crate::values::set::SetRef;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenStringValue;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+
+#[derive(
+    Clone,
+    Default,
+    Trace,
+    Debug,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "extension")]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+fn display_set<T: Display>(
+    f: &mut fmt::Formatter<'_>,
+    len: usize,
+    items: impl Iterator<Item = T>,
+) -> fmt::Result {
+    // `{}` is an empty dict, so an empty set is written as a call to its constructor.
+    if len == 0 {
+        f.write_str("set()")
+    } else {
+        display_container(f, "{", "}", items)
+    }
+}
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let content = self.0.content();
+        display_set(f, content.len(), content.iter())
+    }
+}
+
+impl<'v> Display for Set<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_set(f, self.len(), self.iter())
+    }
+}
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The data stored by the set. The values must all be hashable.
+    content: SmallSet<Value<'v>>,
+}
+
+impl<'v> StarlarkTypeRepr for Set<'v> {
+    fn starlark_type_repr() -> String {
+        Set::TYPE.to_owned()
+    }
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    /// The data stored by the set. The values must all be hashable.
+    pub(crate) content: SmallSet<FrozenValue>,
+}
+
+/// Alias is used in `StarlarkDocs` derive.
+type FrozenSet = SetGen<FrozenSetData>;
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSetData {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl AllocFrozenValue for FrozenSetData {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(SetGen(self))
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Set type string as Starlark frozen string value.
+    pub fn get_type_value_static() -> FrozenStringValue {
+        FrozenSet::get_type_value_static()
+    }
+
+    pub(crate) fn is_set_type(x: TypeId) -> bool {
+        x == TypeId::of::<SetGen<FrozenSetData>>()
+            || x == TypeId::of::<SetGen<RefCell<Set<'static>>>>()
+    }
+
+    /// Create a new [`Set`].
+    pub fn new(content: SmallSet<Value<'v>>) -> Self {
+        Self { content }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the values in the set, in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.iter().copied()
+    }
+
+    /// Iterate through the values in the set, but retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl Iterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|x| x.copied())
+    }
+
+    /// Does the set contain the value? Will be [`Err`] if the value is not hashable.
+    pub fn contains(&self, value: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.contains_hashed(value.get_hashed()?))
+    }
+
+    /// Does the set contain the prehashed value?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_hashed(value.as_ref())
+    }
+
+    /// Reserve capacity to insert `additional` elements without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.content.reserve(additional);
+    }
+
+    /// Insert a value into the set. Returns `true` if it was not already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.insert_hashed(value)
+    }
+
+    /// Remove a value from the set. Returns `true` if it was present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.as_ref())
+    }
+
+    /// Remove the most recently inserted value from the set.
+    pub fn pop(&mut self) -> Option<Value<'v>> {
+        self.content.pop()
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+
+    /// Is every element of this set also in `other`?
+    pub fn is_subset(&self, other: &Set<'v>) -> bool {
+        self.len() <= other.len() && self.iter_hashed().all(|x| other.contains_hashed(x))
+    }
+
+    /// Do the two sets have no elements in common?
+    pub fn is_disjoint(&self, other: &Set<'v>) -> bool {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        !small.iter_hashed().any(|x| large.contains_hashed(x))
+    }
+
+    /// The elements of this set, followed by those of `other` not in this set.
+    pub fn union(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = self.content.clone();
+        content.reserve(other.len());
+        for x in other.iter_hashed() {
+            content.insert_hashed(x);
+        }
+        Set::new(content)
+    }
+
+    /// The elements of this set which are also in `other`.
+    pub fn intersection(&self, other: &Set<'v>) -> Set<'v> {
+        Set::new(
+            self.iter_hashed()
+                .filter(|x| other.contains_hashed(*x))
+                .collect_hashed(),
+        )
+    }
+
+    /// The elements of this set which are not in `other`.
+    pub fn difference(&self, other: &Set<'v>) -> Set<'v> {
+        Set::new(
+            self.iter_hashed()
+                .filter(|x| !other.contains_hashed(*x))
+                .collect_hashed(),
+        )
+    }
+
+    /// The elements which are in exactly one of the two sets.
+    pub fn symmetric_difference(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = self.difference(other).content;
+        for x in other.iter_hashed() {
+            if !self.contains_hashed(x) {
+                content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        Set::new(content)
+    }
+}
+
+trait CollectHashed<'v>: Iterator<Item = Hashed<Value<'v>>> + Sized {
+    /// Collect values which are known to be unique.
+    fn collect_hashed(self) -> SmallSet<Value<'v>> {
+        let mut content = SmallSet::with_capacity(self.size_hint().0);
+        for x in self {
+            content.insert_hashed_unique_unchecked(x);
+        }
+        content
+    }
+}
+
+impl<'v, I: Iterator<Item = Hashed<Value<'v>>>> CollectHashed<'v> for I {}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+trait SetLike<'v>: Debug + Allocative {
+    fn content(&self) -> ARef<Set<'v>>;
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    fn content(&self) -> ARef<Set<'v>> {
+        ARef::new_ref(self.borrow())
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    fn content(&self) -> ARef<Set<'v>> {
+        ARef::new_ptr(coerce(self))
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+impl<'v, T: SetLike<'v> + 'v> SetGen<T>
+where
+    Self: StarlarkValue<'v>,
+{
+    fn binary_op(
+        &self,
+        op: &str,
+        rhs: Value<'v>,
+        heap: &'v Heap,
+        f: impl FnOnce(&Set<'v>, &Set<'v>) -> Set<'v>,
+    ) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, op, rhs), Ok)?;
+        Ok(heap.alloc(f(&self.0.content(), &rhs)))
+    }
+}
+
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType,
+{
+    starlark_type!(Set::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        let content = self.0.content();
+        if content.is_empty() {
+            r.push_str("set()");
+            return;
+        }
+        r.push('{');
+        for (i, x) in content.iter().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            x.collect_repr(r);
+        }
+        r.push('}');
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("{...}");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                let this = self.0.content();
+                Ok(this.len() == other.len() && this.is_subset(&other))
+            }
+        }
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        self.0.content().contains(other)
+    }
+
+    fn iterate<'a>(
+        &'a self,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(Box::new(ARefIterator::new(self.0.content(), |x| x.iter())))
+    }
+
+    fn with_iterator(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        f(&mut self.0.content().iter())
+    }
+
+    fn bit_or(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("|", rhs, heap, Set::union)
+    }
+
+    fn bit_and(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("&", rhs, heap, Set::intersection)
+    }
+
+    fn bit_xor(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("^", rhs, heap, Set::symmetric_difference)
+    }
+
+    fn sub(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binary_op("-", rhs, heap, Set::difference)
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.content().iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::assert::Assert;
+
+    #[test]
+    fn test_set_literal_and_constructor() {
+        assert::all_true(
+            r#"
+{1, 2, 3} == set([3, 2, 1])
+len({1, 1, 2}) == 2
+set() == set([])
+type({1}) == "set"
+not set()
+{1}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        assert::eq("list({3, 1, 2, 1})", "[3, 1, 2]");
+        assert::eq("repr({'b', 'a'})", "\"{'b', 'a'}\"");
+        assert::eq("str(set())", "'set()'");
+    }
+
+    #[test]
+    fn test_operators() {
+        assert::all_true(
+            r#"
+{1, 2} | {2, 3} == {1, 2, 3}
+list({1, 2} | {3, 2}) == [1, 2, 3]
+{1, 2, 3} & {2, 3, 4} == {2, 3}
+{1, 2, 3} - {2} == {1, 3}
+{1, 2} ^ {2, 3} == {1, 3}
+2 in {1, 2}
+3 not in {1, 2}
+"#,
+        );
+        assert::fail("{1} | [1]", "not supported");
+        assert::fail("{[]}", "not hashable");
+    }
+
+    #[test]
+    fn test_bit_or_assign_mutates() {
+        assert::is_true(
+            r#"
+x = {1}
+y = x
+x |= {2}
+y == {1, 2}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_frozen() {
+        let mut a = Assert::new();
+        a.module("s.bzl", "s = {1, 2}");
+        a.is_true("load('s.bzl', 's')\ns == {2, 1} and list(s | {3}) == [1, 2, 3]");
+        a.fail("load('s.bzl', 's')\ns.add(3)", "Immutable");
+    }
+}
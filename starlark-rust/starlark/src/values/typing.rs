@@ -26,6 +26,8 @@ use thiserror::Error;
 use crate::values::dict::Dict;
 use crate::values::dict::DictRef;
 use crate::values::list::ListRef;
+use crate::values::set::Set;
+use crate::values::set::SetRef;
 use crate::values::types::tuple::value::Tuple;
 use crate::values::types::tuple::value::TupleGen;
 use crate::values::Heap;
@@ -240,6 +242,35 @@ impl TypeCompiled {
         TypeCompiled(Box::new(IsDictOf(kt, vt)))
     }
 
+    fn type_set() -> TypeCompiled {
+        #[derive(Allocative)]
+        struct IsSet;
+
+        impl TypeCompiledImpl for IsSet {
+            fn matches(&self, value: Value) -> bool {
+                SetRef::from_value(value).is_some()
+            }
+        }
+
+        TypeCompiled(Box::new(IsSet))
+    }
+
+    fn type_set_of(t: TypeCompiled) -> TypeCompiled {
+        #[derive(Allocative)]
+        struct IsSetOf(TypeCompiled);
+
+        impl TypeCompiledImpl for IsSetOf {
+            fn matches(&self, value: Value) -> bool {
+                match SetRef::from_value(value) {
+                    None => false,
+                    Some(set) => set.iter().all(|v| self.0.matches(v)),
+                }
+            }
+        }
+
+        TypeCompiled(Box::new(IsSetOf(t)))
+    }
+
     fn type_tuple_of(ts: Vec<TypeCompiled>) -> TypeCompiled {
         #[derive(Allocative)]
         struct IsTupleOf(Vec<TypeCompiled>);
@@ -338,6 +369,26 @@ impl TypeCompiled {
         }
     }
 
+    fn from_set<'v>(t: SetRef<'v>, heap: &'v Heap) -> anyhow::Result<TypeCompiled> {
+        // Set with a single element
+        fn unpack_singleton_set<'v>(x: &Set<'v>) -> Option<Value<'v>> {
+            if x.len() == 1 { x.iter().next() } else { None }
+        }
+
+        if let Some(t) = unpack_singleton_set(&t) {
+            if TypeCompiled::is_wildcard_value(t) {
+                Ok(TypeCompiled::type_set())
+            } else {
+                // Set of the form {t} must have all elements matching the type
+                let t = TypeCompiled::new(t, heap)?;
+                Ok(TypeCompiled::type_set_of(t))
+            }
+        } else {
+            // Set type with multiple elements is not allowed
+            Err(TypingError::InvalidTypeAnnotation(t.to_string()).into())
+        }
+    }
+
     pub(crate) fn new<'v>(ty: Value<'v>, heap: &'v Heap) -> anyhow::Result<Self> {
         if let Some(s) = ty.unpack_str() {
             Ok(TypeCompiled::from_str(s))
@@ -349,6 +400,8 @@ impl TypeCompiled {
             TypeCompiled::from_list(t, heap)
         } else if let Some(t) = DictRef::from_value(ty) {
             TypeCompiled::from_dict(t, heap)
+        } else if let Some(t) = SetRef::from_value(ty) {
+            TypeCompiled::from_set(t, heap)
         } else {
             Err(invalid_type_annotation(ty, heap).into())
         }
@@ -485,6 +538,9 @@ is_type('test', [int.type, str.type])
 is_type(('test', None), (str.type, None))
 is_type({"test": 1, "more": 2}, {str.type: int.type})
 is_type({1: 1, 2: 2}, {int.type: int.type})
+is_type({1, 2}, {int.type})
+is_type(set(), {int.type})
+is_type({1, "test"}, {""})
 
 not is_type(1, None)
 not is_type((1, 1), str.type)
@@ -492,6 +548,8 @@ not is_type('test', [int.type, bool.type])
 not is_type([1,2,None], [int.type])
 not is_type({"test": 1, 8: 2}, {str.type: int.type})
 not is_type({"test": 1, "more": None}, {str.type: int.type})
+not is_type({1, None}, {int.type})
+not is_type([1, 2], {int.type})
 
 is_type(1, "")
 is_type([1,2,"test"], ["_a"])
@@ -503,6 +561,7 @@ is_type([1,2,"test"], ["_a"])
         a.fail("is_type(None, [])", "not a valid type");
         a.fail("is_type(None, {'1': '', '2': ''})", "not a valid type");
         a.fail("is_type({}, {1: 'string', 2: 'bool'})", "not a valid type");
+        a.fail("is_type(set(), {'int', 'bool'})", "not a valid type");
 
         // Should check the type of default parameters that aren't used
         a.fail(
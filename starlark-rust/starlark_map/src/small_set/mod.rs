@@ -25,6 +25,8 @@ use std::hash::Hash;
 use std::hash::Hasher;
 
 use allocative::Allocative;
+use gazebo::coerce::Coerce;
+use gazebo::coerce::CoerceKey;
 use gazebo::prelude::*;
 
 use crate::equivalent::Equivalent;
@@ -37,9 +39,12 @@ pub use crate::small_set::iter::IterHashed;
 pub use crate::small_set::iter::IterMutUnchecked;
 
 /// An memory-efficient set with deterministic order, based on [`SmallMap`].
+#[repr(transparent)]
 #[derive(Clone, Default_, Allocative)]
 pub struct SmallSet<T>(SmallMap<T, ()>);
 
+unsafe impl<FromT, ToT> Coerce<SmallSet<ToT>> for SmallSet<FromT> where FromT: CoerceKey<ToT> {}
+
 impl<T: Debug> Debug for SmallSet<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_set().entries(self.iter()).finish()
@@ -90,6 +95,15 @@ impl<T> SmallSet<T> {
         self.0.capacity()
     }
 
+    /// Reserve capacity for at least `additional` more elements to be inserted.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize)
+    where
+        T: Eq,
+    {
+        self.0.reserve(additional);
+    }
+
     /// Iterate the element references.
     #[inline]
     pub fn iter(&self) -> Iter<T> {
@@ -226,6 +240,18 @@ impl<T> SmallSet<T> {
         self.0.remove(key).is_some()
     }
 
+    /// Remove the element from the set if it is present.
+    ///
+    /// Time complexity of this operation is *O(N)* where *N* is the number of entries in the set.
+    #[inline]
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> bool
+    where
+        Q: ?Sized + Equivalent<T>,
+        T: Eq,
+    {
+        self.0.remove_hashed(key).is_some()
+    }
+
     /// Insert entry if it doesn't exist.
     ///
     /// Return the resulting entry in the map.
@@ -303,6 +329,16 @@ impl<T> SmallSet<T> {
         self.0.contains_key(key)
     }
 
+    /// Check if the set contains an element, using a prehashed key.
+    #[inline]
+    pub fn contains_hashed<Q>(&self, key: Hashed<&Q>) -> bool
+    where
+        Q: Equivalent<T> + ?Sized,
+        T: Eq,
+    {
+        self.0.contains_key_hashed(key)
+    }
+
     /// Remove all elements from the set.
     ///
     /// Retain the capacity.